@@ -0,0 +1,81 @@
+//! Measures how aggregation scales with the number of compliance units.
+//!
+//! Aggregates transactions of 2 to 64 padding units (in powers of two) with
+//! both strategies, and prints one CSV row per run: the strategy, the
+//! numbers of units and base proofs, the aggregation time, and the peak
+//! resident memory of the process during the aggregation (Linux only, empty
+//! elsewhere).
+//!
+//! Run with `cargo bench -p anoma-rm-risc0 --features aggregation --bench
+//! aggregation_scaling > scaling.csv`. Pass a maximum number of units to stop
+//! earlier, e.g. `-- 16`. With `RISC0_DEV_MODE=1` proofs are faked, which
+//! only checks the setup.
+
+use anoma_rm_risc0::{
+    aggregation::AggregationStrategy,
+    padding::padding_action,
+    proving_system::ProofType,
+    transaction::{Delta, Transaction},
+};
+use std::time::Instant;
+
+const MAX_UNITS: usize = 64;
+
+// Creates a proved transaction of `units` padding units in one action.
+fn padding_transaction(units: usize) -> Transaction {
+    let (action, delta_witness) = padding_action(units, ProofType::Succinct).unwrap();
+    Transaction::create(vec![action], Delta::Witness(delta_witness))
+        .generate_delta_proof()
+        .unwrap()
+}
+
+// Resets the peak resident memory of the process, returning whether it's
+// supported.
+fn reset_peak_memory() -> bool {
+    std::fs::write("/proc/self/clear_refs", "5").is_ok()
+}
+
+// Returns the peak resident memory of the process in KiB.
+fn peak_memory_kib() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()
+}
+
+fn main() {
+    let max_units = std::env::args()
+        .skip(1)
+        .find_map(|arg| arg.parse().ok())
+        .unwrap_or(MAX_UNITS);
+
+    println!("strategy,units,base_proofs,aggregation_ms,peak_rss_kib");
+    let mut units = 2;
+    while units <= max_units {
+        let tx = padding_transaction(units);
+        for strategy in [AggregationStrategy::Batch, AggregationStrategy::Sequential] {
+            let mut tx = tx.clone();
+            let memory_supported = reset_peak_memory();
+            let start = Instant::now();
+            tx.aggregate_with_strategy(strategy.clone(), ProofType::Succinct)
+                .unwrap();
+            let elapsed = start.elapsed();
+            let peak = memory_supported
+                .then(peak_memory_kib)
+                .flatten()
+                .map(|kib| kib.to_string())
+                .unwrap_or_default();
+            println!(
+                "{strategy:?},{units},{},{},{peak}",
+                3 * units,
+                elapsed.as_millis()
+            );
+        }
+        units *= 2;
+    }
+}
@@ -0,0 +1,49 @@
+//! Compares verifying the logic proofs of an action with fresh verifier
+//! parameters per proof and with a shared `ProofVerifier`.
+//!
+//! Run with `RISC0_DEV_MODE=1 cargo bench -p anoma-rm-risc0 --bench verify_action`.
+
+use anoma_rm_risc0::{
+    logic_proof::{LogicProver, LogicVerifier},
+    padding::PaddingUnit,
+    proving_system::{ProofType, ProofVerifier},
+};
+use std::time::{Duration, Instant};
+
+// Airdrop-style: many proofs under the padding logic verifying key.
+const PROOFS: usize = 32;
+const ROUNDS: u32 = 10;
+
+fn time(verify: impl Fn()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        verify();
+    }
+    start.elapsed() / ROUNDS
+}
+
+fn main() {
+    let units = (0..PROOFS / 2)
+        .map(|_| PaddingUnit::new())
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let verifiers: Vec<LogicVerifier> = units
+        .iter()
+        .flat_map(|unit| unit.logics(Default::default()))
+        .map(|logic| logic.prove(ProofType::Succinct).unwrap())
+        .collect();
+
+    let fresh = time(|| {
+        for verifier in &verifiers {
+            verifier.verify().unwrap();
+        }
+    });
+    let shared = time(|| {
+        let proof_verifier = ProofVerifier::new();
+        for verifier in &verifiers {
+            verifier.verify_with(&proof_verifier).unwrap();
+        }
+    });
+    println!("{PROOFS} logic proofs, fresh parameters:  {fresh:?}");
+    println!("{PROOFS} logic proofs, shared parameters: {shared:?}");
+}
@@ -0,0 +1,63 @@
+//! Compares serial and parallel verification of aggregated transactions.
+//!
+//! Run with `RISC0_DEV_MODE=1 cargo bench -p anoma-rm-risc0 --bench verify_aggregation --features aggregation,prove`.
+
+use anoma_rm_risc0::{
+    aggregation::AggregationStrategy,
+    padding::padding_action,
+    proving_system::ProofType,
+    transaction::{Delta, Transaction},
+};
+use std::time::{Duration, Instant};
+
+const UNITS: usize = 16;
+const ROUNDS: u32 = 10;
+
+fn time_verification(tx: &Transaction, threads: usize) -> Duration {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap();
+    pool.install(|| {
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            tx.verify_aggregation().unwrap();
+        }
+        start.elapsed() / ROUNDS
+    })
+}
+
+fn main() {
+    let threads = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+
+    // One action per unit, so the logic instances are recomputed per action.
+    let mut actions = Vec::with_capacity(UNITS);
+    let mut delta_witness = None;
+    for _ in 0..UNITS {
+        let (action, witness) = padding_action(1, ProofType::Succinct).unwrap();
+        actions.push(action);
+        delta_witness = Some(match delta_witness {
+            None => witness,
+            Some(acc) => witness.compose(&acc),
+        });
+    }
+    let tx = Transaction::create(actions, Delta::Witness(delta_witness.unwrap()))
+        .generate_delta_proof()
+        .unwrap();
+
+    for strategy in [AggregationStrategy::Batch, AggregationStrategy::Sequential] {
+        let mut tx = tx.clone();
+        tx.aggregate_with_strategy(strategy.clone(), ProofType::Succinct)
+            .unwrap();
+
+        let serial = time_verification(&tx, 1);
+        let parallel = time_verification(&tx, threads);
+        println!(
+            "{strategy:?} aggregation of {UNITS} units: serial {serial:?}, \
+             {threads} threads {parallel:?} ({:.2}x)",
+            serial.as_secs_f64() / parallel.as_secs_f64()
+        );
+    }
+}
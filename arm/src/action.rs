@@ -6,36 +6,135 @@ use crate::{
     compliance_unit::ComplianceUnit,
     error::ArmError,
     logic_proof::{LogicVerifier, LogicVerifierInputs},
+    proving_system::ProofVerifier,
+    verification::ActionReport,
 };
 use k256::ProjectivePoint;
 use risc0_zkvm::Digest;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 /// An action consists of compliance units and logic verifier inputs.
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
 pub struct Action {
     /// The compliance units in this action.
     pub compliance_units: Vec<ComplianceUnit>,
     /// The logic verifier inputs in this action.
     pub logic_verifier_inputs: Vec<LogicVerifierInputs>,
+    /// The resources of other actions this action relies on, e.g. the
+    /// ephemeral resources an intent expects a solver's action to consume.
+    #[serde(default)]
+    pub resource_refs: Vec<ResourceRef>,
+}
+
+/// A reference to a resource of a transaction, by tag.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct ResourceRef {
+    /// The index of the action expected to hold the resource. Composition
+    /// shifts action indices, so other actions are searched if it doesn't.
+    pub action_hint: usize,
+    /// The nullifier or commitment of the resource.
+    pub tag: Digest,
+}
+
+impl ResourceRef {
+    /// Creates a reference to the resource `tag` held by action `action_hint`.
+    pub fn new(action_hint: usize, tag: Digest) -> Self {
+        ResourceRef { action_hint, tag }
+    }
 }
 
 impl Action {
-    /// Creates a new Action from compliance units and logic verifiers.
+    /// Creates a new Action from compliance units and logic verifiers, in the
+    /// canonical order: compliance units sorted by consumed nullifier and
+    /// logic verifier inputs by tag. The logics must be proven against the
+    /// action tree of the sorted units, see [`MerkleTree::from_units`].
     pub fn new(
         compliance_units: Vec<ComplianceUnit>,
         logic_verifiers: Vec<LogicVerifier>,
     ) -> Result<Self, ArmError> {
-        let logic_verifier_inputs: Vec<LogicVerifierInputs> = logic_verifiers
+        let mut compliance_units = compliance_units
+            .into_iter()
+            .map(|unit| Ok((unit.get_instance()?.consumed_nullifier, unit)))
+            .collect::<Result<Vec<_>, ArmError>>()?;
+        compliance_units.sort_by_key(|(nullifier, _)| <[u8; 32]>::from(*nullifier));
+        let compliance_units = compliance_units.into_iter().map(|(_, unit)| unit).collect();
+
+        let mut logic_verifier_inputs: Vec<LogicVerifierInputs> = logic_verifiers
             .into_iter()
             .map(|lv| lv.try_into())
             .collect::<Result<_, _>>()?;
+        logic_verifier_inputs.sort_by_key(|input| <[u8; 32]>::from(input.tag));
         Ok(Action {
             compliance_units,
             logic_verifier_inputs,
+            resource_refs: Vec::new(),
         })
     }
 
+    /// Declares the resources of other actions the action relies on.
+    pub fn with_resource_refs(mut self, resource_refs: Vec<ResourceRef>) -> Self {
+        self.resource_refs = resource_refs;
+        self
+    }
+
+    /// Returns whether one of the action's compliance units consumes or
+    /// creates the resource `tag`.
+    pub fn contains_tag(&self, tag: &Digest) -> bool {
+        self.compliance_units.iter().any(|unit| {
+            unit.get_instance().is_ok_and(|instance| {
+                instance.consumed_nullifier == *tag || instance.created_commitment == *tag
+            })
+        })
+    }
+
+    /// Returns the tags of the action in the canonical order: for each
+    /// compliance unit, the consumed nullifier and the created commitment.
+    pub fn tags(&self) -> Result<Vec<Digest>, ArmError> {
+        let mut tags = Vec::with_capacity(self.compliance_units.len() * 2);
+        for unit in &self.compliance_units {
+            let instance = unit.get_instance()?;
+            tags.push(instance.consumed_nullifier);
+            tags.push(instance.created_commitment);
+        }
+        Ok(tags)
+    }
+
+    /// Returns the root of the action tree, the tree of the action's tags.
+    pub fn action_tree_root(&self) -> Result<Digest, ArmError> {
+        MerkleTree::from(self.tags()?).root()
+    }
+
+    /// Checks that the action is in the canonical order of [`Action::new`].
+    /// Units revealing the same nullifier are left to the duplicate nullifier
+    /// checks.
+    pub fn check_canonical_order(&self) -> Result<(), ArmError> {
+        let nullifiers = self
+            .compliance_units
+            .iter()
+            .map(|unit| Ok(unit.get_instance()?.consumed_nullifier))
+            .collect::<Result<Vec<_>, ArmError>>()?;
+        let units_sorted = nullifiers
+            .windows(2)
+            .all(|pair| pair[0].as_bytes() <= pair[1].as_bytes());
+        let inputs_sorted = self
+            .logic_verifier_inputs
+            .windows(2)
+            .all(|pair| pair[0].tag.as_bytes() <= pair[1].tag.as_bytes());
+        if !units_sorted || !inputs_sorted {
+            return Err(ArmError::NonCanonicalOrder);
+        }
+        Ok(())
+    }
+
     /// Returns a reference to the compliance units.
     pub fn get_compliance_units(&self) -> &Vec<ComplianceUnit> {
         &self.compliance_units
@@ -46,70 +145,127 @@ impl Action {
         &self.logic_verifier_inputs
     }
 
-    /// Constructs logic verifiers from the action's compliance units and logic verifier inputs.
-    /// It also checks consistency between compliance instances and logic verifier inputs.
-    pub(crate) fn get_logic_verifiers(&self) -> Result<Vec<LogicVerifier>, ArmError> {
-        let mut logic_verifiers = Vec::new();
-
+    /// Returns the compliance instances and the logic verifier inputs in the
+    /// canonical order: for each compliance unit, the consumed and then the
+    /// created resource. This is the order the action tree is built in and
+    /// the aggregation guests and adapters consume the instances in.
+    pub(crate) fn ordered_inputs(
+        &self,
+    ) -> Result<(Vec<ComplianceInstance>, Vec<&LogicVerifierInputs>), ArmError> {
+        self.check_canonical_order()?;
         let compliance_intances = self
             .compliance_units
             .iter()
             .map(|unit| unit.get_instance())
             .collect::<Result<Vec<ComplianceInstance>, ArmError>>()?;
 
-        // Construct the action tree
-        let tags: Vec<Digest> = compliance_intances
-            .iter()
-            .flat_map(|instance| vec![instance.consumed_nullifier, instance.created_commitment])
-            .collect();
-        let logics = compliance_intances
-            .iter()
-            .flat_map(|instance| vec![instance.consumed_logic_ref, instance.created_logic_ref])
-            .collect::<Vec<_>>();
-        let action_tree = MerkleTree::from(tags.clone());
-        let root = action_tree.root()?;
+        let tags_and_logics = compliance_intances.iter().flat_map(|instance| {
+            vec![
+                (instance.consumed_nullifier, instance.consumed_logic_ref),
+                (instance.created_commitment, instance.created_logic_ref),
+            ]
+        });
 
         // Match logic verifier inputs with the tags in the action tree
-        if tags.len() != self.logic_verifier_inputs.len() {
+        if compliance_intances.len() * 2 != self.logic_verifier_inputs.len() {
             return Err(ArmError::TagNotFound);
         }
 
-        for (index, (tag, logic)) in tags.iter().zip(logics.iter()).enumerate() {
+        let mut inputs = Vec::with_capacity(self.logic_verifier_inputs.len());
+        for (tag, logic) in tags_and_logics {
             // Look up the tag in the `logic_verifier_inputs`.
-            if let Some(input) = self
+            let input = self
                 .logic_verifier_inputs
                 .iter()
-                .find(|input| &input.tag == tag)
-            {
-                if input.verifying_key != *logic {
-                    return Err(ArmError::VerifyingKeyMismatch);
-                }
-
-                let is_consumed = index % 2 == 0;
-                let verifier = input.clone().to_logic_verifier(is_consumed, root)?;
-                logic_verifiers.push(verifier);
-            } else {
-                return Err(ArmError::TagNotFound);
+                .find(|input| input.tag == tag)
+                .ok_or_else(|| ArmError::TagNotFound.with_tag(0, tag))?;
+            if input.logic_ref() != logic {
+                return Err(ArmError::VerifyingKeyMismatch.with_tag(0, tag));
             }
+            inputs.push(input);
         }
 
-        Ok(logic_verifiers)
+        Ok((compliance_intances, inputs))
+    }
+
+    /// Constructs logic verifiers from the action's compliance units and logic verifier inputs.
+    /// It also checks consistency between compliance instances and logic verifier inputs.
+    pub(crate) fn get_logic_verifiers(&self) -> Result<Vec<LogicVerifier>, ArmError> {
+        let (_, inputs) = self.ordered_inputs()?;
+
+        // Construct the action tree
+        let tags: Vec<Digest> = inputs.iter().map(|input| input.tag).collect();
+        let root = MerkleTree::from(tags).root()?;
+
+        inputs
+            .into_iter()
+            .enumerate()
+            .map(|(index, input)| {
+                let is_consumed = index % 2 == 0;
+                input
+                    .clone()
+                    .to_logic_verifier(is_consumed, root)
+                    .map_err(|err| err.with_tag(0, input.tag))
+            })
+            .collect()
     }
 
-    /// Verifies all proofs and consistencies in the action.
+    /// Verifies all proofs and consistencies in the action, including the
+    /// app data visibility rules the logic proofs attest to. Failures of a
+    /// compliance unit or a logic proof carry the offending tag (the consumed
+    /// nullifier for compliance units) with action index 0.
     pub fn verify(self) -> Result<(), ArmError> {
+        self.verify_with(&ProofVerifier::new())
+    }
+
+    /// Verifies the action like [`Action::verify`], with the parameters of a
+    /// shared verifier, e.g. the one of the whole transaction.
+    pub fn verify_with(self, verifier: &ProofVerifier) -> Result<(), ArmError> {
+        self.verify_compliance_units(verifier)?;
+        self.verify_logic_proofs(verifier)
+    }
+
+    /// Verifies only the compliance proofs of the action, e.g. when its logic
+    /// proofs were already verified upstream. The logic proofs are skipped,
+    /// as reported.
+    pub fn verify_compliance_only(&self) -> Result<ActionReport, ArmError> {
+        self.verify_compliance_units(&ProofVerifier::new())?;
+        Ok(ActionReport {
+            compliance_proofs: self.compliance_units.len(),
+            logic_proofs: 0,
+            skipped_logic_proofs: self.logic_verifier_inputs.len(),
+        })
+    }
+
+    pub(crate) fn verify_compliance_units(&self, verifier: &ProofVerifier) -> Result<(), ArmError> {
         for unit in &self.compliance_units {
-            unit.verify()?;
+            let instance = unit.get_instance()?;
+            unit.verify_with(verifier)
+                .map_err(|err| err.with_tag(0, instance.consumed_nullifier))?;
         }
+        Ok(())
+    }
 
+    pub(crate) fn verify_logic_proofs(&self, verifier: &ProofVerifier) -> Result<(), ArmError> {
         let logic_verifiers = self.get_logic_verifiers()?;
-        for verifier in logic_verifiers.iter() {
-            verifier.verify()?;
+        for logic_verifier in logic_verifiers.iter() {
+            let instance = logic_verifier.get_instance()?;
+            logic_verifier
+                .verify_with(verifier)
+                .map_err(|err| err.with_tag(0, instance.tag))?;
         }
-
         Ok(())
     }
 
+    /// Returns the report of a full verification of the action.
+    pub(crate) fn full_report(&self) -> ActionReport {
+        ActionReport {
+            compliance_proofs: self.compliance_units.len(),
+            logic_proofs: self.logic_verifier_inputs.len(),
+            skipped_logic_proofs: 0,
+        }
+    }
+
     /// This function computes the delta of the action by summing up the deltas
     /// of each compliance unit.
     pub fn delta(&self) -> Result<ProjectivePoint, ArmError> {
@@ -134,3 +290,101 @@ impl Action {
         Ok(msg)
     }
 }
+
+/// A one-line summary, e.g. `action (2 unit(s), 4 logic input(s))`.
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "action ({} unit(s), {} logic input(s))",
+            self.compliance_units.len(),
+            self.logic_verifier_inputs.len()
+        )
+    }
+}
+
+/// Returns an action holding the unproven compliance unit of the witness and
+/// no logic inputs, for tests that don't need proofs. The default witness is
+/// balanced by a delta witness with rcv = 1.
+#[cfg(test)]
+pub(crate) fn unproven_action(witness: &crate::compliance::ComplianceWitness) -> Action {
+    let instance = witness.constrain().unwrap();
+    Action {
+        compliance_units: vec![ComplianceUnit::unproven(&instance).unwrap()],
+        logic_verifier_inputs: vec![],
+        resource_refs: vec![],
+    }
+}
+
+#[cfg(feature = "prove")]
+#[test]
+fn test_canonical_order() {
+    use crate::{padding::padding_action, proving_system::ProofType};
+
+    let (action, _) = padding_action(3, ProofType::Succinct).unwrap();
+    assert_eq!(action.check_canonical_order(), Ok(()));
+    let tags = action.tags().unwrap();
+    let units = tags
+        .chunks(2)
+        .map(|unit| (unit[0], unit[1]))
+        .collect::<Vec<_>>();
+    assert_eq!(MerkleTree::from_units(units.into_iter().rev()).leaves, tags);
+    assert!(action.clone().verify().is_ok());
+
+    // Builders ordering units or logic inputs differently are rejected.
+    let mut reordered = action.clone();
+    reordered.compliance_units.swap(0, 2);
+    assert_eq!(reordered.verify(), Err(ArmError::NonCanonicalOrder));
+    let mut reordered = action;
+    reordered.logic_verifier_inputs.swap(0, 1);
+    assert_eq!(
+        reordered.check_canonical_order(),
+        Err(ArmError::NonCanonicalOrder)
+    );
+}
+
+#[test]
+fn test_committed_logic_refs() {
+    use crate::{
+        compliance::{ComplianceWitness, PrivateComplianceWitness},
+        logic_instance::AppData,
+    };
+
+    let witness = PrivateComplianceWitness::new(ComplianceWitness::default());
+    let instance = witness.constrain().unwrap();
+    let logic_ref = witness.witness.consumed_resource_logic();
+    assert_ne!(instance.consumed_logic_ref, logic_ref);
+
+    let input = |tag, logic_ref_opening| LogicVerifierInputs {
+        tag,
+        verifying_key: logic_ref,
+        app_data: AppData::default(),
+        proof: None,
+        app_data_by_digest: false,
+        binds_image_id: false,
+        logic_ref_opening,
+    };
+    let mut logic_verifier_inputs = vec![
+        input(
+            instance.consumed_nullifier,
+            Some(witness.consumed_logic_ref_rand),
+        ),
+        input(
+            instance.created_commitment,
+            Some(witness.created_logic_ref_rand),
+        ),
+    ];
+    logic_verifier_inputs.sort_by_key(|input| <[u8; 32]>::from(input.tag));
+    let action = Action {
+        compliance_units: vec![ComplianceUnit::unproven(&instance).unwrap()],
+        logic_verifier_inputs,
+        resource_refs: vec![],
+    };
+    let verifiers = action.get_logic_verifiers().unwrap();
+    assert!(verifiers.iter().all(|lp| lp.verifying_key == logic_ref));
+
+    // The verifying key must open the committed logic ref.
+    let mut unopened = action;
+    unopened.logic_verifier_inputs[0].logic_ref_opening = None;
+    assert!(unopened.get_logic_verifiers().is_err());
+}
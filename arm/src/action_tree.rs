@@ -20,6 +20,20 @@ impl MerkleTree {
         MerkleTree { leaves }
     }
 
+    /// Creates the action tree of compliance units, given as pairs of the
+    /// consumed nullifier and the created commitment. The units are sorted by
+    /// consumed nullifier, the canonical order of actions.
+    pub fn from_units(units: impl IntoIterator<Item = (Digest, Digest)>) -> Self {
+        let mut units: Vec<_> = units.into_iter().collect();
+        units.sort_by_key(|(nullifier, _)| <[u8; 32]>::from(*nullifier));
+        MerkleTree::new(
+            units
+                .into_iter()
+                .flat_map(|(nullifier, commitment)| [nullifier, commitment])
+                .collect(),
+        )
+    }
+
     /// Inserts a new leaf into the Merkle tree.
     pub fn insert(&mut self, value: Digest) {
         self.leaves.push(value)
@@ -27,24 +41,7 @@ impl MerkleTree {
 
     /// Computes the root of the Merkle tree.
     pub fn root(&self) -> Result<Digest, ArmError> {
-        if self.is_empty() {
-            return Err(ArmError::EmptyTree);
-        }
-
-        let len = self
-            .leaves
-            .len()
-            .checked_next_power_of_two()
-            .ok_or(ArmError::TreeTooLarge)?;
-        let mut cur_layer = self.leaves.clone();
-        cur_layer.resize(len, *PADDING_LEAF);
-        while cur_layer.len() > 1 {
-            cur_layer = cur_layer
-                .chunks(2)
-                .map(|pair| hash_two(&pair[0], &pair[1]))
-                .collect();
-        }
-        Ok(cur_layer[0])
+        fold_root(&self.leaves, hash_two)
     }
 
     // Generate the merkle path for the current leave
@@ -116,6 +113,54 @@ impl MerkleTree {
     pub fn is_empty(&self) -> bool {
         self.leaves.is_empty()
     }
+
+    /// Computes the roots of many trees at once, e.g. all the action trees
+    /// of a block. This is a parallel root, not a SIMD one: trees are spread
+    /// over the rayon thread pool and each is folded one hash at a time with
+    /// the `sha2` SHA-256. The roots are those of [`MerkleTree::root`], in order.
+    #[cfg(feature = "batch-hash")]
+    pub fn root_batched(leaf_sets: &[Vec<Digest>]) -> Result<Vec<Digest>, ArmError> {
+        use rayon::prelude::*;
+        use sha2::{Digest as _, Sha256};
+
+        let hash_two = |left: &Digest, right: &Digest| {
+            let hash: [u8; 32] = Sha256::new()
+                .chain_update(left.as_bytes())
+                .chain_update(right.as_bytes())
+                .finalize()
+                .into();
+            Digest::from_bytes(hash)
+        };
+        leaf_sets
+            .par_iter()
+            .map(|leaves| fold_root(leaves, hash_two))
+            .collect()
+    }
+}
+
+/// Pads `leaves` to a power of two and folds the layers with `hash` down to
+/// the root.
+fn fold_root(
+    leaves: &[Digest],
+    hash: impl Fn(&Digest, &Digest) -> Digest,
+) -> Result<Digest, ArmError> {
+    if leaves.is_empty() {
+        return Err(ArmError::EmptyTree);
+    }
+
+    let len = leaves
+        .len()
+        .checked_next_power_of_two()
+        .ok_or(ArmError::TreeTooLarge)?;
+    let mut cur_layer = leaves.to_vec();
+    cur_layer.resize(len, *PADDING_LEAF);
+    while cur_layer.len() > 1 {
+        cur_layer = cur_layer
+            .chunks(2)
+            .map(|pair| hash(&pair[0], &pair[1]))
+            .collect();
+    }
+    Ok(cur_layer[0])
 }
 
 impl From<Vec<Digest>> for MerkleTree {
@@ -123,3 +168,39 @@ impl From<Vec<Digest>> for MerkleTree {
         MerkleTree::new(leaves)
     }
 }
+
+#[cfg(feature = "batch-hash")]
+#[test]
+fn test_root_batched() {
+    let leaf_sets: Vec<Vec<Digest>> = (1..=9u8)
+        .map(|len| (0..len).map(|i| Digest::from_bytes([i; 32])).collect())
+        .collect();
+    let roots = MerkleTree::root_batched(&leaf_sets).unwrap();
+    for (leaves, root) in leaf_sets.iter().zip(&roots) {
+        assert_eq!(MerkleTree::new(leaves.clone()).root(), Ok(*root));
+    }
+    assert_eq!(
+        MerkleTree::root_batched(&[vec![Digest::default()], vec![]]),
+        Err(ArmError::EmptyTree)
+    );
+}
+
+#[test]
+fn test_from_units() {
+    let units: Vec<(Digest, Digest)> = [3u8, 1, 2]
+        .iter()
+        .map(|i| {
+            (
+                Digest::from_bytes([*i; 32]),
+                Digest::from_bytes([i + 10; 32]),
+            )
+        })
+        .collect();
+    let tree = MerkleTree::from_units(units.clone());
+    let leaves: Vec<Digest> = [1u8, 11, 2, 12, 3, 13]
+        .iter()
+        .map(|i| Digest::from_bytes([*i; 32]))
+        .collect();
+    assert_eq!(tree.leaves, leaves);
+    assert_eq!(tree, MerkleTree::from_units(units.into_iter().rev()));
+}
@@ -1,29 +1,43 @@
 //! Aggregation of base proofs into batch proofs.
 
-use risc0_zkvm::{default_prover, Digest, ExecutorEnv, Receipt, VerifierContext};
-use risc0_zkvm::{InnerReceipt, ProverOpts};
+use rayon::prelude::*;
+#[cfg(feature = "prove")]
+use risc0_zkvm::{
+    Digest, ExecutorEnv, ExecutorEnvBuilder, ProverOpts, ReceiptClaim, VerifierContext,
+};
+use risc0_zkvm::{InnerReceipt, Receipt};
 use serde::{Deserialize, Serialize};
 
-use crate::aggregation::{
-    constants::{BATCH_AGGREGATION_PK, BATCH_AGGREGATION_VK},
-    BatchCU, BatchLP,
-};
+#[cfg(feature = "prove")]
+use crate::aggregation::constants::BATCH_AGGREGATION_PK;
+use crate::aggregation::{constants::BATCH_AGGREGATION_VK, receipt_kind, BatchCU, BatchLP};
 use crate::compliance::ComplianceInstanceWords;
 use crate::constants::COMPLIANCE_VK;
 use crate::error::ArmError;
-use crate::proving_system::ProofType;
+use crate::proving_system::ProofVerifier;
+#[cfg(feature = "prove")]
+use crate::proving_system::{execute_env, ProofType};
 use crate::transaction::Transaction;
 use crate::utils::{bytes_to_words, words_to_bytes};
 
 /// The batch aggregation proof.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct BatchProof(pub InnerReceipt);
 
+impl std::fmt::Debug for BatchProof {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("BatchProof")
+            .field(&format_args!("{}", receipt_kind(&self.0)))
+            .finish()
+    }
+}
+
 /// Aggregates base proofs in batches.
 pub struct BatchAggregation;
 
 impl BatchAggregation {
     /// Proves the aggregation of a transaction's base proofs into a batch proof.
+    #[cfg(feature = "prove")]
     pub fn prove_transaction_aggregation(
         tx: &Transaction,
         proof_type: ProofType,
@@ -40,20 +54,8 @@ impl BatchAggregation {
             receipts: lp_receipts,
         } = tx.get_batch_lp()?;
 
-        let mut cu_instances_u32: Vec<ComplianceInstanceWords> = Vec::new();
-        for ci in cu_instances.iter() {
-            cu_instances_u32.push(ComplianceInstanceWords {
-                u32_words: bytes_to_words(ci).try_into().map_err(|_| {
-                    ArmError::ProveFailed(
-                        "Error converting compliance instance into fixed-size u32 words".into(),
-                    )
-                })?,
-            });
-        }
-        let lp_instances_u32: Vec<Vec<u32>> = lp_instances
-            .iter()
-            .map(|bytes| bytes_to_words(bytes))
-            .collect();
+        let (cu_instances_u32, lp_instances_u32) =
+            instance_words(&cu_instances, &lp_instances, ArmError::ProveFailed)?;
 
         let mut env_builder = ExecutorEnv::builder();
 
@@ -68,19 +70,7 @@ impl BatchAggregation {
             ));
         }
 
-        // Write instances and keys to guest input.
-        let compliance_key: Digest = *COMPLIANCE_VK;
-        let env = env_builder
-            .write(&cu_instances_u32)
-            .map_err(|_| ArmError::WriteWitnessFailed)?
-            .write(&compliance_key)
-            .map_err(|_| ArmError::WriteWitnessFailed)?
-            .write(&lp_instances_u32)
-            .map_err(|_| ArmError::WriteWitnessFailed)?
-            .write(&lp_keys)
-            .map_err(|_| ArmError::WriteWitnessFailed)?
-            .build()
-            .map_err(|_| ArmError::BuildProverEnvFailed)?;
+        let env = guest_env(env_builder, &cu_instances_u32, &lp_instances_u32, &lp_keys)?;
 
         let prover_opts = match proof_type {
             ProofType::Succinct => {
@@ -91,18 +81,22 @@ impl BatchAggregation {
             }
         };
 
-        let prover = default_prover();
+        let prover = crate::proving_system::selected_prover()?;
 
         // Prove batch.
-        let receipt = prover
-            .prove_with_ctx(
-                env,
-                &VerifierContext::default(),
-                BATCH_AGGREGATION_PK,
-                &prover_opts,
-            )
-            .map_err(|err| ArmError::ProveFailed(format!("Proof generation failed: {}", err)))?
-            .receipt;
+        let receipt = crate::proving_system::observe_proof!(
+            Aggregation,
+            prover
+                .prove_with_ctx(
+                    env,
+                    &VerifierContext::default(),
+                    BATCH_AGGREGATION_PK,
+                    &prover_opts,
+                )
+                .map_err(|err| ArmError::ProveFailed(format!("Proof generation failed: {}", err))),
+            |info| bincode::serialized_size(&info.receipt.inner).unwrap_or_default() as usize
+        )?
+        .receipt;
 
         Ok(BatchProof(receipt.inner))
     }
@@ -123,34 +117,114 @@ impl BatchAggregation {
             receipts: _,
         } = tx.get_batch_lp()?;
 
-        let mut compliance_instances_u32: Vec<ComplianceInstanceWords> = Vec::new();
-        for ci in compliance_instances.iter() {
-            compliance_instances_u32.push(ComplianceInstanceWords {
-                u32_words: bytes_to_words(ci).try_into().map_err(|_| {
-                    ArmError::ProofVerificationFailed(
-                        "Error converting compliance instance into fixed-size u32 words".into(),
-                    )
-                })?,
-            });
-        }
-        let logic_instances_u32: Vec<Vec<u32>> = logic_instances
-            .iter()
-            .map(|bytes| bytes_to_words(bytes))
-            .collect();
-
-        let batch_instance = risc0_zkvm::serde::to_vec(&(
-            compliance_instances_u32,
-            *COMPLIANCE_VK,
-            logic_instances_u32,
-            logic_keys,
-        ))
-        .map_err(|_| ArmError::InstanceSerializationFailed)?;
+        let (compliance_instances_u32, logic_instances_u32) = instance_words(
+            &compliance_instances,
+            &logic_instances,
+            ArmError::ProofVerificationFailed,
+        )?;
+        let batch_instance =
+            batch_instance(compliance_instances_u32, logic_instances_u32, logic_keys)?;
 
         // Verify proof on the batch instance.
-        let receipt = Receipt::new(proof.0.clone(), words_to_bytes(&batch_instance).to_vec());
+        let receipt = Receipt::new(proof.0.clone(), batch_instance);
 
-        receipt.verify(*BATCH_AGGREGATION_VK).map_err(|err| {
-            ArmError::ProofVerificationFailed(format!("Proof verification failed: {}", err))
-        })
+        ProofVerifier::new().verify_receipt(&receipt, &BATCH_AGGREGATION_VK)
+    }
+
+    /// Runs the batch guest on the instances of a transaction in the executor
+    /// and checks that it commits the batch instance the verifier expects.
+    /// The proofs the guest verifies are assumed by their claims, so the
+    /// transaction doesn't need its base proofs.
+    #[cfg(feature = "prove")]
+    pub fn check_guest(tx: &Transaction) -> Result<(), ArmError> {
+        let BatchCU {
+            instances: cu_instances,
+            receipts: _,
+        } = tx.get_batch_cu();
+        let BatchLP {
+            instances: lp_instances,
+            keys: lp_keys,
+            receipts: _,
+        } = tx.get_batch_lp()?;
+        let (cu_instances_u32, lp_instances_u32) =
+            instance_words(&cu_instances, &lp_instances, ArmError::ProveFailed)?;
+
+        let mut env_builder = ExecutorEnv::builder();
+        for instance in cu_instances {
+            env_builder.add_assumption(ReceiptClaim::ok(*COMPLIANCE_VK, instance));
+        }
+        for (instance, key) in lp_instances.into_iter().zip(lp_keys.iter()) {
+            env_builder.add_assumption(ReceiptClaim::ok(*key, instance));
+        }
+        let env = guest_env(env_builder, &cu_instances_u32, &lp_instances_u32, &lp_keys)?;
+
+        let journal = execute_env(BATCH_AGGREGATION_PK, env)?;
+        if journal != batch_instance(cu_instances_u32, lp_instances_u32, lp_keys)? {
+            return Err(ArmError::GuestDivergence);
+        }
+        Ok(())
     }
 }
+
+// Converts the compliance and logic instances to the words the batch guest
+// reads, reporting malformed compliance instances with `err`.
+fn instance_words(
+    compliance_instances: &[Vec<u8>],
+    logic_instances: &[Vec<u8>],
+    err: fn(String) -> ArmError,
+) -> Result<(Vec<ComplianceInstanceWords>, Vec<Vec<u32>>), ArmError> {
+    let compliance_instances_u32 = compliance_instances
+        .par_iter()
+        .map(|ci| {
+            Ok(ComplianceInstanceWords {
+                u32_words: bytes_to_words(ci).try_into().map_err(|_| {
+                    err("Error converting compliance instance into fixed-size u32 words".into())
+                })?,
+            })
+        })
+        .collect::<Result<_, ArmError>>()?;
+    let logic_instances_u32 = logic_instances
+        .par_iter()
+        .map(|bytes| bytes_to_words(bytes))
+        .collect();
+    Ok((compliance_instances_u32, logic_instances_u32))
+}
+
+// The instance the batch guest commits.
+fn batch_instance(
+    compliance_instances_u32: Vec<ComplianceInstanceWords>,
+    logic_instances_u32: Vec<Vec<u32>>,
+    logic_keys: Vec<risc0_zkvm::Digest>,
+) -> Result<Vec<u8>, ArmError> {
+    let batch_instance = risc0_zkvm::serde::to_vec(&(
+        compliance_instances_u32,
+        *COMPLIANCE_VK,
+        logic_instances_u32,
+        logic_keys,
+    ))
+    .map_err(|_| ArmError::InstanceSerializationFailed)?;
+    Ok(words_to_bytes(&batch_instance).to_vec())
+}
+
+// Writes the instances and keys to the guest input, in the order the batch
+// guest reads them.
+#[cfg(feature = "prove")]
+fn guest_env<'a>(
+    mut env_builder: ExecutorEnvBuilder<'a>,
+    compliance_instances_u32: &[ComplianceInstanceWords],
+    logic_instances_u32: &[Vec<u32>],
+    logic_keys: &[Digest],
+) -> Result<ExecutorEnv<'a>, ArmError> {
+    let compliance_key: Digest = *COMPLIANCE_VK;
+    env_builder
+        .write(&compliance_instances_u32)
+        .map_err(|_| ArmError::WriteWitnessFailed)?
+        .write(&compliance_key)
+        .map_err(|_| ArmError::WriteWitnessFailed)?
+        .write(&logic_instances_u32)
+        .map_err(|_| ArmError::WriteWitnessFailed)?
+        .write(&logic_keys)
+        .map_err(|_| ArmError::WriteWitnessFailed)?
+        .build()
+        .map_err(|_| ArmError::BuildProverEnvFailed)
+}
@@ -1,6 +1,6 @@
 //! Aggregation constants for proving keys and verification keys.
 
-use hex::FromHex;
+use crate::digest_encoding::DigestEncoding;
 use lazy_static::lazy_static;
 use risc0_zkvm::Digest;
 
@@ -13,8 +13,8 @@ pub const BATCH_AGGREGATION_PK: &[u8] = include_bytes!("../../elfs/batch_aggrega
 lazy_static! {
     /// Sequential aggregation verification key / sequential aggregation image id.
     pub static ref SEQUENTIAL_AGGREGATION_VK: Digest =
-        Digest::from_hex("378bebfe9a8e136e28d309f582474ab02da3c8ef005e7b3c88727c563dfd2752").unwrap();
+        Digest::from_hex(include_str!("../../elfs/sequential_aggregation.id").trim()).unwrap();
 
     /// Batch aggregation verification key / Batch aggregation image id.
-    pub static ref BATCH_AGGREGATION_VK: Digest = Digest::from_hex("213b3f40d7c113c1a012072fcd791fa44bf5166a2300121630bd3228e2b00827").unwrap();
+    pub static ref BATCH_AGGREGATION_VK: Digest = Digest::from_hex(include_str!("../../elfs/batch_aggregation.id").trim()).unwrap();
 }
@@ -1,12 +1,15 @@
 //! Aggregation module for ARM proofs.
+use rayon::prelude::*;
 use risc0_zkvm::{Digest, InnerReceipt, Receipt};
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 
 use crate::{
     aggregation::{batch::BatchProof, pcd::PcdProof},
     compliance_unit::ComplianceUnit,
     error::ArmError,
     logic_proof::LogicVerifier,
+    proving_system::ProofType,
     transaction::Transaction,
 };
 
@@ -16,7 +19,8 @@ pub mod pcd;
 pub mod sequential;
 
 /// Supported strategies to aggregate.
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum AggregationStrategy {
     /// Sequential aggregation strategy.
     Sequential,
@@ -24,6 +28,31 @@ pub enum AggregationStrategy {
     Batch,
 }
 
+impl FromStr for AggregationStrategy {
+    type Err = ArmError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "sequential" => Ok(AggregationStrategy::Sequential),
+            "batch" => Ok(AggregationStrategy::Batch),
+            _ => Err(ArmError::InvalidConfig(format!(
+                "unknown aggregation strategy: {s}"
+            ))),
+        }
+    }
+}
+
+// The kind of a receipt, for `Debug` output.
+pub(crate) fn receipt_kind(receipt: &InnerReceipt) -> &'static str {
+    match receipt {
+        InnerReceipt::Composite(_) => "<composite receipt>",
+        InnerReceipt::Succinct(_) => "<succinct receipt>",
+        InnerReceipt::Groth16(_) => "<groth16 receipt>",
+        InnerReceipt::Fake(_) => "<fake receipt>",
+        _ => "<receipt>",
+    }
+}
+
 /// Aggregation proof discriminating by strategies.
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum AggregationProof {
@@ -33,6 +62,131 @@ pub enum AggregationProof {
     Batch(BatchProof),
 }
 
+/// The approximate size of a succinct receipt in bytes.
+pub const SUCCINCT_PROOF_BYTES: u64 = 220_000;
+/// The size of a Groth16 seal in bytes.
+pub const GROTH16_PROOF_BYTES: u64 = 256;
+/// The number of proofs above which the sequential strategy is recommended.
+/// The batch guest verifies all proofs in a single run, whose memory grows
+/// with their number, while the sequential one folds them one by one.
+pub const BATCH_AGGREGATION_MAX_PROOFS: usize = 64;
+
+/// The number of base proofs of a transaction.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TxShape {
+    /// The number of compliance units.
+    pub compliance_units: usize,
+    /// The number of resource logic proofs.
+    pub logic_proofs: usize,
+}
+
+impl TxShape {
+    /// Returns the shape of a transaction.
+    pub fn of(tx: &Transaction) -> Self {
+        TxShape {
+            compliance_units: tx
+                .actions
+                .iter()
+                .map(|a| a.get_compliance_units().len())
+                .sum(),
+            logic_proofs: tx
+                .actions
+                .iter()
+                .map(|a| a.get_logic_verifier_inputs().len())
+                .sum(),
+        }
+    }
+
+    /// Returns the total number of base proofs.
+    pub fn proofs(&self) -> usize {
+        self.compliance_units + self.logic_proofs
+    }
+}
+
+/// The verification costs of the chain a transaction settles on.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SettlementTarget {
+    /// The gas to verify a succinct proof, `None` if the chain can't.
+    pub succinct_verify_gas: Option<u64>,
+    /// The gas to verify a Groth16 proof, `None` if the chain can't.
+    pub groth16_verify_gas: Option<u64>,
+    /// The gas per byte of calldata.
+    pub calldata_gas_per_byte: u64,
+}
+
+impl SettlementTarget {
+    /// An EVM chain verifying Groth16 proofs through the RISC Zero verifier
+    /// contract.
+    pub fn evm() -> Self {
+        SettlementTarget {
+            succinct_verify_gas: None,
+            groth16_verify_gas: Some(280_000),
+            calldata_gas_per_byte: 16,
+        }
+    }
+
+    /// Returns the gas to verify and post a proof of the given type, `None`
+    /// if the chain can't verify it.
+    pub fn proof_gas(&self, proof_type: ProofType) -> Option<u64> {
+        let (verify_gas, bytes) = match proof_type {
+            ProofType::Succinct => (self.succinct_verify_gas?, SUCCINCT_PROOF_BYTES),
+            ProofType::Groth16 => (self.groth16_verify_gas?, GROTH16_PROOF_BYTES),
+        };
+        Some(verify_gas.saturating_add(bytes.saturating_mul(self.calldata_gas_per_byte)))
+    }
+}
+
+/// How to prove a transaction for a settlement target.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AggregationRecommendation {
+    /// The aggregation strategy, `None` if the proofs aren't worth
+    /// aggregating.
+    pub strategy: Option<AggregationStrategy>,
+    /// The type of the final proofs: the aggregation proof, or each base
+    /// proof without aggregation.
+    pub proof_type: ProofType,
+    /// The estimated settlement gas following the recommendation.
+    pub estimated_gas: u64,
+    /// The estimated settlement gas without aggregation.
+    pub unaggregated_gas: u64,
+}
+
+/// Recommends whether and how to aggregate the proofs of a transaction of
+/// the given shape settling on `target`. Fails if the target can't verify
+/// any proof type.
+pub fn recommend_strategy(
+    tx_shape: &TxShape,
+    target: &SettlementTarget,
+) -> Result<AggregationRecommendation, ArmError> {
+    let (proof_type, proof_gas) = [ProofType::Groth16, ProofType::Succinct]
+        .into_iter()
+        .filter_map(|proof_type| Some((proof_type, target.proof_gas(proof_type)?)))
+        .min_by_key(|(_, gas)| *gas)
+        .ok_or(ArmError::UnsupportedProofType)?;
+
+    let proofs = tx_shape.proofs();
+    let unaggregated_gas = proof_gas.saturating_mul(proofs as u64);
+    if proofs <= 1 {
+        return Ok(AggregationRecommendation {
+            strategy: None,
+            proof_type,
+            estimated_gas: unaggregated_gas,
+            unaggregated_gas,
+        });
+    }
+    let strategy = if proofs > BATCH_AGGREGATION_MAX_PROOFS {
+        AggregationStrategy::Sequential
+    } else {
+        AggregationStrategy::Batch
+    };
+    Ok(AggregationRecommendation {
+        strategy: Some(strategy),
+        proof_type,
+        estimated_gas: proof_gas,
+        unaggregated_gas,
+    })
+}
+
 /// Holds the compliance instances, and compliance proofs (if all present)
 /// of a transaction.
 #[derive(Debug, Clone)]
@@ -63,7 +217,7 @@ impl Transaction {
             None
         } else {
             let inner_receipts: Vec<Result<InnerReceipt, _>> = cus
-                .iter()
+                .par_iter()
                 .map(|cu| {
                     let inner: Result<InnerReceipt, _> =
                         bincode::deserialize(&cu.proof.clone().unwrap());
@@ -95,12 +249,16 @@ impl Transaction {
     }
 
     fn get_batch_lp(&self) -> Result<BatchLP, ArmError> {
-        let mut lps: Vec<LogicVerifier> = Vec::new();
-
-        for action in self.actions.iter() {
-            let mut lp_vec: Vec<LogicVerifier> = action.get_logic_verifiers()?;
-            lps.append(&mut lp_vec);
-        }
+        // Recomputing the logic instances of the actions is independent, so
+        // it runs in parallel.
+        let lps: Vec<LogicVerifier> = self
+            .actions
+            .par_iter()
+            .map(|action| action.get_logic_verifiers())
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .flatten()
+            .collect();
 
         let logic_instances: Vec<Vec<u8>> = lps.iter().map(|lp| lp.instance.clone()).collect();
 
@@ -108,7 +266,7 @@ impl Transaction {
             None
         } else {
             let inner_receipts: Vec<Result<InnerReceipt, _>> = lps
-                .iter()
+                .par_iter()
                 .map(|lp| {
                     let inner: Result<InnerReceipt, _> =
                         bincode::deserialize(&lp.proof.clone().unwrap());
@@ -161,3 +319,45 @@ impl Transaction {
         false
     }
 }
+
+#[test]
+fn test_recommend_strategy() {
+    let evm = SettlementTarget::evm();
+    let single = TxShape {
+        compliance_units: 1,
+        logic_proofs: 0,
+    };
+    let recommendation = recommend_strategy(&single, &evm).unwrap();
+    assert_eq!(recommendation.strategy, None);
+    assert_eq!(recommendation.proof_type, ProofType::Groth16);
+
+    let shape = TxShape {
+        compliance_units: 2,
+        logic_proofs: 4,
+    };
+    let recommendation = recommend_strategy(&shape, &evm).unwrap();
+    assert_eq!(recommendation.strategy, Some(AggregationStrategy::Batch));
+    assert_eq!(recommendation.proof_type, ProofType::Groth16);
+    assert_eq!(
+        recommendation.unaggregated_gas,
+        6 * recommendation.estimated_gas
+    );
+
+    let large = TxShape {
+        compliance_units: 40,
+        logic_proofs: 80,
+    };
+    assert_eq!(
+        recommend_strategy(&large, &evm).unwrap().strategy,
+        Some(AggregationStrategy::Sequential)
+    );
+
+    let nothing = SettlementTarget {
+        groth16_verify_gas: None,
+        ..evm
+    };
+    assert_eq!(
+        recommend_strategy(&shape, &nothing),
+        Err(ArmError::UnsupportedProofType)
+    );
+}
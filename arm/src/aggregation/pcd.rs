@@ -1,15 +1,18 @@
 //! A module defining traits and structures for Proof-Carrying Data (PCD) aggregation.
 
-use crate::aggregation::{BatchCU, BatchLP};
+use crate::aggregation::{receipt_kind, BatchCU, BatchLP};
 use crate::constants::COMPLIANCE_VK;
 use crate::error::ArmError;
+use crate::hash::BASE_CASE_DIGEST;
 use crate::proving_system;
+#[cfg(feature = "prove")]
 use crate::proving_system::ProofType;
+use crate::proving_system::ProofVerifier;
 use crate::utils::words_to_bytes;
-use crate::{compliance::ComplianceInstance, logic_instance::LogicInstance};
-use risc0_zkvm::{
-    default_prover, Digest, ExecutorEnv, InnerReceipt, ProverOpts, Receipt, VerifierContext,
-};
+use crate::{compliance::ComplianceInstance, logic_proof::decode_journal};
+use risc0_zkvm::{Digest, InnerReceipt, Receipt};
+#[cfg(feature = "prove")]
+use risc0_zkvm::{ExecutorEnv, ExecutorEnvBuilder, ProverOpts, ReceiptClaim, VerifierContext};
 use serde::{Deserialize, Serialize};
 
 /// A trait to aggregate proofs across nodes.
@@ -55,6 +58,7 @@ pub trait PCDAggregation {
 
     /// Proves correctness of [PCDAggregation::aggregate_step] and
     /// verifies the input [PcdProof]s and [StepProof].
+    #[cfg(feature = "prove")]
     fn prove_step(
         input_aggregations: &[PcdMessage],
         input_proofs: &[PcdProof],
@@ -92,25 +96,12 @@ pub trait PCDAggregation {
             }
         }
 
-        // Write all inputs. Make sure the corresponding guest circuit
-        // reads in the same order.
-        env_builder
-            .write(&<Self as PCDAggregation>::verifying_key())
-            .map_err(|_| ArmError::WriteWitnessFailed)?;
-        for aggregation in input_aggregations {
-            env_builder
-                .write(&(
-                    aggregation.step_output_digest,
-                    aggregation.step_program_digest,
-                ))
-                .map_err(|_| ArmError::WriteWitnessFailed)?;
-        }
-
-        let env = env_builder
-            .write(&(&step_instance.program, &step_instance.output.clone()))
-            .map_err(|_| ArmError::WriteWitnessFailed)?
-            .build()
-            .map_err(|_| ArmError::BuildProverEnvFailed)?;
+        let env = step_env(
+            env_builder,
+            <Self as PCDAggregation>::verifying_key(),
+            input_aggregations,
+            step_instance,
+        )?;
 
         // If not an output node, prove fast.
         let prover_opts = if output_node {
@@ -126,37 +117,112 @@ pub trait PCDAggregation {
             ProverOpts::fast()
         };
 
-        let prover = default_prover();
+        let prover = proving_system::selected_prover()?;
 
         // Prove step.
-        let next_receipt = prover
-            .prove_with_ctx(
-                env,
-                &VerifierContext::default(),
-                <Self as PCDAggregation>::proving_key(),
-                &prover_opts,
-            )
-            .map_err(|err| ArmError::ProveFailed(format!("Pcd proof generation failed: {}", err)))?
-            .receipt;
+        let next_receipt = proving_system::observe_proof!(
+            Aggregation,
+            prover
+                .prove_with_ctx(
+                    env,
+                    &VerifierContext::default(),
+                    <Self as PCDAggregation>::proving_key(),
+                    &prover_opts,
+                )
+                .map_err(|err| ArmError::ProveFailed(format!(
+                    "Pcd proof generation failed: {}",
+                    err
+                ))),
+            |info| bincode::serialized_size(&info.receipt.inner).unwrap_or_default() as usize
+        )?
+        .receipt;
 
         Ok(PcdProof(next_receipt.inner))
     }
 
+    /// Runs a step in the executor and checks that the guest commits the
+    /// instance [PCDAggregation::verify_step] expects of the output of
+    /// [PCDAggregation::aggregate_step], which it returns. The input
+    /// [PcdProof]s and the [StepProof] are assumed by their claims.
+    #[cfg(feature = "prove")]
+    fn check_step_guest(
+        input_aggregations: &[PcdMessage],
+        step_instance: &StepInstance,
+    ) -> Result<PcdMessage, ArmError> {
+        let ag_program_key = <Self as PCDAggregation>::verifying_key();
+        let mut env_builder = ExecutorEnv::builder();
+        env_builder.add_assumption(ReceiptClaim::ok(
+            step_instance.program,
+            words_to_bytes(&step_instance.output).to_vec(),
+        ));
+        for aggregation in input_aggregations {
+            env_builder.add_assumption(ReceiptClaim::ok(
+                ag_program_key,
+                pcd_instance(ag_program_key, aggregation)?,
+            ));
+        }
+        let env = step_env(
+            env_builder,
+            ag_program_key,
+            input_aggregations,
+            step_instance,
+        )?;
+
+        let journal = proving_system::execute_env(<Self as PCDAggregation>::proving_key(), env)?;
+        let aggregation = Self::aggregate_step(input_aggregations, step_instance);
+        if journal != pcd_instance(ag_program_key, &aggregation)? {
+            return Err(ArmError::GuestDivergence);
+        }
+        Ok(aggregation)
+    }
+
     /// Verifies validity of a step aggregation.
     fn verify_step(aggregation: &PcdMessage, proof: &PcdProof) -> Result<(), ArmError> {
         // Form the instance.
         let ag_program_key = <Self as PCDAggregation>::verifying_key();
-        let h_out = aggregation.step_output_digest;
-        let d_out = aggregation.step_program_digest;
-        let pcd_instance = risc0_zkvm::serde::to_vec(&(ag_program_key, h_out, d_out))
-            .map_err(|_| ArmError::InstanceSerializationFailed)?;
+        let pcd_instance = pcd_instance(ag_program_key, aggregation)?;
 
         // Verify the receipt.
-        let receipt = Receipt::new(proof.0.clone(), words_to_bytes(&pcd_instance).to_vec());
-        receipt.verify(ag_program_key).map_err(|err| {
-            ArmError::ProofVerificationFailed(format!("Pcd proof verification failed: {}", err))
-        })
+        let receipt = Receipt::new(proof.0.clone(), pcd_instance);
+        ProofVerifier::new().verify_receipt(&receipt, &ag_program_key)
+    }
+}
+
+// The instance an aggregation node committing the message proves.
+fn pcd_instance(ag_program_key: Digest, aggregation: &PcdMessage) -> Result<Vec<u8>, ArmError> {
+    let h_out = aggregation.step_output_digest;
+    let d_out = aggregation.step_program_digest;
+    let pcd_instance = risc0_zkvm::serde::to_vec(&(ag_program_key, h_out, d_out))
+        .map_err(|_| ArmError::InstanceSerializationFailed)?;
+    Ok(words_to_bytes(&pcd_instance).to_vec())
+}
+
+// Writes all inputs of a step. Make sure the corresponding guest circuit
+// reads in the same order.
+#[cfg(feature = "prove")]
+fn step_env<'a>(
+    mut env_builder: ExecutorEnvBuilder<'a>,
+    ag_program_key: Digest,
+    input_aggregations: &[PcdMessage],
+    step_instance: &StepInstance,
+) -> Result<ExecutorEnv<'a>, ArmError> {
+    env_builder
+        .write(&ag_program_key)
+        .map_err(|_| ArmError::WriteWitnessFailed)?;
+    for aggregation in input_aggregations {
+        env_builder
+            .write(&(
+                aggregation.step_output_digest,
+                aggregation.step_program_digest,
+            ))
+            .map_err(|_| ArmError::WriteWitnessFailed)?;
     }
+
+    env_builder
+        .write(&(&step_instance.program, &step_instance.output))
+        .map_err(|_| ArmError::WriteWitnessFailed)?
+        .build()
+        .map_err(|_| ArmError::BuildProverEnvFailed)
 }
 
 /// The input and output of an aggregation node (step).
@@ -174,8 +240,8 @@ pub struct PcdMessage {
 impl Default for PcdMessage {
     fn default() -> Self {
         PcdMessage {
-            step_output_digest: Digest::ZERO,
-            step_program_digest: Digest::ZERO,
+            step_output_digest: BASE_CASE_DIGEST,
+            step_program_digest: BASE_CASE_DIGEST,
         }
     }
 }
@@ -226,18 +292,33 @@ impl TryFrom<BatchLP> for Vec<StepInstance> {
     fn try_from(value: BatchLP) -> Result<Self, Self::Error> {
         let mut step_instances = Vec::new();
         for (lp_instance, lp_key) in value.instances.into_iter().zip(value.keys.into_iter()) {
-            let output: LogicInstance = proving_system::journal_to_instance(&lp_instance)?;
-            let image_id = lp_key;
-            step_instances.push(StepInstance::new(output, image_id)?);
+            // Bound instances are verified with their image id, which must
+            // be the key.
+            let step_instance = match decode_journal(&lp_instance)? {
+                (output, None) => StepInstance::new(output, lp_key)?,
+                (output, Some(image_id)) if image_id == lp_key => {
+                    StepInstance::new(output.bind_image_id(image_id), lp_key)?
+                }
+                _ => return Err(ArmError::VerifyingKeyMismatch),
+            };
+            step_instances.push(step_instance);
         }
         Ok(step_instances)
     }
 }
 
 /// A proof attesting to the correctness of an [PcdMessage].
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct PcdProof(pub InnerReceipt);
 
+impl std::fmt::Debug for PcdProof {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("PcdProof")
+            .field(&format_args!("{}", receipt_kind(&self.0)))
+            .finish()
+    }
+}
+
 /// A proof attesting to the correctness of a [StepInstance].
 #[derive(Debug)]
 pub struct StepProof(pub Receipt);
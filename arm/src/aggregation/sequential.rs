@@ -2,10 +2,11 @@
 
 use risc0_zkvm::Digest;
 
+#[cfg(feature = "prove")]
+use crate::proving_system::ProofType;
 use crate::{
     aggregation::constants::{SEQUENTIAL_AGGREGATION_PK, SEQUENTIAL_AGGREGATION_VK},
     error::ArmError,
-    proving_system::ProofType,
     transaction::Transaction,
 };
 
@@ -30,6 +31,7 @@ impl SequentialAggregation {
     }
 
     /// Prove correctness of an aggregation transcript.
+    #[cfg(feature = "prove")]
     pub fn prove_transcript_aggregation(
         instances: &[StepInstance],
         proofs: &[StepProof],
@@ -63,6 +65,7 @@ impl SequentialAggregation {
     }
 
     /// Prove correctness of the transcript induced by a transaction.
+    #[cfg(feature = "prove")]
     pub fn prove_transaction_aggregation(
         tx: &Transaction,
         proof_type: ProofType,
@@ -96,6 +99,23 @@ impl SequentialAggregation {
         SequentialAggregation::verify_transcript_aggregation(&instances, proof)
     }
 
+    /// Runs every step of the transcript induced by the transaction in the
+    /// executor and checks that the guest commits the aggregation the
+    /// verifier expects. See [PCDAggregation::check_step_guest].
+    #[cfg(feature = "prove")]
+    pub fn check_guest(tx: &Transaction) -> Result<(), ArmError> {
+        let (instances, _) = SequentialAggregation::transaction_transcript(tx)?;
+        instances
+            .iter()
+            .try_fold(PcdMessage::default(), |aggregation, instance| {
+                <SequentialAggregation as PCDAggregation>::check_step_guest(
+                    &[aggregation],
+                    instance,
+                )
+            })?;
+        Ok(())
+    }
+
     /// Derives the transcript induced by the transaction.
     pub fn transaction_transcript(
         tx: &Transaction,
@@ -0,0 +1,115 @@
+//! Verifiers of transaction aggregation proofs.
+//!
+//! [`Transaction::verify`] checks aggregation proofs with the aggregation
+//! circuits, which requires the `aggregation` feature and its guests.
+//! [`Transaction::verify_with_aggregation_verifier`] takes the verifier as an
+//! [`AggregationVerifier`] instead, so downstream crates can unit-test flows
+//! with aggregated transactions against a [`MockAggregationVerifier`] and
+//! [`MockAggregationProof`]s (feature `test-aggregation`), without compiling
+//! the aggregation guests or proving. `Transaction::verify` never accepts
+//! mock proofs.
+
+#[cfg(any(test, feature = "test-aggregation"))]
+use crate::utils::hash_bytes;
+use crate::{error::ArmError, transaction::Transaction};
+
+/// Checks the aggregation proof of a transaction.
+pub trait AggregationVerifier: Send + Sync {
+    /// Verifies `proof`, the aggregation proof of `tx`, against its
+    /// compliance and logic instances.
+    fn verify_aggregation(&self, tx: &Transaction, proof: &[u8]) -> Result<(), ArmError>;
+}
+
+/// The verifier of the aggregation circuits, as used by
+/// [`Transaction::verify`].
+#[cfg(feature = "aggregation")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CircuitAggregationVerifier;
+
+#[cfg(feature = "aggregation")]
+impl AggregationVerifier for CircuitAggregationVerifier {
+    fn verify_aggregation(&self, tx: &Transaction, _proof: &[u8]) -> Result<(), ArmError> {
+        tx.verify_aggregation()
+    }
+}
+
+#[cfg(any(test, feature = "test-aggregation"))]
+const MOCK_AGGREGATION_DOMAIN: &[u8] = b"ARM_MOCK_AGGREGATION";
+
+/// A fake aggregation proof for tests: it's bound to the delta message of
+/// the transaction and only accepted by [`MockAggregationVerifier`].
+#[cfg(any(test, feature = "test-aggregation"))]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MockAggregationProof;
+
+#[cfg(any(test, feature = "test-aggregation"))]
+impl MockAggregationProof {
+    /// Attaches a mock aggregation proof to the transaction and erases its
+    /// compliance and logic proofs, like a real aggregation does.
+    pub fn attach(tx: &mut Transaction) -> Result<(), ArmError> {
+        tx.aggregation_proof = Some(Self::proof_of(tx)?);
+        tx.erase_base_proofs();
+        Ok(())
+    }
+
+    fn proof_of(tx: &Transaction) -> Result<Vec<u8>, ArmError> {
+        let mut proof = MOCK_AGGREGATION_DOMAIN.to_vec();
+        proof.extend_from_slice(hash_bytes(&tx.get_delta_msg()?).as_bytes());
+        Ok(proof)
+    }
+}
+
+/// Accepts the [`MockAggregationProof`]s of transactions, and nothing else.
+#[cfg(any(test, feature = "test-aggregation"))]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MockAggregationVerifier;
+
+#[cfg(any(test, feature = "test-aggregation"))]
+impl AggregationVerifier for MockAggregationVerifier {
+    fn verify_aggregation(&self, tx: &Transaction, proof: &[u8]) -> Result<(), ArmError> {
+        if proof != MockAggregationProof::proof_of(tx)? {
+            return Err(ArmError::ProofVerificationFailed(
+                "invalid mock aggregation proof".into(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_mock_aggregation() {
+    use crate::{
+        action::unproven_action, compliance::ComplianceWitness, delta_proof::DeltaWitness,
+        transaction::Delta,
+    };
+
+    // An action without proofs, balanced with rcv = 1.
+    let action = unproven_action(&ComplianceWitness::default());
+    let mut one = [0u8; 32];
+    one[31] = 1;
+    let mut tx = Transaction::create(
+        vec![action],
+        Delta::Witness(DeltaWitness::from_bytes(&one).unwrap()),
+    )
+    .generate_delta_proof()
+    .unwrap();
+    assert!(tx
+        .clone()
+        .verify_with_aggregation_verifier(&MockAggregationVerifier)
+        .is_err());
+
+    MockAggregationProof::attach(&mut tx).unwrap();
+    assert!(tx.aggregation_proof.is_some());
+    assert_eq!(
+        tx.clone()
+            .verify_with_aggregation_verifier(&MockAggregationVerifier),
+        Ok(())
+    );
+    // The real verifier rejects mock proofs.
+    assert!(tx.clone().verify().is_err());
+
+    tx.aggregation_proof.as_mut().unwrap()[0] ^= 1;
+    assert!(tx
+        .verify_with_aggregation_verifier(&MockAggregationVerifier)
+        .is_err());
+}
@@ -0,0 +1,179 @@
+//! On-disk cache of proving artifacts with a signed integrity manifest.
+//!
+//! Receipts and aggregation intermediates are stored keyed by the digest of
+//! the witness that produced them, so a crashed transaction-build process can
+//! resume assembly without re-proving finished units. The manifest records
+//! the digest of every artifact and is signed by the cache owner; artifacts
+//! that don't match it are never returned.
+
+use crate::{error::ArmError, utils::hash_bytes};
+use k256::ecdsa::{
+    signature::{Signer, Verifier},
+    Signature, SigningKey, VerifyingKey,
+};
+use risc0_zkvm::Digest;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "prove")]
+use crate::{
+    compliance::ComplianceWitness, compliance_unit::ComplianceUnit, proving_system::ProofType,
+};
+
+const MANIFEST_FILE: &str = "manifest.bin";
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct SignedManifest {
+    // Witness digest -> artifact digest
+    entries: BTreeMap<Digest, Digest>,
+    signature: Vec<u8>,
+}
+
+/// A directory of proving artifacts keyed by witness digest.
+pub struct ArtifactCache {
+    dir: PathBuf,
+    signing_key: SigningKey,
+    entries: BTreeMap<Digest, Digest>,
+}
+
+impl ArtifactCache {
+    /// Opens (or creates) a cache directory, checking the manifest signature
+    /// against the given key.
+    pub fn open(dir: impl AsRef<Path>, signing_key: SigningKey) -> Result<Self, ArmError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir).map_err(io_error)?;
+
+        let manifest_path = dir.join(MANIFEST_FILE);
+        let entries = if manifest_path.exists() {
+            let bytes = fs::read(&manifest_path).map_err(io_error)?;
+            let manifest: SignedManifest =
+                bincode::deserialize(&bytes).map_err(|_| ArmError::InvalidArtifactManifest)?;
+            let signature = Signature::from_slice(&manifest.signature)
+                .map_err(|_| ArmError::InvalidArtifactManifest)?;
+            VerifyingKey::from(&signing_key)
+                .verify(&manifest_message(&manifest.entries)?, &signature)
+                .map_err(|_| ArmError::InvalidArtifactManifest)?;
+            manifest.entries
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(ArtifactCache {
+            dir,
+            signing_key,
+            entries,
+        })
+    }
+
+    /// Computes the cache key of a witness.
+    pub fn witness_digest<T: Serialize>(witness: &T) -> Result<Digest, ArmError> {
+        let bytes = bincode::serialize(witness).map_err(|_| ArmError::SerializationError)?;
+        Ok(hash_bytes(&bytes))
+    }
+
+    /// Returns whether an artifact is recorded for the key.
+    pub fn contains(&self, key: &Digest) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// Returns the artifact stored under the key, checking its integrity.
+    pub fn get(&self, key: &Digest) -> Result<Option<Vec<u8>>, ArmError> {
+        let Some(expected) = self.entries.get(key) else {
+            return Ok(None);
+        };
+        let artifact = fs::read(self.artifact_path(key)).map_err(io_error)?;
+        if hash_bytes(&artifact) != *expected {
+            return Err(ArmError::InvalidArtifactManifest);
+        }
+        Ok(Some(artifact))
+    }
+
+    /// Stores an artifact under the key and re-signs the manifest.
+    pub fn put(&mut self, key: &Digest, artifact: &[u8]) -> Result<(), ArmError> {
+        write_atomic(&self.artifact_path(key), artifact)?;
+        self.entries.insert(*key, hash_bytes(artifact));
+        self.write_manifest()
+    }
+
+    /// Returns the cached compliance unit for the witness, proving and
+    /// caching it if absent.
+    #[cfg(feature = "prove")]
+    pub fn compliance_unit(
+        &mut self,
+        witness: &ComplianceWitness,
+        proof_type: ProofType,
+    ) -> Result<ComplianceUnit, ArmError> {
+        let key = Self::witness_digest(&(witness, proof_type as u8))?;
+        if let Some(bytes) = self.get(&key)? {
+            return bincode::deserialize(&bytes).map_err(|_| ArmError::DeserializationError);
+        }
+
+        let unit = ComplianceUnit::create(witness, proof_type)?;
+        let bytes = bincode::serialize(&unit).map_err(|_| ArmError::SerializationError)?;
+        self.put(&key, &bytes)?;
+        Ok(unit)
+    }
+
+    fn artifact_path(&self, key: &Digest) -> PathBuf {
+        self.dir
+            .join(format!("{}.bin", hex::encode(key.as_bytes())))
+    }
+
+    fn write_manifest(&self) -> Result<(), ArmError> {
+        let signature: Signature = self.signing_key.sign(&manifest_message(&self.entries)?);
+        let manifest = SignedManifest {
+            entries: self.entries.clone(),
+            signature: signature.to_bytes().to_vec(),
+        };
+        let bytes = bincode::serialize(&manifest).map_err(|_| ArmError::SerializationError)?;
+        write_atomic(&self.dir.join(MANIFEST_FILE), &bytes)
+    }
+}
+
+fn manifest_message(entries: &BTreeMap<Digest, Digest>) -> Result<Vec<u8>, ArmError> {
+    bincode::serialize(entries).map_err(|_| ArmError::SerializationError)
+}
+
+// Write to a temporary file first so a crash never leaves a truncated file.
+pub(crate) fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), ArmError> {
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, bytes).map_err(io_error)?;
+    fs::rename(&tmp_path, path).map_err(io_error)
+}
+
+fn io_error(err: std::io::Error) -> ArmError {
+    ArmError::ArtifactCacheError(err.to_string())
+}
+
+#[test]
+fn test_artifact_cache() {
+    use crate::compliance::ComplianceWitness;
+    use crate::rng::ArmRng;
+    use rand::RngCore;
+
+    let dir = std::env::temp_dir().join(format!("arm-artifacts-{}", ArmRng::new().next_u64()));
+    let signing_key = SigningKey::random(&mut ArmRng::new());
+    let key = ArtifactCache::witness_digest(&ComplianceWitness::default()).unwrap();
+
+    let mut cache = ArtifactCache::open(&dir, signing_key.clone()).unwrap();
+    assert_eq!(cache.get(&key).unwrap(), None);
+    cache.put(&key, b"receipt").unwrap();
+
+    // Resume from disk.
+    let cache = ArtifactCache::open(&dir, signing_key.clone()).unwrap();
+    assert!(cache.contains(&key));
+    assert_eq!(cache.get(&key).unwrap(), Some(b"receipt".to_vec()));
+
+    // Tampered artifacts are rejected.
+    fs::write(cache.artifact_path(&key), b"forged").unwrap();
+    assert_eq!(cache.get(&key), Err(ArmError::InvalidArtifactManifest));
+
+    // Manifests signed by someone else are rejected.
+    assert!(ArtifactCache::open(&dir, SigningKey::random(&mut ArmRng::new())).is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,321 @@
+//! Standalone audit bundles of transaction proofs.
+//!
+//! An [`AuditBundle`] records the receipts, journals and image ids of a
+//! transaction's compliance and logic proofs, its delta proof, and the steps
+//! verifying them. A third party can check it with [`AuditBundle::verify`]
+//! without access to the environment that built the transaction.
+
+use crate::{
+    action_tree::MerkleTree,
+    compliance::ComplianceInstance,
+    constants::COMPLIANCE_VK,
+    delta_proof::DeltaProof,
+    error::ArmError,
+    logic_instance::LogicInstance,
+    proving_system::{journal_to_instance, verify as verify_proof},
+    transaction::{Delta, Transaction},
+};
+use risc0_zkvm::Digest;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// The version of the audit bundle format.
+pub const AUDIT_BUNDLE_VERSION: u32 = 1;
+
+/// The circuit a proof in an audit bundle belongs to.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum AuditProofKind {
+    /// A compliance proof.
+    Compliance,
+    /// A resource logic proof.
+    Logic,
+}
+
+/// A proof recorded in an audit bundle.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct AuditProof {
+    /// The circuit the proof belongs to.
+    pub kind: AuditProofKind,
+    /// The index of the action the proof belongs to.
+    pub action: usize,
+    /// The image id the receipt verifies against.
+    pub image_id: Digest,
+    /// The journal of the receipt, i.e. the serialized instance.
+    pub journal: Vec<u8>,
+    /// The serialized inner receipt.
+    pub receipt: Vec<u8>,
+}
+
+/// A verification step of an audit bundle.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum AuditStep {
+    /// Verifies the receipt of the proof at the given index.
+    VerifyReceipt(usize),
+    /// Checks that the logic journals of the action commit to the action
+    /// tree of its compliance journals, with the committed logic refs.
+    CheckActionTree(usize),
+    /// Checks that no nullifier is revealed twice.
+    CheckNullifiers,
+    /// Verifies the delta proof against the compliance journals.
+    VerifyDelta,
+}
+
+/// A standalone export of a transaction's proofs.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct AuditBundle {
+    /// The version of the bundle format.
+    pub version: u32,
+    /// The version of the crate that exported the bundle.
+    pub arm_version: String,
+    /// The number of actions in the transaction.
+    pub actions: usize,
+    /// The compliance and logic proofs, in canonical order.
+    pub proofs: Vec<AuditProof>,
+    /// The message signed by the delta proof.
+    pub delta_msg: Vec<u8>,
+    /// The serialized delta proof.
+    pub delta_proof: Vec<u8>,
+    /// The verification steps, in the order they run.
+    pub steps: Vec<AuditStep>,
+}
+
+impl AuditBundle {
+    /// Returns the steps verifying the given proofs of a transaction with
+    /// `actions` actions.
+    pub fn expected_steps(proofs: &[AuditProof], actions: usize) -> Vec<AuditStep> {
+        (0..proofs.len())
+            .map(AuditStep::VerifyReceipt)
+            .chain((0..actions).map(AuditStep::CheckActionTree))
+            .chain([AuditStep::CheckNullifiers, AuditStep::VerifyDelta])
+            .collect()
+    }
+
+    /// Serializes the bundle into a standalone archive.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ArmError> {
+        bincode::serialize(self).map_err(|_| ArmError::SerializationError)
+    }
+
+    /// Deserializes a bundle from an archive.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ArmError> {
+        bincode::deserialize(bytes).map_err(|_| ArmError::DeserializationError)
+    }
+
+    /// Runs all the verification steps of the bundle. The steps must be the
+    /// expected steps for its proofs, so none can be left out.
+    pub fn verify(&self) -> Result<(), ArmError> {
+        if self.version != AUDIT_BUNDLE_VERSION
+            || self.proofs.iter().any(|proof| proof.action >= self.actions)
+            || self.steps != Self::expected_steps(&self.proofs, self.actions)
+        {
+            return Err(ArmError::InvalidAuditBundle);
+        }
+        for step in &self.steps {
+            self.run(*step)?;
+        }
+        Ok(())
+    }
+
+    fn run(&self, step: AuditStep) -> Result<(), ArmError> {
+        match step {
+            AuditStep::VerifyReceipt(index) => {
+                let proof = &self.proofs[index];
+                if proof.kind == AuditProofKind::Compliance && proof.image_id != *COMPLIANCE_VK {
+                    return Err(ArmError::InvalidAuditBundle);
+                }
+                verify_proof(&proof.image_id, &proof.journal, &proof.receipt)
+            }
+            AuditStep::CheckActionTree(action) => {
+                let compliance_instances = self.compliance_instances(Some(action))?;
+                let tags_and_logics: Vec<(Digest, Digest)> = compliance_instances
+                    .iter()
+                    .flat_map(|instance| {
+                        vec![
+                            (instance.consumed_nullifier, instance.consumed_logic_ref),
+                            (instance.created_commitment, instance.created_logic_ref),
+                        ]
+                    })
+                    .collect();
+                let root = MerkleTree::from(
+                    tags_and_logics
+                        .iter()
+                        .map(|(tag, _)| *tag)
+                        .collect::<Vec<_>>(),
+                )
+                .root()?;
+
+                let logic_proofs = self
+                    .proofs
+                    .iter()
+                    .filter(|proof| proof.kind == AuditProofKind::Logic && proof.action == action)
+                    .collect::<Vec<_>>();
+                if logic_proofs.len() != tags_and_logics.len() {
+                    return Err(ArmError::InvalidAuditBundle);
+                }
+                for (proof, (tag, logic)) in logic_proofs.into_iter().zip(tags_and_logics) {
+                    let instance: LogicInstance = journal_to_instance(&proof.journal)?;
+                    if instance.tag != tag || instance.root != root {
+                        return Err(ArmError::TagNotFound.with_tag(action, tag));
+                    }
+                    if proof.image_id != logic {
+                        return Err(ArmError::VerifyingKeyMismatch.with_tag(action, tag));
+                    }
+                }
+                Ok(())
+            }
+            AuditStep::CheckNullifiers => {
+                let mut seen_nullifiers = HashSet::new();
+                for proof in self.compliance_proofs(None) {
+                    let instance: ComplianceInstance = journal_to_instance(&proof.journal)?;
+                    if !seen_nullifiers.insert(instance.consumed_nullifier) {
+                        return Err(ArmError::NullifierDuplication
+                            .with_tag(proof.action, instance.consumed_nullifier));
+                    }
+                }
+                Ok(())
+            }
+            AuditStep::VerifyDelta => {
+                let compliance_instances = self.compliance_instances(None)?;
+                // The delta message starts with the compliance delta messages.
+                let msg: Vec<u8> = compliance_instances
+                    .iter()
+                    .flat_map(|instance| instance.delta_msg())
+                    .collect();
+                if !self.delta_msg.starts_with(&msg) {
+                    return Err(ArmError::InvalidAuditBundle);
+                }
+                DeltaProof::verify(
+                    &self.delta_msg,
+                    &DeltaProof::from_bytes(&self.delta_proof)?,
+                    DeltaProof::expected_key(&compliance_instances)?,
+                )
+            }
+        }
+    }
+
+    fn compliance_proofs(&self, action: Option<usize>) -> impl Iterator<Item = &AuditProof> {
+        self.proofs.iter().filter(move |proof| {
+            proof.kind == AuditProofKind::Compliance && action.is_none_or(|a| proof.action == a)
+        })
+    }
+
+    fn compliance_instances(
+        &self,
+        action: Option<usize>,
+    ) -> Result<Vec<ComplianceInstance>, ArmError> {
+        self.compliance_proofs(action)
+            .map(|proof| journal_to_instance(&proof.journal))
+            .collect()
+    }
+}
+
+impl Transaction {
+    /// Exports the proofs of the transaction into an audit bundle. Every
+    /// compliance and logic proof must be present, so aggregated transactions
+    /// can't be exported.
+    pub fn export_audit_bundle(&self) -> Result<AuditBundle, ArmError> {
+        let delta_proof = match &self.delta_proof {
+            Delta::Proof(proof) => proof.to_bytes().to_vec(),
+            Delta::OpeningProof(_) => return Err(ArmError::UnsupportedDeltaProofMode),
+            Delta::Witness(_) | Delta::Unbalanced { .. } => {
+                return Err(ArmError::ExpectedDeltaProof)
+            }
+        };
+        let missing = || {
+            ArmError::ProofVerificationFailed(
+                "Cannot export audit bundle: missing individual proof(s)".into(),
+            )
+        };
+
+        let mut proofs = Vec::new();
+        for (index, action) in self.actions.iter().enumerate() {
+            for unit in action.get_compliance_units() {
+                proofs.push(AuditProof {
+                    kind: AuditProofKind::Compliance,
+                    action: index,
+                    image_id: *COMPLIANCE_VK,
+                    journal: unit.instance.clone(),
+                    receipt: unit.proof.clone().ok_or_else(missing)?,
+                });
+            }
+            let logic_verifiers = action
+                .get_logic_verifiers()
+                .map_err(|err| err.at_action(index))?;
+            for verifier in logic_verifiers {
+                proofs.push(AuditProof {
+                    kind: AuditProofKind::Logic,
+                    action: index,
+                    image_id: verifier.verifying_key,
+                    journal: verifier.instance,
+                    receipt: verifier.proof.ok_or_else(missing)?,
+                });
+            }
+        }
+
+        Ok(AuditBundle {
+            version: AUDIT_BUNDLE_VERSION,
+            arm_version: env!("CARGO_PKG_VERSION").to_string(),
+            actions: self.actions.len(),
+            steps: AuditBundle::expected_steps(&proofs, self.actions.len()),
+            proofs,
+            delta_msg: self.get_delta_msg()?,
+            delta_proof,
+        })
+    }
+}
+
+#[test]
+fn test_audit_bundle() {
+    use crate::{
+        action::unproven_action, compliance::ComplianceWitness, delta_proof::DeltaWitness,
+    };
+
+    let action = unproven_action(&ComplianceWitness::default());
+    let journal = action.compliance_units[0].instance.clone();
+    let mut one = [0u8; 32];
+    one[31] = 1;
+    let tx = Transaction::create(
+        vec![action],
+        Delta::Witness(DeltaWitness::from_bytes(&one).unwrap()),
+    );
+    assert_eq!(tx.export_audit_bundle(), Err(ArmError::ExpectedDeltaProof));
+    let tx = tx.generate_delta_proof().unwrap();
+    assert!(tx.export_audit_bundle().is_err());
+
+    let proofs = vec![AuditProof {
+        kind: AuditProofKind::Compliance,
+        action: 0,
+        image_id: *COMPLIANCE_VK,
+        journal,
+        receipt: vec![],
+    }];
+    let mut bundle = AuditBundle {
+        version: AUDIT_BUNDLE_VERSION,
+        arm_version: env!("CARGO_PKG_VERSION").to_string(),
+        actions: 1,
+        steps: AuditBundle::expected_steps(&proofs, 1),
+        proofs,
+        delta_msg: tx.get_delta_msg().unwrap(),
+        delta_proof: match &tx.delta_proof {
+            Delta::Proof(proof) => proof.to_bytes().to_vec(),
+            _ => unreachable!(),
+        },
+    };
+    assert_eq!(
+        AuditBundle::from_bytes(&bundle.to_bytes().unwrap()).unwrap(),
+        bundle
+    );
+    assert!(bundle.run(AuditStep::CheckNullifiers).is_ok());
+    assert!(bundle.run(AuditStep::CheckActionTree(0)).is_err());
+    assert!(bundle.run(AuditStep::VerifyDelta).is_ok());
+    assert_eq!(
+        bundle.verify(),
+        Err(ArmError::InnerReceiptDeserializationError)
+    );
+
+    // Steps can't be left out.
+    bundle.steps.remove(0);
+    assert_eq!(bundle.verify(), Err(ArmError::InvalidAuditBundle));
+    bundle.actions = 0;
+    bundle.steps = AuditBundle::expected_steps(&bundle.proofs, 0);
+    assert_eq!(bundle.verify(), Err(ArmError::InvalidAuditBundle));
+}
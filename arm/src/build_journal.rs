@@ -0,0 +1,221 @@
+//! Durable journal of transaction-build progress.
+//!
+//! A [`BuildJournal`] records which steps of a transaction build (compliance
+//! and logic proofs, aggregation) were planned and which are done, keyed like
+//! the [`ArtifactCache`] entries holding their results. A wallet killed
+//! mid-build reopens the journal, replays the same steps, and only the
+//! unfinished ones are proved; the finished ones are read back from the cache.
+
+use crate::{
+    artifact_cache::{write_atomic, ArtifactCache},
+    error::ArmError,
+};
+use risc0_zkvm::Digest;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "prove")]
+use crate::proving_system::ProofType;
+#[cfg(all(feature = "aggregation", feature = "prove"))]
+use crate::transaction::Transaction;
+#[cfg(feature = "prove")]
+use crate::{
+    compliance::ComplianceWitness,
+    compliance_unit::ComplianceUnit,
+    logic_proof::{LogicProver, LogicVerifier},
+};
+
+/// The recorded progress of a transaction build.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BuildProgress {
+    /// The keys of the planned steps, in planning order.
+    pub planned: Vec<Digest>,
+    /// The keys of the finished steps.
+    pub done: BTreeSet<Digest>,
+    /// The key of the aggregation step, once planned.
+    pub aggregation: Option<Digest>,
+}
+
+impl BuildProgress {
+    /// Returns the keys of the planned steps that aren't finished yet.
+    pub fn pending(&self) -> Vec<Digest> {
+        self.planned
+            .iter()
+            .filter(|key| !self.done.contains(key))
+            .copied()
+            .collect()
+    }
+
+    /// Returns whether the aggregation step is finished.
+    pub fn is_aggregated(&self) -> bool {
+        self.aggregation.is_some_and(|key| self.done.contains(&key))
+    }
+}
+
+/// A journal of build progress persisted to a file.
+pub struct BuildJournal {
+    path: PathBuf,
+    progress: BuildProgress,
+}
+
+impl BuildJournal {
+    /// Opens (or creates) the journal at `path`, resuming its progress.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, ArmError> {
+        let path = path.as_ref().to_path_buf();
+        let progress = if path.exists() {
+            let bytes =
+                fs::read(&path).map_err(|err| ArmError::ArtifactCacheError(err.to_string()))?;
+            bincode::deserialize(&bytes).map_err(|_| ArmError::DeserializationError)?
+        } else {
+            BuildProgress::default()
+        };
+        Ok(BuildJournal { path, progress })
+    }
+
+    /// Returns the recorded progress.
+    pub fn progress(&self) -> &BuildProgress {
+        &self.progress
+    }
+
+    /// Records a planned step (e.g. a prepared witness), returning whether it
+    /// is already finished.
+    pub fn plan(&mut self, key: Digest) -> Result<bool, ArmError> {
+        if !self.progress.planned.contains(&key) {
+            self.progress.planned.push(key);
+            self.persist()?;
+        }
+        Ok(self.progress.done.contains(&key))
+    }
+
+    /// Runs the step `key`: returns its artifact from the cache if it's
+    /// finished, otherwise produces it with `produce`, caches it, and records
+    /// it as finished.
+    pub fn run_step<T: Serialize + DeserializeOwned>(
+        &mut self,
+        cache: &mut ArtifactCache,
+        key: Digest,
+        produce: impl FnOnce() -> Result<T, ArmError>,
+    ) -> Result<T, ArmError> {
+        self.plan(key)?;
+        if self.progress.done.contains(&key) {
+            if let Some(bytes) = cache.get(&key)? {
+                return bincode::deserialize(&bytes).map_err(|_| ArmError::DeserializationError);
+            }
+            // The artifact was evicted from the cache; redo the step.
+            self.progress.done.remove(&key);
+        }
+
+        let artifact = produce()?;
+        let bytes = bincode::serialize(&artifact).map_err(|_| ArmError::SerializationError)?;
+        cache.put(&key, &bytes)?;
+        self.progress.done.insert(key);
+        self.persist()?;
+        Ok(artifact)
+    }
+
+    /// Proves a compliance unit, or reuses it if a previous run finished it.
+    #[cfg(feature = "prove")]
+    pub fn prove_compliance(
+        &mut self,
+        cache: &mut ArtifactCache,
+        witness: &ComplianceWitness,
+        proof_type: ProofType,
+    ) -> Result<ComplianceUnit, ArmError> {
+        let key = ArtifactCache::witness_digest(&(witness, proof_type as u8))?;
+        self.run_step(cache, key, || ComplianceUnit::create(witness, proof_type))
+    }
+
+    /// Proves a resource logic, or reuses it if a previous run finished it.
+    #[cfg(feature = "prove")]
+    pub fn prove_logic<L: LogicProver>(
+        &mut self,
+        cache: &mut ArtifactCache,
+        logic: &L,
+        proof_type: ProofType,
+    ) -> Result<LogicVerifier, ArmError> {
+        let key = ArtifactCache::witness_digest(&(
+            L::verifying_key(),
+            logic.witness(),
+            proof_type as u8,
+        ))?;
+        self.run_step(cache, key, || logic.prove(proof_type))
+    }
+
+    /// Aggregates the transaction proofs, or reuses the aggregated
+    /// transaction if a previous run finished it.
+    #[cfg(all(feature = "aggregation", feature = "prove"))]
+    pub fn aggregate(
+        &mut self,
+        cache: &mut ArtifactCache,
+        tx: Transaction,
+        proof_type: ProofType,
+    ) -> Result<Transaction, ArmError> {
+        let key = ArtifactCache::witness_digest(&(&tx, proof_type as u8))?;
+        self.progress.aggregation = Some(key);
+        self.run_step(cache, key, move || {
+            let mut tx = tx;
+            tx.aggregate(proof_type)?;
+            Ok(tx)
+        })
+    }
+
+    /// Deletes the journal once the build is complete.
+    pub fn finish(self) -> Result<(), ArmError> {
+        if self.path.exists() {
+            fs::remove_file(&self.path)
+                .map_err(|err| ArmError::ArtifactCacheError(err.to_string()))?;
+        }
+        Ok(())
+    }
+
+    fn persist(&self) -> Result<(), ArmError> {
+        let bytes = bincode::serialize(&self.progress).map_err(|_| ArmError::SerializationError)?;
+        write_atomic(&self.path, &bytes)
+    }
+}
+
+#[test]
+fn test_build_journal_resume() {
+    use crate::rng::ArmRng;
+    use k256::ecdsa::SigningKey;
+    use rand::RngCore;
+
+    let dir = std::env::temp_dir().join(format!("arm-build-{}", ArmRng::new().next_u64()));
+    let signing_key = SigningKey::random(&mut ArmRng::new());
+    let journal_path = dir.join("journal.bin");
+    let mut cache = ArtifactCache::open(&dir, signing_key).unwrap();
+    let first = Digest::from_bytes([1u8; 32]);
+    let second = Digest::from_bytes([2u8; 32]);
+
+    // The build is interrupted while producing the second artifact.
+    let mut journal = BuildJournal::open(&journal_path).unwrap();
+    assert!(!journal.plan(second).unwrap());
+    let artifact: u32 = journal.run_step(&mut cache, first, || Ok(1)).unwrap();
+    assert_eq!(artifact, 1);
+    assert!(journal
+        .run_step::<u32>(&mut cache, second, || Err(ArmError::ProveFailed(
+            "killed".into()
+        )))
+        .is_err());
+    drop(journal);
+
+    // On resume, finished steps come from the cache.
+    let mut journal = BuildJournal::open(&journal_path).unwrap();
+    assert_eq!(journal.progress().pending(), vec![second]);
+    let artifact: u32 = journal
+        .run_step(&mut cache, first, || panic!("already proved"))
+        .unwrap();
+    assert_eq!(artifact, 1);
+    let artifact: u32 = journal.run_step(&mut cache, second, || Ok(2)).unwrap();
+    assert_eq!(artifact, 2);
+    assert!(journal.progress().pending().is_empty());
+    assert!(!journal.progress().is_aggregated());
+
+    journal.finish().unwrap();
+    assert!(!journal_path.exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
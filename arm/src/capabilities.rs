@@ -0,0 +1,144 @@
+//! Capabilities of this build of the crate.
+//!
+//! Bindings and tools call [`capabilities`] to find out which proof types,
+//! aggregation strategies, hash accelerations and prover backends are
+//! compiled in and usable, instead of calling unsupported paths and handling
+//! the errors. Names are those accepted by the corresponding `FromStr`
+//! implementations and `ARM_*` variables, so they can be passed back as is.
+
+use serde::{Deserialize, Serialize};
+
+/// What this build of the crate supports.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Capabilities {
+    /// The version of this crate.
+    pub arm_version: String,
+    /// The enabled cargo features.
+    pub features: Vec<String>,
+    /// The proof types that can be generated.
+    pub proof_types: Vec<String>,
+    /// The proof types that can be verified.
+    pub verifiable_proof_types: Vec<String>,
+    /// The aggregation strategies that can be used.
+    pub aggregation_strategies: Vec<String>,
+    /// The accelerated hash implementations.
+    pub hash_accelerations: Vec<String>,
+    /// The prover backends that can be selected.
+    pub prover_backends: Vec<String>,
+    /// The major protocol versions of the transaction encodings that can be
+    /// decoded, the last one being the version transactions are encoded with.
+    pub protocol_versions: Vec<u16>,
+    /// Whether RISC0 dev mode is on, i.e. proofs are faked and not checked.
+    pub dev_mode: bool,
+}
+
+impl Capabilities {
+    /// Returns whether a proof type can be generated.
+    pub fn can_prove(&self, proof_type: &str) -> bool {
+        self.proof_types.iter().any(|t| t == proof_type)
+    }
+}
+
+const FEATURES: &[(&str, bool)] = &[
+    ("transaction", cfg!(feature = "transaction")),
+    ("compliance_circuit", cfg!(feature = "compliance_circuit")),
+    ("prove", cfg!(feature = "prove")),
+    ("bonsai", cfg!(feature = "bonsai")),
+    ("cuda", cfg!(feature = "cuda")),
+    ("aggregation", cfg!(feature = "aggregation")),
+    ("aggregation_circuit", cfg!(feature = "aggregation_circuit")),
+    ("compression", cfg!(feature = "compression")),
+    ("config", cfg!(feature = "config")),
+    ("metrics", cfg!(feature = "metrics")),
+    ("parallel", cfg!(feature = "parallel")),
+    ("borsh", cfg!(feature = "borsh")),
+    ("groth16-verify-only", cfg!(feature = "groth16-verify-only")),
+    ("test-rng", cfg!(feature = "test-rng")),
+    ("test-aggregation", cfg!(feature = "test-aggregation")),
+    ("bench-circuits", cfg!(feature = "bench-circuits")),
+    ("batch-hash", cfg!(feature = "batch-hash")),
+];
+
+fn names(candidates: &[(&str, bool)]) -> Vec<String> {
+    candidates
+        .iter()
+        .filter(|(_, available)| *available)
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
+/// Reports the capabilities of this build.
+pub fn capabilities() -> Capabilities {
+    let prove = cfg!(feature = "prove");
+    let transaction = cfg!(feature = "transaction");
+    Capabilities {
+        arm_version: env!("CARGO_PKG_VERSION").to_string(),
+        features: names(FEATURES),
+        proof_types: names(&[
+            ("succinct", prove && transaction),
+            // Groth16 proving requires x86_64 machines.
+            (
+                "groth16",
+                prove && transaction && cfg!(target_arch = "x86_64"),
+            ),
+        ]),
+        verifiable_proof_types: names(&[
+            ("succinct", transaction),
+            (
+                "groth16",
+                transaction || cfg!(feature = "groth16-verify-only"),
+            ),
+        ]),
+        aggregation_strategies: names(&[
+            ("sequential", cfg!(feature = "aggregation")),
+            ("batch", cfg!(feature = "aggregation")),
+        ]),
+        hash_accelerations: names(&[
+            ("sha256-zkvm", cfg!(target_os = "zkvm")),
+            ("cuda", cfg!(feature = "cuda")),
+        ]),
+        prover_backends: names(&[
+            ("default", prove),
+            ("local", prove),
+            ("bonsai", prove && cfg!(feature = "bonsai")),
+        ]),
+        protocol_versions: protocol_versions(),
+        dev_mode: dev_mode(),
+    }
+}
+
+#[cfg(feature = "transaction")]
+fn protocol_versions() -> Vec<u16> {
+    crate::compat::supported_major_versions()
+}
+
+#[cfg(not(feature = "transaction"))]
+fn protocol_versions() -> Vec<u16> {
+    vec![]
+}
+
+#[cfg(not(target_os = "zkvm"))]
+fn dev_mode() -> bool {
+    risc0_zkvm::VerifierContext::default().dev_mode()
+}
+
+#[cfg(target_os = "zkvm")]
+fn dev_mode() -> bool {
+    false
+}
+
+#[test]
+fn test_capabilities() {
+    let capabilities = capabilities();
+    assert_eq!(capabilities.arm_version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(
+        capabilities.features.contains(&"transaction".to_string()),
+        cfg!(feature = "transaction")
+    );
+    assert_eq!(capabilities.can_prove("succinct"), cfg!(feature = "prove"));
+    assert!(!capabilities.can_prove("plonk"));
+    assert_eq!(
+        capabilities.aggregation_strategies.is_empty(),
+        !cfg!(feature = "aggregation")
+    );
+}
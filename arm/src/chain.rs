@@ -0,0 +1,41 @@
+//! The state of the chain a transaction is verified against.
+//!
+//! Verification isn't context-free: expiries, timelocks and the pruning of
+//! app data depend on when and where the transaction settles. A
+//! [`ChainContext`] makes that explicit, and is passed to the checks that
+//! need it instead of each reading the clock.
+
+use serde::{Deserialize, Serialize};
+
+/// The deletion criterion of blobs that can be deleted once the transaction
+/// has settled.
+pub const DELETE_AFTER_SETTLEMENT: u32 = 0;
+/// The deletion criterion of blobs that must be kept.
+pub const KEEP_FOREVER: u32 = 1;
+
+/// The height, time and network of the chain.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ChainContext {
+    /// The current block height.
+    pub height: u64,
+    /// The current block time, as a unix time in seconds.
+    pub timestamp: u64,
+    /// The id of the network, e.g. `mainnet`.
+    pub network_id: String,
+}
+
+impl ChainContext {
+    /// Creates the context of a chain at the given height and time.
+    pub fn new(network_id: &str, height: u64, timestamp: u64) -> Self {
+        ChainContext {
+            height,
+            timestamp,
+            network_id: network_id.to_string(),
+        }
+    }
+
+    /// Returns the current epoch for epochs of `epoch_seconds` length.
+    pub fn epoch(&self, epoch_seconds: u64) -> u64 {
+        self.timestamp / epoch_seconds.max(1)
+    }
+}
@@ -0,0 +1,412 @@
+//! The commitment tree and anchor proofs of its evolution.
+//!
+//! A [`CommitmentTree`] is the append-only tree of resource commitments
+//! maintained by wallets and test harnesses, producing the [`MerklePath`]s of
+//! consumed resources to its current root.
+//!
+//! The commitment tree is append-only: its root after a block is determined
+//! by the root before it and the commitments the block appends. The
+//! commitment anchor circuit proves exactly that, from the
+//! [`CommitmentTreeFrontier`] of the old tree, so that a light client
+//! trusting `old_root` can accept `new_root` with an [`AnchorProof`] instead
+//! of replaying all the leaves.
+//!
+//! Roots are those of [`MerkleTree::root`](crate::action_tree::MerkleTree::root)
+//! over the leaves, padded to the next power of two with [`PADDING_LEAF`];
+//! the root of the empty tree is [`PADDING_LEAF`].
+
+use crate::{
+    error::ArmError,
+    merkle_path::{MerklePath, PADDING_LEAF},
+    utils::hash_two,
+};
+use risc0_zkvm::sha::{Digest, Impl, Sha256};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "transaction")]
+use crate::proving_system::{journal_to_instance, verify};
+#[cfg(all(feature = "prove", feature = "transaction"))]
+use crate::proving_system::{prove, ProofType};
+
+/// The maximum depth of the commitment tree.
+pub const MAX_TREE_DEPTH: usize = 32;
+
+/// The rightmost nodes of an append-only tree: all that's needed to append
+/// leaves and compute the root.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitmentTreeFrontier {
+    /// The number of leaves.
+    pub size: u64,
+    /// At every level, the root of the last complete left subtree. Only the
+    /// levels of the set bits of `size` are meaningful.
+    pub branch: Vec<Digest>,
+}
+
+impl Default for CommitmentTreeFrontier {
+    fn default() -> Self {
+        CommitmentTreeFrontier {
+            size: 0,
+            branch: vec![*PADDING_LEAF; MAX_TREE_DEPTH + 1],
+        }
+    }
+}
+
+impl CommitmentTreeFrontier {
+    /// Creates the frontier of the empty tree.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a leaf.
+    pub fn append(&mut self, leaf: Digest) -> Result<(), ArmError> {
+        if self.branch.len() != MAX_TREE_DEPTH + 1 || self.size >= 1 << MAX_TREE_DEPTH {
+            return Err(ArmError::TreeTooLarge);
+        }
+        self.size += 1;
+        let mut size = self.size;
+        let mut node = leaf;
+        for branch in self.branch.iter_mut() {
+            if size & 1 == 1 {
+                *branch = node;
+                break;
+            }
+            node = hash_two(branch, &node);
+            size >>= 1;
+        }
+        Ok(())
+    }
+
+    /// Returns the root of the tree.
+    pub fn root(&self) -> Result<Digest, ArmError> {
+        if self.branch.len() != MAX_TREE_DEPTH + 1 || self.size > 1 << MAX_TREE_DEPTH {
+            return Err(ArmError::TreeTooLarge);
+        }
+        let depth = self.size.next_power_of_two().trailing_zeros() as usize;
+        if self.size.is_power_of_two() {
+            return Ok(self.branch[depth]);
+        }
+        let mut size = self.size;
+        let mut node = *PADDING_LEAF;
+        let mut empty = *PADDING_LEAF;
+        for branch in &self.branch[..depth] {
+            node = if size & 1 == 1 {
+                hash_two(branch, &node)
+            } else {
+                hash_two(&node, &empty)
+            };
+            empty = hash_two(&empty, &empty);
+            size >>= 1;
+        }
+        Ok(node)
+    }
+}
+
+/// An append-only commitment tree keeping the roots of its complete subtrees,
+/// so that it can produce the path of any leaf to the current root. It's
+/// serialized as its leaves.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<Digest>", into = "Vec<Digest>")]
+pub struct CommitmentTree {
+    // At every level, the roots of the complete subtrees from the left; the
+    // leaves are at level 0.
+    levels: Vec<Vec<Digest>>,
+}
+
+impl CommitmentTree {
+    /// Creates the empty tree.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates the tree of the leaves.
+    pub fn from_leaves(leaves: impl IntoIterator<Item = Digest>) -> Result<Self, ArmError> {
+        let mut tree = CommitmentTree::new();
+        for leaf in leaves {
+            tree.append(leaf)?;
+        }
+        Ok(tree)
+    }
+
+    /// Returns the number of leaves.
+    pub fn size(&self) -> u64 {
+        self.leaves().len() as u64
+    }
+
+    /// Returns the leaves, in order.
+    pub fn leaves(&self) -> &[Digest] {
+        self.levels.first().map_or(&[], Vec::as_slice)
+    }
+
+    /// Appends a commitment, returning its position.
+    pub fn append(&mut self, commitment: Digest) -> Result<u64, ArmError> {
+        let position = self.size();
+        if position >= 1 << MAX_TREE_DEPTH {
+            return Err(ArmError::TreeTooLarge);
+        }
+        let mut node = commitment;
+        for level in 0.. {
+            if self.levels.len() == level {
+                self.levels.push(Vec::new());
+            }
+            let nodes = &mut self.levels[level];
+            nodes.push(node);
+            if nodes.len() % 2 == 1 {
+                break;
+            }
+            node = hash_two(&nodes[nodes.len() - 2], &node);
+        }
+        Ok(position)
+    }
+
+    /// Returns the root of the tree.
+    pub fn root(&self) -> Digest {
+        self.node(self.depth(), 0)
+    }
+
+    /// Returns the path of the leaf at the position to the current root.
+    pub fn witness(&self, position: u64) -> Result<MerklePath, ArmError> {
+        if position >= self.size() {
+            return Err(ArmError::InvalidLeaf);
+        }
+        let position = position as usize;
+        let path = (0..self.depth())
+            .map(|level| {
+                let index = position >> level;
+                (self.node(level, index ^ 1), index & 1 == 1)
+            })
+            .collect();
+        Ok(MerklePath(path))
+    }
+
+    /// Returns the frontier of the tree, to keep appending without the
+    /// leaves or to prove anchors.
+    pub fn frontier(&self) -> CommitmentTreeFrontier {
+        let mut frontier = CommitmentTreeFrontier::new();
+        frontier.size = self.size();
+        for (branch, nodes) in frontier.branch.iter_mut().zip(&self.levels) {
+            // The last left child, as the frontier keeps it
+            if let Some(node) = nodes.get(nodes.len().saturating_sub(1) & !1) {
+                *branch = *node;
+            }
+        }
+        frontier
+    }
+
+    // The depth of the tree, padded to the next power of two.
+    fn depth(&self) -> usize {
+        self.size().next_power_of_two().trailing_zeros() as usize
+    }
+
+    // The root of the subtree at the level and index, padded with
+    // `PADDING_LEAF`.
+    fn node(&self, level: usize, index: usize) -> Digest {
+        if let Some(node) = self.levels.get(level).and_then(|nodes| nodes.get(index)) {
+            return *node;
+        }
+        if (index << level) as u64 >= self.size() {
+            return (0..level).fold(*PADDING_LEAF, |empty, _| hash_two(&empty, &empty));
+        }
+        // Partially filled subtrees are on the right edge of the tree
+        hash_two(
+            &self.node(level - 1, 2 * index),
+            &self.node(level - 1, 2 * index + 1),
+        )
+    }
+}
+
+impl TryFrom<Vec<Digest>> for CommitmentTree {
+    type Error = ArmError;
+
+    fn try_from(leaves: Vec<Digest>) -> Result<Self, ArmError> {
+        CommitmentTree::from_leaves(leaves)
+    }
+}
+
+impl From<CommitmentTree> for Vec<Digest> {
+    fn from(mut tree: CommitmentTree) -> Self {
+        if tree.levels.is_empty() {
+            Vec::new()
+        } else {
+            tree.levels.swap_remove(0)
+        }
+    }
+}
+
+/// The witness of the commitment anchor circuit.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnchorWitness {
+    /// The frontier of the tree before the block.
+    pub frontier: CommitmentTreeFrontier,
+    /// The commitments appended by the block, in order.
+    pub commitments: Vec<Digest>,
+}
+
+/// The instance of the commitment anchor circuit.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnchorInstance {
+    /// The root before the block.
+    pub old_root: Digest,
+    /// The number of leaves before the block.
+    pub old_size: u64,
+    /// The root after the block.
+    pub new_root: Digest,
+    /// The number of leaves after the block.
+    pub new_size: u64,
+    /// The hash of the concatenated commitments of the block.
+    pub commitments_digest: Digest,
+}
+
+impl AnchorWitness {
+    /// Creates the witness of appending `commitments` to the tree.
+    pub fn new(frontier: CommitmentTreeFrontier, commitments: Vec<Digest>) -> Self {
+        AnchorWitness {
+            frontier,
+            commitments,
+        }
+    }
+
+    /// Appends the commitments, returning the instance. This is the
+    /// computation proven by the commitment anchor circuit.
+    pub fn constrain(&self) -> Result<AnchorInstance, ArmError> {
+        let old_root = self.frontier.root()?;
+        let mut frontier = self.frontier.clone();
+        for commitment in &self.commitments {
+            frontier.append(*commitment)?;
+        }
+        Ok(AnchorInstance {
+            old_root,
+            old_size: self.frontier.size,
+            new_root: frontier.root()?,
+            new_size: frontier.size,
+            commitments_digest: commitments_digest(&self.commitments),
+        })
+    }
+}
+
+/// Hashes the concatenated commitments of a block, as committed by
+/// [`AnchorInstance::commitments_digest`].
+pub fn commitments_digest(commitments: &[Digest]) -> Digest {
+    let bytes: Vec<u8> = commitments
+        .iter()
+        .flat_map(|commitment| commitment.as_bytes().to_vec())
+        .collect();
+    *Impl::hash_bytes(&bytes)
+}
+
+/// A proof that a root extends another one by the commitments of a block.
+#[cfg(feature = "transaction")]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnchorProof {
+    /// The serialized receipt of the commitment anchor circuit.
+    pub proof: Vec<u8>,
+    /// The journal of the receipt, an [`AnchorInstance`].
+    pub instance: Vec<u8>,
+}
+
+#[cfg(feature = "transaction")]
+impl AnchorProof {
+    /// Proves the witness with the commitment anchor guest, built from
+    /// `arm_circuits/commitment_anchor`.
+    #[cfg(feature = "prove")]
+    pub fn prove(
+        anchor_pk: &[u8],
+        witness: &AnchorWitness,
+        proof_type: ProofType,
+    ) -> Result<Self, ArmError> {
+        let (proof, instance) = prove(anchor_pk, witness, proof_type)?;
+        Ok(AnchorProof { proof, instance })
+    }
+
+    /// Verifies the proof against the image id of the commitment anchor
+    /// guest and the root the light client trusts, returning the instance
+    /// with the new root.
+    pub fn verify(
+        &self,
+        anchor_vk: &Digest,
+        old_root: &Digest,
+    ) -> Result<AnchorInstance, ArmError> {
+        verify(anchor_vk, &self.instance, &self.proof)?;
+        let instance: AnchorInstance = journal_to_instance(&self.instance)?;
+        if instance.old_root != *old_root {
+            return Err(ArmError::AnchorMismatch);
+        }
+        Ok(instance)
+    }
+}
+
+#[test]
+fn test_commitment_tree_frontier() {
+    use crate::action_tree::MerkleTree;
+
+    let mut frontier = CommitmentTreeFrontier::new();
+    assert_eq!(frontier.root(), Ok(*PADDING_LEAF));
+    let leaves: Vec<_> = (1..=9u8).map(|i| Digest::from_bytes([i; 32])).collect();
+    for (i, leaf) in leaves.iter().enumerate() {
+        frontier.append(*leaf).unwrap();
+        assert_eq!(
+            frontier.root(),
+            MerkleTree::new(leaves[..=i].to_vec()).root()
+        );
+    }
+}
+
+#[test]
+fn test_commitment_tree() {
+    use crate::action_tree::MerkleTree;
+
+    let mut tree = CommitmentTree::new();
+    assert_eq!(tree.root(), *PADDING_LEAF);
+    assert_eq!(tree.witness(0), Err(ArmError::InvalidLeaf));
+    let leaves: Vec<_> = (1..=11u8).map(|i| Digest::from_bytes([i; 32])).collect();
+    let mut frontier = CommitmentTreeFrontier::new();
+    for (i, leaf) in leaves.iter().enumerate() {
+        assert_eq!(tree.append(*leaf), Ok(i as u64));
+        frontier.append(*leaf).unwrap();
+        assert_eq!(
+            Ok(tree.root()),
+            MerkleTree::new(leaves[..=i].to_vec()).root()
+        );
+        assert_eq!(tree.frontier(), frontier);
+        for (position, leaf) in leaves[..=i].iter().enumerate() {
+            let path = tree.witness(position as u64).unwrap();
+            assert_eq!(path.root(leaf), tree.root());
+        }
+    }
+    assert_eq!(
+        tree.witness(3),
+        MerkleTree::new(leaves.clone()).generate_path(&leaves[3])
+    );
+
+    let bytes = bincode::serialize(&tree).unwrap();
+    assert_eq!(
+        bincode::deserialize::<CommitmentTree>(&bytes).unwrap(),
+        tree
+    );
+}
+
+#[test]
+fn test_anchor_witness() {
+    let leaves: Vec<_> = (1..=7u8).map(|i| Digest::from_bytes([i; 32])).collect();
+    let mut frontier = CommitmentTreeFrontier::new();
+    for leaf in &leaves[..3] {
+        frontier.append(*leaf).unwrap();
+    }
+    let old_root = frontier.root().unwrap();
+
+    let witness = AnchorWitness::new(frontier.clone(), leaves[3..].to_vec());
+    let instance = witness.constrain().unwrap();
+    assert_eq!(instance.old_root, old_root);
+    assert_eq!((instance.old_size, instance.new_size), (3, 7));
+    for leaf in &leaves[3..] {
+        frontier.append(*leaf).unwrap();
+    }
+    assert_eq!(instance.new_root, frontier.root().unwrap());
+    assert_eq!(
+        instance.commitments_digest,
+        commitments_digest(&leaves[3..])
+    );
+
+    let mut malformed = witness;
+    malformed.frontier.branch.pop();
+    assert_eq!(malformed.constrain(), Err(ArmError::TreeTooLarge));
+}
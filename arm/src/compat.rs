@@ -0,0 +1,361 @@
+//! Versioned encoding of transactions.
+//!
+//! [`Transaction::to_bytes`] prefixes the encoded transaction with the
+//! [`PROTOCOL_VERSION`] it was encoded with. The 1.0 crates predate the
+//! header and encode transactions as plain bincode of the [`v1`] format.
+//! [`Transaction::from_bytes`] decodes transactions of the current major
+//! version, falls back to headerless version 1 transactions and translates
+//! them, and rejects the others, so the SDKs, the EVM adapter and Rust
+//! services can upgrade independently. Minor versions are backwards
+//! compatible and don't change the decoding.
+//!
+//! The protocol version is unrelated to the format version of the canonical
+//! encoding, see [`encoding`](crate::transaction::encoding#versions).
+
+use crate::{
+    action::Action,
+    error::ArmError,
+    logic_proof::LogicVerifierInputs,
+    transaction::{Delta, Transaction},
+};
+use serde::{Deserialize, Serialize};
+
+/// The version of the transaction encoding.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ProtocolVersion {
+    /// Bumped when encoded transactions can't be decoded by earlier versions.
+    pub major: u16,
+    /// Bumped when the encoding is extended compatibly.
+    pub minor: u16,
+}
+
+/// The protocol version transactions are encoded with.
+pub const PROTOCOL_VERSION: ProtocolVersion = ProtocolVersion { major: 2, minor: 0 };
+
+/// The length of the version header of encoded transactions.
+pub const VERSION_HEADER_BYTES: usize = 4;
+
+impl ProtocolVersion {
+    /// Encodes the version into the header of encoded transactions.
+    pub fn to_header(&self) -> [u8; VERSION_HEADER_BYTES] {
+        let [a, b] = self.major.to_be_bytes();
+        let [c, d] = self.minor.to_be_bytes();
+        [a, b, c, d]
+    }
+
+    /// Splits an encoded transaction into its version and its body.
+    pub fn split_header(bytes: &[u8]) -> Result<(ProtocolVersion, &[u8]), ArmError> {
+        if bytes.len() < VERSION_HEADER_BYTES {
+            return Err(ArmError::DeserializationError);
+        }
+        let (header, body) = bytes.split_at(VERSION_HEADER_BYTES);
+        let version = ProtocolVersion {
+            major: u16::from_be_bytes([header[0], header[1]]),
+            minor: u16::from_be_bytes([header[2], header[3]]),
+        };
+        Ok((version, body))
+    }
+}
+
+/// The major versions [`Transaction::from_bytes`] decodes.
+pub fn supported_major_versions() -> Vec<u16> {
+    vec![1, PROTOCOL_VERSION.major]
+}
+
+/// The transaction format of protocol version 1.
+///
+/// It predates expiries, provenance, tags roots, delta opening proofs,
+/// unbalanced deltas, app data digests, image id bindings, logic ref
+/// commitments and resource references.
+pub mod v1 {
+    use crate::{
+        compliance_unit::ComplianceUnit,
+        delta_proof::{DeltaProof, DeltaWitness},
+        logic_instance::AppData,
+        Digest,
+    };
+    use serde::{Deserialize, Serialize};
+
+    /// A version 1 transaction.
+    #[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+    pub struct Transaction {
+        /// The actions of the transaction.
+        pub actions: Vec<Action>,
+        /// The delta witness or proof.
+        pub delta_proof: Delta,
+        /// The expected balance placeholder.
+        pub expected_balance: Option<Vec<u8>>,
+        /// The aggregation proof, if present.
+        pub aggregation_proof: Option<Vec<u8>>,
+    }
+
+    /// A version 1 action.
+    #[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+    pub struct Action {
+        /// The compliance units of the action.
+        pub compliance_units: Vec<ComplianceUnit>,
+        /// The logic verifier inputs of the action.
+        pub logic_verifier_inputs: Vec<LogicVerifierInputs>,
+    }
+
+    /// Version 1 logic verifier inputs.
+    #[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+    pub struct LogicVerifierInputs {
+        /// The tag of the resource.
+        pub tag: Digest,
+        /// The verifying key of the logic.
+        pub verifying_key: Digest,
+        /// The app data of the logic instance.
+        pub app_data: AppData,
+        /// The logic proof, if present.
+        pub proof: Option<Vec<u8>>,
+    }
+
+    /// A version 1 delta.
+    #[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+    pub enum Delta {
+        /// The delta witness.
+        Witness(DeltaWitness),
+        /// The delta proof.
+        Proof(DeltaProof),
+    }
+}
+
+impl From<v1::Transaction> for Transaction {
+    fn from(tx: v1::Transaction) -> Self {
+        let actions = tx
+            .actions
+            .into_iter()
+            .map(|action| Action {
+                compliance_units: action.compliance_units,
+                logic_verifier_inputs: action
+                    .logic_verifier_inputs
+                    .into_iter()
+                    .map(|input| LogicVerifierInputs {
+                        tag: input.tag,
+                        verifying_key: input.verifying_key,
+                        app_data: input.app_data,
+                        proof: input.proof,
+                        app_data_by_digest: false,
+                        binds_image_id: false,
+                        logic_ref_opening: None,
+                    })
+                    .collect(),
+                resource_refs: vec![],
+            })
+            .collect();
+        let delta = match tx.delta_proof {
+            v1::Delta::Witness(witness) => Delta::Witness(witness),
+            v1::Delta::Proof(proof) => Delta::Proof(proof),
+        };
+        let mut upgraded = Transaction::create(actions, delta);
+        upgraded.expected_balance = tx.expected_balance;
+        upgraded.aggregation_proof = tx.aggregation_proof;
+        upgraded
+    }
+}
+
+/// Fails if the transaction uses features version 1 can't represent.
+impl TryFrom<&Transaction> for v1::Transaction {
+    type Error = ArmError;
+
+    fn try_from(tx: &Transaction) -> Result<Self, ArmError> {
+        let unrepresentable = ArmError::NotRepresentableInVersion(1);
+        if tx.valid_until.is_some() || tx.provenance.is_some() || tx.tags_root.is_some() {
+            return Err(unrepresentable);
+        }
+        let delta_proof = match &tx.delta_proof {
+            Delta::Witness(witness) => v1::Delta::Witness(witness.clone()),
+            Delta::Proof(proof) => v1::Delta::Proof(proof.clone()),
+            Delta::OpeningProof(_) | Delta::Unbalanced { .. } => return Err(unrepresentable),
+        };
+        let actions = tx
+            .actions
+            .iter()
+            .map(|action| {
+                if !action.resource_refs.is_empty() {
+                    return Err(unrepresentable.clone());
+                }
+                let logic_verifier_inputs = action
+                    .logic_verifier_inputs
+                    .iter()
+                    .map(|input| {
+                        if input.app_data_by_digest
+                            || input.binds_image_id
+                            || input.logic_ref_opening.is_some()
+                        {
+                            return Err(unrepresentable.clone());
+                        }
+                        Ok(v1::LogicVerifierInputs {
+                            tag: input.tag,
+                            verifying_key: input.verifying_key,
+                            app_data: input.app_data.clone(),
+                            proof: input.proof.clone(),
+                        })
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok(v1::Action {
+                    compliance_units: action.compliance_units.clone(),
+                    logic_verifier_inputs,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(v1::Transaction {
+            actions,
+            delta_proof,
+            expected_balance: tx.expected_balance.clone(),
+            aggregation_proof: tx.aggregation_proof.clone(),
+        })
+    }
+}
+
+// Encodes a transaction in the format of the major version: headerless for
+// version 1, prefixed with the version header otherwise.
+pub(crate) fn encode(tx: &Transaction, major: u16) -> Result<Vec<u8>, ArmError> {
+    let serialization_error = |_| ArmError::SerializationError;
+    match major {
+        1 => bincode::serialize(&v1::Transaction::try_from(tx)?).map_err(serialization_error),
+        major if major == PROTOCOL_VERSION.major => {
+            let body = bincode::serialize(tx).map_err(serialization_error)?;
+            Ok([&PROTOCOL_VERSION.to_header()[..], &body].concat())
+        }
+        found => Err(ArmError::ProtocolVersionMismatch {
+            host: PROTOCOL_VERSION.major,
+            found,
+        }),
+    }
+}
+
+// Decodes a transaction of the current major version, or else a headerless
+// version 1 transaction. The first bytes of a version 1 transaction are its
+// number of actions, so they are only read as a header if the body decodes.
+pub(crate) fn decode(bytes: &[u8]) -> Result<Transaction, ArmError> {
+    let (version, body) = ProtocolVersion::split_header(bytes)?;
+    if version.major == PROTOCOL_VERSION.major {
+        if let Ok(tx) = bincode::deserialize(body) {
+            return Ok(tx);
+        }
+    }
+    if let Ok(tx) = decode_v1(bytes) {
+        return Ok(tx.into());
+    }
+    if version.major == PROTOCOL_VERSION.major {
+        Err(ArmError::DeserializationError)
+    } else {
+        Err(ArmError::ProtocolVersionMismatch {
+            host: PROTOCOL_VERSION.major,
+            found: version.major,
+        })
+    }
+}
+
+// Decodes the plain bincode of a version 1 transaction, rejecting trailing
+// bytes so that headered transactions aren't mistaken for it.
+fn decode_v1(bytes: &[u8]) -> bincode::Result<v1::Transaction> {
+    use bincode::Options;
+
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .reject_trailing_bytes()
+        .deserialize(bytes)
+}
+
+#[test]
+fn test_versioned_encoding() {
+    use crate::{
+        action::unproven_action, compliance::ComplianceWitness, delta_proof::DeltaWitness,
+    };
+
+    let mut action = unproven_action(&ComplianceWitness::default());
+    action.compliance_units[0].proof = Some(vec![1, 2, 3]);
+    // The default witness is balanced with rcv = 1.
+    let mut one = [0u8; 32];
+    one[31] = 1;
+    let tx = Transaction::create(
+        vec![action],
+        Delta::Witness(DeltaWitness::from_bytes(&one).unwrap()),
+    )
+    .generate_delta_proof()
+    .unwrap();
+
+    let bytes = tx.to_bytes().unwrap();
+    let (version, _) = ProtocolVersion::split_header(&bytes).unwrap();
+    assert_eq!(version, PROTOCOL_VERSION);
+    assert_eq!(Transaction::from_bytes(&bytes).unwrap(), tx);
+
+    // Version 1 transactions are headerless and translated both ways.
+    let legacy = tx.to_bytes_with_version(1).unwrap();
+    assert_eq!(
+        legacy,
+        bincode::serialize(&v1::Transaction::try_from(&tx).unwrap()).unwrap()
+    );
+    assert_eq!(Transaction::from_bytes(&legacy).unwrap(), tx);
+    let mut expiring = tx.clone();
+    expiring.valid_until = Some(1_000);
+    assert_eq!(
+        expiring.to_bytes_with_version(1),
+        Err(ArmError::NotRepresentableInVersion(1))
+    );
+
+    // Newer minor versions are decoded, other major versions are rejected.
+    let mut newer = bytes.clone();
+    newer[3] += 1;
+    assert_eq!(Transaction::from_bytes(&newer).unwrap(), tx);
+    let mut future = bytes;
+    future[1] += 1;
+    assert_eq!(
+        Transaction::from_bytes(&future),
+        Err(ArmError::ProtocolVersionMismatch {
+            host: PROTOCOL_VERSION.major,
+            found: PROTOCOL_VERSION.major + 1,
+        })
+    );
+    assert!(Transaction::from_bytes(&[0, 2]).is_err());
+}
+
+#[test]
+fn test_decode_v1_bytes() {
+    use crate::delta_proof::DeltaWitness;
+
+    // A transaction without actions as encoded by the 1.0 crates: the
+    // action count, the `Witness` variant, the length-prefixed signing key,
+    // and no expected balance nor aggregation proof.
+    let mut key = [0u8; 32];
+    key[31] = 1;
+    let bytes = [
+        &0u64.to_le_bytes()[..],
+        &0u32.to_le_bytes(),
+        &32u64.to_le_bytes(),
+        &key,
+        &[0, 0],
+    ]
+    .concat();
+    let tx = Transaction::create(
+        vec![],
+        Delta::Witness(DeltaWitness::from_bytes(&key).unwrap()),
+    );
+    assert_eq!(Transaction::from_bytes(&bytes).unwrap(), tx);
+    assert_eq!(tx.to_bytes_with_version(1).unwrap(), bytes);
+
+    // A 1.0 transaction whose action count looks like a header is still
+    // decoded as version 1.
+    let action = v1::Action {
+        compliance_units: vec![],
+        logic_verifier_inputs: vec![],
+    };
+    let legacy = v1::Transaction {
+        actions: vec![action; 512],
+        delta_proof: v1::Delta::Witness(DeltaWitness::from_bytes(&key).unwrap()),
+        expected_balance: None,
+        aggregation_proof: None,
+    };
+    let bytes = bincode::serialize(&legacy).unwrap();
+    assert_eq!(
+        ProtocolVersion::split_header(&bytes).unwrap().0,
+        PROTOCOL_VERSION
+    );
+    assert_eq!(
+        Transaction::from_bytes(&bytes).unwrap(),
+        Transaction::from(legacy)
+    );
+}
@@ -4,23 +4,24 @@
 const COMPLIANCE_INSTANCE_SIZE: usize = 56;
 
 use crate::{
+    digest_encoding::DigestEncoding,
     error::ArmError,
     merkle_path::MerklePath,
     nullifier_key::NullifierKey,
     resource::Resource,
-    utils::{bytes_to_words, words_to_bytes},
+    rng::ArmRng,
+    utils::{bytes_to_words, hash_bytes, words_to_bytes, Redacted},
 };
-use hex::FromHex;
 use k256::{
     elliptic_curve::{
         sec1::{FromEncodedPoint, ToEncodedPoint},
-        Field, PrimeField,
+        PrimeField,
     },
     EncodedPoint, ProjectivePoint, Scalar,
 };
 use lazy_static::lazy_static;
-use rand::rngs::OsRng;
 use risc0_zkvm::Digest;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_with::serde_as;
 
 lazy_static! {
@@ -32,6 +33,10 @@ lazy_static! {
 
 /// The compliance instance contains all public inputs to the compliance proof.
 #[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
 pub struct ComplianceInstance {
     /// The nullifier of the consumed resource.
     pub consumed_nullifier: Digest,
@@ -59,8 +64,74 @@ pub struct ComplianceInstanceWords {
     pub u32_words: [u32; COMPLIANCE_INSTANCE_SIZE],
 }
 
+/// The random scalar blinding the delta commitment of a compliance unit.
+///
+/// It can only be sampled from a CSPRNG or decoded from bytes holding a
+/// canonical scalar, so a witness never carries a malformed rcv. It's encoded
+/// like the `Vec<u8>` it replaces, keeping the witness layout unchanged.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Rcv(Scalar);
+
+impl std::fmt::Debug for Rcv {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Rcv").field(&Redacted).finish()
+    }
+}
+
+impl Rcv {
+    /// Samples a random rcv.
+    pub fn random() -> Self {
+        Rcv(ArmRng::new().scalar())
+    }
+
+    /// Samples a random rcv, failing if entropy is unavailable.
+    pub fn try_random() -> Result<Self, ArmError> {
+        ArmRng::new().try_scalar().map(Rcv)
+    }
+
+    /// Decodes an rcv from 32 big-endian bytes, checking that they hold a
+    /// scalar in range.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ArmError> {
+        let array: [u8; 32] = bytes.try_into().map_err(|_| ArmError::InvalidRcv)?;
+        Scalar::from_repr(array.into())
+            .into_option()
+            .map(Rcv)
+            .ok_or(ArmError::InvalidRcv)
+    }
+
+    /// Encodes the rcv to bytes.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0.to_bytes().into()
+    }
+
+    /// Returns the rcv scalar.
+    pub fn as_scalar(&self) -> &Scalar {
+        &self.0
+    }
+}
+
+#[cfg(feature = "borsh")]
+crate::utils::borsh_via_bytes!(Rcv, 32);
+
+impl Serialize for Rcv {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.to_bytes())
+    }
+}
+
+impl<'de> Deserialize<'de> for Rcv {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes = Vec::<u8>::deserialize(deserializer)?;
+        Rcv::from_bytes(&bytes).map_err(serde::de::Error::custom)
+    }
+}
+
 /// The compliance witness contains all private inputs to the compliance proof.
 #[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
 pub struct ComplianceWitness {
     /// The consumed resource
     pub consumed_resource: Resource,
@@ -73,10 +144,7 @@ pub struct ComplianceWitness {
     /// The created resource
     pub created_resource: Resource,
     /// Random scalar for delta commitment
-    pub rcv: Vec<u8>,
-    // TODO: If we want to add function privacy, include:
-    // pub input_resource_logic_cm_r: [u8; DATA_BYTES],
-    // pub output_resource_logic_cm_r: [u8; DATA_BYTES],
+    pub rcv: Rcv,
 }
 
 impl ComplianceWitness {
@@ -92,7 +160,7 @@ impl ComplianceWitness {
             consumed_resource,
             created_resource,
             merkle_path: MerklePath::empty(),
-            rcv: Scalar::random(&mut OsRng).to_bytes().to_vec(),
+            rcv: Rcv::random(),
             nf_key,
             ephemeral_root: latest_root,
         }
@@ -110,7 +178,7 @@ impl ComplianceWitness {
             consumed_resource,
             created_resource,
             merkle_path,
-            rcv: Scalar::random(&mut OsRng).to_bytes().to_vec(),
+            rcv: Rcv::random(),
             nf_key,
             ephemeral_root: *INITIAL_ROOT,
         }
@@ -118,9 +186,26 @@ impl ComplianceWitness {
 
     /// Compliance constraints
     pub fn constrain(&self) -> Result<ComplianceInstance, ArmError> {
+        self.constrain_inner(true)
+    }
+
+    /// Compliance constraints without the Merkle path hashing of the
+    /// consumed resource, which dominates the cycle count, for profiling the
+    /// other constraints. The instance has the witness' ephemeral root as
+    /// commitment tree root.
+    #[cfg(feature = "bench-circuits")]
+    pub fn constrain_without_merkle_check(&self) -> Result<ComplianceInstance, ArmError> {
+        self.constrain_inner(false)
+    }
+
+    fn constrain_inner(&self, check_merkle_path: bool) -> Result<ComplianceInstance, ArmError> {
         let consumed_cm = self.consumed_commitment();
         let consumed_logic_ref = self.consumed_resource_logic();
-        let consumed_commitment_tree_root = self.consumed_commitment_tree_root(&consumed_cm);
+        let consumed_commitment_tree_root = if check_merkle_path {
+            self.consumed_commitment_tree_root(&consumed_cm)
+        } else {
+            self.ephemeral_root
+        };
 
         let consumed_nullifier = self.consumed_nullifier(&consumed_cm)?;
         let created_logic_ref = self.created_resource_logic();
@@ -184,31 +269,28 @@ impl ComplianceWitness {
     /// Compute the delta commitment
     pub fn delta(&self) -> Result<([u32; 8], [u32; 8]), ArmError> {
         // Compute delta and make delta commitment public
-        let rcv_array: [u8; 32] = self
-            .rcv
-            .as_slice()
-            .try_into()
-            .map_err(|_| ArmError::InvalidRcv)?;
-        let rcv_scalar = Scalar::from_repr(rcv_array.into())
-            .into_option()
-            .ok_or(ArmError::InvalidRcv)?;
         let consumed_kind = self.consumed_resource.kind()?;
         let created_kind = self.created_resource.kind()?;
         let delta = created_kind * self.created_resource.quantity_scalar()
             - consumed_kind * self.consumed_resource.quantity_scalar()
-            + ProjectivePoint::GENERATOR * rcv_scalar;
+            + ProjectivePoint::GENERATOR * self.rcv.as_scalar();
 
-        let encoded_delta = delta.to_encoded_point(false);
-        let delta_x: [u32; 8] = bytes_to_words(encoded_delta.x().ok_or(ArmError::InvalidDelta)?)
-            .try_into()
-            .map_err(|_| ArmError::InvalidDelta)?;
+        delta_coordinates(&delta)
+    }
+}
 
-        let delta_y: [u32; 8] = bytes_to_words(encoded_delta.y().ok_or(ArmError::InvalidDelta)?)
-            .try_into()
-            .map_err(|_| ArmError::InvalidDelta)?;
+// Encodes a delta commitment as the affine coordinates of the instance.
+fn delta_coordinates(delta: &ProjectivePoint) -> Result<([u32; 8], [u32; 8]), ArmError> {
+    let encoded_delta = delta.to_encoded_point(false);
+    let delta_x: [u32; 8] = bytes_to_words(encoded_delta.x().ok_or(ArmError::InvalidDelta)?)
+        .try_into()
+        .map_err(|_| ArmError::InvalidDelta)?;
 
-        Ok((delta_x, delta_y))
-    }
+    let delta_y: [u32; 8] = bytes_to_words(encoded_delta.y().ok_or(ArmError::InvalidDelta)?)
+        .try_into()
+        .map_err(|_| ArmError::InvalidDelta)?;
+
+    Ok((delta_x, delta_y))
 }
 
 impl Default for ComplianceWitness {
@@ -241,7 +323,7 @@ impl Default for ComplianceWitness {
 
         let merkle_path = MerklePath::default();
 
-        let rcv = Scalar::ONE.to_bytes().to_vec();
+        let rcv = Rcv(Scalar::ONE);
 
         ComplianceWitness {
             consumed_resource,
@@ -275,3 +357,313 @@ impl ComplianceInstance {
         msg
     }
 }
+
+/// The domain logic refs are committed under.
+pub const LOGIC_REF_COMMITMENT_DOMAIN: &[u8] = b"ARM_LOGIC_REF_COMMITMENT";
+
+/// Commits to a logic ref with a blinding randomness: the hash of
+/// [`LOGIC_REF_COMMITMENT_DOMAIN`], the logic ref and the randomness.
+pub fn commit_logic_ref(logic_ref: &Digest, rand: &[u8; 32]) -> Digest {
+    hash_bytes(&[LOGIC_REF_COMMITMENT_DOMAIN, logic_ref.as_bytes(), &rand[..]].concat())
+}
+
+/// A compliance witness committing to the logic refs of its resources. Its
+/// instance has the layout of a [`ComplianceInstance`], with the logic refs
+/// replaced by their [`commit_logic_ref`] commitments; the logic verifier
+/// inputs of the resources carry the randomness as their
+/// `logic_ref_opening`.
+///
+/// This doesn't hide which logic a resource uses: the logic verifier inputs
+/// still carry the verifying keys in the clear. No guest of the witness is
+/// pinned either, so its units can't be proven yet (see
+/// `arm_circuits/private_compliance`).
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct PrivateComplianceWitness {
+    /// The compliance witness of the resources
+    pub witness: ComplianceWitness,
+    /// The randomness of the consumed resource's logic ref commitment
+    pub consumed_logic_ref_rand: [u8; 32],
+    /// The randomness of the created resource's logic ref commitment
+    pub created_logic_ref_rand: [u8; 32],
+}
+
+impl PrivateComplianceWitness {
+    /// Commits to the logic refs of the witness with fresh randomness.
+    pub fn new(witness: ComplianceWitness) -> Self {
+        let mut rng = ArmRng::new();
+        PrivateComplianceWitness {
+            witness,
+            consumed_logic_ref_rand: rng.bytes(),
+            created_logic_ref_rand: rng.bytes(),
+        }
+    }
+
+    /// Compliance constraints
+    pub fn constrain(&self) -> Result<ComplianceInstance, ArmError> {
+        let instance = self.witness.constrain()?;
+        Ok(ComplianceInstance {
+            consumed_logic_ref: commit_logic_ref(
+                &instance.consumed_logic_ref,
+                &self.consumed_logic_ref_rand,
+            ),
+            created_logic_ref: commit_logic_ref(
+                &instance.created_logic_ref,
+                &self.created_logic_ref_rand,
+            ),
+            ..instance
+        })
+    }
+}
+
+/// A resource consumed by a [`MultiComplianceWitness`].
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct ConsumedInput {
+    /// The consumed resource
+    pub resource: Resource,
+    /// The path from the consumed commitment to the root in the commitment tree
+    pub merkle_path: MerklePath,
+    /// Nullifier key of the consumed resource
+    pub nf_key: NullifierKey,
+}
+
+/// The compliance witness of a unit consuming and creating any number of
+/// resources, e.g. 2-in-1-out merges or 1-in-2-out splits without padding
+/// resources.
+///
+/// Created resource `i` must have the nonce [`created_nonce`] derives from
+/// the consumed nullifiers, so a 1-in-1-out witness constrains the same
+/// nonce as a [`ComplianceWitness`].
+///
+/// Only the constraints are provided: no guest of the witness is pinned, and
+/// [`ComplianceUnit`](crate::compliance_unit::ComplianceUnit)s and actions
+/// still hold 1-in-1-out units (see `arm_circuits/multi_compliance`).
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct MultiComplianceWitness {
+    /// The consumed resources
+    pub consumed: Vec<ConsumedInput>,
+    /// The existing root for the ephemeral consumed resources
+    pub ephemeral_root: Digest,
+    /// The created resources
+    pub created: Vec<Resource>,
+    /// Random scalar for delta commitment
+    pub rcv: Rcv,
+}
+
+/// The public inputs of the compliance proof of a [`MultiComplianceWitness`].
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+pub struct MultiComplianceInstance {
+    /// The nullifiers of the consumed resources.
+    pub consumed_nullifiers: Vec<Digest>,
+    /// The logic refs of the consumed resources.
+    pub consumed_logic_refs: Vec<Digest>,
+    /// The commitment tree roots for the consumed resources.
+    pub consumed_commitment_tree_roots: Vec<Digest>,
+    /// The commitments of the created resources.
+    pub created_commitments: Vec<Digest>,
+    /// The logic refs of the created resources.
+    pub created_logic_refs: Vec<Digest>,
+    /// The delta x coordinate of all the resources of the unit.
+    pub delta_x: [u32; 8],
+    /// The delta y coordinate of all the resources of the unit.
+    pub delta_y: [u32; 8],
+}
+
+/// Returns the nonce of created resource `index`: the nullifier of consumed
+/// resource `index` if there's one, or else the hash of the first nullifier
+/// and the index (as 4 little-endian bytes).
+pub fn created_nonce(consumed_nullifiers: &[Digest], index: usize) -> Result<[u8; 32], ArmError> {
+    let nullifier = match consumed_nullifiers.get(index) {
+        Some(nullifier) => *nullifier,
+        None => {
+            let first = consumed_nullifiers
+                .first()
+                .ok_or(ArmError::EmptyComplianceUnit)?;
+            let index = u32::try_from(index).map_err(|_| ArmError::InvalidResourceNonce)?;
+            hash_bytes(&[first.as_bytes(), &index.to_le_bytes()[..]].concat())
+        }
+    };
+    Ok(nullifier.into())
+}
+
+impl MultiComplianceWitness {
+    /// Compliance constraints
+    pub fn constrain(&self) -> Result<MultiComplianceInstance, ArmError> {
+        if self.consumed.is_empty() || self.created.is_empty() {
+            return Err(ArmError::EmptyComplianceUnit);
+        }
+
+        let mut instance = MultiComplianceInstance::default();
+        let mut delta = ProjectivePoint::GENERATOR * self.rcv.as_scalar();
+        // A resource consumed twice would pay for its quantity twice.
+        let mut seen_nullifiers = std::collections::HashSet::new();
+        for input in &self.consumed {
+            let cm = input.resource.commitment();
+            let nullifier = input
+                .resource
+                .nullifier_from_commitment(&input.nf_key, &cm)?;
+            if !seen_nullifiers.insert(nullifier) {
+                return Err(ArmError::NullifierDuplication);
+            }
+            instance.consumed_nullifiers.push(nullifier);
+            instance.consumed_logic_refs.push(input.resource.logic_ref);
+            instance
+                .consumed_commitment_tree_roots
+                .push(if input.resource.is_ephemeral {
+                    self.ephemeral_root
+                } else {
+                    input.merkle_path.root(&cm)
+                });
+            delta -= input.resource.kind()? * input.resource.quantity_scalar();
+        }
+        for (index, resource) in self.created.iter().enumerate() {
+            if resource.nonce != created_nonce(&instance.consumed_nullifiers, index)? {
+                return Err(ArmError::InvalidResourceNonce);
+            }
+            instance.created_commitments.push(resource.commitment());
+            instance.created_logic_refs.push(resource.logic_ref);
+            delta += resource.kind()? * resource.quantity_scalar();
+        }
+        (instance.delta_x, instance.delta_y) = delta_coordinates(&delta)?;
+
+        Ok(instance)
+    }
+}
+
+impl From<ComplianceWitness> for MultiComplianceWitness {
+    fn from(witness: ComplianceWitness) -> Self {
+        MultiComplianceWitness {
+            consumed: vec![ConsumedInput {
+                resource: witness.consumed_resource,
+                merkle_path: witness.merkle_path,
+                nf_key: witness.nf_key,
+            }],
+            ephemeral_root: witness.ephemeral_root,
+            created: vec![witness.created_resource],
+            rcv: witness.rcv,
+        }
+    }
+}
+
+impl MultiComplianceInstance {
+    /// Returns the tags of the unit: the consumed nullifiers, followed by the
+    /// created commitments.
+    pub fn tags(&self) -> Vec<Digest> {
+        [&self.consumed_nullifiers[..], &self.created_commitments[..]].concat()
+    }
+
+    /// Converts the delta commitment from affine coordinates to a ProjectivePoint.
+    pub fn delta_projective(&self) -> Result<ProjectivePoint, ArmError> {
+        ComplianceInstance {
+            delta_x: self.delta_x,
+            delta_y: self.delta_y,
+            ..ComplianceInstance::default()
+        }
+        .delta_projective()
+    }
+
+    /// Retrieves the delta message used for signing: the tags of the unit.
+    pub fn delta_msg(&self) -> Vec<u8> {
+        self.tags()
+            .iter()
+            .flat_map(|tag| tag.as_bytes().to_vec())
+            .collect()
+    }
+}
+
+#[test]
+fn test_rcv_encoding() {
+    let rcv = Rcv::random();
+    // The encoding matches the former `Vec<u8>` field
+    assert_eq!(
+        risc0_zkvm::serde::to_vec(&rcv).unwrap(),
+        risc0_zkvm::serde::to_vec(&rcv.to_bytes().to_vec()).unwrap()
+    );
+    let words = risc0_zkvm::serde::to_vec(&rcv).unwrap();
+    assert_eq!(
+        risc0_zkvm::serde::from_slice::<Rcv, _>(&words).unwrap(),
+        rcv
+    );
+
+    let malformed = risc0_zkvm::serde::to_vec(&vec![0xffu8; 32]).unwrap();
+    assert!(risc0_zkvm::serde::from_slice::<Rcv, _>(&malformed).is_err());
+    assert_eq!(Rcv::from_bytes(&[0u8; 31]), Err(ArmError::InvalidRcv));
+}
+
+#[cfg(feature = "bench-circuits")]
+#[test]
+fn test_constrain_without_merkle_check() {
+    let witness = ComplianceWitness::default();
+    let instance = witness.constrain().unwrap();
+    let bench_instance = witness.constrain_without_merkle_check().unwrap();
+    assert_eq!(
+        bench_instance.consumed_commitment_tree_root,
+        witness.ephemeral_root
+    );
+    assert_eq!(
+        ComplianceInstance {
+            consumed_commitment_tree_root: instance.consumed_commitment_tree_root,
+            ..bench_instance
+        },
+        instance
+    );
+}
+
+#[test]
+fn test_multi_compliance_witness() {
+    let witness = ComplianceWitness::default();
+    let single = witness.constrain().unwrap();
+    let multi = MultiComplianceWitness::from(witness.clone())
+        .constrain()
+        .unwrap();
+    assert_eq!(multi.consumed_nullifiers, vec![single.consumed_nullifier]);
+    assert_eq!(multi.created_commitments, vec![single.created_commitment]);
+    assert_eq!(
+        (multi.delta_x, multi.delta_y),
+        (single.delta_x, single.delta_y)
+    );
+    assert_eq!(multi.delta_msg(), single.delta_msg());
+
+    // Merge two resources into one, keeping the unit balanced.
+    let mut merge = MultiComplianceWitness::from(witness.clone());
+    let mut second = merge.consumed[0].clone();
+    second.resource.nonce = [1u8; 32];
+    merge.consumed.push(second);
+    merge.created[0].quantity = 2;
+    let instance = merge.constrain().unwrap();
+    assert_eq!(instance.tags().len(), 3);
+    assert_eq!(instance.tags()[0], single.consumed_nullifier);
+    assert_eq!(
+        instance.delta_projective().unwrap(),
+        ProjectivePoint::GENERATOR
+    );
+
+    // Split one resource into two, with a derived nonce for the second.
+    let mut split = MultiComplianceWitness::from(witness);
+    split.consumed[0].resource.quantity = 2;
+    let nullifiers = [split.consumed[0]
+        .resource
+        .nullifier(&split.consumed[0].nf_key)
+        .unwrap()];
+    let mut created = split.created[0];
+    created.nonce = nullifiers[0].into();
+    split.created = vec![created, created];
+    split.created[1].nonce = created_nonce(&nullifiers, 1).unwrap();
+    let instance = split.constrain().unwrap();
+    assert_eq!(instance.created_commitments.len(), 2);
+    assert_eq!(
+        instance.delta_projective().unwrap(),
+        ProjectivePoint::GENERATOR
+    );
+
+    let mut reused = split.clone();
+    reused.created[1].nonce = reused.created[0].nonce;
+    assert_eq!(reused.constrain(), Err(ArmError::InvalidResourceNonce));
+
+    // Consuming the same resource twice would double its quantity.
+    let mut doubled = MultiComplianceWitness::from(ComplianceWitness::default());
+    doubled.consumed.push(doubled.consumed[0].clone());
+    doubled.created[0].quantity *= 2;
+    assert_eq!(doubled.constrain(), Err(ArmError::NullifierDuplication));
+    split.created.clear();
+    assert_eq!(split.constrain(), Err(ArmError::EmptyComplianceUnit));
+}
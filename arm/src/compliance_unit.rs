@@ -4,21 +4,52 @@ use crate::{
     compliance::ComplianceInstance,
     constants::COMPLIANCE_VK,
     error::ArmError,
-    proving_system::{journal_to_instance, verify as verify_proof},
+    proving_system::{journal_to_instance, ProofVerifier},
+    utils::{format_size, short_hex, words_to_bytes, Elided},
 };
 use k256::ProjectivePoint;
+use risc0_zkvm::Receipt;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 #[cfg(feature = "prove")]
 use crate::{
     compliance::ComplianceWitness,
+    config::ArmConfig,
     constants::COMPLIANCE_PK,
-    proving_system::{prove, ProofType},
+    proving_system::{
+        observe_proof, prove, prove_with_config, prove_with_handle, ProofType, ProverHandle,
+    },
 };
 
+/// The path of the ELF of the [`CircuitVariant::BenchNoMerkle`] compliance
+/// guest.
+#[cfg(feature = "bench-circuits")]
+pub const ENV_BENCH_NO_MERKLE_ELF: &str = "ARM_BENCH_NO_MERKLE_ELF";
+
+/// The compliance circuit a unit is proved with, independently of the proof
+/// type. Only [`CircuitVariant::Production`] units verify: the other
+/// variants have their own image ids and are for profiling only.
+#[cfg(feature = "bench-circuits")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CircuitVariant {
+    /// The compliance circuit.
+    #[default]
+    Production,
+    /// The compliance circuit without the Merkle path check of the consumed
+    /// resource. Its guest isn't embedded; its ELF is read from the path in
+    /// [`ENV_BENCH_NO_MERKLE_ELF`].
+    BenchNoMerkle,
+}
+
 /// A compliance unit consists of a compliance proof and its corresponding instance.
 /// The vk is a constant in the compliance unit, so we don't place it here.
-#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
 pub struct ComplianceUnit {
     /// The compliance proof (optional, would be absent when aggregation is enabled).
     pub proof: Option<Vec<u8>>,
@@ -32,17 +63,117 @@ impl ComplianceUnit {
     /// them as parameters. Instance is generated by proving.
     #[cfg(feature = "prove")]
     pub fn create(witness: &ComplianceWitness, proof_type: ProofType) -> Result<Self, ArmError> {
-        let (proof, instance) = prove(COMPLIANCE_PK, witness, proof_type)?;
+        let (proof, instance) = observe_proof!(
+            Compliance,
+            prove(COMPLIANCE_PK, witness, proof_type),
+            |(proof, _)| proof.len()
+        )?;
+        Ok(ComplianceUnit {
+            proof: Some(proof),
+            instance,
+        })
+    }
+
+    /// Creates a new compliance unit by proving the witness with the given
+    /// circuit variant.
+    #[cfg(all(feature = "prove", feature = "bench-circuits"))]
+    pub fn create_with_variant(
+        witness: &ComplianceWitness,
+        proof_type: ProofType,
+        variant: CircuitVariant,
+    ) -> Result<Self, ArmError> {
+        match variant {
+            CircuitVariant::Production => Self::create(witness, proof_type),
+            CircuitVariant::BenchNoMerkle => {
+                let elf = std::env::var(ENV_BENCH_NO_MERKLE_ELF)
+                    .ok()
+                    .and_then(|path| std::fs::read(path).ok())
+                    .ok_or(ArmError::BenchCircuitUnavailable)?;
+                let (proof, instance) = observe_proof!(
+                    Compliance,
+                    prove(&elf, witness, proof_type),
+                    |(proof, _)| proof.len()
+                )?;
+                Ok(ComplianceUnit {
+                    proof: Some(proof),
+                    instance,
+                })
+            }
+        }
+    }
+
+    /// Creates a new compliance unit using the proving defaults of the given
+    /// configuration.
+    #[cfg(feature = "prove")]
+    pub fn create_with_config(
+        witness: &ComplianceWitness,
+        config: &ArmConfig,
+    ) -> Result<Self, ArmError> {
+        let (proof, instance) = observe_proof!(
+            Compliance,
+            prove_with_config(COMPLIANCE_PK, witness, config),
+            |(proof, _)| proof.len()
+        )?;
         Ok(ComplianceUnit {
             proof: Some(proof),
             instance,
         })
     }
 
+    /// Creates a new compliance unit with the configuration and pooled
+    /// provers of a shared handle.
+    #[cfg(feature = "prove")]
+    pub fn create_with_handle(
+        witness: &ComplianceWitness,
+        handle: &ProverHandle,
+    ) -> Result<Self, ArmError> {
+        let (proof, instance) = observe_proof!(
+            Compliance,
+            prove_with_handle(COMPLIANCE_PK, witness, handle),
+            |(proof, _)| proof.len()
+        )?;
+        Ok(ComplianceUnit {
+            proof: Some(proof),
+            instance,
+        })
+    }
+
+    /// Wraps an externally produced compliance receipt (e.g. from a remote
+    /// prover) into a compliance unit. The receipt must verify against the
+    /// compliance verifying key and its journal must decode into a
+    /// compliance instance.
+    pub fn from_receipt(receipt: &Receipt) -> Result<Self, ArmError> {
+        ProofVerifier::new().verify_receipt(receipt, &COMPLIANCE_VK)?;
+        let _: ComplianceInstance = journal_to_instance(&receipt.journal.bytes)?;
+
+        let proof = bincode::serialize(&receipt.inner).map_err(|_| ArmError::SerializationError)?;
+        Ok(ComplianceUnit {
+            proof: Some(proof),
+            instance: receipt.journal.bytes.clone(),
+        })
+    }
+
+    /// Creates a unit of the instance without a proof, e.g. to inspect a
+    /// transaction before proving it.
+    pub fn unproven(instance: &ComplianceInstance) -> Result<Self, ArmError> {
+        let words = risc0_zkvm::serde::to_vec(instance)
+            .map_err(|_| ArmError::InstanceSerializationFailed)?;
+        Ok(ComplianceUnit {
+            proof: None,
+            instance: words_to_bytes(&words).to_vec(),
+        })
+    }
+
     /// Verifies the compliance proof against the instance using the constant verifying key.
     pub fn verify(&self) -> Result<(), ArmError> {
+        self.verify_with(&ProofVerifier::new())
+    }
+
+    /// Verifies the compliance proof like [`ComplianceUnit::verify`], with
+    /// the parameters of a shared verifier.
+    pub fn verify_with(&self, verifier: &ProofVerifier) -> Result<(), ArmError> {
         if let Some(proof) = &self.proof {
-            verify_proof(&COMPLIANCE_VK, &self.instance, proof)
+            verifier.verify(&COMPLIANCE_VK, &self.instance, proof)
         } else {
             Err(ArmError::ProofVerificationFailed(
                 "Missing compliance proof".into(),
@@ -60,3 +191,81 @@ impl ComplianceUnit {
         journal_to_instance(&self.instance)
     }
 }
+
+impl fmt::Debug for ComplianceUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ComplianceUnit")
+            .field("proof", &self.proof.as_deref().map(Elided::bytes))
+            .field("instance", &Elided::bytes(&self.instance))
+            .finish()
+    }
+}
+
+/// A one-line summary, e.g. `unit 1a2b3c4d… -> 5e6f7a8b… (proof 215.3 KiB)`,
+/// showing the consumed nullifier and the created commitment.
+impl fmt::Display for ComplianceUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.get_instance() {
+            Ok(instance) => write!(
+                f,
+                "unit {} -> {}",
+                short_hex(&instance.consumed_nullifier),
+                short_hex(&instance.created_commitment)
+            )?,
+            Err(_) => write!(f, "unit <malformed instance>")?,
+        }
+        match &self.proof {
+            Some(proof) => write!(f, " (proof {})", format_size(proof.len())),
+            None => write!(f, " (no proof)"),
+        }
+    }
+}
+
+#[test]
+fn test_compliance_unit_from_receipt() {
+    use crate::logic_proof::{LogicProver, PaddingResourceLogic};
+    use risc0_zkvm::InnerReceipt;
+
+    let unit = ComplianceUnit::create(&ComplianceWitness::default(), ProofType::Succinct).unwrap();
+    let inner: InnerReceipt = bincode::deserialize(unit.proof.as_ref().unwrap()).unwrap();
+    let receipt = Receipt::new(inner, unit.instance.clone());
+    assert_eq!(ComplianceUnit::from_receipt(&receipt).unwrap(), unit);
+
+    let bytes = bincode::serialize(&receipt).unwrap();
+    let verified = crate::proving_system::verify_receipt(&bytes, &COMPLIANCE_VK).unwrap();
+    assert_eq!(verified.journal.bytes, unit.instance);
+    assert!(crate::proving_system::verify_receipt(&bytes[1..], &COMPLIANCE_VK).is_err());
+
+    // Receipts of other circuits are rejected.
+    let logic = PaddingResourceLogic::default()
+        .prove(ProofType::Succinct)
+        .unwrap();
+    let inner: InnerReceipt = bincode::deserialize(logic.proof.as_ref().unwrap()).unwrap();
+    let receipt = Receipt::new(inner, logic.instance);
+    assert!(ComplianceUnit::from_receipt(&receipt).is_err());
+}
+
+#[cfg(feature = "prove")]
+#[test]
+fn test_compliance_unit_with_handle() {
+    use crate::config::ProverBackend;
+
+    let handle = ProverHandle::init(ArmConfig::default()).unwrap();
+    let units: Vec<_> = (0..2)
+        .map(|_| {
+            let handle = handle.clone();
+            std::thread::spawn(move || {
+                ComplianceUnit::create_with_handle(&ComplianceWitness::default(), &handle)
+            })
+        })
+        .map(|thread| thread.join().unwrap().unwrap())
+        .collect();
+    assert_eq!(units[0].instance, units[1].instance);
+    assert!(units[0].verify().is_ok());
+
+    let bonsai = ArmConfig {
+        prover_backend: ProverBackend::Bonsai,
+        ..ArmConfig::default()
+    };
+    assert_eq!(ProverHandle::init(bonsai).is_ok(), cfg!(feature = "bonsai"));
+}
@@ -0,0 +1,41 @@
+//! Compression of serialized witnesses written into the zkVM executor.
+//!
+//! Large witnesses (long merkle paths, multi-resource application witnesses)
+//! dominate the executor input size and the cycles spent reading it. The
+//! witness is serialized with bincode and compressed with LZ4 on the host,
+//! then written as a single frame. In the guest, read it back with:
+//!
+//! ```ignore
+//! let witness: MyWitness = decompress_witness(&env::read_frame()).unwrap();
+//! ```
+
+use crate::error::ArmError;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Serializes and compresses a witness.
+pub fn compress_witness<T: Serialize>(witness: &T) -> Result<Vec<u8>, ArmError> {
+    let bytes = bincode::serialize(witness).map_err(|_| ArmError::CompressionFailed)?;
+    Ok(lz4_flex::compress_prepend_size(&bytes))
+}
+
+/// Decompresses and deserializes a witness produced by [`compress_witness`].
+pub fn decompress_witness<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, ArmError> {
+    let bytes =
+        lz4_flex::decompress_size_prepended(bytes).map_err(|_| ArmError::DecompressionFailed)?;
+    bincode::deserialize(&bytes).map_err(|_| ArmError::DecompressionFailed)
+}
+
+#[test]
+fn test_witness_compression() {
+    use crate::merkle_path::MerklePath;
+
+    let path = MerklePath::default();
+    let compressed = compress_witness(&path).unwrap();
+    assert!(compressed.len() < bincode::serialize(&path).unwrap().len());
+    assert_eq!(path, decompress_witness::<MerklePath>(&compressed).unwrap());
+
+    assert_eq!(
+        decompress_witness::<MerklePath>(&compressed[..compressed.len() - 1]),
+        Err(ArmError::DecompressionFailed)
+    );
+}
@@ -0,0 +1,195 @@
+//! Typed configuration of proving defaults.
+//!
+//! [`ArmConfig`] gathers the choices that used to be hard-coded at call sites
+//! (proof type, aggregation strategy, prover backend, segment size). It can
+//! be loaded from `ARM_*` environment variables or, with the `config`
+//! feature, from a TOML file, and is accepted by the `*_with_config` proving
+//! APIs.
+
+#[cfg(feature = "aggregation")]
+use crate::aggregation::AggregationStrategy;
+use crate::{error::ArmError, proving_system::ProofType};
+use std::str::FromStr;
+
+/// Environment variable selecting the default proof type.
+pub const ENV_PROOF_TYPE: &str = "ARM_PROOF_TYPE";
+/// Environment variable selecting the aggregation strategy.
+#[cfg(feature = "aggregation")]
+pub const ENV_AGGREGATION_STRATEGY: &str = "ARM_AGGREGATION_STRATEGY";
+/// Environment variable selecting the prover backend.
+pub const ENV_PROVER_BACKEND: &str = "ARM_PROVER_BACKEND";
+/// Environment variable setting the segment size (power of two of cycles).
+pub const ENV_SEGMENT_PO2: &str = "ARM_SEGMENT_PO2";
+
+/// The backend generating the proofs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ProverBackend {
+    /// The RISC0 default prover, selected by `RISC0_PROVER` and the Bonsai
+    /// environment variables.
+    #[default]
+    Default,
+    /// The local prover.
+    Local,
+    /// The Bonsai remote prover (requires the `bonsai` feature).
+    Bonsai,
+}
+
+impl FromStr for ProverBackend {
+    type Err = ArmError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "default" => Ok(ProverBackend::Default),
+            "local" => Ok(ProverBackend::Local),
+            "bonsai" => Ok(ProverBackend::Bonsai),
+            _ => Err(ArmError::InvalidConfig(format!(
+                "unknown prover backend: {s}"
+            ))),
+        }
+    }
+}
+
+/// Proving defaults for an environment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArmConfig {
+    /// The proof type used when none is given explicitly.
+    pub default_proof_type: ProofType,
+    /// The aggregation strategy.
+    #[cfg(feature = "aggregation")]
+    pub aggregation_strategy: AggregationStrategy,
+    /// The prover backend.
+    pub prover_backend: ProverBackend,
+    /// The segment size as a power of two of cycles, or the RISC0 default.
+    pub segment_po2: Option<u32>,
+}
+
+impl Default for ArmConfig {
+    fn default() -> Self {
+        ArmConfig {
+            default_proof_type: ProofType::Succinct,
+            #[cfg(feature = "aggregation")]
+            aggregation_strategy: AggregationStrategy::Batch,
+            prover_backend: ProverBackend::Default,
+            segment_po2: None,
+        }
+    }
+}
+
+impl ArmConfig {
+    /// Loads the configuration from the `ARM_*` environment variables,
+    /// falling back to the defaults for unset variables.
+    pub fn from_env() -> Result<Self, ArmError> {
+        Self::from_lookup(|key| std::env::var(key).ok())
+    }
+
+    /// Parses the configuration from a TOML document. Keys are the field
+    /// names, e.g. `default_proof_type = "groth16"`.
+    #[cfg(feature = "config")]
+    pub fn from_toml_str(s: &str) -> Result<Self, ArmError> {
+        let table: toml::Table = s
+            .parse()
+            .map_err(|err| ArmError::InvalidConfig(format!("invalid TOML: {err}")))?;
+        for key in table.keys() {
+            if !Self::TOML_KEYS.contains(&key.as_str()) {
+                return Err(ArmError::InvalidConfig(format!("unknown key: {key}")));
+            }
+        }
+        Self::from_lookup(|key| {
+            let field = Self::TOML_KEYS[Self::ENV_KEYS.iter().position(|k| *k == key)?];
+            table.get(field).map(|value| match value {
+                toml::Value::String(s) => s.clone(),
+                other => other.to_string(),
+            })
+        })
+    }
+
+    /// Loads the configuration from a TOML file.
+    #[cfg(feature = "config")]
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> Result<Self, ArmError> {
+        let s = std::fs::read_to_string(path)
+            .map_err(|err| ArmError::InvalidConfig(format!("can't read config: {err}")))?;
+        Self::from_toml_str(&s)
+    }
+
+    #[cfg(feature = "config")]
+    const TOML_KEYS: &'static [&'static str] = &[
+        "default_proof_type",
+        #[cfg(feature = "aggregation")]
+        "aggregation_strategy",
+        "prover_backend",
+        "segment_po2",
+    ];
+
+    #[cfg(feature = "config")]
+    const ENV_KEYS: &'static [&'static str] = &[
+        ENV_PROOF_TYPE,
+        #[cfg(feature = "aggregation")]
+        ENV_AGGREGATION_STRATEGY,
+        ENV_PROVER_BACKEND,
+        ENV_SEGMENT_PO2,
+    ];
+
+    // Builds the configuration from a key-value source using the environment
+    // variable names as keys.
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, ArmError> {
+        let mut config = ArmConfig::default();
+        if let Some(value) = lookup(ENV_PROOF_TYPE) {
+            config.default_proof_type = value.parse()?;
+        }
+        #[cfg(feature = "aggregation")]
+        if let Some(value) = lookup(ENV_AGGREGATION_STRATEGY) {
+            config.aggregation_strategy = value.parse()?;
+        }
+        if let Some(value) = lookup(ENV_PROVER_BACKEND) {
+            config.prover_backend = value.parse()?;
+        }
+        if let Some(value) = lookup(ENV_SEGMENT_PO2) {
+            config.segment_po2 =
+                Some(value.parse().map_err(|_| {
+                    ArmError::InvalidConfig(format!("invalid segment po2: {value}"))
+                })?);
+        }
+        Ok(config)
+    }
+}
+
+#[test]
+fn test_config_from_lookup() {
+    let config = ArmConfig::from_lookup(|key| match key {
+        ENV_PROOF_TYPE => Some("Groth16".to_string()),
+        ENV_SEGMENT_PO2 => Some("20".to_string()),
+        _ => None,
+    })
+    .unwrap();
+    assert_eq!(config.default_proof_type, ProofType::Groth16);
+    assert_eq!(config.prover_backend, ProverBackend::Default);
+    assert_eq!(config.segment_po2, Some(20));
+
+    assert!(
+        ArmConfig::from_lookup(|key| (key == ENV_PROVER_BACKEND).then(|| "gpu".to_string()))
+            .is_err()
+    );
+    assert_eq!(
+        ArmConfig::from_lookup(|_| None).unwrap(),
+        ArmConfig::default()
+    );
+}
+
+#[cfg(feature = "config")]
+#[test]
+fn test_config_from_toml() {
+    let config = ArmConfig::from_toml_str(
+        r#"
+        default_proof_type = "groth16"
+        prover_backend = "local"
+        segment_po2 = 21
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.default_proof_type, ProofType::Groth16);
+    assert_eq!(config.prover_backend, ProverBackend::Local);
+    assert_eq!(config.segment_po2, Some(21));
+
+    assert!(ArmConfig::from_toml_str("proof = \"groth16\"").is_err());
+}
@@ -1,6 +1,6 @@
 //! Constants for compliance and padding logic proving and verification keys.
 
-use hex::FromHex;
+use crate::digest_encoding::DigestEncoding;
 use lazy_static::lazy_static;
 use risc0_zkvm::Digest;
 
@@ -12,11 +12,9 @@ pub const PADDING_LOGIC_PK: &[u8] = include_bytes!("../elfs/trivial-logic-guest.
 lazy_static! {
     /// compliance verification key / compliance image id
     pub static ref COMPLIANCE_VK: Digest =
-        Digest::from_hex("919e13001cd3319be5a5a7cb189203be083674acb3fff23d05aae9c3ed86314d")
-            .unwrap();
+        Digest::from_hex(include_str!("../elfs/compliance-guest.id").trim()).unwrap();
 
     /// padding logic verification key / compliance image id
     pub static ref PADDING_LOGIC_VK: Digest =
-        Digest::from_hex("21fcc2fc2c07f9753405d3070f2488c67389f7d797b6f6e20a9f2529fe4a0bff")
-            .unwrap();
+        Digest::from_hex(include_str!("../elfs/trivial-logic-guest.id").trim()).unwrap();
 }
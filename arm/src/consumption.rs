@@ -0,0 +1,151 @@
+//! Proofs of consumption for external protocols.
+//!
+//! External protocols, e.g. an L1 escrow releasing funds once a resource is
+//! consumed, can't verify ARM transactions themselves. A party that verified
+//! the transaction (e.g. the operator settling it) exports a
+//! [`ConsumptionStatement`] per revealed nullifier instead, with
+//! [`Transaction::verified_consumptions`], and signs it with its attester
+//! key. The statement is 96 bytes: the nullifier, the root of the action
+//! tree revealing it and the digest of the transaction's delta message,
+//! which commits to all its tags. Statements are signed like delta proofs,
+//! with a recoverable ECDSA signature over the Keccak-256 hash of the domain
+//! and the statement, so EVM contracts check them with `ecrecover`.
+
+use crate::{
+    delta_proof::{DeltaInstance, DeltaProof, DeltaWitness},
+    error::ArmError,
+    transaction::Transaction,
+    utils::hash_bytes,
+    Digest,
+};
+use k256::ecdsa::{SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+
+/// The domain consumption statements are signed under.
+pub const CONSUMPTION_DOMAIN: &[u8] = b"ARM_CONSUMPTION_V1";
+
+/// The length of an encoded consumption statement.
+pub const CONSUMPTION_STATEMENT_BYTES: usize = 96;
+
+/// A statement that a nullifier was revealed in a transaction whose proofs
+/// verified.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConsumptionStatement {
+    /// The revealed nullifier.
+    pub nullifier: Digest,
+    /// The root of the action tree the nullifier is a leaf of.
+    pub action_tree_root: Digest,
+    /// The SHA-256 digest of the transaction's delta message.
+    pub transaction_digest: Digest,
+}
+
+impl ConsumptionStatement {
+    /// Encodes the statement: the nullifier, the action tree root and the
+    /// transaction digest.
+    pub fn to_bytes(&self) -> [u8; CONSUMPTION_STATEMENT_BYTES] {
+        let mut bytes = [0u8; CONSUMPTION_STATEMENT_BYTES];
+        bytes[..32].copy_from_slice(self.nullifier.as_bytes());
+        bytes[32..64].copy_from_slice(self.action_tree_root.as_bytes());
+        bytes[64..].copy_from_slice(self.transaction_digest.as_bytes());
+        bytes
+    }
+
+    /// Returns the message signed by attesters: [`CONSUMPTION_DOMAIN`]
+    /// followed by the encoded statement.
+    pub fn signing_message(&self) -> Vec<u8> {
+        [CONSUMPTION_DOMAIN, &self.to_bytes()[..]].concat()
+    }
+
+    /// Signs the statement with the attester's key.
+    pub fn sign(&self, attester: &SigningKey) -> Result<SignedConsumption, ArmError> {
+        let witness = DeltaWitness {
+            signing_key: attester.clone(),
+        };
+        Ok(SignedConsumption {
+            statement: *self,
+            signature: DeltaProof::prove(&self.signing_message(), &witness)?,
+        })
+    }
+}
+
+/// A consumption statement signed by an attester.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedConsumption {
+    /// The signed statement.
+    pub statement: ConsumptionStatement,
+    /// The attester's signature, encoded like delta proofs (`r || s || v`).
+    pub signature: DeltaProof,
+}
+
+impl SignedConsumption {
+    /// Checks that the statement was signed by the attester, returning it.
+    pub fn verify(&self, attester: &VerifyingKey) -> Result<&ConsumptionStatement, ArmError> {
+        let instance = DeltaInstance {
+            verifying_key: *attester,
+        };
+        DeltaProof::verify(&self.statement.signing_message(), &self.signature, instance)
+            .map_err(|_| ArmError::InvalidSignature)?;
+        Ok(&self.statement)
+    }
+}
+
+impl Transaction {
+    /// Verifies the transaction and returns the statements of consumption of
+    /// its nullifiers, in order.
+    pub fn verified_consumptions(self) -> Result<Vec<ConsumptionStatement>, ArmError> {
+        let transaction_digest = hash_bytes(&self.get_delta_msg()?);
+        let mut statements = Vec::new();
+        for action in &self.actions {
+            let action_tree_root = action.action_tree_root()?;
+            for unit in action.get_compliance_units() {
+                statements.push(ConsumptionStatement {
+                    nullifier: unit.get_instance()?.consumed_nullifier,
+                    action_tree_root,
+                    transaction_digest,
+                });
+            }
+        }
+        self.verify()?;
+        Ok(statements)
+    }
+}
+
+#[cfg(feature = "prove")]
+#[test]
+fn test_consumption_statements() {
+    use crate::{
+        padding::padding_action, proving_system::ProofType, rng::ArmRng, transaction::Delta,
+    };
+
+    let (action, delta_witness) = padding_action(2, ProofType::Succinct).unwrap();
+    let tx = Transaction::create(vec![action], Delta::Witness(delta_witness))
+        .generate_delta_proof()
+        .unwrap();
+    let unit = tx.actions[0].get_compliance_units()[0]
+        .get_instance()
+        .unwrap();
+    let root = tx.actions[0].action_tree_root().unwrap();
+
+    let statements = tx.clone().verified_consumptions().unwrap();
+    assert_eq!(statements.len(), 2);
+    assert_eq!(statements[0].nullifier, unit.consumed_nullifier);
+    assert_eq!(statements[0].action_tree_root, root);
+
+    let mut rng = ArmRng::new();
+    let attester = SigningKey::random(&mut rng);
+    let signed = statements[0].sign(&attester).unwrap();
+    assert_eq!(signed.verify(attester.verifying_key()), Ok(&statements[0]));
+    let other = SigningKey::random(&mut rng);
+    assert_eq!(
+        signed.verify(other.verifying_key()),
+        Err(ArmError::InvalidSignature)
+    );
+    let mut forged = signed;
+    forged.statement.nullifier = statements[1].nullifier;
+    assert!(forged.verify(attester.verifying_key()).is_err());
+
+    // Statements are only exported for verified transactions.
+    let mut unproven = tx;
+    unproven.actions[0].compliance_units[0].proof = None;
+    assert!(unproven.verified_consumptions().is_err());
+}
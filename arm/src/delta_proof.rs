@@ -2,12 +2,22 @@
 
 use k256::ecdsa::{RecoveryId, Signature, SigningKey, VerifyingKey};
 use k256::{
-    elliptic_curve::{scalar::IsHigh, PublicKey, ScalarPrimitive},
-    ProjectivePoint, Scalar, SecretKey,
+    elliptic_curve::{
+        ops::Reduce,
+        scalar::IsHigh,
+        sec1::{FromEncodedPoint, ToEncodedPoint},
+        PrimeField, PublicKey, ScalarPrimitive,
+    },
+    EncodedPoint, ProjectivePoint, Scalar, SecretKey, U256,
 };
 use serde::{Deserialize, Serialize};
 
-use crate::error::ArmError;
+use crate::{
+    action::Action,
+    compliance::{ComplianceInstance, Rcv},
+    error::ArmError,
+    utils::Redacted,
+};
 use sha3::{Digest, Keccak256};
 
 /// The delta proof consists of an ECDSA signature and a recovery ID.
@@ -19,13 +29,53 @@ pub struct DeltaProof {
     pub recid: RecoveryId,
 }
 
-/// The delta witness contains the signing key used to generate the delta proof.
+/// An alternative delta proof: a Schnorr proof of knowledge of the opening
+/// of the summed delta commitment, i.e. of `x` with `delta = x * G`, bound to
+/// the delta message. Unlike [`DeltaProof`] it doesn't rely on the delta
+/// being usable as an ECDSA key, which simplifies its analysis and non-EVM
+/// verifiers.
 #[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeltaOpeningProof {
+    /// The commitment to the nonce, `k * G`.
+    pub commitment: ProjectivePoint,
+    /// The response, `k + c * x` for the challenge `c`.
+    pub response: Scalar,
+}
+
+/// How the balance of a transaction is proven, selected per deployment.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum DeltaProofMode {
+    /// An ECDSA signature with the delta as verifying key ([`DeltaProof`]).
+    #[default]
+    Signature,
+    /// A proof of knowledge of the delta opening ([`DeltaOpeningProof`]).
+    Opening,
+}
+
+const OPENING_NONCE_DOMAIN: &[u8] = b"ARM_DELTA_OPENING_NONCE";
+const OPENING_CHALLENGE_DOMAIN: &[u8] = b"ARM_DELTA_OPENING_CHALLENGE";
+
+/// The delta witness contains the signing key used to generate the delta proof.
+#[derive(Clone, PartialEq, Eq)]
 pub struct DeltaWitness {
     /// The signing key.
     pub signing_key: SigningKey,
 }
 
+impl std::fmt::Debug for DeltaWitness {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeltaWitness")
+            .field("signing_key", &Redacted)
+            .finish()
+    }
+}
+
 /// The delta instance contains the verifying key used to verify the delta proof.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct DeltaInstance {
@@ -85,6 +135,20 @@ impl DeltaProof {
         Ok(())
     }
 
+    /// Computes the verifying key a delta proof must match: the sum of the
+    /// deltas of the compliance instances, in any order. This is the reference
+    /// computation shared with the transaction verifier, against which other
+    /// implementations can be checked.
+    pub fn expected_key(
+        compliance_instances: &[ComplianceInstance],
+    ) -> Result<DeltaInstance, ArmError> {
+        let deltas = compliance_instances
+            .iter()
+            .map(|instance| instance.delta_projective())
+            .collect::<Result<Vec<_>, _>>()?;
+        DeltaInstance::from_deltas(&deltas)
+    }
+
     /// Serializes the delta proof to bytes.
     pub fn to_bytes(&self) -> [u8; 65] {
         let mut bytes = [0u8; 65];
@@ -103,6 +167,92 @@ impl DeltaProof {
     }
 }
 
+impl DeltaOpeningProof {
+    /// Proves knowledge of the opening of the delta, binding the proof to the
+    /// message. The nonce is derived from the witness and the message, so
+    /// proving is deterministic.
+    pub fn prove(message: &[u8], witness: &DeltaWitness) -> Result<DeltaOpeningProof, ArmError> {
+        let x = *witness.signing_key.as_nonzero_scalar().as_ref();
+        let k = hash_to_scalar(&[OPENING_NONCE_DOMAIN, &x.to_bytes(), message]);
+        if bool::from(k.is_zero()) {
+            return Err(ArmError::DeltaProofGenerationFailed);
+        }
+        let commitment = ProjectivePoint::GENERATOR * k;
+        let delta = ProjectivePoint::GENERATOR * x;
+        let challenge = Self::challenge(message, &commitment, &delta);
+        Ok(DeltaOpeningProof {
+            commitment,
+            response: k + challenge * x,
+        })
+    }
+
+    /// Verifies the proof against the given message and instance.
+    pub fn verify(
+        message: &[u8],
+        proof: &DeltaOpeningProof,
+        instance: DeltaInstance,
+    ) -> Result<(), ArmError> {
+        if proof.commitment == ProjectivePoint::IDENTITY {
+            return Err(ArmError::InvalidDeltaProof);
+        }
+        let delta = ProjectivePoint::from(*instance.verifying_key.as_affine());
+        let challenge = Self::challenge(message, &proof.commitment, &delta);
+        if ProjectivePoint::GENERATOR * proof.response != proof.commitment + delta * challenge {
+            return Err(ArmError::DeltaProofVerificationFailed);
+        }
+        Ok(())
+    }
+
+    // The Fiat-Shamir challenge of the nonce commitment, the delta and the
+    // message.
+    fn challenge(message: &[u8], commitment: &ProjectivePoint, delta: &ProjectivePoint) -> Scalar {
+        hash_to_scalar(&[
+            OPENING_CHALLENGE_DOMAIN,
+            commitment.to_encoded_point(true).as_bytes(),
+            delta.to_encoded_point(true).as_bytes(),
+            message,
+        ])
+    }
+
+    /// Serializes the proof to bytes: the compressed commitment followed by
+    /// the response.
+    pub fn to_bytes(&self) -> [u8; 65] {
+        let mut bytes = [0u8; 65];
+        bytes[0..33].clone_from_slice(self.commitment.to_encoded_point(true).as_bytes());
+        bytes[33..65].clone_from_slice(&self.response.to_bytes());
+        bytes
+    }
+
+    /// Deserializes the proof from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<DeltaOpeningProof, ArmError> {
+        if bytes.len() != 65 {
+            return Err(ArmError::InvalidDeltaProof);
+        }
+        let point =
+            EncodedPoint::from_bytes(&bytes[0..33]).map_err(|_| ArmError::InvalidDeltaProof)?;
+        let commitment =
+            Option::<ProjectivePoint>::from(ProjectivePoint::from_encoded_point(&point))
+                .ok_or(ArmError::InvalidDeltaProof)?;
+        let response = Option::<Scalar>::from(Scalar::from_repr(*k256::FieldBytes::from_slice(
+            &bytes[33..65],
+        )))
+        .ok_or(ArmError::InvalidDeltaProof)?;
+        Ok(DeltaOpeningProof {
+            commitment,
+            response,
+        })
+    }
+}
+
+// Hashes the concatenated parts with Keccak256 into a scalar.
+fn hash_to_scalar(parts: &[&[u8]]) -> Scalar {
+    let mut digest = Keccak256::new();
+    for part in parts {
+        digest.update(part);
+    }
+    <Scalar as Reduce<U256>>::reduce_bytes(&digest.finalize())
+}
+
 impl DeltaWitness {
     /// Creates a delta witness from a list of secret keys by summing them up.
     pub fn from_scalars(secret_keys: &[Scalar]) -> DeltaWitness {
@@ -115,17 +265,32 @@ impl DeltaWitness {
         DeltaWitness { signing_key }
     }
 
+    /// Creates a delta witness from the rcvs of compliance witnesses by
+    /// summing them up. Fails if there are no rcvs or they sum up to zero.
+    pub fn from_rcvs(rcvs: &[Rcv]) -> Result<DeltaWitness, ArmError> {
+        let sum = rcvs
+            .iter()
+            .fold(Scalar::ZERO, |acc, rcv| acc + rcv.as_scalar());
+        let signing_key =
+            SigningKey::from_bytes(&sum.to_bytes()).map_err(|_| ArmError::InvalidSigningKey)?;
+        Ok(DeltaWitness { signing_key })
+    }
+
     /// Creates a delta witness from a list of byte vectors representing secret keys.
+    /// Every key is validated as an [`Rcv`] before summing.
     pub fn from_bytes_vec(keys: &[Vec<u8>]) -> Result<DeltaWitness, ArmError> {
-        let witnesses: Result<Vec<DeltaWitness>, ArmError> = keys
+        let rcvs = keys
             .iter()
-            .map(|key| DeltaWitness::from_bytes(key))
-            .collect();
-        Ok(DeltaWitness::compress(&witnesses?))
+            .map(|key| Rcv::from_bytes(key))
+            .collect::<Result<Vec<_>, _>>()?;
+        DeltaWitness::from_rcvs(&rcvs)
     }
 
     /// Creates a delta witness from bytes.
     pub fn from_bytes(bytes: &[u8]) -> Result<DeltaWitness, ArmError> {
+        if bytes.len() != 32 {
+            return Err(ArmError::InvalidSigningKey);
+        }
         Ok(DeltaWitness {
             signing_key: SigningKey::from_bytes(bytes.into())
                 .map_err(|_| ArmError::InvalidSigningKey)?,
@@ -155,6 +320,79 @@ impl DeltaWitness {
         }
         sum
     }
+
+    /// Compresses delta witnesses like [`DeltaWitness::compress`], but fails
+    /// on an empty list or when a witness appears twice (usually a unit
+    /// counted twice while assembling the transaction).
+    pub fn compress_checked(witnesses: &[DeltaWitness]) -> Result<DeltaWitness, ArmError> {
+        if witnesses.is_empty() {
+            return Err(ArmError::RcvCountMismatch);
+        }
+        let mut seen = std::collections::HashSet::new();
+        if !witnesses.iter().all(|w| seen.insert(w.to_bytes())) {
+            return Err(ArmError::DuplicateDeltaWitness);
+        }
+        Ok(DeltaWitness::compress(witnesses))
+    }
+
+    /// Builds the delta witness of the actions from the rcvs of their
+    /// compliance units, given in unit order. Fails if the number of rcvs
+    /// doesn't match the number of units or an rcv appears twice. Also
+    /// returns the mapping of every rcv to its unit, flagging units whose
+    /// delta isn't explained by their rcv alone, to help locate the source of
+    /// an unbalanced transaction.
+    pub fn from_rcvs_checked(
+        rcvs: &[Rcv],
+        actions: &[Action],
+    ) -> Result<(DeltaWitness, Vec<DeltaAuditEntry>), ArmError> {
+        let unit_count: usize = actions.iter().map(|a| a.compliance_units.len()).sum();
+        if rcvs.len() != unit_count {
+            return Err(ArmError::RcvCountMismatch);
+        }
+        let mut seen = std::collections::HashSet::new();
+        if !rcvs.iter().all(|rcv| seen.insert(rcv.to_bytes())) {
+            return Err(ArmError::DuplicateDeltaWitness);
+        }
+
+        let mut audit = Vec::with_capacity(unit_count);
+        let units = actions
+            .iter()
+            .enumerate()
+            .flat_map(|(action_index, action)| {
+                action
+                    .compliance_units
+                    .iter()
+                    .enumerate()
+                    .map(move |(unit_index, unit)| (action_index, unit_index, unit))
+            });
+        for (witness_index, (rcv, (action_index, unit_index, unit))) in
+            rcvs.iter().zip(units).enumerate()
+        {
+            let residual = unit.delta()? - ProjectivePoint::GENERATOR * rcv.as_scalar();
+            audit.push(DeltaAuditEntry {
+                witness_index,
+                action_index,
+                unit_index,
+                balanced: residual == ProjectivePoint::IDENTITY,
+            });
+        }
+        Ok((DeltaWitness::from_rcvs(rcvs)?, audit))
+    }
+}
+
+/// Maps a delta witness component (an rcv) to its compliance unit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeltaAuditEntry {
+    /// The index of the rcv.
+    pub witness_index: usize,
+    /// The index of the action holding the unit.
+    pub action_index: usize,
+    /// The index of the unit within the action.
+    pub unit_index: usize,
+    /// Whether the unit consumes and creates the same quantity of the same
+    /// kind. Units of a balanced transaction may be individually unbalanced
+    /// (e.g. when splitting), but an unbalanced transaction always has some.
+    pub balanced: bool,
 }
 
 impl DeltaInstance {
@@ -167,8 +405,24 @@ impl DeltaInstance {
         let vk = VerifyingKey::from(&pk);
         Ok(DeltaInstance { verifying_key: vk })
     }
+
+    /// Returns the uncompressed SEC1 encoding of the verifying key.
+    pub fn to_uncompressed_bytes(&self) -> [u8; 65] {
+        self.verifying_key
+            .to_encoded_point(false)
+            .as_bytes()
+            .try_into()
+            .expect("uncompressed points are 65 bytes")
+    }
 }
 
+#[cfg(feature = "borsh")]
+crate::utils::borsh_via_bytes!(DeltaProof, 65);
+#[cfg(feature = "borsh")]
+crate::utils::borsh_via_bytes!(DeltaOpeningProof, 65);
+#[cfg(feature = "borsh")]
+crate::utils::borsh_via_bytes!(DeltaWitness, 32);
+
 impl Serialize for DeltaProof {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -195,6 +449,27 @@ impl<'de> Deserialize<'de> for DeltaProof {
     }
 }
 
+impl Serialize for DeltaOpeningProof {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_bytes(&self.to_bytes())
+    }
+}
+
+impl<'de> Deserialize<'de> for DeltaOpeningProof {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let bytes: Vec<u8> = Vec::deserialize(deserializer)?;
+        DeltaOpeningProof::from_bytes(&bytes).map_err(|e| {
+            serde::de::Error::custom(format!("Failed to deserialize DeltaOpeningProof: {:?}", e))
+        })
+    }
+}
+
 impl Serialize for DeltaWitness {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -209,7 +484,8 @@ impl<'de> Deserialize<'de> for DeltaWitness {
     where
         D: serde::Deserializer<'de>,
     {
-        let bytes = <[u8; 32]>::deserialize(deserializer)?;
+        // Read back the byte string written by `serialize_bytes`.
+        let bytes = <Vec<u8>>::deserialize(deserializer)?;
         DeltaWitness::from_bytes(&bytes).map_err(|e| {
             serde::de::Error::custom(format!("Failed to deserialize DeltaWitness: {:?}", e))
         })
@@ -218,9 +494,9 @@ impl<'de> Deserialize<'de> for DeltaWitness {
 
 #[test]
 fn test_delta_proof() {
-    use k256::elliptic_curve::rand_core::OsRng;
+    use crate::rng::ArmRng;
 
-    let mut rng = OsRng;
+    let mut rng = ArmRng::new();
     let signing_key = SigningKey::random(&mut rng);
     let verifying_key = VerifyingKey::from(&signing_key);
 
@@ -230,4 +506,135 @@ fn test_delta_proof() {
     let instance = DeltaInstance { verifying_key };
 
     DeltaProof::verify(message, &proof, instance).unwrap();
+
+    let bytes = bincode::serialize(&witness).unwrap();
+    assert!(bincode::deserialize::<DeltaWitness>(&bytes).unwrap() == witness);
+}
+
+#[test]
+fn test_delta_opening_proof() {
+    let witness = DeltaWitness::from_rcvs(&[Rcv::random(), Rcv::random()]).unwrap();
+    let instance = DeltaInstance {
+        verifying_key: *witness.signing_key.verifying_key(),
+    };
+
+    let message = b"Hello, world!";
+    let proof = DeltaOpeningProof::prove(message, &witness).unwrap();
+    assert_eq!(
+        DeltaOpeningProof::prove(message, &witness),
+        Ok(proof.clone())
+    );
+    DeltaOpeningProof::verify(message, &proof, instance.clone()).unwrap();
+    assert_eq!(
+        DeltaOpeningProof::from_bytes(&proof.to_bytes()),
+        Ok(proof.clone())
+    );
+
+    assert_eq!(
+        DeltaOpeningProof::verify(b"Hello, world?", &proof, instance.clone()),
+        Err(ArmError::DeltaProofVerificationFailed)
+    );
+    let other = DeltaInstance {
+        verifying_key: *DeltaWitness::from_rcvs(&[Rcv::random()])
+            .unwrap()
+            .signing_key
+            .verifying_key(),
+    };
+    assert!(DeltaOpeningProof::verify(message, &proof, other).is_err());
+    let mut forged = proof;
+    forged.response += Scalar::ONE;
+    assert!(DeltaOpeningProof::verify(message, &forged, instance).is_err());
+}
+
+#[test]
+fn test_delta_witness_from_rcvs() {
+    let rcvs = [Rcv::random(), Rcv::random()];
+    let bytes: Vec<Vec<u8>> = rcvs.iter().map(|rcv| rcv.to_bytes().to_vec()).collect();
+    assert_eq!(
+        DeltaWitness::from_rcvs(&rcvs).unwrap(),
+        DeltaWitness::from_bytes_vec(&bytes).unwrap()
+    );
+
+    assert_eq!(
+        DeltaWitness::from_rcvs(&[]),
+        Err(ArmError::InvalidSigningKey)
+    );
+    assert_eq!(
+        DeltaWitness::from_bytes_vec(&[vec![0xff; 32]]),
+        Err(ArmError::InvalidRcv)
+    );
+    assert_eq!(
+        DeltaWitness::from_bytes_vec(&[vec![1; 31]]),
+        Err(ArmError::InvalidRcv)
+    );
+    assert_eq!(
+        DeltaWitness::from_bytes(&[]),
+        Err(ArmError::InvalidSigningKey)
+    );
+}
+
+#[test]
+fn test_delta_witness_checked_compression() {
+    use crate::{action::unproven_action, compliance::ComplianceWitness};
+
+    // The default witness is balanced with rcv = 1.
+    let witness = ComplianceWitness::default();
+    let actions = [unproven_action(&witness)];
+
+    let (delta_witness, audit) = DeltaWitness::from_rcvs_checked(&[witness.rcv], &actions).unwrap();
+    assert_eq!(
+        delta_witness,
+        DeltaWitness::from_rcvs(&[witness.rcv]).unwrap()
+    );
+    assert_eq!(
+        audit,
+        vec![DeltaAuditEntry {
+            witness_index: 0,
+            action_index: 0,
+            unit_index: 0,
+            balanced: true,
+        }]
+    );
+
+    let (_, audit) = DeltaWitness::from_rcvs_checked(&[Rcv::random()], &actions).unwrap();
+    assert!(!audit[0].balanced);
+    assert_eq!(
+        DeltaWitness::from_rcvs_checked(&[], &actions).map(|_| ()),
+        Err(ArmError::RcvCountMismatch)
+    );
+
+    let rcv = Rcv::random();
+    let duplicate = DeltaWitness::from_rcvs(&[rcv]).unwrap();
+    assert_eq!(
+        DeltaWitness::compress_checked(&[duplicate.clone(), duplicate]),
+        Err(ArmError::DuplicateDeltaWitness)
+    );
+    assert_eq!(
+        DeltaWitness::compress_checked(&[]),
+        Err(ArmError::RcvCountMismatch)
+    );
+}
+
+#[test]
+fn test_expected_key_vectors() {
+    use crate::compliance::ComplianceWitness;
+
+    // The default witness is balanced with rcv = 1, so its delta is the generator.
+    let instance = ComplianceWitness::default().constrain().unwrap();
+    let one = DeltaProof::expected_key(std::slice::from_ref(&instance)).unwrap();
+    assert_eq!(
+        hex::encode(one.to_uncompressed_bytes()),
+        "0479be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798\
+         483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8"
+    );
+
+    let two = DeltaProof::expected_key(&[instance.clone(), instance]).unwrap();
+    assert_eq!(
+        hex::encode(two.to_uncompressed_bytes()),
+        "04c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5\
+         1ae168fea63dc339a3c58419466ceaeef7f632653266d0e1236431a950cfe52a"
+    );
+
+    // The sum of no deltas is the identity, which isn't a valid key.
+    assert!(DeltaProof::expected_key(&[]).is_err());
 }
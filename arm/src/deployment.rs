@@ -0,0 +1,301 @@
+//! Public parameters of a deployment.
+//!
+//! A [`DeploymentManifest`] records what a network was deployed with: the
+//! compliance and aggregation verifying keys, the initial commitment tree
+//! root, the tree depth and the registry of accepted logics. Operators
+//! publish it as a TOML or JSON file (with the `config` feature), and
+//! verifiers check it against this build before deriving their
+//! [`VerificationContext`] from it, so rolling out a new network doesn't
+//! require code changes.
+
+#[cfg(feature = "aggregation")]
+use crate::aggregation::constants::{BATCH_AGGREGATION_VK, SEQUENTIAL_AGGREGATION_VK};
+use crate::{
+    compliance::INITIAL_ROOT, constants::COMPLIANCE_VK, constants::PADDING_LOGIC_VK,
+    delta_proof::DeltaProofMode, digest_encoding::DigestEncoding, error::ArmError,
+    logic_registry::LogicRegistry, verification::VerificationContext,
+};
+use risc0_zkvm::Digest;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// The maximum supported depth of the commitment tree.
+pub const MAX_COMMITMENT_TREE_DEPTH: u8 = 64;
+
+/// The public parameters of a deployment. Digests are hex encoded.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct DeploymentManifest {
+    /// The id of the network, e.g. `mainnet`.
+    pub network_id: String,
+    /// The image id of the compliance circuit.
+    #[serde(with = "hex_digest")]
+    pub compliance_vk: Digest,
+    /// The image id of the sequential aggregation circuit, if aggregation is
+    /// deployed.
+    #[serde(default, with = "hex_digest_opt")]
+    pub sequential_aggregation_vk: Option<Digest>,
+    /// The image id of the batch aggregation circuit, if aggregation is
+    /// deployed.
+    #[serde(default, with = "hex_digest_opt")]
+    pub batch_aggregation_vk: Option<Digest>,
+    /// The root of the empty commitment tree.
+    #[serde(with = "hex_digest")]
+    pub initial_root: Digest,
+    /// The depth of the commitment tree.
+    pub commitment_tree_depth: u8,
+    /// The [`LogicRegistry::root`] of the accepted logics, or `None` if any
+    /// logic is accepted.
+    #[serde(default, with = "hex_digest_opt")]
+    pub logic_registry_root: Option<Digest>,
+    /// How transactions prove their balance.
+    #[serde(default)]
+    pub delta_proof_mode: DeltaProofMode,
+}
+
+impl DeploymentManifest {
+    /// Returns the manifest of a network deployed with this build, accepting
+    /// any logic.
+    pub fn current(network_id: &str, commitment_tree_depth: u8) -> Self {
+        DeploymentManifest {
+            network_id: network_id.to_string(),
+            compliance_vk: *COMPLIANCE_VK,
+            #[cfg(feature = "aggregation")]
+            sequential_aggregation_vk: Some(*SEQUENTIAL_AGGREGATION_VK),
+            #[cfg(not(feature = "aggregation"))]
+            sequential_aggregation_vk: None,
+            #[cfg(feature = "aggregation")]
+            batch_aggregation_vk: Some(*BATCH_AGGREGATION_VK),
+            #[cfg(not(feature = "aggregation"))]
+            batch_aggregation_vk: None,
+            initial_root: *INITIAL_ROOT,
+            commitment_tree_depth,
+            logic_registry_root: None,
+            delta_proof_mode: DeltaProofMode::default(),
+        }
+    }
+
+    /// Accepts only the logics of `registry`.
+    pub fn with_logic_registry(mut self, registry: &LogicRegistry) -> Result<Self, ArmError> {
+        self.logic_registry_root = Some(registry.root()?);
+        Ok(self)
+    }
+
+    /// Parses a manifest from a TOML document.
+    #[cfg(feature = "config")]
+    pub fn from_toml_str(s: &str) -> Result<Self, ArmError> {
+        toml::from_str(s).map_err(|err| ArmError::InvalidManifest(format!("invalid TOML: {err}")))
+    }
+
+    /// Parses a manifest from a JSON document.
+    #[cfg(feature = "config")]
+    pub fn from_json_str(s: &str) -> Result<Self, ArmError> {
+        serde_json::from_str(s)
+            .map_err(|err| ArmError::InvalidManifest(format!("invalid JSON: {err}")))
+    }
+
+    /// Loads a manifest from a `.json` file, or a TOML file otherwise.
+    #[cfg(feature = "config")]
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> Result<Self, ArmError> {
+        let path = path.as_ref();
+        let s = std::fs::read_to_string(path)
+            .map_err(|err| ArmError::InvalidManifest(format!("can't read manifest: {err}")))?;
+        if path.extension().is_some_and(|ext| ext == "json") {
+            Self::from_json_str(&s)
+        } else {
+            Self::from_toml_str(&s)
+        }
+    }
+
+    /// Serializes the manifest as a TOML document.
+    #[cfg(feature = "config")]
+    pub fn to_toml_string(&self) -> Result<String, ArmError> {
+        toml::to_string(self).map_err(|_| ArmError::SerializationError)
+    }
+
+    /// Serializes the manifest as a JSON document.
+    #[cfg(feature = "config")]
+    pub fn to_json_string(&self) -> Result<String, ArmError> {
+        serde_json::to_string_pretty(self).map_err(|_| ArmError::SerializationError)
+    }
+
+    /// Checks that this build can verify transactions of the deployment: its
+    /// circuits and initial root must be those of the manifest.
+    pub fn validate(&self) -> Result<(), ArmError> {
+        if self.network_id.is_empty() {
+            return Err(ArmError::InvalidManifest("empty network id".to_string()));
+        }
+        if self.compliance_vk != *COMPLIANCE_VK {
+            return Err(ArmError::InvalidManifest(format!(
+                "compliance vk {} isn't the one of this build",
+                self.compliance_vk
+            )));
+        }
+        #[cfg(feature = "aggregation")]
+        for (name, vk, expected) in [
+            (
+                "sequential aggregation",
+                self.sequential_aggregation_vk,
+                *SEQUENTIAL_AGGREGATION_VK,
+            ),
+            (
+                "batch aggregation",
+                self.batch_aggregation_vk,
+                *BATCH_AGGREGATION_VK,
+            ),
+        ] {
+            if vk.is_some_and(|vk| vk != expected) {
+                return Err(ArmError::InvalidManifest(format!(
+                    "{name} vk isn't the one of this build"
+                )));
+            }
+        }
+        if self.initial_root != *INITIAL_ROOT {
+            return Err(ArmError::InvalidManifest(format!(
+                "unexpected initial root {}",
+                self.initial_root
+            )));
+        }
+        if !(1..=MAX_COMMITMENT_TREE_DEPTH).contains(&self.commitment_tree_depth) {
+            return Err(ArmError::InvalidManifest(format!(
+                "unsupported commitment tree depth {}",
+                self.commitment_tree_depth
+            )));
+        }
+        Ok(())
+    }
+
+    /// Validates the manifest and returns the verification context of the
+    /// deployment. If the manifest restricts the accepted logics, `registry`
+    /// must be the one it commits to, and the context only accepts its logics
+    /// and the padding logic. The context only accepts delta proofs of the
+    /// manifest's mode.
+    pub fn verification_context(
+        &self,
+        registry: &LogicRegistry,
+    ) -> Result<VerificationContext, ArmError> {
+        self.validate()?;
+        let mut ctx = VerificationContext {
+            delta_proof_mode: Some(self.delta_proof_mode),
+            ..Default::default()
+        };
+        if let Some(root) = self.logic_registry_root {
+            if registry.root()? != root {
+                return Err(ArmError::InvalidManifest(
+                    "logic registry doesn't match the manifest".to_string(),
+                ));
+            }
+            ctx.known_logics = Some(
+                registry
+                    .logic_refs()
+                    .copied()
+                    .chain([*PADDING_LOGIC_VK])
+                    .collect(),
+            );
+        }
+        Ok(ctx)
+    }
+}
+
+mod hex_digest {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(digest: &Digest, serializer: S) -> Result<S::Ok, S::Error> {
+        digest.to_hex().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Digest, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        Digest::from_hex(&hex).map_err(serde::de::Error::custom)
+    }
+}
+
+mod hex_digest_opt {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        digest: &Option<Digest>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        digest.map(|digest| digest.to_hex()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Digest>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|hex| Digest::from_hex(&hex).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+#[test]
+fn test_deployment_manifest() {
+    let logic = Digest::from_bytes([1u8; 32]);
+    let mut registry = LogicRegistry::new();
+    registry.register(logic, "transfer", "1.0.0").unwrap();
+
+    let manifest = DeploymentManifest::current("testnet", 32);
+    assert_eq!(manifest.validate(), Ok(()));
+    let ctx = manifest.verification_context(&registry).unwrap();
+    assert_eq!(ctx.known_logics, None);
+    assert_eq!(ctx.delta_proof_mode, Some(DeltaProofMode::Signature));
+
+    let manifest = manifest.with_logic_registry(&registry).unwrap();
+    let ctx = manifest.verification_context(&registry).unwrap();
+    let known_logics = ctx.known_logics.unwrap();
+    assert!(known_logics.contains(&logic) && known_logics.contains(&PADDING_LOGIC_VK));
+    assert!(manifest
+        .verification_context(&LogicRegistry::new())
+        .is_err());
+
+    let mut other = manifest.clone();
+    other.compliance_vk = Digest::default();
+    assert!(other.validate().is_err());
+    let mut other = manifest.clone();
+    other.commitment_tree_depth = 0;
+    assert!(other.validate().is_err());
+    let mut other = manifest;
+    other.network_id.clear();
+    assert!(other.validate().is_err());
+}
+
+#[cfg(feature = "config")]
+#[test]
+fn test_deployment_manifest_files() {
+    let mut registry = LogicRegistry::new();
+    registry
+        .register(Digest::from_bytes([1u8; 32]), "transfer", "1.0.0")
+        .unwrap();
+    let manifest = DeploymentManifest::current("testnet", 32)
+        .with_logic_registry(&registry)
+        .unwrap();
+
+    let toml = manifest.to_toml_string().unwrap();
+    assert!(toml.contains(&format!("compliance_vk = \"{}\"", COMPLIANCE_VK.to_hex())));
+    assert_eq!(
+        DeploymentManifest::from_toml_str(&toml),
+        Ok(manifest.clone())
+    );
+    let json = manifest.to_json_string().unwrap();
+    assert_eq!(
+        DeploymentManifest::from_json_str(&json),
+        Ok(manifest.clone())
+    );
+
+    let minimal = format!(
+        "network_id = \"devnet\"\ncompliance_vk = \"0x{}\"\ninitial_root = \"{}\"\ncommitment_tree_depth = 32\n",
+        COMPLIANCE_VK.to_hex(),
+        INITIAL_ROOT.to_hex()
+    );
+    let parsed = DeploymentManifest::from_toml_str(&minimal).unwrap();
+    assert_eq!(parsed.validate(), Ok(()));
+    assert_eq!(parsed.logic_registry_root, None);
+    assert_eq!(parsed.delta_proof_mode, DeltaProofMode::Signature);
+    let parsed =
+        DeploymentManifest::from_toml_str(&format!("{minimal}delta_proof_mode = \"opening\"\n"))
+            .unwrap();
+    assert_eq!(parsed.delta_proof_mode, DeltaProofMode::Opening);
+
+    assert!(DeploymentManifest::from_toml_str(&format!("{minimal}network = 1\n")).is_err());
+    assert!(DeploymentManifest::from_json_str("{\"network_id\": \"devnet\"}").is_err());
+}
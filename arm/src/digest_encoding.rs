@@ -0,0 +1,76 @@
+//! Hex and byte conversions for digest-like values.
+//!
+//! Tags, roots, logic refs and verifying keys are plain [`Digest`]s, and
+//! commitments wrap one. [`DigestEncoding`] gives all of them the same
+//! fallible conversions, so callers don't need `hex::FromHex` and `unwrap`.
+
+use crate::error::ArmError;
+use risc0_zkvm::sha::{Digest, DIGEST_BYTES};
+
+/// Conversions of a value that is, or wraps, a 32-byte digest.
+pub trait DigestEncoding: Sized {
+    /// Wraps a digest.
+    fn from_digest(digest: Digest) -> Self;
+
+    /// Returns the wrapped digest.
+    fn to_digest(&self) -> Digest;
+
+    /// Returns the 32 bytes of the digest.
+    fn to_bytes(&self) -> [u8; DIGEST_BYTES] {
+        self.to_digest().into()
+    }
+
+    /// Parses exactly 32 bytes.
+    fn try_from_bytes(bytes: &[u8]) -> Result<Self, ArmError> {
+        Digest::try_from(bytes)
+            .map(Self::from_digest)
+            .map_err(|_| ArmError::InvalidDigest)
+    }
+
+    /// Returns the lowercase hex encoding of the digest, without prefix.
+    fn to_hex(&self) -> String {
+        hex::encode(self.to_bytes())
+    }
+
+    /// Parses 64 hex digits, with or without a `0x` prefix.
+    fn from_hex(hex: &str) -> Result<Self, ArmError> {
+        let hex = hex.strip_prefix("0x").unwrap_or(hex);
+        let bytes = hex::decode(hex).map_err(|_| ArmError::InvalidDigest)?;
+        Self::try_from_bytes(&bytes)
+    }
+}
+
+impl DigestEncoding for Digest {
+    fn from_digest(digest: Digest) -> Self {
+        digest
+    }
+
+    fn to_digest(&self) -> Digest {
+        *self
+    }
+}
+
+#[test]
+fn test_digest_encoding() {
+    use crate::nullifier_key::{NullifierKey, NullifierKeyCommitment};
+
+    let digest = Digest::from_bytes([0xab; 32]);
+    let hex = digest.to_hex();
+    assert_eq!(hex, "ab".repeat(32));
+    assert_eq!(Digest::from_hex(&hex), Ok(digest));
+    assert_eq!(Digest::from_hex(&format!("0x{hex}")), Ok(digest));
+    assert_eq!(Digest::from_hex(&hex[2..]), Err(ArmError::InvalidDigest));
+    assert_eq!(Digest::from_hex("zz"), Err(ArmError::InvalidDigest));
+    assert_eq!(
+        Digest::try_from_bytes(&[0xab; 31]),
+        Err(ArmError::InvalidDigest)
+    );
+
+    let nk_commitment = NullifierKey::default().commit();
+    let parsed = NullifierKeyCommitment::from_hex(&nk_commitment.to_hex()).unwrap();
+    assert_eq!(parsed, nk_commitment);
+    assert_eq!(
+        NullifierKeyCommitment::try_from_bytes(&nk_commitment.to_bytes()),
+        Ok(nk_commitment)
+    );
+}
@@ -0,0 +1,86 @@
+//! Minimum quantities of created resources.
+//!
+//! Every created resource adds a leaf to the commitment tree, so spamming
+//! resources of negligible quantity bloats it for everyone. A [`DustPolicy`]
+//! sets a minimum quantity per kind, keyed like the [`KindRegistry`], which
+//! builders enforce when creating resources. Ephemeral resources never reach
+//! the tree and are exempt.
+//!
+//! [`KindRegistry`]: crate::kind_registry::KindRegistry
+
+use crate::{error::ArmError, resource::Resource};
+use risc0_zkvm::Digest;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Minimum quantities of resource kinds, keyed by `(logic_ref, label_ref)`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DustPolicy {
+    min_quantities: BTreeMap<(Digest, Digest), u128>,
+}
+
+impl DustPolicy {
+    /// Creates a policy without any minimum.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the minimum quantity of a kind, returning the previous minimum.
+    pub fn set_min_quantity(
+        &mut self,
+        logic_ref: Digest,
+        label_ref: Digest,
+        min_quantity: u128,
+    ) -> Option<u128> {
+        self.min_quantities
+            .insert((logic_ref, label_ref), min_quantity)
+    }
+
+    /// Returns the minimum quantity of a kind, zero if it has none.
+    pub fn min_quantity(&self, logic_ref: &Digest, label_ref: &Digest) -> u128 {
+        self.min_quantities
+            .get(&(*logic_ref, *label_ref))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Checks that a created resource isn't dust.
+    pub fn check_resource(&self, resource: &Resource) -> Result<(), ArmError> {
+        if !resource.is_ephemeral
+            && resource.quantity < self.min_quantity(&resource.logic_ref, &resource.label_ref)
+        {
+            return Err(ArmError::DustQuantity);
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_dust_policy() {
+    let logic_ref = Digest::from_bytes([1u8; 32]);
+    let label_ref = Digest::from_bytes([2u8; 32]);
+    let mut policy = DustPolicy::new();
+    assert_eq!(policy.set_min_quantity(logic_ref, label_ref, 100), None);
+
+    let mut resource = Resource {
+        logic_ref,
+        label_ref,
+        quantity: 99,
+        is_ephemeral: false,
+        ..Default::default()
+    };
+    assert_eq!(
+        policy.check_resource(&resource),
+        Err(ArmError::DustQuantity)
+    );
+    resource.quantity = 100;
+    assert!(policy.check_resource(&resource).is_ok());
+
+    // Ephemeral resources and other kinds are exempt.
+    resource.quantity = 0;
+    resource.is_ephemeral = true;
+    assert!(policy.check_resource(&resource).is_ok());
+    resource.is_ephemeral = false;
+    resource.label_ref = Digest::default();
+    assert!(policy.check_resource(&resource).is_ok());
+}
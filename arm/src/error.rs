@@ -1,8 +1,10 @@
 //! Arm-specific error types.
 #![allow(missing_docs)]
+use risc0_zkvm::Digest;
 use thiserror::Error;
 
 #[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum ArmError {
     #[error("Invalid resource kind")]
     InvalidResourceKind,
@@ -42,6 +44,8 @@ pub enum ArmError {
     DeltaProofVerificationFailed,
     #[error("Expected delta proof, but found witness")]
     ExpectedDeltaProof,
+    #[error("Expected delta witness, but found proof")]
+    ExpectedDeltaWitness,
     #[error("Invalid resource value reference")]
     InvalidResourceValueRef,
     #[error("Invalid leaf")]
@@ -50,6 +54,8 @@ pub enum ArmError {
     ProveFailed(String),
     #[error("Proof verification failed with return code {0}")]
     ProofVerificationFailed(String),
+    #[error("Invalid logic instance")]
+    InvalidLogicInstance,
     #[error("Invalid compliance instance")]
     InvalidComplianceInstance,
     #[error("Delta proof generation failed")]
@@ -80,4 +86,170 @@ pub enum ArmError {
     TreeTooLarge,
     #[error("Invalid delta proof: pls regenerate the proof")]
     InvalidDeltaProof,
+    #[error("Invalid sealed transaction commitment")]
+    InvalidTransactionCommitment,
+    #[error("Invalid signing payload")]
+    InvalidSigningPayload,
+    #[error("Transaction rejected with reason code {0}")]
+    TransactionRejected(u8),
+    #[error("Artifact cache error: {0}")]
+    ArtifactCacheError(String),
+    #[error("Invalid artifact manifest")]
+    InvalidArtifactManifest,
+    #[error("Rate limit exceeded")]
+    RateLimitExceeded,
+    #[error("Invalid key rotation")]
+    InvalidKeyRotation,
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+    #[error("Transaction doesn't fit the padding shape")]
+    TransactionShapeMismatch,
+    #[error("Duplicate delta witness")]
+    DuplicateDeltaWitness,
+    #[error("Number of rcvs doesn't match the number of compliance units")]
+    RcvCountMismatch,
+    #[error("Witness compression failed")]
+    CompressionFailed,
+    #[error("Witness decompression failed")]
+    DecompressionFailed,
+    #[error("External payload on a non-ephemeral resource")]
+    ExternalPayloadNotEphemeral,
+    #[error("Timelock not open yet")]
+    TimelockNotOpen,
+    #[error("Invalid key encoding")]
+    InvalidKeyEncoding,
+    #[error("Witness schema mismatch: host expects version {host}, guest ELF was built for version {guest}")]
+    WitnessSchemaMismatch { host: u32, guest: u32 },
+    #[error("App data doesn't match the committed digest")]
+    AppDataDigestMismatch,
+    #[error("Action index {0} is out of range")]
+    ActionIndexOutOfRange(usize),
+    #[error("Invalid audit bundle")]
+    InvalidAuditBundle,
+    #[error("Unresolved resource reference")]
+    UnresolvedResourceRef,
+    #[error("Quantities don't add up")]
+    QuantityMismatch,
+    #[error("Created resource quantity below the kind's minimum")]
+    DustQuantity,
+    #[error("Memo too long")]
+    MemoTooLong,
+    #[error("Entropy unavailable")]
+    EntropyUnavailable,
+    #[error("Invalid logic version")]
+    InvalidLogicVersion,
+    #[error("Invalid recovery threshold")]
+    InvalidRecoveryThreshold,
+    #[error("Key recovery failed")]
+    RecoveryFailed,
+    #[error("Invalid digest encoding")]
+    InvalidDigest,
+    #[error("Tags root doesn't match the actions")]
+    TagsRootMismatch,
+    #[error("Transaction doesn't commit to a tags root")]
+    MissingTagsRoot,
+    #[error("Invalid inclusion receipt")]
+    InvalidInclusionReceipt,
+    #[error("Bench circuit ELF unavailable")]
+    BenchCircuitUnavailable,
+    #[error("Invalid deployment manifest: {0}")]
+    InvalidManifest(String),
+    #[error("Unsupported delta proof mode")]
+    UnsupportedDeltaProofMode,
+    #[error("Anchor proof doesn't extend the trusted root")]
+    AnchorMismatch,
+    #[error("Invalid viewing grant")]
+    InvalidViewingGrant,
+    #[error("Scope not granted by the viewing credential")]
+    ScopeNotGranted,
+    #[error("Payload lint failed: {0}")]
+    PayloadLintFailed(String),
+    #[error("Oracle attestation is for another feed")]
+    OracleFeedMismatch,
+    #[error("Oracle attestation is stale")]
+    StaleAttestation,
+    #[error("Invalid fee route")]
+    InvalidFeeRoute,
+    #[error("Action is not in canonical order")]
+    NonCanonicalOrder,
+    #[error("Transaction is not balanced")]
+    UnbalancedTransaction,
+    #[error("Protocol version mismatch: host supports major version {host}, found {found}")]
+    ProtocolVersionMismatch { host: u16, found: u16 },
+    #[error("Transaction can't be encoded in protocol version {0}")]
+    NotRepresentableInVersion(u16),
+    #[error("Nullifier is spent")]
+    NullifierSpent,
+    #[error("Invalid non-membership proof")]
+    InvalidNonMembershipProof,
+    #[error("Compliance unit without consumed or created resources")]
+    EmptyComplianceUnit,
+    #[error("The guest committed a different instance than the host constraints")]
+    GuestDivergence,
+    #[error("Receipt was produced under verifier parameters other than those of risc0 {0}")]
+    IncompatibleReceipt(String),
+    #[error("Unsupported canonical encoding version {0}")]
+    UnsupportedEncodingVersion(u8),
+    #[error("Issuer isn't authorized")]
+    UnauthorizedIssuer,
+    #[error("Instance commits to an app data digest but the full app data is missing")]
+    MissingFullAppData,
+    #[error("{source} (action {action_index}, tag {tag})")]
+    WithTag {
+        action_index: usize,
+        tag: Digest,
+        source: Box<ArmError>,
+    },
+}
+
+impl ArmError {
+    /// Attaches the offending tag and action index to the error. An error
+    /// that already carries a tag only gets its action index updated.
+    pub fn with_tag(self, action_index: usize, tag: Digest) -> Self {
+        match self {
+            ArmError::WithTag { tag, source, .. } => ArmError::WithTag {
+                action_index,
+                tag,
+                source,
+            },
+            source => ArmError::WithTag {
+                action_index,
+                tag,
+                source: Box::new(source),
+            },
+        }
+    }
+
+    /// Sets the action index of an error carrying a tag; other errors are
+    /// returned unchanged.
+    pub fn at_action(self, action_index: usize) -> Self {
+        match self {
+            ArmError::WithTag { tag, source, .. } => ArmError::WithTag {
+                action_index,
+                tag,
+                source,
+            },
+            other => other,
+        }
+    }
+
+    /// Returns the underlying error, without tag context.
+    pub fn root_cause(&self) -> &ArmError {
+        match self {
+            ArmError::WithTag { source, .. } => source.root_cause(),
+            other => other,
+        }
+    }
+}
+
+#[test]
+fn test_error_with_tag() {
+    let tag = Digest::from_bytes([1u8; 32]);
+    let err = ArmError::TagNotFound.with_tag(0, tag).at_action(3);
+    assert_eq!(err.root_cause(), &ArmError::TagNotFound);
+    assert_eq!(
+        err.to_string(),
+        format!("Tag not found (action 3, tag {tag})")
+    );
+    assert_eq!(ArmError::TagNotFound.at_action(3), ArmError::TagNotFound);
 }
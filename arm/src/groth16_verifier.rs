@@ -0,0 +1,79 @@
+//! Lean verification of Groth16 receipts.
+//!
+//! Settled transactions carry constant-size Groth16 seals (see
+//! [`encode_seal`](crate::proving_system::encode_seal)). Checking one only
+//! needs the BN254 pairing against the fixed risc0 verifying key, so this
+//! module rebuilds the receipt claim from the image id and journal and runs
+//! the pairing check directly, without deserializing receipts or touching the
+//! prover/executor stack. It is meant for constrained verifiers such as mobile
+//! clients or services next to an HSM.
+
+use crate::error::ArmError;
+use risc0_binfmt::tagged_struct;
+use risc0_circuit_recursion::control_id::{ALLOWED_CONTROL_ROOT, BN254_IDENTITY_CONTROL_ID};
+use risc0_zkvm::sha::{Digest, Impl, Sha256};
+
+/// Length of the verifier selector prepended by `encode_seal`.
+pub const SELECTOR_LEN: usize = 4;
+
+/// Computes the digest of a successful (halted with exit code 0) receipt claim
+/// for the given image id and journal, with no assumptions.
+pub fn receipt_claim_digest(image_id: &Digest, journal: &[u8]) -> Digest {
+    // Terminal system state of a halted program: pc 0 and an empty memory root.
+    let post = tagged_struct::<Impl>("risc0.SystemState", &[Digest::ZERO], &[0]);
+    let output = tagged_struct::<Impl>(
+        "risc0.Output",
+        &[*Impl::hash_bytes(journal), Digest::ZERO],
+        &[],
+    );
+    tagged_struct::<Impl>(
+        "risc0.ReceiptClaim",
+        &[Digest::ZERO, *image_id, post, output],
+        &[0, 0],
+    )
+}
+
+/// Verifies a raw 256-byte Groth16 seal for the given image id and journal.
+pub fn verify_groth16(seal: &[u8], image_id: &Digest, journal: &[u8]) -> Result<(), ArmError> {
+    let claim_digest = receipt_claim_digest(image_id, journal);
+    risc0_groth16::Verifier::new(
+        seal,
+        ALLOWED_CONTROL_ROOT,
+        claim_digest,
+        BN254_IDENTITY_CONTROL_ID,
+        &risc0_groth16::verifying_key(),
+    )
+    .and_then(|verifier| verifier.verify())
+    .map_err(|err| ArmError::ProofVerificationFailed(format!("Groth16 verification failed: {err}")))
+}
+
+/// Verifies a selector-prefixed seal as produced by `encode_seal`.
+pub fn verify_encoded_seal(
+    encoded_seal: &[u8],
+    image_id: &Digest,
+    journal: &[u8],
+) -> Result<(), ArmError> {
+    if encoded_seal.len() <= SELECTOR_LEN {
+        return Err(ArmError::UnsupportedProofType);
+    }
+    verify_groth16(&encoded_seal[SELECTOR_LEN..], image_id, journal)
+}
+
+#[test]
+fn test_receipt_claim_digest() {
+    use risc0_zkvm::{sha::Digestible, ReceiptClaim};
+
+    let image_id = Digest::from([7u32; 8]);
+    let journal = b"settled transaction".to_vec();
+    assert_eq!(
+        receipt_claim_digest(&image_id, &journal),
+        ReceiptClaim::ok(image_id, journal.clone()).digest()
+    );
+
+    let seal = vec![0u8; SELECTOR_LEN + 256];
+    assert!(verify_encoded_seal(&seal, &image_id, &journal).is_err());
+    assert_eq!(
+        verify_encoded_seal(&seal[..SELECTOR_LEN], &image_id, &journal),
+        Err(ArmError::UnsupportedProofType)
+    );
+}
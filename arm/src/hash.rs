@@ -1,40 +1,140 @@
-//! Hashing utilities for the aggregation circuits.
+//! Hash chains of the aggregation circuits.
+//!
+//! Aggregation steps commit to the outputs and programs of the proofs they
+//! verify by hash-chaining them with the digests of the previous steps. The
+//! chains are SHA-256 over fixed-length encodings:
+//!
+//! - the output hash of a step is `SHA256(output || h_1 || ... || h_n)`,
+//!   where `output` is the step output's RISC0 serde words, each encoded as 4
+//!   little-endian bytes, and `h_i` are the 32-byte output hashes of the
+//!   previous steps;
+//! - the program hash of a step is `SHA256(program || d_1 || ... || d_n)`,
+//!   where `program` is the 32-byte image id of the step program and `d_i`
+//!   are the program hashes of the previous steps;
+//! - a chain starts from [`BASE_CASE_DIGEST`].
+//!
+//! The circuits in `arm_circuits` use these functions; alternative
+//! implementations must reproduce the [`test_vectors`] to commit identical
+//! chains.
 
+use crate::digest_encoding::DigestEncoding;
 use risc0_zkvm::sha::{Impl, Sha256, DIGEST_BYTES};
 use risc0_zkvm::Digest;
 
-use crate::utils::words_to_bytes;
+/// The digest the output and program hashes of a chain start from.
+pub const BASE_CASE_DIGEST: Digest = Digest::ZERO;
 
 /// Commits the step output together with the previous hashes using SHA256.
 pub fn commit_step_output_with_sha(prev_hashes: &[Digest], step_output_serde: &[u32]) -> Digest {
-    sha_digest(
-        &flatten_prev_hashes(prev_hashes),
-        words_to_bytes(step_output_serde),
-    )
+    let tip: Vec<u8> = step_output_serde
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .collect();
+    sha_digest(&tip, prev_hashes)
 }
 
 /// Commits the step program together with the previous hashes using SHA256.
 pub fn commit_step_program_with_sha(prev_hashes: &[Digest], step_program: &Digest) -> Digest {
-    sha_digest(&flatten_prev_hashes(prev_hashes), step_program.as_bytes())
+    sha_digest(step_program.as_bytes(), prev_hashes)
 }
 
-fn sha_digest(prev_hashes: &[u8], tip: &[u8]) -> Digest {
-    let prev_hashes_length: usize = prev_hashes.len();
-    let tip_length: usize = tip.len();
-    let mut bytes = vec![0u8; tip_length + prev_hashes_length];
-    bytes[0..tip_length].clone_from_slice(tip);
-    bytes[tip_length..tip_length + prev_hashes_length].clone_from_slice(prev_hashes);
+/// Computes the output and program hashes of a sequential chain of steps,
+/// given as their programs and serialized outputs, as committed by the last
+/// step of the sequential aggregation.
+pub fn sequential_chain<'a>(
+    steps: impl IntoIterator<Item = (&'a Digest, &'a [u32])>,
+) -> (Digest, Digest) {
+    steps.into_iter().fold(
+        (BASE_CASE_DIGEST, BASE_CASE_DIGEST),
+        |(h_in, d_in), (program, output)| {
+            (
+                commit_step_output_with_sha(&[h_in], output),
+                commit_step_program_with_sha(&[d_in], program),
+            )
+        },
+    )
+}
 
+fn sha_digest(tip: &[u8], prev_hashes: &[Digest]) -> Digest {
+    let mut bytes = Vec::with_capacity(tip.len() + DIGEST_BYTES * prev_hashes.len());
+    bytes.extend_from_slice(tip);
+    for hash in prev_hashes {
+        bytes.extend_from_slice(hash.as_bytes());
+    }
     *Impl::hash_bytes(&bytes)
 }
 
-fn flatten_prev_hashes(prev_hashes: &[Digest]) -> Vec<u8> {
-    let mut prev_hashes_bytes = vec![0u8; DIGEST_BYTES * prev_hashes.len()];
-    let mut offset: usize = 0;
-    for h in prev_hashes {
-        prev_hashes_bytes[offset..offset + DIGEST_BYTES].clone_from_slice(h.as_bytes());
-        offset += DIGEST_BYTES;
+/// Inputs and expected hashes of a step.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StepHashVector {
+    /// The hashes of the previous steps.
+    pub prev_hashes: Vec<Digest>,
+    /// The serialized step output.
+    pub step_output: Vec<u32>,
+    /// The image id of the step program.
+    pub step_program: Digest,
+    /// The expected [`commit_step_output_with_sha`].
+    pub output_hash: Digest,
+    /// The expected [`commit_step_program_with_sha`].
+    pub program_hash: Digest,
+}
+
+/// Test vectors of the step hashes: a base step, a sequential step and a
+/// step of arity 2.
+pub fn test_vectors() -> Vec<StepHashVector> {
+    // The vectors are valid hex
+    let digest = |hex: &str| Digest::from_hex(hex).unwrap();
+    vec![
+        StepHashVector {
+            prev_hashes: vec![BASE_CASE_DIGEST],
+            step_output: vec![],
+            step_program: Digest::from_bytes([0xaa; 32]),
+            output_hash: digest("66687aadf862bd776c8fc18b8e9f8e20089714856ee233b3902a591d0d5f2925"),
+            program_hash: digest(
+                "0af8d914beb10528917ce4dd749623ed65095c2464b23f939f49eba9e21a1ca4",
+            ),
+        },
+        StepHashVector {
+            prev_hashes: vec![Digest::from_bytes([0x11; 32])],
+            step_output: vec![1, 2, 0xdead_beef],
+            step_program: Digest::from_bytes([0xbb; 32]),
+            output_hash: digest("25a63eec9e87760930e400795431fce71cbb91f78e25d017bdfd4bccf12e2cd9"),
+            program_hash: digest(
+                "0504edd6bd57a70ab063536347e5def756e95205158d58f271ff3f82280db9ba",
+            ),
+        },
+        StepHashVector {
+            prev_hashes: vec![
+                Digest::from_bytes([0x11; 32]),
+                Digest::from_bytes([0x22; 32]),
+            ],
+            step_output: vec![0x0102_0304; 9],
+            step_program: Digest::from_bytes([0xcc; 32]),
+            output_hash: digest("3b745bad2282ca2d3cb9a64ffce652d9c6b3e746efbe985c783e24ae03d3bbd1"),
+            program_hash: digest(
+                "1bab094e5d5beca180473c987cbbe4ed890ad252ef32c35f126d7b0f47e8b816",
+            ),
+        },
+    ]
+}
+
+#[test]
+fn test_step_hash_vectors() {
+    for vector in test_vectors() {
+        assert_eq!(
+            commit_step_output_with_sha(&vector.prev_hashes, &vector.step_output),
+            vector.output_hash
+        );
+        assert_eq!(
+            commit_step_program_with_sha(&vector.prev_hashes, &vector.step_program),
+            vector.program_hash
+        );
     }
 
-    prev_hashes_bytes
+    let program = Digest::from_bytes([0xaa; 32]);
+    let (h, d) = sequential_chain([(&program, &[][..]), (&program, &[1u32, 2][..])]);
+    let h_1 = commit_step_output_with_sha(&[BASE_CASE_DIGEST], &[]);
+    let d_1 = commit_step_program_with_sha(&[BASE_CASE_DIGEST], &program);
+    assert_eq!(h, commit_step_output_with_sha(&[h_1], &[1, 2]));
+    assert_eq!(d, commit_step_program_with_sha(&[d_1], &program));
 }
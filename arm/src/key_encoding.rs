@@ -0,0 +1,244 @@
+//! Checksummed text encodings for keys.
+//!
+//! Keys moved between tools (e.g. the Elixir SDK and Rust wallets) as raw hex
+//! are easy to truncate or paste into the wrong field. [`KeyEncoding`] gives
+//! each key type a human-readable prefix and two checksummed encodings:
+//! bech32m (`<hrp>1<data><checksum>`, BIP-350) and checked hex
+//! (`<hrp>:<hex><checksum>`, the checksum being the first 4 bytes of
+//! `sha256(hrp || key)`).
+
+use crate::{
+    error::ArmError,
+    nullifier_key::{NullifierKey, NullifierKeyCommitment},
+    utils::hash_bytes,
+};
+use subtle::ConstantTimeEq;
+
+const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const BECH32M_CONST: u32 = 0x2bc830a3;
+const CHECKSUM_LEN: usize = 6;
+const HEX_CHECKSUM_BYTES: usize = 4;
+
+/// Text encodings of a key type, identified by its human-readable prefix.
+pub trait KeyEncoding: Sized {
+    /// The human-readable prefix of the key type.
+    const HRP: &'static str;
+
+    /// Returns the raw key bytes.
+    fn key_bytes(&self) -> Vec<u8>;
+
+    /// Parses the raw key bytes.
+    fn from_key_bytes(bytes: &[u8]) -> Result<Self, ArmError>;
+
+    /// Encodes the key as a bech32m string.
+    fn to_encoded(&self) -> String {
+        encode_bech32m(Self::HRP, &self.key_bytes())
+    }
+
+    /// Decodes a key from a bech32m string, checking its prefix and checksum.
+    fn from_encoded(encoded: &str) -> Result<Self, ArmError> {
+        let (hrp, bytes) = decode_bech32m(encoded)?;
+        if hrp != Self::HRP {
+            return Err(ArmError::InvalidKeyEncoding);
+        }
+        Self::from_key_bytes(&bytes)
+    }
+
+    /// Encodes the key as checked hex.
+    fn to_checked_hex(&self) -> String {
+        let bytes = self.key_bytes();
+        let checksum = hex_checksum(Self::HRP, &bytes);
+        format!(
+            "{}:{}{}",
+            Self::HRP,
+            hex::encode(bytes),
+            hex::encode(checksum)
+        )
+    }
+
+    /// Decodes a key from checked hex, checking its prefix and checksum.
+    fn from_checked_hex(encoded: &str) -> Result<Self, ArmError> {
+        let (hrp, data) = encoded
+            .split_once(':')
+            .ok_or(ArmError::InvalidKeyEncoding)?;
+        let data = hex::decode(data).map_err(|_| ArmError::InvalidKeyEncoding)?;
+        if hrp != Self::HRP || data.len() < HEX_CHECKSUM_BYTES {
+            return Err(ArmError::InvalidKeyEncoding);
+        }
+        let (bytes, checksum) = data.split_at(data.len() - HEX_CHECKSUM_BYTES);
+        if !bool::from(hex_checksum(hrp, bytes).ct_eq(checksum)) {
+            return Err(ArmError::InvalidKeyEncoding);
+        }
+        Self::from_key_bytes(bytes)
+    }
+}
+
+impl KeyEncoding for NullifierKey {
+    const HRP: &'static str = "armnk";
+
+    fn key_bytes(&self) -> Vec<u8> {
+        self.inner().to_vec()
+    }
+
+    fn from_key_bytes(bytes: &[u8]) -> Result<Self, ArmError> {
+        let bytes = bytes
+            .try_into()
+            .map_err(|_| ArmError::InvalidNullifierKey)?;
+        Ok(NullifierKey::from_bytes(bytes))
+    }
+}
+
+impl KeyEncoding for NullifierKeyCommitment {
+    const HRP: &'static str = "armnkc";
+
+    fn key_bytes(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+
+    fn from_key_bytes(bytes: &[u8]) -> Result<Self, ArmError> {
+        NullifierKeyCommitment::from_bytes(bytes)
+    }
+}
+
+/// Encodes data as a bech32m string with the given prefix.
+pub fn encode_bech32m(hrp: &str, data: &[u8]) -> String {
+    let mut values = convert_bits(data, 8, 5, true).expect("padding never fails");
+    let checksum = create_checksum(hrp, &values);
+    values.extend_from_slice(&checksum);
+
+    let mut encoded = String::with_capacity(hrp.len() + 1 + values.len());
+    encoded.push_str(hrp);
+    encoded.push('1');
+    encoded.extend(values.iter().map(|v| CHARSET[*v as usize] as char));
+    encoded
+}
+
+/// Decodes a bech32m string into its prefix and data.
+pub fn decode_bech32m(encoded: &str) -> Result<(String, Vec<u8>), ArmError> {
+    if encoded.to_lowercase() != encoded && encoded.to_uppercase() != encoded {
+        return Err(ArmError::InvalidKeyEncoding);
+    }
+    let encoded = encoded.to_lowercase();
+    let (hrp, data) = encoded
+        .rsplit_once('1')
+        .ok_or(ArmError::InvalidKeyEncoding)?;
+    if hrp.is_empty() || data.len() < CHECKSUM_LEN || !hrp.bytes().all(|c| (33..=126).contains(&c))
+    {
+        return Err(ArmError::InvalidKeyEncoding);
+    }
+
+    let values = data
+        .bytes()
+        .map(|c| CHARSET.iter().position(|v| *v == c).map(|v| v as u8))
+        .collect::<Option<Vec<u8>>>()
+        .ok_or(ArmError::InvalidKeyEncoding)?;
+    if polymod(&[hrp_expand(hrp), values.clone()].concat()) != BECH32M_CONST {
+        return Err(ArmError::InvalidKeyEncoding);
+    }
+
+    let bytes = convert_bits(&values[..values.len() - CHECKSUM_LEN], 5, 8, false)
+        .ok_or(ArmError::InvalidKeyEncoding)?;
+    Ok((hrp.to_string(), bytes))
+}
+
+fn polymod(values: &[u8]) -> u32 {
+    const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+    values.iter().fold(1u32, |chk, value| {
+        let top = chk >> 25;
+        let chk = ((chk & 0x1ffffff) << 5) ^ *value as u32;
+        GENERATOR
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| (top >> i) & 1 == 1)
+            .fold(chk, |chk, (_, g)| chk ^ g)
+    })
+}
+
+fn hrp_expand(hrp: &str) -> Vec<u8> {
+    let mut expanded: Vec<u8> = hrp.bytes().map(|c| c >> 5).collect();
+    expanded.push(0);
+    expanded.extend(hrp.bytes().map(|c| c & 31));
+    expanded
+}
+
+fn create_checksum(hrp: &str, values: &[u8]) -> [u8; CHECKSUM_LEN] {
+    let mut input = [hrp_expand(hrp), values.to_vec()].concat();
+    input.extend_from_slice(&[0u8; CHECKSUM_LEN]);
+    let polymod = polymod(&input) ^ BECH32M_CONST;
+    let mut checksum = [0u8; CHECKSUM_LEN];
+    for (i, value) in checksum.iter_mut().enumerate() {
+        *value = ((polymod >> (5 * (5 - i))) & 31) as u8;
+    }
+    checksum
+}
+
+// Regroups bits, e.g. bytes into 5-bit values and back.
+fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Option<Vec<u8>> {
+    let mut acc = 0u32;
+    let mut bits = 0u32;
+    let max = (1u32 << to) - 1;
+    let max_acc = (1u32 << (from + to - 1)) - 1;
+    let mut out = Vec::new();
+    for value in data {
+        acc = ((acc << from) | *value as u32) & max_acc;
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            out.push(((acc >> bits) & max) as u8);
+        }
+    }
+    if pad {
+        if bits > 0 {
+            out.push(((acc << (to - bits)) & max) as u8);
+        }
+    } else if bits >= from || ((acc << (to - bits)) & max) != 0 {
+        return None;
+    }
+    Some(out)
+}
+
+fn hex_checksum(hrp: &str, bytes: &[u8]) -> Vec<u8> {
+    hash_bytes(&[hrp.as_bytes(), bytes].concat()).as_bytes()[..HEX_CHECKSUM_BYTES].to_vec()
+}
+
+#[test]
+fn test_bech32m_vectors() {
+    // Valid bech32m strings from BIP-350.
+    assert_eq!(
+        decode_bech32m("a1lqfn3a").unwrap(),
+        ("a".to_string(), vec![])
+    );
+    assert!(decode_bech32m("abcdef1l7aum6echk45nj3s0wdvt2fg8x9yrzpqzd3ryx").is_ok());
+    assert!(decode_bech32m("A1LQFN3A").is_ok());
+    // Bech32 (not bech32m) checksum and mixed case are rejected.
+    assert!(decode_bech32m("a12uel5l").is_err());
+    assert!(decode_bech32m("A1lqfn3a").is_err());
+}
+
+#[test]
+fn test_key_encoding() {
+    let (nf_key, nk_commitment) = NullifierKey::random_pair();
+
+    let encoded = nf_key.to_encoded();
+    assert!(encoded.starts_with("armnk1"));
+    assert!(NullifierKey::from_encoded(&encoded).unwrap() == nf_key);
+    assert_eq!(
+        NullifierKeyCommitment::from_encoded(&nk_commitment.to_encoded()).unwrap(),
+        nk_commitment
+    );
+
+    // A wrong prefix or a typo is caught.
+    assert!(NullifierKeyCommitment::from_encoded(&encoded).is_err());
+    let mut typo = encoded.clone().into_bytes();
+    typo[10] = if typo[10] == b'q' { b'p' } else { b'q' };
+    assert!(NullifierKey::from_encoded(std::str::from_utf8(&typo).unwrap()).is_err());
+
+    let hex = nk_commitment.to_checked_hex();
+    assert!(hex.starts_with("armnkc:"));
+    assert_eq!(
+        NullifierKeyCommitment::from_checked_hex(&hex).unwrap(),
+        nk_commitment
+    );
+    assert!(NullifierKeyCommitment::from_checked_hex(&hex[..hex.len() - 2]).is_err());
+    assert!(NullifierKey::from_checked_hex(&hex).is_err());
+}
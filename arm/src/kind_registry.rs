@@ -0,0 +1,114 @@
+//! Registry of per-kind display metadata.
+//!
+//! Resource quantities are plain integers; how they're shown (decimals,
+//! symbol, name) is application knowledge. [`KindRegistry`] keeps that
+//! knowledge in one place, keyed by the kind's logic and label references,
+//! so wallets and tools format quantities consistently.
+
+use crate::resource::Resource;
+use risc0_zkvm::Digest;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Display metadata of a resource kind.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct KindMetadata {
+    /// The number of decimals of the quantity.
+    pub decimals: u8,
+    /// The ticker symbol.
+    pub symbol: String,
+    /// The human-readable name.
+    pub display_name: String,
+}
+
+impl KindMetadata {
+    /// Formats a quantity with the kind's decimals and symbol, e.g.
+    /// `1234.500 ABC` for 1234500 with 3 decimals.
+    pub fn format_quantity(&self, quantity: u128) -> String {
+        let digits = quantity.to_string();
+        let decimals = self.decimals as usize;
+        let amount = if decimals == 0 {
+            digits
+        } else {
+            let padded = format!("{digits:0>width$}", width = decimals + 1);
+            let (int, frac) = padded.split_at(padded.len() - decimals);
+            format!("{int}.{frac}")
+        };
+        if self.symbol.is_empty() {
+            amount
+        } else {
+            format!("{amount} {}", self.symbol)
+        }
+    }
+}
+
+/// Metadata of resource kinds, keyed by `(logic_ref, label_ref)`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct KindRegistry {
+    kinds: BTreeMap<(Digest, Digest), KindMetadata>,
+}
+
+impl KindRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the metadata of a kind, returning the previous metadata.
+    pub fn register(
+        &mut self,
+        logic_ref: Digest,
+        label_ref: Digest,
+        metadata: KindMetadata,
+    ) -> Option<KindMetadata> {
+        self.kinds.insert((logic_ref, label_ref), metadata)
+    }
+
+    /// Looks up the metadata of a kind.
+    pub fn get(&self, logic_ref: &Digest, label_ref: &Digest) -> Option<&KindMetadata> {
+        self.kinds.get(&(*logic_ref, *label_ref))
+    }
+
+    /// Looks up the metadata of a resource's kind.
+    pub fn get_for_resource(&self, resource: &Resource) -> Option<&KindMetadata> {
+        self.get(&resource.logic_ref, &resource.label_ref)
+    }
+
+    /// Formats the quantity of a resource, falling back to the raw quantity
+    /// for unregistered kinds.
+    pub fn format_resource(&self, resource: &Resource) -> String {
+        match self.get_for_resource(resource) {
+            Some(metadata) => metadata.format_quantity(resource.quantity),
+            None => resource.quantity.to_string(),
+        }
+    }
+}
+
+#[test]
+fn test_kind_registry() {
+    let logic_ref = Digest::from_bytes([1u8; 32]);
+    let label_ref = Digest::from_bytes([2u8; 32]);
+    let mut registry = KindRegistry::new();
+    registry.register(
+        logic_ref,
+        label_ref,
+        KindMetadata {
+            decimals: 3,
+            symbol: "ABC".to_string(),
+            display_name: "Alphabet".to_string(),
+        },
+    );
+
+    let mut resource = Resource {
+        logic_ref,
+        label_ref,
+        quantity: 1234500,
+        ..Default::default()
+    };
+    assert_eq!(registry.format_resource(&resource), "1234.500 ABC");
+    resource.quantity = 7;
+    assert_eq!(registry.format_resource(&resource), "0.007 ABC");
+
+    resource.label_ref = Digest::default();
+    assert_eq!(registry.format_resource(&resource), "7");
+}
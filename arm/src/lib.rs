@@ -7,28 +7,70 @@ pub mod action;
 pub mod action_tree;
 #[cfg(feature = "aggregation")]
 pub mod aggregation;
+#[cfg(feature = "transaction")]
+pub mod aggregation_verifier;
+#[cfg(feature = "transaction")]
+pub mod artifact_cache;
+#[cfg(feature = "transaction")]
+pub mod audit;
+#[cfg(feature = "transaction")]
+pub mod build_journal;
+pub mod capabilities;
+pub mod chain;
+pub mod commitment_tree;
+#[cfg(feature = "transaction")]
+pub mod compat;
 #[cfg(any(feature = "compliance_circuit", feature = "aggregation_circuit"))]
 pub mod compliance;
 #[cfg(feature = "transaction")]
 pub mod compliance_unit;
+#[cfg(feature = "compression")]
+pub mod compression;
+#[cfg(feature = "transaction")]
+pub mod config;
 #[cfg(feature = "transaction")]
 pub mod constants;
 #[cfg(feature = "transaction")]
+pub mod consumption;
+#[cfg(feature = "transaction")]
 pub mod delta_proof;
+#[cfg(feature = "transaction")]
+pub mod deployment;
+pub mod digest_encoding;
+pub mod dust_policy;
 pub mod error;
-#[cfg(feature = "aggregation_circuit")]
+#[cfg(feature = "groth16-verify-only")]
+pub mod groth16_verifier;
 pub mod hash;
+pub mod key_encoding;
+pub mod kind_registry;
 pub mod logic_instance;
 #[cfg(feature = "transaction")]
 pub mod logic_proof;
+#[cfg(feature = "transaction")]
+pub mod logic_registry;
 pub mod merkle_path;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 pub mod nullifier_key;
+pub mod nullifier_set;
+#[cfg(feature = "transaction")]
+pub mod padding;
+pub mod prelude;
 #[cfg(feature = "transaction")]
 pub mod proving_system;
+#[cfg(all(feature = "prove", feature = "transaction"))]
+pub mod quickstart;
 pub mod resource;
 pub mod resource_logic;
+pub mod rng;
 #[cfg(feature = "transaction")]
 pub mod transaction;
 pub mod utils;
+#[cfg(feature = "transaction")]
+pub mod verification;
+pub mod witness_schema;
 
+pub use capabilities::capabilities;
+pub use digest_encoding::DigestEncoding;
 pub use risc0_zkvm::Digest;
@@ -1,10 +1,28 @@
 //! Logic instance for ARM resource logic proofs.
 
-use risc0_zkvm::Digest;
+use crate::{
+    action_tree::MerkleTree,
+    chain::{ChainContext, DELETE_AFTER_SETTLEMENT},
+    error::ArmError,
+    merkle_path::MerklePath,
+    utils::{hash_bytes, words_to_bytes, Elided},
+};
+use risc0_zkvm::{serde::to_vec, Digest};
 use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt};
+
+// Tags the application payload blob carrying an AppData digest.
+const APP_DATA_DIGEST_DOMAIN: &[u8] = b"ARM_APP_DATA_DIGEST";
+
+// Marks the app data digest commitments, whose blob starts with its hash.
+const APP_DATA_DIGEST_COMMITMENT_DOMAIN: &[u8] = b"ARM_APP_DATA_DIGEST_COMMITMENT";
 
 /// Represents a logic instance with its associated data.
 #[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
 pub struct LogicInstance {
     /// The logic instance's tag (either commitment or nullifier)
     pub tag: Digest,
@@ -16,8 +34,52 @@ pub struct LogicInstance {
     pub app_data: AppData,
 }
 
+impl LogicInstance {
+    /// Replaces the app data with its digest commitment (see
+    /// [`AppData::to_digest_commitment`]). Logic circuits with large payloads
+    /// call it before committing the instance; the full app data then travels
+    /// in the logic verifier inputs only.
+    pub fn commit_app_data_digest(mut self) -> Result<LogicInstance, ArmError> {
+        self.app_data = self.app_data.to_digest_commitment()?;
+        Ok(self)
+    }
+
+    /// Binds the instance to the image id of the logic circuit committing it
+    /// (see [`BoundLogicInstance`]).
+    pub fn bind_image_id(self, image_id: Digest) -> BoundLogicInstance {
+        BoundLogicInstance {
+            instance: self,
+            image_id,
+        }
+    }
+}
+
+/// A logic instance followed by the image id of the circuit committing it.
+///
+/// Circuits can't compute their own image id, so they read it as a public
+/// input and commit it with the instance (see
+/// [`arm_bound_logic_main!`](crate::arm_bound_logic_main)). Verifiers check
+/// the committed image id against the verifying key of the proof, so a proof
+/// can't be presented under another verifying key even when a circuit with
+/// that key would accept the same instance.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct BoundLogicInstance {
+    /// The logic instance.
+    pub instance: LogicInstance,
+    /// The image id of the circuit committing the instance.
+    pub image_id: Digest,
+}
+
 /// Application data contains four different types of payloads.
 #[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
 pub struct AppData {
     /// The resource payload blobs.
     pub resource_payload: Vec<ExpirableBlob>,
@@ -29,8 +91,56 @@ pub struct AppData {
     pub application_payload: Vec<ExpirableBlob>,
 }
 
+/// The payload categories of [`AppData`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PayloadCategory {
+    /// Resource data, typically encrypted to the resource owner.
+    Resource,
+    /// Discovery hints, encrypted to the intended recipients.
+    Discovery,
+    /// Calls to external systems, consumed by the settlement adapter.
+    External,
+    /// Application-defined public data.
+    Application,
+}
+
+/// Declared visibility of a payload category.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PayloadVisibility {
+    /// Readable by everyone.
+    Public,
+    /// Encrypted to specific parties.
+    Encrypted,
+    /// Interpreted only by external systems; only allowed on ephemeral
+    /// resources.
+    ExternalOnly,
+}
+
+impl PayloadCategory {
+    /// All categories in the order they appear in [`AppData`].
+    pub const ALL: [PayloadCategory; 4] = [
+        PayloadCategory::Resource,
+        PayloadCategory::Discovery,
+        PayloadCategory::External,
+        PayloadCategory::Application,
+    ];
+
+    /// Returns the declared visibility of the category.
+    pub fn visibility(&self) -> PayloadVisibility {
+        match self {
+            PayloadCategory::Resource | PayloadCategory::Discovery => PayloadVisibility::Encrypted,
+            PayloadCategory::External => PayloadVisibility::ExternalOnly,
+            PayloadCategory::Application => PayloadVisibility::Public,
+        }
+    }
+}
+
 /// An expirable blob consists of a blob and a deletion criterion.
-#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
 pub struct ExpirableBlob {
     /// The blob data as a vector of u32 words.
     pub blob: Vec<u32>,
@@ -38,6 +148,76 @@ pub struct ExpirableBlob {
     pub deletion_criterion: u32,
 }
 
+impl fmt::Debug for ExpirableBlob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExpirableBlob")
+            .field("blob", &Elided::words(&self.blob))
+            .field("deletion_criterion", &self.deletion_criterion)
+            .finish()
+    }
+}
+
+/// A large blob split into fixed-size chunks committed by a Merkle root.
+///
+/// Only the root travels in the logic instance (see
+/// [`AppData::commit_chunked`]); the chunks are transported separately and
+/// checked against the root with [`ChunkedBlob::verify_chunk`].
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ChunkedBlob {
+    /// The blob chunks as vectors of u32 words.
+    pub chunks: Vec<Vec<u32>>,
+    /// The deletion criterion for the blob.
+    pub deletion_criterion: u32,
+}
+
+impl ChunkedBlob {
+    /// Splits a blob into chunks of at most `chunk_words` words.
+    pub fn new(blob: &[u32], chunk_words: usize, deletion_criterion: u32) -> Self {
+        ChunkedBlob {
+            chunks: blob
+                .chunks(chunk_words.max(1))
+                .map(|chunk| chunk.to_vec())
+                .collect(),
+            deletion_criterion,
+        }
+    }
+
+    /// Computes the leaf committing to a chunk.
+    pub fn chunk_leaf(chunk: &[u32]) -> Digest {
+        hash_bytes(words_to_bytes(chunk))
+    }
+
+    /// Computes the Merkle root of the chunks.
+    pub fn root(&self) -> Result<Digest, ArmError> {
+        self.tree().root()
+    }
+
+    /// Generates the Merkle path of the chunk at the given index.
+    pub fn chunk_path(&self, index: usize) -> Result<MerklePath, ArmError> {
+        let chunk = self.chunks.get(index).ok_or(ArmError::InvalidLeaf)?;
+        self.tree().generate_path(&Self::chunk_leaf(chunk))
+    }
+
+    /// Checks that a chunk belongs to the blob committed by `root`.
+    pub fn verify_chunk(root: &Digest, chunk: &[u32], path: &MerklePath) -> bool {
+        path.root(&Self::chunk_leaf(chunk)) == *root
+    }
+
+    /// Reassembles the original blob.
+    pub fn to_blob(&self) -> Vec<u32> {
+        self.chunks.concat()
+    }
+
+    fn tree(&self) -> MerkleTree {
+        MerkleTree::new(
+            self.chunks
+                .iter()
+                .map(|chunk| Self::chunk_leaf(chunk))
+                .collect(),
+        )
+    }
+}
+
 impl AppData {
     /// Creates a new, empty AppData.
     pub fn new() -> Self {
@@ -68,4 +248,388 @@ impl AppData {
     pub fn add_application_payload(&mut self, blob: ExpirableBlob) {
         self.application_payload.push(blob);
     }
+
+    /// Returns the payload blobs of the given category.
+    pub fn payloads(&self, category: PayloadCategory) -> &[ExpirableBlob] {
+        match category {
+            PayloadCategory::Resource => &self.resource_payload,
+            PayloadCategory::Discovery => &self.discovery_payload,
+            PayloadCategory::External => &self.external_payload,
+            PayloadCategory::Application => &self.application_payload,
+        }
+    }
+
+    /// Removes the blobs that can be deleted on the chain, for a transaction
+    /// settled at `settled_height`, and returns how many were removed. Blobs
+    /// with [`DELETE_AFTER_SETTLEMENT`] are deleted from the settlement
+    /// height on; other criteria are kept. The digest of the app data changes,
+    /// so only prune stored app data once it has been verified.
+    pub fn prune(&mut self, settled_height: u64, chain: &ChainContext) -> usize {
+        if chain.height < settled_height {
+            return 0;
+        }
+        [
+            &mut self.resource_payload,
+            &mut self.discovery_payload,
+            &mut self.external_payload,
+            &mut self.application_payload,
+        ]
+        .into_iter()
+        .map(|blobs| {
+            let len = blobs.len();
+            blobs.retain(|blob| blob.deletion_criterion != DELETE_AFTER_SETTLEMENT);
+            len - blobs.len()
+        })
+        .sum()
+    }
+
+    /// Adds a payload blob to the given category.
+    pub fn add_payload(&mut self, category: PayloadCategory, blob: ExpirableBlob) {
+        match category {
+            PayloadCategory::Resource => self.add_resource_payload(blob),
+            PayloadCategory::Discovery => self.add_discovery_payload(blob),
+            PayloadCategory::External => self.add_external_payload(blob),
+            PayloadCategory::Application => self.add_application_payload(blob),
+        }
+    }
+
+    /// Returns the non-empty payload categories keyed by category.
+    pub fn to_map(&self) -> BTreeMap<PayloadCategory, Vec<ExpirableBlob>> {
+        PayloadCategory::ALL
+            .iter()
+            .filter(|category| !self.payloads(**category).is_empty())
+            .map(|category| (*category, self.payloads(*category).to_vec()))
+            .collect()
+    }
+
+    /// Builds AppData from payloads keyed by category.
+    pub fn from_map(map: BTreeMap<PayloadCategory, Vec<ExpirableBlob>>) -> Self {
+        let mut app_data = AppData::new();
+        for (category, blobs) in map {
+            for blob in blobs {
+                app_data.add_payload(category, blob);
+            }
+        }
+        app_data
+    }
+
+    /// Checks the declared visibility rules: payloads of
+    /// [`PayloadVisibility::ExternalOnly`] categories may only be attached to
+    /// ephemeral resources. Whether a resource is ephemeral isn't part of the
+    /// proven instances, so the resource logic checks it before committing
+    /// its instance (see [`arm_logic_main!`](crate::arm_logic_main)), and
+    /// verifiers enforce it by verifying the logic proofs.
+    pub fn check_visibility(&self, is_ephemeral: bool) -> Result<(), ArmError> {
+        let has_external_only = PayloadCategory::ALL.iter().any(|category| {
+            category.visibility() == PayloadVisibility::ExternalOnly
+                && !self.payloads(*category).is_empty()
+        });
+        if has_external_only && !is_ephemeral {
+            return Err(ArmError::ExternalPayloadNotEphemeral);
+        }
+        Ok(())
+    }
+
+    /// Checks the payloads for common mistakes before proving: external
+    /// payloads on a persistent resource, and whatever the `linters` detect
+    /// in the blobs (undecryptable ciphertexts, malformed discovery blobs,
+    /// word-length mismatches). Returns the findings, errors first.
+    pub fn lint(&self, is_ephemeral: bool, linters: &[&dyn PayloadLinter]) -> Vec<PayloadLint> {
+        let mut lints = Vec::new();
+        for category in PayloadCategory::ALL {
+            for (index, blob) in self.payloads(category).iter().enumerate() {
+                if category.visibility() == PayloadVisibility::ExternalOnly && !is_ephemeral {
+                    lints.push(PayloadLint::new(
+                        category,
+                        index,
+                        LintIssue::ExternalPayloadNotEphemeral,
+                    ));
+                }
+                lints.extend(
+                    linters
+                        .iter()
+                        .filter_map(|linter| linter.lint_blob(category, blob))
+                        .map(|issue| PayloadLint::new(category, index, issue)),
+                );
+            }
+        }
+        lints.sort_by_key(|lint| lint.severity != LintSeverity::Error);
+        lints
+    }
+
+    /// Computes the digest of the app data.
+    pub fn digest(&self) -> Result<Digest, ArmError> {
+        let words = to_vec(self).map_err(|_| ArmError::SerializationError)?;
+        Ok(hash_bytes(
+            &[APP_DATA_DIGEST_DOMAIN, words_to_bytes(&words)].concat(),
+        ))
+    }
+
+    /// Returns the app data committing only to the digest of `self`: a single
+    /// application payload blob holding a marker and the digest. The marker
+    /// binds the digest mode in the instance, see
+    /// [`AppData::is_digest_commitment`].
+    pub fn to_digest_commitment(&self) -> Result<AppData, ArmError> {
+        let mut blob = hash_bytes(APP_DATA_DIGEST_COMMITMENT_DOMAIN)
+            .as_words()
+            .to_vec();
+        blob.extend_from_slice(self.digest()?.as_words());
+        let mut commitment = AppData::new();
+        commitment.add_application_payload(ExpirableBlob {
+            blob,
+            deletion_criterion: 0,
+        });
+        Ok(commitment)
+    }
+
+    /// Returns whether the app data is a digest commitment produced by
+    /// [`AppData::to_digest_commitment`]. Logic instances committing to such
+    /// an app data only verify with the full app data it commits to.
+    pub fn is_digest_commitment(&self) -> bool {
+        let marker = hash_bytes(APP_DATA_DIGEST_COMMITMENT_DOMAIN);
+        match self.application_payload.as_slice() {
+            [blob] => {
+                self.resource_payload.is_empty()
+                    && self.discovery_payload.is_empty()
+                    && self.external_payload.is_empty()
+                    && blob.deletion_criterion == 0
+                    && blob.blob.len() == 16
+                    && blob.blob[..8] == *marker.as_words()
+            }
+            _ => false,
+        }
+    }
+
+    /// Adds the Merkle root of a chunked blob to the application payload
+    /// instead of the full blob, returning the root.
+    pub fn commit_chunked(&mut self, blob: &ChunkedBlob) -> Result<Digest, ArmError> {
+        let root = blob.root()?;
+        self.add_application_payload(ExpirableBlob {
+            blob: root.as_words().to_vec(),
+            deletion_criterion: blob.deletion_criterion,
+        });
+        Ok(root)
+    }
+}
+
+/// Checks the payload blobs of a category, for [`AppData::lint`].
+/// Applications implement it for the encodings of their payloads.
+pub trait PayloadLinter {
+    /// Returns the issue of the blob, if any.
+    fn lint_blob(&self, category: PayloadCategory, blob: &ExpirableBlob) -> Option<LintIssue>;
+}
+
+/// How serious a [`PayloadLint`] is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LintSeverity {
+    /// The payload works but is likely a mistake.
+    Warning,
+    /// The transaction won't verify, or the payload is useless to its
+    /// recipient.
+    Error,
+}
+
+/// An issue found in a payload blob.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LintIssue {
+    /// The resource ciphertext can't be decrypted with the declared receiver
+    /// key.
+    Undecryptable,
+    /// The discovery blob can't be decoded.
+    MalformedDiscoveryBlob,
+    /// An external payload is attached to a persistent resource.
+    ExternalPayloadNotEphemeral,
+    /// The blob doesn't have the word length of its encoding.
+    WordLengthMismatch {
+        /// The length of the encoding, in words.
+        expected: usize,
+        /// The length of the blob, in words.
+        actual: usize,
+    },
+}
+
+impl LintIssue {
+    /// Returns the severity of the issue.
+    pub fn severity(&self) -> LintSeverity {
+        match self {
+            LintIssue::WordLengthMismatch { .. } => LintSeverity::Warning,
+            _ => LintSeverity::Error,
+        }
+    }
+}
+
+/// A finding of [`AppData::lint`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PayloadLint {
+    /// The category of the blob.
+    pub category: PayloadCategory,
+    /// The index of the blob in its category.
+    pub index: usize,
+    /// The severity of the issue.
+    pub severity: LintSeverity,
+    /// The issue.
+    pub issue: LintIssue,
+}
+
+impl PayloadLint {
+    /// Creates the finding of an issue in a blob.
+    pub fn new(category: PayloadCategory, index: usize, issue: LintIssue) -> Self {
+        PayloadLint {
+            category,
+            index,
+            severity: issue.severity(),
+            issue,
+        }
+    }
+}
+
+impl fmt::Display for PayloadLint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} in {:?} payload blob {}: {:?}",
+            self.severity, self.category, self.index, self.issue
+        )
+    }
+}
+
+#[test]
+fn test_chunked_blob() {
+    let blob: Vec<u32> = (0..1000).collect();
+    let chunked = ChunkedBlob::new(&blob, 64, 1);
+    assert_eq!(chunked.chunks.len(), 16);
+    assert_eq!(chunked.to_blob(), blob);
+
+    let mut app_data = AppData::new();
+    let root = app_data.commit_chunked(&chunked).unwrap();
+    assert_eq!(app_data.application_payload[0].blob, root.as_words());
+
+    for (index, chunk) in chunked.chunks.iter().enumerate() {
+        let path = chunked.chunk_path(index).unwrap();
+        assert!(ChunkedBlob::verify_chunk(&root, chunk, &path));
+    }
+
+    let path = chunked.chunk_path(0).unwrap();
+    assert!(!ChunkedBlob::verify_chunk(&root, &chunked.chunks[1], &path));
+    assert!(chunked.chunk_path(16).is_err());
+}
+
+#[test]
+fn test_app_data_categories() {
+    let blob = ExpirableBlob {
+        blob: vec![1, 2, 3],
+        deletion_criterion: 0,
+    };
+    let mut app_data = AppData::new();
+    app_data.add_payload(PayloadCategory::Application, blob.clone());
+    assert!(app_data.check_visibility(false).is_ok());
+
+    app_data.add_payload(PayloadCategory::External, blob.clone());
+    assert_eq!(app_data.external_payload, vec![blob]);
+    assert_eq!(
+        app_data.check_visibility(false),
+        Err(ArmError::ExternalPayloadNotEphemeral)
+    );
+    assert!(app_data.check_visibility(true).is_ok());
+
+    let map = app_data.to_map();
+    assert_eq!(map.len(), 2);
+    assert_eq!(AppData::from_map(map), app_data);
+}
+
+#[test]
+fn test_app_data_digest_commitment() {
+    let mut app_data = AppData::new();
+    app_data.add_resource_payload(ExpirableBlob {
+        blob: vec![7; 1024],
+        deletion_criterion: 1,
+    });
+    let commitment = app_data.to_digest_commitment().unwrap();
+    assert_eq!(commitment.application_payload.len(), 1);
+    assert_eq!(
+        commitment.application_payload[0].blob[8..],
+        *app_data.digest().unwrap().as_words()
+    );
+    assert!(commitment.is_digest_commitment());
+    assert!(!app_data.is_digest_commitment());
+    assert!(!AppData::new().is_digest_commitment());
+
+    let instance = LogicInstance {
+        app_data: app_data.clone(),
+        ..Default::default()
+    }
+    .commit_app_data_digest()
+    .unwrap();
+    assert_eq!(instance.app_data, commitment);
+    assert_ne!(AppData::new().digest().unwrap(), app_data.digest().unwrap());
+}
+
+#[test]
+fn test_app_data_prune() {
+    use crate::chain::KEEP_FOREVER;
+
+    let blob = |deletion_criterion| ExpirableBlob {
+        blob: vec![1, 2],
+        deletion_criterion,
+    };
+    let mut app_data = AppData::new();
+    app_data.add_resource_payload(blob(KEEP_FOREVER));
+    app_data.add_discovery_payload(blob(DELETE_AFTER_SETTLEMENT));
+    app_data.add_application_payload(blob(DELETE_AFTER_SETTLEMENT));
+
+    let mut pruned = app_data.clone();
+    assert_eq!(pruned.prune(10, &ChainContext::new("testnet", 9, 0)), 0);
+    assert_eq!(pruned, app_data);
+    assert_eq!(pruned.prune(10, &ChainContext::new("testnet", 10, 0)), 2);
+    assert_eq!(pruned.resource_payload, vec![blob(KEEP_FOREVER)]);
+    assert!(pruned.discovery_payload.is_empty() && pruned.application_payload.is_empty());
+}
+
+#[test]
+fn test_app_data_lint() {
+    struct LengthLinter;
+
+    impl PayloadLinter for LengthLinter {
+        fn lint_blob(&self, category: PayloadCategory, blob: &ExpirableBlob) -> Option<LintIssue> {
+            (category == PayloadCategory::Resource && blob.blob.len() != 2).then_some(
+                LintIssue::WordLengthMismatch {
+                    expected: 2,
+                    actual: blob.blob.len(),
+                },
+            )
+        }
+    }
+
+    let blob = |len| ExpirableBlob {
+        blob: vec![1; len],
+        deletion_criterion: 0,
+    };
+    let mut app_data = AppData::new();
+    app_data.add_resource_payload(blob(2));
+    assert!(app_data.lint(false, &[&LengthLinter]).is_empty());
+
+    app_data.add_resource_payload(blob(3));
+    app_data.add_external_payload(blob(1));
+    let lints = app_data.lint(false, &[&LengthLinter]);
+    assert_eq!(
+        lints,
+        vec![
+            PayloadLint::new(
+                PayloadCategory::External,
+                0,
+                LintIssue::ExternalPayloadNotEphemeral
+            ),
+            PayloadLint::new(
+                PayloadCategory::Resource,
+                1,
+                LintIssue::WordLengthMismatch {
+                    expected: 2,
+                    actual: 3
+                }
+            ),
+        ]
+    );
+    assert_eq!(lints[1].severity, LintSeverity::Warning);
+    assert_eq!(app_data.lint(true, &[&LengthLinter]).len(), 1);
 }
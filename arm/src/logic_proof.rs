@@ -1,22 +1,32 @@
 //! Logic proof structures and traits for proving and verifying logic statements.
 
 use crate::{
+    compliance::commit_logic_ref,
     constants::{PADDING_LOGIC_PK, PADDING_LOGIC_VK},
     error::ArmError,
-    logic_instance::{AppData, LogicInstance},
+    logic_instance::{AppData, BoundLogicInstance, LogicInstance},
     nullifier_key::{NullifierKey, NullifierKeyCommitment},
-    proving_system::{journal_to_instance, verify as verify_proof},
+    proving_system::{journal_to_instance, verify as verify_proof, ProofVerifier},
     resource::Resource,
     resource_logic::TrivialLogicWitness,
-    utils::words_to_bytes,
+    rng::ArmRng,
+    utils::{words_to_bytes, Elided, Redacted},
 };
-use rand::rngs::OsRng;
-use rand::Rng;
 use risc0_zkvm::{serde::to_vec, sha::Digest};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 #[cfg(feature = "prove")]
-use crate::proving_system::{prove, ProofType};
+use crate::{
+    config::ArmConfig,
+    proving_system::{
+        check_guest_equivalence, observe_proof, prove, prove_with_assumptions, prove_with_config,
+        prove_with_handle, ProofType, ProverHandle,
+    },
+    resource_logic::LogicCircuit,
+};
+#[cfg(feature = "prove")]
+use risc0_zkvm::Receipt;
 
 /// Trait for logic provers, defining the necessary methods and associated types.
 pub trait LogicProver: Default + Clone + Serialize + for<'de> Deserialize<'de> {
@@ -40,7 +50,115 @@ pub trait LogicProver: Default + Clone + Serialize + for<'de> Deserialize<'de> {
     /// Proves the logic statement using the provided witness and proof type.
     #[cfg(feature = "prove")]
     fn prove(&self, proof_type: ProofType) -> Result<LogicVerifier, ArmError> {
-        let (proof, instance) = prove(Self::proving_key(), self.witness(), proof_type)?;
+        let (proof, instance) = observe_proof!(
+            Logic,
+            prove(Self::proving_key(), self.witness(), proof_type),
+            |(proof, _)| proof.len()
+        )?;
+        Ok(LogicVerifier {
+            proof: Some(proof),
+            instance,
+            verifying_key: Self::verifying_key(),
+        })
+    }
+
+    /// Proves the logic statement of a circuit binding its image id (see
+    /// [`arm_bound_logic_main!`](crate::arm_bound_logic_main)), passing the
+    /// verifying key as its public input.
+    #[cfg(feature = "prove")]
+    fn prove_bound(&self, proof_type: ProofType) -> Result<LogicVerifier, ArmError> {
+        let image_id = Self::verifying_key();
+        let (proof, instance) = observe_proof!(
+            Logic,
+            prove(Self::proving_key(), &(image_id, self.witness()), proof_type),
+            |(proof, _)| proof.len()
+        )?;
+        Ok(LogicVerifier {
+            proof: Some(proof),
+            instance,
+            verifying_key: image_id,
+        })
+    }
+
+    /// Proves the logic statement using the proving defaults of the given
+    /// configuration.
+    #[cfg(feature = "prove")]
+    fn prove_with_config(&self, config: &ArmConfig) -> Result<LogicVerifier, ArmError> {
+        let (proof, instance) = observe_proof!(
+            Logic,
+            prove_with_config(Self::proving_key(), self.witness(), config),
+            |(proof, _)| proof.len()
+        )?;
+        Ok(LogicVerifier {
+            proof: Some(proof),
+            instance,
+            verifying_key: Self::verifying_key(),
+        })
+    }
+
+    /// Proves the logic statement with the configuration and pooled provers
+    /// of a shared handle.
+    #[cfg(feature = "prove")]
+    fn prove_with_handle(&self, handle: &ProverHandle) -> Result<LogicVerifier, ArmError> {
+        let (proof, instance) = observe_proof!(
+            Logic,
+            prove_with_handle(Self::proving_key(), self.witness(), handle),
+            |(proof, _)| proof.len()
+        )?;
+        Ok(LogicVerifier {
+            proof: Some(proof),
+            instance,
+            verifying_key: Self::verifying_key(),
+        })
+    }
+
+    /// Proves the logic statement with receipts of other circuits as
+    /// assumptions, which the guest verifies with `env::verify`, e.g. an
+    /// oracle attestation proof.
+    #[cfg(feature = "prove")]
+    fn prove_with_assumptions(
+        &self,
+        assumptions: &[Receipt],
+        proof_type: ProofType,
+    ) -> Result<LogicVerifier, ArmError> {
+        let (proof, instance) = observe_proof!(
+            Logic,
+            prove_with_assumptions(Self::proving_key(), self.witness(), assumptions, proof_type),
+            |(proof, _)| proof.len()
+        )?;
+        Ok(LogicVerifier {
+            proof: Some(proof),
+            instance,
+            verifying_key: Self::verifying_key(),
+        })
+    }
+
+    /// Runs the circuit in the executor and checks that it commits the
+    /// instance of the host-side constraints of the witness. See
+    /// [`check_guest_equivalence`](crate::proving_system::check_guest_equivalence).
+    #[cfg(feature = "prove")]
+    fn check_guest(&self) -> Result<(), ArmError>
+    where
+        Self::Witness: LogicCircuit,
+    {
+        let instance = self.witness().constrain()?;
+        check_guest_equivalence(Self::proving_key(), self.witness(), &instance)
+    }
+
+    /// Proves the logic statement with a compressed witness. The guest must
+    /// read the witness with `env::read_frame()` and
+    /// [`crate::compression::decompress_witness`].
+    #[cfg(all(feature = "prove", feature = "compression"))]
+    fn prove_compressed(&self, proof_type: ProofType) -> Result<LogicVerifier, ArmError> {
+        let (proof, instance) = observe_proof!(
+            Logic,
+            crate::proving_system::prove_compressed(
+                Self::proving_key(),
+                self.witness(),
+                proof_type,
+            ),
+            |(proof, _)| proof.len()
+        )?;
         Ok(LogicVerifier {
             proof: Some(proof),
             instance,
@@ -50,7 +168,11 @@ pub trait LogicProver: Default + Clone + Serialize + for<'de> Deserialize<'de> {
 }
 
 /// Represents a logic verifier with its proof, instance, and verifying key.
-#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
 pub struct LogicVerifier {
     /// The logic proof (optional, would be absent when aggregation is enabled).
     pub proof: Option<Vec<u8>>,
@@ -61,7 +183,11 @@ pub struct LogicVerifier {
 }
 
 /// Inputs required to create a logic verifier.
-#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
 pub struct LogicVerifierInputs {
     /// The tag (either commitment or nullifier) for the logic instance.
     pub tag: Digest,
@@ -71,13 +197,92 @@ pub struct LogicVerifierInputs {
     pub app_data: AppData,
     /// The logic proof (optional, would be absent when aggregation is enabled).
     pub proof: Option<Vec<u8>>,
+    /// Indicates whether the logic instance only commits to the digest of
+    /// `app_data` (see [`LogicInstance::commit_app_data_digest`]). Digest
+    /// commitments are marked in the instance, so the flag can't be cleared
+    /// to drop the full app data of a proof committing to its digest.
+    #[serde(default)]
+    pub app_data_by_digest: bool,
+    /// Indicates whether the logic instance is bound to the verifying key
+    /// (see [`BoundLogicInstance`]).
+    #[serde(default)]
+    pub binds_image_id: bool,
+    /// The randomness of the commitment to the verifying key in the
+    /// compliance instance, if it only commits to the logic ref (see
+    /// [`PrivateComplianceWitness`](crate::compliance::PrivateComplianceWitness)).
+    #[serde(default)]
+    pub logic_ref_opening: Option<[u8; 32]>,
+}
+
+impl fmt::Debug for LogicVerifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LogicVerifier")
+            .field("proof", &self.proof.as_deref().map(Elided::bytes))
+            .field("instance", &Elided::bytes(&self.instance))
+            .field("verifying_key", &self.verifying_key)
+            .finish()
+    }
+}
+
+impl fmt::Debug for LogicVerifierInputs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LogicVerifierInputs")
+            .field("tag", &self.tag)
+            .field("verifying_key", &self.verifying_key)
+            .field("app_data", &self.app_data)
+            .field("proof", &self.proof.as_deref().map(Elided::bytes))
+            .field("app_data_by_digest", &self.app_data_by_digest)
+            .field("binds_image_id", &self.binds_image_id)
+            .field(
+                "logic_ref_opening",
+                &self.logic_ref_opening.map(|_| Redacted),
+            )
+            .finish()
+    }
 }
 
 impl LogicVerifier {
+    /// Constructs a logic verifier from raw parts (e.g. produced by a remote
+    /// prover), checking up front that the proof verifies against the
+    /// verifying key and that the instance is a canonically encoded logic
+    /// instance, possibly bound to the verifying key, so it round-trips
+    /// through [`LogicVerifierInputs`].
+    pub fn from_parts(
+        proof: Vec<u8>,
+        verifying_key: Digest,
+        instance: Vec<u8>,
+    ) -> Result<Self, ArmError> {
+        if let (_, Some(image_id)) = decode_journal(&instance)? {
+            if image_id != verifying_key {
+                return Err(ArmError::VerifyingKeyMismatch);
+            }
+        }
+
+        verify_proof(&verifying_key, &instance, &proof)?;
+        Ok(LogicVerifier {
+            proof: Some(proof),
+            instance,
+            verifying_key,
+        })
+    }
+
     /// Verifies the logic proof against the instance using the provided verifying key.
     pub fn verify(&self) -> Result<(), ArmError> {
+        self.verify_with(&ProofVerifier::new())
+    }
+
+    /// Verifies the logic proof like [`LogicVerifier::verify`], with the
+    /// parameters of a shared verifier. Instances bound to an image id must
+    /// be bound to the verifying key.
+    pub fn verify_with(&self, verifier: &ProofVerifier) -> Result<(), ArmError> {
+        if let Ok((_, Some(image_id))) = decode_journal(&self.instance) {
+            if image_id != self.verifying_key {
+                return Err(ArmError::VerifyingKeyMismatch);
+            }
+        }
         if let Some(proof) = &self.proof {
-            verify_proof(&self.verifying_key, &self.instance, proof)
+            verifier
+                .verify(&self.verifying_key, &self.instance, proof)
                 .map_err(|err| ArmError::ProofVerificationFailed(err.to_string()))
         } else {
             Err(ArmError::ProofVerificationFailed(
@@ -90,6 +295,31 @@ impl LogicVerifier {
     pub fn get_instance(&self) -> Result<LogicInstance, ArmError> {
         journal_to_instance(&self.instance)
     }
+
+    /// Returns the image id the instance is bound to, if it's bound.
+    pub fn bound_image_id(&self) -> Result<Option<Digest>, ArmError> {
+        Ok(decode_journal(&self.instance)?.1)
+    }
+}
+
+// Decodes a canonically encoded logic proof journal: a logic instance,
+// followed by an image id if it's bound.
+pub(crate) fn decode_journal(journal: &[u8]) -> Result<(LogicInstance, Option<Digest>), ArmError> {
+    let instance: LogicInstance = journal_to_instance(journal)?;
+    if encode_instance(&instance)? == journal {
+        return Ok((instance, None));
+    }
+    let bound: BoundLogicInstance =
+        journal_to_instance(journal).map_err(|_| ArmError::InvalidLogicInstance)?;
+    if encode_instance(&bound)? != journal {
+        return Err(ArmError::InvalidLogicInstance);
+    }
+    Ok((bound.instance, Some(bound.image_id)))
+}
+
+fn encode_instance<T: Serialize>(instance: &T) -> Result<Vec<u8>, ArmError> {
+    let words = to_vec(instance).map_err(|_| ArmError::InstanceSerializationFailed)?;
+    Ok(words_to_bytes(&words).to_vec())
 }
 
 impl LogicVerifierInputs {
@@ -99,23 +329,60 @@ impl LogicVerifierInputs {
         is_consumed: bool,
         root: Digest,
     ) -> Result<LogicVerifier, ArmError> {
-        let instance_words = to_vec(&self.to_instance(is_consumed, root))
-            .map_err(|_| ArmError::InstanceSerializationFailed)?;
+        let instance = self.to_instance(is_consumed, root)?;
+        let instance = if self.binds_image_id {
+            encode_instance(&instance.bind_image_id(self.verifying_key))?
+        } else {
+            encode_instance(&instance)?
+        };
         Ok(LogicVerifier {
             proof: self.proof,
-            instance: words_to_bytes(&instance_words).to_vec(),
+            instance,
             verifying_key: self.verifying_key,
         })
     }
 
-    /// Converts the LogicVerifierInputs into a LogicInstance.
-    fn to_instance(&self, is_consumed: bool, root: Digest) -> LogicInstance {
-        LogicInstance {
+    /// Returns the logic ref the compliance instance commits to: the
+    /// verifying key, or its commitment if the inputs carry an opening.
+    pub fn logic_ref(&self) -> Digest {
+        match &self.logic_ref_opening {
+            Some(rand) => commit_logic_ref(&self.verifying_key, rand),
+            None => self.verifying_key,
+        }
+    }
+
+    /// Attaches the full app data of a logic proof whose instance only
+    /// commits to its digest, checking it against the committed digest.
+    pub fn with_full_app_data(self, app_data: AppData) -> Result<Self, ArmError> {
+        if self.app_data_by_digest || self.app_data != app_data.to_digest_commitment()? {
+            return Err(ArmError::AppDataDigestMismatch);
+        }
+        Ok(LogicVerifierInputs {
+            app_data,
+            app_data_by_digest: true,
+            ..self
+        })
+    }
+
+    /// Converts the LogicVerifierInputs into a LogicInstance. In digest mode,
+    /// the instance commits to the digest of the carried app data, so proofs
+    /// only verify if the digests are equal. Outside of it, the app data
+    /// can't be a digest commitment: the full app data must be attached with
+    /// [`LogicVerifierInputs::with_full_app_data`].
+    fn to_instance(&self, is_consumed: bool, root: Digest) -> Result<LogicInstance, ArmError> {
+        let app_data = if self.app_data_by_digest {
+            self.app_data.to_digest_commitment()?
+        } else if self.app_data.is_digest_commitment() {
+            return Err(ArmError::MissingFullAppData);
+        } else {
+            self.app_data.clone()
+        };
+        Ok(LogicInstance {
             tag: self.tag,
             is_consumed,
             root,
-            app_data: self.app_data.clone(),
-        }
+            app_data,
+        })
     }
 }
 
@@ -123,12 +390,18 @@ impl TryFrom<LogicVerifier> for LogicVerifierInputs {
     type Error = ArmError;
 
     fn try_from(logic_proof: LogicVerifier) -> Result<LogicVerifierInputs, Self::Error> {
-        let instance = logic_proof.get_instance()?;
+        let (instance, image_id) = decode_journal(&logic_proof.instance)?;
+        if image_id.is_some_and(|image_id| image_id != logic_proof.verifying_key) {
+            return Err(ArmError::VerifyingKeyMismatch);
+        }
         Ok(LogicVerifierInputs {
             tag: instance.tag,
             verifying_key: logic_proof.verifying_key,
             app_data: instance.app_data,
             proof: logic_proof.proof,
+            app_data_by_digest: false,
+            binds_image_id: image_id.is_some(),
+            logic_ref_opening: None,
         })
     }
 }
@@ -180,9 +453,9 @@ impl PaddingResourceLogic {
             quantity: 0,
             value_ref: Digest::default(),
             is_ephemeral: true,
-            nonce: OsRng.gen(),
+            nonce: ArmRng::new().bytes(),
             nk_commitment,
-            rand_seed: OsRng.gen(),
+            rand_seed: ArmRng::new().bytes(),
         }
     }
 }
@@ -217,9 +490,146 @@ impl LogicProver for TrivialLogicWitness {
     }
 }
 
+#[test]
+fn test_logic_verifier_from_parts() {
+    let verifier = PaddingResourceLogic::default()
+        .prove(ProofType::Succinct)
+        .unwrap();
+    let proof = verifier.proof.clone().unwrap();
+    let rebuilt = LogicVerifier::from_parts(
+        proof.clone(),
+        verifier.verifying_key,
+        verifier.instance.clone(),
+    )
+    .unwrap();
+    assert_eq!(rebuilt, verifier);
+
+    assert!(LogicVerifier::from_parts(
+        proof.clone(),
+        Digest::from_bytes([1u8; 32]),
+        verifier.instance.clone()
+    )
+    .is_err());
+
+    let mut padded_instance = verifier.instance.clone();
+    padded_instance.extend_from_slice(&[0u8; 4]);
+    assert!(LogicVerifier::from_parts(proof, verifier.verifying_key, padded_instance).is_err());
+}
+
 #[test]
 fn test_padding_logic_prover() {
     let trivial_logic = PaddingResourceLogic::default();
     let proof = trivial_logic.prove(ProofType::Succinct).unwrap();
     proof.verify().unwrap();
 }
+
+#[test]
+fn test_shared_proof_verifier() {
+    let verifier = ProofVerifier::new();
+    let proofs = (0..3)
+        .map(|_| PaddingResourceLogic::default().prove(ProofType::Succinct))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    for proof in &proofs {
+        proof.verify_with(&verifier).unwrap();
+    }
+
+    let mut wrong_key = proofs[0].clone();
+    wrong_key.verifying_key = Digest::default();
+    assert!(wrong_key.verify_with(&verifier).is_err());
+}
+
+#[test]
+fn test_prove_with_assumptions() {
+    use crate::proving_system::to_receipt;
+
+    let logic = PaddingResourceLogic::default();
+    let attestation = logic.prove(ProofType::Succinct).unwrap();
+    let receipt = to_receipt(attestation.proof.as_ref().unwrap(), &attestation.instance).unwrap();
+    assert!(receipt.verify(attestation.verifying_key).is_ok());
+
+    let verifier = logic
+        .prove_with_assumptions(&[receipt], ProofType::Succinct)
+        .unwrap();
+    verifier.verify().unwrap();
+}
+
+#[test]
+fn test_app_data_by_digest() {
+    use crate::logic_instance::ExpirableBlob;
+
+    let mut app_data = AppData::new();
+    app_data.add_application_payload(ExpirableBlob {
+        blob: vec![1; 4096],
+        deletion_criterion: 0,
+    });
+    let inputs = LogicVerifierInputs {
+        tag: Digest::default(),
+        verifying_key: Digest::default(),
+        app_data: app_data.to_digest_commitment().unwrap(),
+        proof: None,
+        app_data_by_digest: false,
+        binds_image_id: false,
+        logic_ref_opening: None,
+    };
+    assert_eq!(
+        inputs.clone().with_full_app_data(AppData::new()),
+        Err(ArmError::AppDataDigestMismatch)
+    );
+
+    // The instance of the commitment requires the full app data.
+    assert_eq!(
+        inputs
+            .clone()
+            .to_logic_verifier(false, Digest::default())
+            .unwrap_err(),
+        ArmError::MissingFullAppData
+    );
+
+    let inputs = inputs.with_full_app_data(app_data.clone()).unwrap();
+    assert_eq!(inputs.app_data, app_data);
+    let verifier = inputs.to_logic_verifier(false, Digest::default()).unwrap();
+    assert_eq!(
+        verifier.get_instance().unwrap().app_data,
+        app_data.to_digest_commitment().unwrap()
+    );
+}
+
+#[test]
+fn test_image_id_binding() {
+    let root = Digest::from_bytes([1; 32]);
+    let inputs = LogicVerifierInputs {
+        tag: Digest::from_bytes([2; 32]),
+        verifying_key: *PADDING_LOGIC_VK,
+        app_data: AppData::new(),
+        proof: Some(vec![0; 4]),
+        app_data_by_digest: false,
+        binds_image_id: true,
+        logic_ref_opening: None,
+    };
+    let verifier = inputs.clone().to_logic_verifier(true, root).unwrap();
+    assert_eq!(verifier.bound_image_id(), Ok(Some(*PADDING_LOGIC_VK)));
+    assert_eq!(verifier.get_instance().unwrap().root, root);
+    assert_eq!(
+        LogicVerifierInputs::try_from(verifier.clone()),
+        Ok(inputs.clone())
+    );
+
+    let unbound = LogicVerifierInputs {
+        binds_image_id: false,
+        logic_ref_opening: None,
+        ..inputs
+    }
+    .to_logic_verifier(true, root)
+    .unwrap();
+    assert_eq!(unbound.bound_image_id(), Ok(None));
+
+    // Substituting the verifying key is rejected before checking the proof.
+    let mut substituted = verifier;
+    substituted.verifying_key = Digest::from_bytes([3; 32]);
+    assert_eq!(substituted.verify(), Err(ArmError::VerifyingKeyMismatch));
+    assert_eq!(
+        LogicVerifierInputs::try_from(substituted),
+        Err(ArmError::VerifyingKeyMismatch)
+    );
+}
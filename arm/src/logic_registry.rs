@@ -0,0 +1,185 @@
+//! Registry of versioned resource logics.
+//!
+//! A `logic_ref` is the image id of a logic circuit, so every release of an
+//! application's logic has a different one. [`LogicRegistry`] maps image ids
+//! to the application and semantic version they were released as, yielding
+//! typed [`LogicRef`]s. A [`LogicRequirement`] then lets verification
+//! policies accept e.g. any 1.x release of a transfer logic instead of
+//! pinning exactly one digest.
+
+use crate::{error::ArmError, transaction::Transaction, utils::hash_bytes};
+use risc0_zkvm::Digest;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The application and version a logic was released as.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LogicVersion {
+    /// The id of the application, e.g. `transfer`.
+    pub app_id: String,
+    /// The semantic version of the release.
+    pub version: Version,
+}
+
+/// A logic reference, with its release if the registry knows it.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LogicRef {
+    digest: Digest,
+    version: Option<LogicVersion>,
+}
+
+impl LogicRef {
+    /// Creates a logic reference without a known release.
+    pub fn new(digest: Digest) -> Self {
+        LogicRef {
+            digest,
+            version: None,
+        }
+    }
+
+    /// Returns the image id of the logic.
+    pub fn digest(&self) -> &Digest {
+        &self.digest
+    }
+
+    /// Returns the release of the logic, if known.
+    pub fn version(&self) -> Option<&LogicVersion> {
+        self.version.as_ref()
+    }
+}
+
+/// Releases of resource logics, keyed by their image id.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LogicRegistry {
+    logics: BTreeMap<Digest, LogicVersion>,
+}
+
+impl LogicRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a logic release, returning the previous release of the
+    /// image id. Fails if `version` isn't a semantic version.
+    pub fn register(
+        &mut self,
+        logic_ref: Digest,
+        app_id: &str,
+        version: &str,
+    ) -> Result<Option<LogicVersion>, ArmError> {
+        let version = Version::parse(version).map_err(|_| ArmError::InvalidLogicVersion)?;
+        Ok(self.logics.insert(
+            logic_ref,
+            LogicVersion {
+                app_id: app_id.to_string(),
+                version,
+            },
+        ))
+    }
+
+    /// Returns the image ids of the registered logics.
+    pub fn logic_refs(&self) -> impl Iterator<Item = &Digest> {
+        self.logics.keys()
+    }
+
+    /// Returns a commitment to the registered releases, e.g. to pin the
+    /// accepted logics of a deployment.
+    pub fn root(&self) -> Result<Digest, ArmError> {
+        let bytes = bincode::serialize(&self.logics).map_err(|_| ArmError::SerializationError)?;
+        Ok(hash_bytes(&bytes))
+    }
+
+    /// Resolves the release of a logic reference.
+    pub fn resolve(&self, logic_ref: &Digest) -> LogicRef {
+        LogicRef {
+            digest: *logic_ref,
+            version: self.logics.get(logic_ref).cloned(),
+        }
+    }
+}
+
+/// The logics a verification policy accepts.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum LogicRequirement {
+    /// Exactly one image id.
+    Exact(Digest),
+    /// Any registered release of the application matching the version
+    /// requirement, e.g. `^1` for any 1.x release.
+    Compatible {
+        /// The id of the application.
+        app_id: String,
+        /// The accepted versions.
+        version_req: VersionReq,
+    },
+}
+
+impl LogicRequirement {
+    /// Requires a release of the application matching `version_req`. Fails
+    /// if it isn't a semantic version requirement.
+    pub fn compatible(app_id: &str, version_req: &str) -> Result<Self, ArmError> {
+        Ok(LogicRequirement::Compatible {
+            app_id: app_id.to_string(),
+            version_req: VersionReq::parse(version_req)
+                .map_err(|_| ArmError::InvalidLogicVersion)?,
+        })
+    }
+
+    /// Returns whether the logic satisfies the requirement.
+    pub fn accepts(&self, logic_ref: &LogicRef) -> bool {
+        match self {
+            LogicRequirement::Exact(digest) => logic_ref.digest == *digest,
+            LogicRequirement::Compatible {
+                app_id,
+                version_req,
+            } => logic_ref
+                .version
+                .as_ref()
+                .is_some_and(|v| v.app_id == *app_id && version_req.matches(&v.version)),
+        }
+    }
+
+    /// Returns whether every logic of the transaction satisfies one of the
+    /// requirements, resolving their releases through the registry.
+    pub fn accepts_transaction(
+        requirements: &[LogicRequirement],
+        registry: &LogicRegistry,
+        tx: &Transaction,
+    ) -> bool {
+        tx.actions
+            .iter()
+            .flat_map(|action| action.get_logic_verifier_inputs())
+            .all(|input| {
+                let logic_ref = registry.resolve(&input.verifying_key);
+                requirements.iter().any(|req| req.accepts(&logic_ref))
+            })
+    }
+}
+
+#[test]
+fn test_logic_requirement() {
+    let v1 = Digest::from_bytes([1u8; 32]);
+    let v1_2 = Digest::from_bytes([2u8; 32]);
+    let v2 = Digest::from_bytes([3u8; 32]);
+    let mut registry = LogicRegistry::new();
+    registry.register(v1, "transfer", "1.0.0").unwrap();
+    registry.register(v1_2, "transfer", "1.2.3").unwrap();
+    registry.register(v2, "transfer", "2.0.0").unwrap();
+    assert_eq!(
+        registry.register(v2, "transfer", "two"),
+        Err(ArmError::InvalidLogicVersion)
+    );
+
+    let any_1x = LogicRequirement::compatible("transfer", "^1").unwrap();
+    assert!(any_1x.accepts(&registry.resolve(&v1)));
+    assert!(any_1x.accepts(&registry.resolve(&v1_2)));
+    assert!(!any_1x.accepts(&registry.resolve(&v2)));
+    assert!(!any_1x.accepts(&LogicRef::new(v1)));
+    assert!(!LogicRequirement::compatible("swap", "^1")
+        .unwrap()
+        .accepts(&registry.resolve(&v1)));
+
+    let unknown = Digest::from_bytes([4u8; 32]);
+    assert!(LogicRequirement::Exact(unknown).accepts(&registry.resolve(&unknown)));
+}
@@ -1,9 +1,14 @@
 //! A Merkle path from a leaf to a root in a commitment/action tree.
+//!
+//! The serde encoding of [`MerklePath`] is read by the prebuilt guests, so it
+//! stays a list of `(node, bool)` pairs. Host-side formats (receipts, stored
+//! witnesses, network messages) should use the compact encoding instead: a
+//! version byte, the depth, the positions packed in a bitmap and the nodes.
+//! See [`MerklePath::to_compact_bytes`] and the [`compact`] serde adapter.
 
-use crate::utils::hash_two;
-use hex::FromHex;
+use crate::{digest_encoding::DigestEncoding, error::ArmError, utils::hash_two};
 use lazy_static::lazy_static;
-use risc0_zkvm::sha::Digest;
+use risc0_zkvm::sha::{Digest, DIGEST_BYTES};
 use serde::{Deserialize, Serialize};
 
 lazy_static! {
@@ -16,6 +21,10 @@ lazy_static! {
 
 /// A path from a position in a particular commitment tree to the root of that tree.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
 pub struct MerklePath(pub Vec<(Digest, bool)>);
 
 impl MerklePath {
@@ -49,6 +58,83 @@ impl MerklePath {
     pub fn empty() -> Self {
         MerklePath(vec![])
     }
+
+    /// Encodes the path compactly: the [`COMPACT_ENCODING_VERSION`] byte, the
+    /// depth byte, the positions as a little-endian bitmap (bit `i` is set if
+    /// the leaf is on the right at level `i`) and the nodes from the leaf up.
+    /// Fails for paths deeper than 255.
+    pub fn to_compact_bytes(&self) -> Result<Vec<u8>, ArmError> {
+        let depth = u8::try_from(self.len()).map_err(|_| ArmError::SerializationError)?;
+        let bitmap_len = self.len().div_ceil(8);
+        let mut bytes = Vec::with_capacity(2 + bitmap_len + self.len() * DIGEST_BYTES);
+        bytes.extend([COMPACT_ENCODING_VERSION, depth]);
+        bytes.resize(2 + bitmap_len, 0);
+        for (i, (_, leaf_is_on_right)) in self.0.iter().enumerate() {
+            if *leaf_is_on_right {
+                bytes[2 + i / 8] |= 1 << (i % 8);
+            }
+        }
+        for (node, _) in &self.0 {
+            bytes.extend_from_slice(node.as_bytes());
+        }
+        Ok(bytes)
+    }
+
+    /// Decodes a path encoded with [`MerklePath::to_compact_bytes`]. Fails on
+    /// unknown versions, trailing bytes and bits set beyond the depth.
+    pub fn from_compact_bytes(bytes: &[u8]) -> Result<Self, ArmError> {
+        let (version, depth, rest) = match bytes {
+            [version, depth, rest @ ..] => (*version, *depth as usize, rest),
+            _ => return Err(ArmError::DeserializationError),
+        };
+        if version != COMPACT_ENCODING_VERSION {
+            return Err(ArmError::DeserializationError);
+        }
+        let bitmap_len = depth.div_ceil(8);
+        if rest.len() != bitmap_len + depth * DIGEST_BYTES {
+            return Err(ArmError::DeserializationError);
+        }
+        let (bitmap, nodes) = rest.split_at(bitmap_len);
+        if depth % 8 != 0 && bitmap[bitmap_len - 1] >> (depth % 8) != 0 {
+            return Err(ArmError::DeserializationError);
+        }
+        let path = nodes
+            .chunks_exact(DIGEST_BYTES)
+            .enumerate()
+            .map(|(i, node)| {
+                let node = Digest::try_from(node).map_err(|_| ArmError::DeserializationError)?;
+                Ok((node, bitmap[i / 8] & (1 << (i % 8)) != 0))
+            })
+            .collect::<Result<_, ArmError>>()?;
+        Ok(MerklePath(path))
+    }
+}
+
+/// The version of the compact encoding written by
+/// [`MerklePath::to_compact_bytes`].
+pub const COMPACT_ENCODING_VERSION: u8 = 1;
+
+/// Serde adapter encoding a [`MerklePath`] field with the compact encoding,
+/// e.g. `#[serde(with = "anoma_rm_risc0::merkle_path::compact")]`. Don't use
+/// it in witnesses read by the prebuilt guests.
+pub mod compact {
+    use super::MerklePath;
+    use serde::{
+        de::Error as _, ser::Error as _, Deserialize, Deserializer, Serialize, Serializer,
+    };
+
+    /// Serializes the path with [`MerklePath::to_compact_bytes`].
+    pub fn serialize<S: Serializer>(path: &MerklePath, serializer: S) -> Result<S::Ok, S::Error> {
+        path.to_compact_bytes()
+            .map_err(S::Error::custom)?
+            .serialize(serializer)
+    }
+
+    /// Deserializes a path with [`MerklePath::from_compact_bytes`].
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<MerklePath, D::Error> {
+        let bytes = Vec::<u8>::deserialize(deserializer)?;
+        MerklePath::from_compact_bytes(&bytes).map_err(D::Error::custom)
+    }
 }
 
 impl Default for MerklePath {
@@ -59,3 +145,33 @@ impl Default for MerklePath {
         ])
     }
 }
+
+#[test]
+fn test_compact_encoding() {
+    let path = MerklePath(
+        (0..32u8)
+            .map(|i| (Digest::from_bytes([i; 32]), i % 3 == 0))
+            .collect(),
+    );
+    let bytes = path.to_compact_bytes().unwrap();
+    assert_eq!(bytes.len(), 2 + 4 + 32 * DIGEST_BYTES);
+    assert!(bytes.len() < bincode::serialized_size(&path).unwrap() as usize);
+    assert_eq!(MerklePath::from_compact_bytes(&bytes), Ok(path.clone()));
+
+    let odd = MerklePath(vec![(Digest::default(), true); 3]);
+    let mut bytes = odd.to_compact_bytes().unwrap();
+    assert_eq!(bytes[2], 0b111);
+    assert_eq!(MerklePath::from_compact_bytes(&bytes), Ok(odd));
+    bytes[2] |= 1 << 3;
+    assert!(MerklePath::from_compact_bytes(&bytes).is_err());
+    bytes[0] = COMPACT_ENCODING_VERSION + 1;
+    assert!(MerklePath::from_compact_bytes(&bytes).is_err());
+    assert!(MerklePath::from_compact_bytes(&[COMPACT_ENCODING_VERSION]).is_err());
+
+    let empty = MerklePath::empty().to_compact_bytes().unwrap();
+    assert_eq!(empty, vec![COMPACT_ENCODING_VERSION, 0]);
+    assert_eq!(
+        MerklePath::from_compact_bytes(&empty),
+        Ok(MerklePath::empty())
+    );
+}
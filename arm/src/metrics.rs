@@ -0,0 +1,209 @@
+//! Proving and verification statistics in the Prometheus text format.
+//!
+//! The proving APIs of compliance units, logic proofs and aggregation record
+//! every proof they generate, and transaction verification records its
+//! failures, in process-wide metrics. [`render`] returns them in the
+//! Prometheus text exposition format, for the metrics endpoint of a proving
+//! or verification service:
+//!
+//! - `arm_proofs_generated_total{circuit}`: the number of proofs generated,
+//! - `arm_proof_duration_seconds{circuit}`: a histogram of the proving time,
+//! - `arm_proof_size_bytes{circuit}`: a histogram of the proof size,
+//! - `arm_verification_failures_total{reason}`: the number of rejected
+//!   transactions, by [`ArmError`] variant for [`Transaction::verify`] and by
+//!   [`RejectionReason`] for [`Transaction::verify_with_context`].
+//!
+//! [`Transaction::verify`]: crate::transaction::Transaction::verify
+//! [`Transaction::verify_with_context`]: crate::transaction::Transaction::verify_with_context
+//! [`RejectionReason`]: crate::verification::RejectionReason
+
+use crate::error::ArmError;
+use std::{collections::BTreeMap, fmt::Write, sync::Mutex, time::Duration};
+
+/// The upper bounds of the proof duration buckets, in seconds.
+pub const DURATION_BUCKETS: &[f64] = &[0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0];
+/// The upper bounds of the proof size buckets, in bytes.
+pub const SIZE_BUCKETS: &[f64] = &[
+    256.0, 1024.0, 4096.0, 16384.0, 65536.0, 262144.0, 1048576.0, 4194304.0,
+];
+
+/// The circuits whose proofs are recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum Circuit {
+    /// The compliance circuit.
+    Compliance,
+    /// A resource logic circuit.
+    Logic,
+    /// An aggregation circuit.
+    Aggregation,
+}
+
+impl Circuit {
+    /// Returns the value of the `circuit` label.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Circuit::Compliance => "compliance",
+            Circuit::Logic => "logic",
+            Circuit::Aggregation => "aggregation",
+        }
+    }
+}
+
+struct Histogram {
+    buckets: &'static [f64],
+    counts: Vec<u64>,
+    sum: f64,
+}
+
+impl Histogram {
+    fn new(buckets: &'static [f64]) -> Self {
+        Histogram {
+            buckets,
+            counts: vec![0; buckets.len()],
+            sum: 0.0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        for (bound, count) in self.buckets.iter().zip(self.counts.iter_mut()) {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += value;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str, total: u64) {
+        for (bound, count) in self.buckets.iter().zip(&self.counts) {
+            let _ = writeln!(out, "{name}_bucket{{{labels},le=\"{bound}\"}} {count}");
+        }
+        let _ = writeln!(out, "{name}_bucket{{{labels},le=\"+Inf\"}} {total}");
+        let _ = writeln!(out, "{name}_sum{{{labels}}} {}", self.sum);
+        let _ = writeln!(out, "{name}_count{{{labels}}} {total}");
+    }
+}
+
+struct ProofStats {
+    count: u64,
+    duration: Histogram,
+    size: Histogram,
+}
+
+struct Metrics {
+    proofs: BTreeMap<Circuit, ProofStats>,
+    failures: BTreeMap<String, u64>,
+}
+
+static METRICS: Mutex<Metrics> = Mutex::new(Metrics {
+    proofs: BTreeMap::new(),
+    failures: BTreeMap::new(),
+});
+
+fn with_metrics<T>(f: impl FnOnce(&mut Metrics) -> T) -> T {
+    f(&mut METRICS.lock().unwrap_or_else(|err| err.into_inner()))
+}
+
+/// Records a proof of the circuit generated in the given time.
+#[cfg_attr(not(feature = "prove"), allow(dead_code))]
+pub(crate) fn record_proof(circuit: Circuit, duration: Duration, size: usize) {
+    with_metrics(|metrics| {
+        let stats = metrics.proofs.entry(circuit).or_insert_with(|| ProofStats {
+            count: 0,
+            duration: Histogram::new(DURATION_BUCKETS),
+            size: Histogram::new(SIZE_BUCKETS),
+        });
+        stats.count += 1;
+        stats.duration.observe(duration.as_secs_f64());
+        stats.size.observe(size as f64);
+    })
+}
+
+/// Records a rejected transaction.
+pub(crate) fn record_verification_failure(reason: impl std::fmt::Debug) {
+    // The reason is the name of the variant, without its fields.
+    let reason = format!("{reason:?}");
+    let name = reason
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .next()
+        .unwrap_or_default();
+    with_metrics(|metrics| *metrics.failures.entry(name.to_string()).or_default() += 1)
+}
+
+/// Records a transaction rejected by [`Transaction::verify`](crate::transaction::Transaction::verify),
+/// under the variant of the error without its tag context.
+pub(crate) fn record_verification_error(err: &ArmError) {
+    record_verification_failure(err.root_cause())
+}
+
+/// Returns the number of proofs of the circuit generated so far.
+pub fn proofs_generated(circuit: Circuit) -> u64 {
+    with_metrics(|metrics| metrics.proofs.get(&circuit).map_or(0, |stats| stats.count))
+}
+
+/// Returns the number of transactions rejected for the reason so far, e.g.
+/// `"NullifierDuplication"` or `"UnknownRoot"`.
+pub fn verification_failures(reason: &str) -> u64 {
+    with_metrics(|metrics| metrics.failures.get(reason).copied().unwrap_or(0))
+}
+
+/// Returns the metrics in the Prometheus text exposition format.
+pub fn render() -> String {
+    with_metrics(|metrics| {
+        let mut out = String::new();
+        out.push_str("# HELP arm_proofs_generated_total The number of proofs generated.\n");
+        out.push_str("# TYPE arm_proofs_generated_total counter\n");
+        for (circuit, stats) in &metrics.proofs {
+            let _ = writeln!(
+                out,
+                "arm_proofs_generated_total{{circuit=\"{}\"}} {}",
+                circuit.label(),
+                stats.count
+            );
+        }
+        out.push_str("# HELP arm_proof_duration_seconds The time to generate a proof.\n");
+        out.push_str("# TYPE arm_proof_duration_seconds histogram\n");
+        for (circuit, stats) in &metrics.proofs {
+            let labels = format!("circuit=\"{}\"", circuit.label());
+            stats
+                .duration
+                .render(&mut out, "arm_proof_duration_seconds", &labels, stats.count);
+        }
+        out.push_str("# HELP arm_proof_size_bytes The size of the generated proofs.\n");
+        out.push_str("# TYPE arm_proof_size_bytes histogram\n");
+        for (circuit, stats) in &metrics.proofs {
+            let labels = format!("circuit=\"{}\"", circuit.label());
+            stats
+                .size
+                .render(&mut out, "arm_proof_size_bytes", &labels, stats.count);
+        }
+        out.push_str(
+            "# HELP arm_verification_failures_total The number of rejected transactions.\n",
+        );
+        out.push_str("# TYPE arm_verification_failures_total counter\n");
+        for (reason, count) in &metrics.failures {
+            let _ = writeln!(
+                out,
+                "arm_verification_failures_total{{reason=\"{reason}\"}} {count}"
+            );
+        }
+        out
+    })
+}
+
+#[test]
+fn test_metrics() {
+    // Other tests record concurrently, so only lower bounds are checked.
+    let proofs = proofs_generated(Circuit::Aggregation);
+    record_proof(Circuit::Aggregation, Duration::from_millis(300), 2000);
+    assert!(proofs_generated(Circuit::Aggregation) > proofs);
+
+    let failures = verification_failures("TagNotFound");
+    record_verification_error(&ArmError::TagNotFound.with_tag(1, Default::default()));
+    assert!(verification_failures("TagNotFound") > failures);
+
+    let text = render();
+    assert!(text.contains("# TYPE arm_proof_duration_seconds histogram\n"));
+    assert!(text.contains("arm_proof_size_bytes_bucket{circuit=\"aggregation\",le=\"+Inf\"}"));
+    assert!(text.contains("arm_verification_failures_total{reason=\"TagNotFound\"}"));
+}
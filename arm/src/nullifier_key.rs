@@ -1,12 +1,19 @@
 //! Nullifier key and its commitment
+//!
+//! Nullifier keys are secrets, so they're only ever compared in constant
+//! time, through [`ConstantTimeEq`]; `==` on them does the same.
 
-use crate::error::ArmError;
-use rand::{rngs::OsRng, Rng};
+use crate::{digest_encoding::DigestEncoding, error::ArmError, rng::ArmRng};
 use risc0_zkvm::sha::{Digest, Impl, Sha256, DIGEST_BYTES};
 use serde::{Deserialize, Serialize};
+use subtle::{Choice, ConstantTimeEq};
 
 /// Nullifier key
-#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
 pub struct NullifierKey([u8; DIGEST_BYTES]);
 
 impl NullifierKey {
@@ -32,13 +39,27 @@ impl NullifierKey {
 
     /// Generate a random nullifier key and its commitment
     pub fn random_pair() -> (NullifierKey, NullifierKeyCommitment) {
-        let rng_bytes: [u8; DIGEST_BYTES] = OsRng.gen();
+        let rng_bytes: [u8; DIGEST_BYTES] = ArmRng::new().bytes();
         let nf_key = NullifierKey::from_bytes(rng_bytes);
         let nk_commitment = nf_key.commit();
         (nf_key, nk_commitment)
     }
 }
 
+impl ConstantTimeEq for NullifierKey {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
+
+impl PartialEq for NullifierKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for NullifierKey {}
+
 impl Default for NullifierKey {
     fn default() -> Self {
         NullifierKey([0u8; DIGEST_BYTES])
@@ -47,6 +68,10 @@ impl Default for NullifierKey {
 
 /// Commitment to nullifier key
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
 pub struct NullifierKeyCommitment(Digest);
 
 impl NullifierKeyCommitment {
@@ -66,6 +91,21 @@ impl NullifierKeyCommitment {
     pub fn as_bytes(&self) -> &[u8] {
         self.0.as_bytes()
     }
+
+    /// Checks in constant time whether the commitment opens to `nf_key`.
+    pub fn is_commitment_of(&self, nf_key: &NullifierKey) -> bool {
+        self.as_bytes().ct_eq(nf_key.commit().as_bytes()).into()
+    }
+}
+
+impl DigestEncoding for NullifierKeyCommitment {
+    fn from_digest(digest: Digest) -> Self {
+        NullifierKeyCommitment(digest)
+    }
+
+    fn to_digest(&self) -> Digest {
+        self.0
+    }
 }
 
 impl Default for NullifierKeyCommitment {
@@ -73,3 +113,14 @@ impl Default for NullifierKeyCommitment {
         NullifierKey::default().commit()
     }
 }
+
+#[test]
+fn test_nullifier_key_ct_eq() {
+    let (nf_key, nk_commitment) = NullifierKey::random_pair();
+    let other = NullifierKey::random_pair().0;
+    assert!(bool::from(nf_key.ct_eq(&nf_key.clone())));
+    assert!(!bool::from(nf_key.ct_eq(&other)));
+    assert!(nf_key == nf_key.clone() && nf_key != other);
+    assert!(nk_commitment.is_commitment_of(&nf_key));
+    assert!(!nk_commitment.is_commitment_of(&other));
+}
@@ -0,0 +1,246 @@
+//! The set of revealed nullifiers, as a sparse Merkle tree.
+//!
+//! A [`NullifierSet`] places every nullifier at the leaf indexed by its 256
+//! bits, read as a big-endian integer, of a tree of depth
+//! [`NULLIFIER_TREE_DEPTH`]. Leaves of revealed nullifiers are the nullifiers
+//! themselves, the others are [`PADDING_LEAF`], and nodes are combined with
+//! [`hash_two`] like in [`MerklePath`]. Node implementations and the protocol
+//! adapter prove that a nullifier was unspent at a root with a
+//! [`NonMembershipProof`]: the path of the nullifier's leaf to the root, from
+//! an empty leaf.
+
+use crate::{
+    error::ArmError,
+    merkle_path::{MerklePath, PADDING_LEAF},
+    utils::hash_two,
+    Digest,
+};
+use lazy_static::lazy_static;
+use risc0_zkvm::sha::DIGEST_BYTES;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+/// The depth of the nullifier tree: one level per bit of the nullifiers.
+pub const NULLIFIER_TREE_DEPTH: usize = 8 * DIGEST_BYTES;
+
+lazy_static! {
+    // The roots of the empty subtrees, by height.
+    static ref EMPTY_NODES: Vec<Digest> = {
+        let mut nodes = vec![*PADDING_LEAF];
+        for height in 0..NULLIFIER_TREE_DEPTH {
+            nodes.push(hash_two(&nodes[height], &nodes[height]));
+        }
+        nodes
+    };
+}
+
+/// The revealed nullifiers, maintained in a sparse Merkle tree. It's
+/// serialized as its nullifiers.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<Digest>", into = "Vec<Digest>")]
+pub struct NullifierSet {
+    nullifiers: BTreeSet<[u8; DIGEST_BYTES]>,
+    // The non-empty nodes above the leaves, by height and index (the leaf
+    // index with the bits below the height cleared).
+    nodes: HashMap<(usize, [u8; DIGEST_BYTES]), Digest>,
+}
+
+// Returns bit `height` of the leaf index, counting from the least significant.
+fn bit(index: &[u8; DIGEST_BYTES], height: usize) -> bool {
+    (index[DIGEST_BYTES - 1 - height / 8] >> (height % 8)) & 1 == 1
+}
+
+// Returns the index of the node at the height above the leaf.
+fn node_index(leaf: &[u8; DIGEST_BYTES], height: usize) -> [u8; DIGEST_BYTES] {
+    let mut index = *leaf;
+    for (i, byte) in index.iter_mut().rev().enumerate() {
+        let cleared = height.saturating_sub(8 * i).min(8);
+        *byte &= !((1u16 << cleared) - 1) as u8;
+    }
+    index
+}
+
+// Returns the index of the sibling of the node at the height above the leaf.
+fn sibling_index(leaf: &[u8; DIGEST_BYTES], height: usize) -> [u8; DIGEST_BYTES] {
+    let mut index = node_index(leaf, height);
+    index[DIGEST_BYTES - 1 - height / 8] ^= 1 << (height % 8);
+    index
+}
+
+impl NullifierSet {
+    /// Creates the empty set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of nullifiers.
+    pub fn len(&self) -> usize {
+        self.nullifiers.len()
+    }
+
+    /// Returns whether the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.nullifiers.is_empty()
+    }
+
+    /// Returns whether the nullifier was revealed.
+    pub fn contains(&self, nullifier: &Digest) -> bool {
+        self.nullifiers
+            .contains(&<[u8; DIGEST_BYTES]>::from(*nullifier))
+    }
+
+    /// Adds a revealed nullifier. Fails if it was already revealed.
+    pub fn insert(&mut self, nullifier: Digest) -> Result<(), ArmError> {
+        let leaf = <[u8; DIGEST_BYTES]>::from(nullifier);
+        if !self.nullifiers.insert(leaf) {
+            return Err(ArmError::NullifierDuplication);
+        }
+        let mut node = nullifier;
+        for height in 0..NULLIFIER_TREE_DEPTH {
+            let sibling = self.node(height, &sibling_index(&leaf, height));
+            node = if bit(&leaf, height) {
+                hash_two(&sibling, &node)
+            } else {
+                hash_two(&node, &sibling)
+            };
+            self.nodes
+                .insert((height + 1, node_index(&leaf, height + 1)), node);
+        }
+        Ok(())
+    }
+
+    /// Returns the root of the tree.
+    pub fn root(&self) -> Digest {
+        self.node(NULLIFIER_TREE_DEPTH, &[0u8; DIGEST_BYTES])
+    }
+
+    /// Returns the path of the nullifier's leaf to the root, whether it was
+    /// revealed or not.
+    pub fn merkle_path(&self, nullifier: &Digest) -> MerklePath {
+        let leaf = <[u8; DIGEST_BYTES]>::from(*nullifier);
+        let path = (0..NULLIFIER_TREE_DEPTH)
+            .map(|height| {
+                (
+                    self.node(height, &sibling_index(&leaf, height)),
+                    bit(&leaf, height),
+                )
+            })
+            .collect();
+        MerklePath(path)
+    }
+
+    /// Proves that the nullifier wasn't revealed at the current root. Fails
+    /// if it was.
+    pub fn prove_non_membership(&self, nullifier: &Digest) -> Result<NonMembershipProof, ArmError> {
+        if self.contains(nullifier) {
+            return Err(ArmError::NullifierSpent);
+        }
+        Ok(NonMembershipProof {
+            nullifier: *nullifier,
+            path: self.merkle_path(nullifier),
+        })
+    }
+
+    fn node(&self, height: usize, index: &[u8; DIGEST_BYTES]) -> Digest {
+        if height == 0 {
+            return match self.nullifiers.contains(index) {
+                true => Digest::from_bytes(*index),
+                false => *PADDING_LEAF,
+            };
+        }
+        self.nodes
+            .get(&(height, *index))
+            .copied()
+            .unwrap_or(EMPTY_NODES[height])
+    }
+}
+
+impl TryFrom<Vec<Digest>> for NullifierSet {
+    type Error = ArmError;
+
+    fn try_from(nullifiers: Vec<Digest>) -> Result<Self, ArmError> {
+        let mut set = NullifierSet::new();
+        for nullifier in nullifiers {
+            set.insert(nullifier)?;
+        }
+        Ok(set)
+    }
+}
+
+impl From<NullifierSet> for Vec<Digest> {
+    fn from(set: NullifierSet) -> Self {
+        set.nullifiers.into_iter().map(Digest::from_bytes).collect()
+    }
+}
+
+/// A proof that a nullifier wasn't revealed at a root of the nullifier set.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NonMembershipProof {
+    /// The unspent nullifier.
+    pub nullifier: Digest,
+    /// The path of the nullifier's empty leaf to the root.
+    pub path: MerklePath,
+}
+
+impl NonMembershipProof {
+    /// Checks that the path leads from the nullifier's leaf, empty, to the
+    /// root.
+    pub fn verify(&self, root: &Digest) -> Result<(), ArmError> {
+        let leaf = <[u8; DIGEST_BYTES]>::from(self.nullifier);
+        let at_leaf = self.path.len() == NULLIFIER_TREE_DEPTH
+            && self
+                .path
+                .0
+                .iter()
+                .enumerate()
+                .all(|(height, (_, is_right))| *is_right == bit(&leaf, height));
+        if !at_leaf || self.path.root(&PADDING_LEAF) != *root {
+            return Err(ArmError::InvalidNonMembershipProof);
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_nullifier_set() {
+    let mut set = NullifierSet::new();
+    assert_eq!(set.root(), EMPTY_NODES[NULLIFIER_TREE_DEPTH]);
+    let nullifiers: Vec<_> = [[0u8; 32], [1u8; 32], [0xff; 32], [0x80; 32]]
+        .map(Digest::from_bytes)
+        .to_vec();
+    let unspent = Digest::from_bytes([0x81; 32]);
+
+    for nullifier in &nullifiers {
+        let proof = set.prove_non_membership(nullifier).unwrap();
+        assert_eq!(proof.verify(&set.root()), Ok(()));
+        set.insert(*nullifier).unwrap();
+        assert_eq!(
+            proof.verify(&set.root()),
+            Err(ArmError::InvalidNonMembershipProof)
+        );
+        assert_eq!(set.merkle_path(nullifier).root(nullifier), set.root());
+    }
+    assert_eq!(
+        set.insert(nullifiers[0]),
+        Err(ArmError::NullifierDuplication)
+    );
+    assert_eq!(
+        set.prove_non_membership(&nullifiers[1]),
+        Err(ArmError::NullifierSpent)
+    );
+
+    // The root doesn't depend on the insertion order.
+    let reordered = NullifierSet::try_from(nullifiers.iter().rev().copied().collect::<Vec<_>>());
+    assert_eq!(reordered.unwrap().root(), set.root());
+
+    // Proofs are bound to their nullifier's leaf.
+    let root = set.root();
+    let proof = set.prove_non_membership(&unspent).unwrap();
+    assert_eq!(proof.verify(&root), Ok(()));
+    let mut moved = proof;
+    moved.nullifier = Digest::from_bytes([0x82; 32]);
+    assert!(moved.verify(&root).is_err());
+
+    let bytes = bincode::serialize(&set).unwrap();
+    assert_eq!(bincode::deserialize::<NullifierSet>(&bytes).unwrap(), set);
+}
@@ -0,0 +1,188 @@
+//! Padding of transactions to a fixed shape.
+//!
+//! Transactions padded to a common [`TransactionShape`] all have the same
+//! number of actions and compliance units per action, so their size doesn't
+//! reveal the application. Padding units consume and create ephemeral
+//! zero-quantity resources of the trivial padding logic, so they don't
+//! change the transaction balance.
+
+#[cfg(feature = "prove")]
+use crate::{
+    action::Action, action_tree::MerkleTree, compliance_unit::ComplianceUnit,
+    delta_proof::DeltaWitness, logic_proof::LogicProver, proving_system::ProofType,
+    transaction::Delta,
+};
+use crate::{
+    compliance::{ComplianceWitness, INITIAL_ROOT},
+    error::ArmError,
+    logic_proof::PaddingResourceLogic,
+    nullifier_key::NullifierKey,
+    transaction::Transaction,
+    Digest,
+};
+
+/// The shape of a transaction: its number of actions and of compliance units
+/// per action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransactionShape {
+    /// The number of actions.
+    pub actions: usize,
+    /// The number of compliance units in every action.
+    pub units_per_action: usize,
+}
+
+impl TransactionShape {
+    /// Checks whether the transaction has exactly this shape.
+    pub fn matches(&self, tx: &Transaction) -> bool {
+        tx.actions.len() == self.actions
+            && tx
+                .actions
+                .iter()
+                .all(|action| action.compliance_units.len() == self.units_per_action)
+    }
+}
+
+/// A compliance unit consuming and creating padding resources. Builders use
+/// it to fill actions up to the shape's number of units before proving the
+/// logics of the action.
+#[derive(Clone)]
+pub struct PaddingUnit {
+    /// The compliance witness of the padding unit.
+    pub witness: ComplianceWitness,
+}
+
+impl PaddingUnit {
+    /// Creates a padding unit with fresh padding resources.
+    pub fn new() -> Result<Self, ArmError> {
+        let (nf_key, nk_commitment) = NullifierKey::random_pair();
+        let consumed = PaddingResourceLogic::create_padding_resource(nk_commitment);
+        let mut created = PaddingResourceLogic::create_padding_resource(nk_commitment);
+        created.set_nonce_from_nf(&consumed, &nf_key)?;
+        Ok(PaddingUnit {
+            witness: ComplianceWitness::from_resources(consumed, *INITIAL_ROOT, nf_key, created),
+        })
+    }
+
+    /// Returns the action tree leaves of the unit: the consumed resource's
+    /// nullifier and the created resource's commitment.
+    pub fn tags(&self) -> Result<[Digest; 2], ArmError> {
+        Ok([
+            self.witness
+                .consumed_resource
+                .nullifier(&self.witness.nf_key)?,
+            self.witness.created_resource.commitment(),
+        ])
+    }
+
+    /// Returns the logic provers of the consumed and created padding
+    /// resources in an action with the given tree root.
+    pub fn logics(&self, action_tree_root: Digest) -> [PaddingResourceLogic; 2] {
+        [
+            PaddingResourceLogic::new(
+                self.witness.consumed_resource,
+                action_tree_root,
+                self.witness.nf_key.clone(),
+                true,
+            ),
+            PaddingResourceLogic::new(
+                self.witness.created_resource,
+                action_tree_root,
+                self.witness.nf_key.clone(),
+                false,
+            ),
+        ]
+    }
+}
+
+/// Creates and proves an action of `units` padding units. Returns it with
+/// the delta witness to compose into the transaction's.
+#[cfg(feature = "prove")]
+pub fn padding_action(
+    units: usize,
+    proof_type: ProofType,
+) -> Result<(Action, DeltaWitness), ArmError> {
+    let padding_units = (0..units)
+        .map(|_| PaddingUnit::new())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let tags = padding_units
+        .iter()
+        .map(|unit| {
+            unit.tags()
+                .map(|[nullifier, commitment]| (nullifier, commitment))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let root = MerkleTree::from_units(tags).root()?;
+
+    let mut compliance_units = Vec::with_capacity(units);
+    let mut logic_verifiers = Vec::with_capacity(2 * units);
+    for unit in padding_units.iter() {
+        compliance_units.push(ComplianceUnit::create(&unit.witness, proof_type)?);
+        for logic in unit.logics(root) {
+            logic_verifiers.push(logic.prove(proof_type)?);
+        }
+    }
+
+    let rcvs: Vec<_> = padding_units.iter().map(|u| u.witness.rcv).collect();
+    let delta_witness = DeltaWitness::from_rcvs(&rcvs)?;
+    Ok((
+        Action::new(compliance_units, logic_verifiers)?,
+        delta_witness,
+    ))
+}
+
+#[cfg(feature = "prove")]
+impl Transaction {
+    /// Pads the transaction with padding actions up to the given shape. The
+    /// transaction must still hold its delta witness, and every existing
+    /// action must already have the shape's number of units (padding units
+    /// can't be added to actions whose logics are proven).
+    pub fn pad_to_shape(
+        mut self,
+        shape: &TransactionShape,
+        proof_type: ProofType,
+    ) -> Result<Transaction, ArmError> {
+        if self.actions.len() > shape.actions
+            || self
+                .actions
+                .iter()
+                .any(|action| action.compliance_units.len() != shape.units_per_action)
+        {
+            return Err(ArmError::TransactionShapeMismatch);
+        }
+        let Delta::Witness(mut delta_witness) = self.delta_proof else {
+            return Err(ArmError::ExpectedDeltaWitness);
+        };
+
+        while self.actions.len() < shape.actions {
+            let (action, padding_witness) = padding_action(shape.units_per_action, proof_type)?;
+            self.actions.push(action);
+            delta_witness = delta_witness.compose(&padding_witness);
+        }
+        self.delta_proof = Delta::Witness(delta_witness);
+        Ok(self)
+    }
+}
+
+#[cfg(feature = "prove")]
+#[test]
+fn test_pad_to_shape() {
+    let shape = TransactionShape {
+        actions: 2,
+        units_per_action: 2,
+    };
+    let (action, delta_witness) = padding_action(2, ProofType::Succinct).unwrap();
+    let tx = Transaction::create(vec![action], Delta::Witness(delta_witness));
+    assert!(!shape.matches(&tx));
+
+    let tx = tx.pad_to_shape(&shape, ProofType::Succinct).unwrap();
+    assert!(shape.matches(&tx));
+    tx.generate_delta_proof().unwrap().verify().unwrap();
+
+    let (action, delta_witness) = padding_action(1, ProofType::Succinct).unwrap();
+    let tx = Transaction::create(vec![action], Delta::Witness(delta_witness));
+    assert_eq!(
+        tx.pad_to_shape(&shape, ProofType::Succinct).map(|_| ()),
+        Err(ArmError::TransactionShapeMismatch)
+    );
+}
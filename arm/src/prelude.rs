@@ -0,0 +1,24 @@
+//! The types and traits most applications use, for a single glob import:
+//! `use anoma_rm_risc0::prelude::*;`. Items only leave the prelude in a
+//! major release.
+
+pub use crate::{
+    digest_encoding::DigestEncoding,
+    error::ArmError,
+    logic_instance::{AppData, ExpirableBlob, LogicInstance},
+    nullifier_key::{NullifierKey, NullifierKeyCommitment},
+    resource::Resource,
+    Digest,
+};
+
+#[cfg(feature = "transaction")]
+pub use crate::{
+    action::Action,
+    compliance::ComplianceWitness,
+    compliance_unit::ComplianceUnit,
+    delta_proof::DeltaWitness,
+    logic_proof::{LogicProver, LogicVerifier, LogicVerifierInputs},
+    proving_system::ProofType,
+    transaction::{Delta, Transaction},
+    verification::{RejectionReason, VerificationContext},
+};
@@ -1,16 +1,31 @@
 //! Proving system interface for generating and verifying proofs.
 
 use crate::error::ArmError;
-use risc0_zkvm::{sha::Digest, InnerReceipt, Receipt};
-use serde::de::DeserializeOwned;
+use risc0_zkvm::{
+    sha::{Digest, Digestible},
+    InnerReceipt, Receipt, VerifierContext,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::str::FromStr;
 
 #[cfg(feature = "prove")]
-use risc0_zkvm::{default_prover, ExecutorEnv, ProverOpts, VerifierContext};
+use crate::{
+    config::{ArmConfig, ProverBackend},
+    witness_schema::check_witness_schema,
+};
 #[cfg(feature = "prove")]
-use serde::Serialize;
+use risc0_zkvm::{default_executor, default_prover, ExecutorEnv, LocalProver, Prover, ProverOpts};
+#[cfg(feature = "prove")]
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::Rc,
+    sync::{Arc, RwLock},
+};
 
 /// Types of proofs supported.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub enum ProofType {
     /// Succinct(STARK) proof type.
     Succinct,
@@ -18,6 +33,18 @@ pub enum ProofType {
     Groth16,
 }
 
+impl FromStr for ProofType {
+    type Err = ArmError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "succinct" => Ok(ProofType::Succinct),
+            "groth16" => Ok(ProofType::Groth16),
+            _ => Err(ArmError::InvalidConfig(format!("unknown proof type: {s}"))),
+        }
+    }
+}
+
 /// Proves a statement given a proving key and a witness, returning the proof and the instance.
 #[cfg(feature = "prove")]
 pub fn prove<T: Serialize>(
@@ -32,15 +59,344 @@ pub fn prove<T: Serialize>(
     Ok((proof, instance))
 }
 
+/// Proves a statement like [`prove`], using the proof type, prover backend
+/// and segment size of the given configuration.
+#[cfg(feature = "prove")]
+pub fn prove_with_config<T: Serialize>(
+    proving_key: &[u8],
+    witness: &T,
+    config: &ArmConfig,
+) -> Result<(Vec<u8>, Vec<u8>), ArmError> {
+    prove_configured(prover(config.prover_backend)?, proving_key, witness, config)
+}
+
+/// Proves a statement like [`prove_with_config`], with the configuration and
+/// the pooled provers of the handle.
+#[cfg(feature = "prove")]
+pub fn prove_with_handle<T: Serialize>(
+    proving_key: &[u8],
+    witness: &T,
+    handle: &ProverHandle,
+) -> Result<(Vec<u8>, Vec<u8>), ArmError> {
+    prove_configured(handle.prover()?, proving_key, witness, handle.config())
+}
+
+#[cfg(feature = "prove")]
+static PROVER_BACKEND: RwLock<ProverBackend> = RwLock::new(ProverBackend::Default);
+
+/// Selects the backend of the proving APIs without a configuration:
+/// [`prove`], [`prove_with_assumptions`], [`prove_compressed`] and the
+/// aggregation provers, so that e.g. `ComplianceUnit::create` and
+/// `LogicProver::prove` are offloaded to Bonsai without changing the call
+/// sites. It applies to the whole process, from the next proof on. Fails if
+/// the backend isn't available in this build.
+///
+/// A service loads its choice from `ARM_PROVER_BACKEND` with
+/// `set_prover_backend(ArmConfig::from_env()?.prover_backend)`. Bonsai only
+/// accepts succinct assumptions, so logic proofs verified in other guests
+/// must be proven as [`ProofType::Succinct`].
+#[cfg(feature = "prove")]
+pub fn set_prover_backend(backend: ProverBackend) -> Result<(), ArmError> {
+    prover(backend)?;
+    *PROVER_BACKEND
+        .write()
+        .unwrap_or_else(|err| err.into_inner()) = backend;
+    Ok(())
+}
+
+/// Returns the backend selected with [`set_prover_backend`],
+/// [`ProverBackend::Default`] until then.
+#[cfg(feature = "prove")]
+pub fn prover_backend() -> ProverBackend {
+    *PROVER_BACKEND.read().unwrap_or_else(|err| err.into_inner())
+}
+
+/// A prover set up once and shared by the threads of a long-running service.
+///
+/// RISC0 provers aren't thread-safe, so every thread sets up the prover of the
+/// configured backend on first use and reuses it for all later proofs through
+/// any handle, instead of setting it up again on every call. Provers are set
+/// up from the environment (e.g. `RISC0_PROVER` or the Bonsai variables) at
+/// that time.
+#[cfg(feature = "prove")]
+#[derive(Clone, Debug)]
+pub struct ProverHandle {
+    config: Arc<ArmConfig>,
+}
+
+#[cfg(feature = "prove")]
+thread_local! {
+    static PROVERS: RefCell<HashMap<ProverBackend, Rc<dyn Prover>>> = RefCell::new(HashMap::new());
+}
+
+#[cfg(feature = "prove")]
+impl ProverHandle {
+    /// Sets up the prover of the configuration on the current thread. Fails if
+    /// the backend isn't available in this build.
+    pub fn init(config: ArmConfig) -> Result<Self, ArmError> {
+        let handle = ProverHandle {
+            config: Arc::new(config),
+        };
+        handle.prover()?;
+        Ok(handle)
+    }
+
+    /// Returns the proving defaults of the handle.
+    pub fn config(&self) -> &ArmConfig {
+        &self.config
+    }
+
+    // Returns the prover of the current thread, setting it up if needed.
+    fn prover(&self) -> Result<Rc<dyn Prover>, ArmError> {
+        let backend = self.config.prover_backend;
+        PROVERS.with(|provers| {
+            if let Some(prover) = provers.borrow().get(&backend) {
+                return Ok(prover.clone());
+            }
+            let prover = prover(backend)?;
+            provers.borrow_mut().insert(backend, prover.clone());
+            Ok(prover)
+        })
+    }
+}
+
+/// Internal function to prove a statement with the given prover, using the
+/// proof type and segment size of the configuration.
+#[cfg(feature = "prove")]
+fn prove_configured<T: Serialize>(
+    prover: Rc<dyn Prover>,
+    proving_key: &[u8],
+    witness: &T,
+    config: &ArmConfig,
+) -> Result<(Vec<u8>, Vec<u8>), ArmError> {
+    let mut builder = ExecutorEnv::builder();
+    builder
+        .write(witness)
+        .map_err(|_| ArmError::WriteWitnessFailed)?;
+    if let Some(po2) = config.segment_po2 {
+        builder.segment_limit_po2(po2);
+    }
+    let env = builder
+        .build()
+        .map_err(|_| ArmError::BuildProverEnvFailed)?;
+    let receipt = prove_with_prover(prover, env, proving_key, config.default_proof_type)?;
+
+    let proof = bincode::serialize(&receipt.inner).map_err(|_| ArmError::SerializationError)?;
+    let instance = receipt.journal.bytes;
+    Ok((proof, instance))
+}
+
+/// Proves a statement like [`prove`], adding the receipts as assumptions so
+/// the guest can verify them with `env::verify`. The assumptions are resolved
+/// into the returned proof, which then only depends on the verifying key and
+/// the instance like any other.
+#[cfg(feature = "prove")]
+pub fn prove_with_assumptions<T: Serialize>(
+    proving_key: &[u8],
+    witness: &T,
+    assumptions: &[Receipt],
+    proof_type: ProofType,
+) -> Result<(Vec<u8>, Vec<u8>), ArmError> {
+    let mut builder = ExecutorEnv::builder();
+    builder
+        .write(witness)
+        .map_err(|_| ArmError::WriteWitnessFailed)?;
+    for assumption in assumptions {
+        builder.add_assumption(assumption.clone());
+    }
+    let env = builder
+        .build()
+        .map_err(|_| ArmError::BuildProverEnvFailed)?;
+    let receipt = prove_with_env(env, proving_key, proof_type)?;
+
+    let proof = bincode::serialize(&receipt.inner).map_err(|_| ArmError::SerializationError)?;
+    let instance = receipt.journal.bytes;
+    Ok((proof, instance))
+}
+
+/// Proves a statement like [`prove`], but writes the witness compressed into
+/// a single frame. The guest must read it with `env::read_frame()` and
+/// [`crate::compression::decompress_witness`].
+#[cfg(all(feature = "prove", feature = "compression"))]
+pub fn prove_compressed<T: Serialize>(
+    proving_key: &[u8],
+    witness: &T,
+    proof_type: ProofType,
+) -> Result<(Vec<u8>, Vec<u8>), ArmError> {
+    let compressed = crate::compression::compress_witness(witness)?;
+    let env = ExecutorEnv::builder()
+        .write_frame(&compressed)
+        .build()
+        .map_err(|_| ArmError::BuildProverEnvFailed)?;
+    let receipt = prove_with_env(env, proving_key, proof_type)?;
+
+    let proof = bincode::serialize(&receipt.inner).map_err(|_| ArmError::SerializationError)?;
+    let instance = receipt.journal.bytes;
+    Ok((proof, instance))
+}
+
+/// Evaluates a proving expression and, with the `metrics` feature, records
+/// the generated proof in the [`metrics`](crate::metrics) of the circuit,
+/// with the size computed from the proven value by the closure.
+#[cfg(feature = "prove")]
+macro_rules! observe_proof {
+    ($circuit:ident, $prove:expr, |$proven:pat_param| $size:expr) => {{
+        #[cfg(feature = "metrics")]
+        let started = std::time::Instant::now();
+        let result = $prove;
+        #[cfg(feature = "metrics")]
+        if let Ok($proven) = &result {
+            $crate::metrics::record_proof(
+                $crate::metrics::Circuit::$circuit,
+                started.elapsed(),
+                $size,
+            );
+        }
+        result
+    }};
+}
+#[cfg(feature = "prove")]
+pub(crate) use observe_proof;
+
+/// Runs the guest with the witness in the executor, without proving, and
+/// returns the instance it commits.
+#[cfg(feature = "prove")]
+pub fn execute<T: Serialize>(proving_key: &[u8], witness: &T) -> Result<Vec<u8>, ArmError> {
+    check_witness_schema(proving_key)?;
+    let env = ExecutorEnv::builder()
+        .write(witness)
+        .map_err(|_| ArmError::WriteWitnessFailed)?
+        .build()
+        .map_err(|_| ArmError::BuildProverEnvFailed)?;
+    execute_env(proving_key, env)
+}
+
+/// Runs the guest in the executor with a prepared environment, e.g. holding
+/// the claims of the proofs an aggregation guest verifies as assumptions,
+/// and returns the instance it commits.
+#[cfg(feature = "prove")]
+pub(crate) fn execute_env(proving_key: &[u8], env: ExecutorEnv<'_>) -> Result<Vec<u8>, ArmError> {
+    let session = default_executor()
+        .execute(env, proving_key)
+        .map_err(|err| ArmError::ProveFailed(format!("Guest execution failed: {}", err)))?;
+    Ok(session.journal.bytes)
+}
+
+/// Checks that the guest commits the instance computed by the host-side
+/// constraints of the witness, e.g. `witness.constrain()?`, byte for byte.
+/// It catches host and guest builds of a circuit diverging (different
+/// dependencies, features or platform-specific code) before proofs of
+/// unexpected instances are generated.
+#[cfg(feature = "prove")]
+pub fn check_guest_equivalence<T: Serialize, I: Serialize>(
+    proving_key: &[u8],
+    witness: &T,
+    host_instance: &I,
+) -> Result<(), ArmError> {
+    let expected =
+        risc0_zkvm::serde::to_vec(host_instance).map_err(|_| ArmError::SerializationError)?;
+    if execute(proving_key, witness)? != crate::utils::words_to_bytes(&expected) {
+        return Err(ArmError::GuestDivergence);
+    }
+    Ok(())
+}
+
 /// Verifies a proof against the given verifying key and instance.
 pub fn verify(verifying_key: &Digest, instance: &[u8], proof: &[u8]) -> Result<(), ArmError> {
+    ProofVerifier::new().verify(verifying_key, instance, proof)
+}
+
+/// Verifies proofs with verifier parameters prepared once.
+///
+/// Preparing the parameters of the recursion circuit is a fixed cost of
+/// every [`verify`] call, which dominates when an action holds many proofs
+/// under the same verifying key (e.g. an airdrop minting dozens of
+/// resources of the same kind). Actions and transactions verify all their
+/// proofs with a single verifier.
+#[derive(Default)]
+pub struct ProofVerifier {
+    ctx: VerifierContext,
+}
+
+impl ProofVerifier {
+    /// Prepares the verifier parameters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Verifies a proof against the instance and verifying key, like
+    /// [`verify`].
+    pub fn verify(
+        &self,
+        verifying_key: &Digest,
+        instance: &[u8],
+        proof: &[u8],
+    ) -> Result<(), ArmError> {
+        self.verify_receipt(&to_receipt(proof, instance)?, verifying_key)
+    }
+
+    /// Verifies a receipt against the image id, rejecting receipts whose
+    /// verifier parameters (the control roots of the recursion circuit and
+    /// the protocol of the proof system) aren't the ones pinned by the risc0
+    /// version this crate is linked with.
+    pub fn verify_receipt(&self, receipt: &Receipt, image_id: &Digest) -> Result<(), ArmError> {
+        let pinned = match &receipt.inner {
+            InnerReceipt::Succinct(_) => self
+                .ctx
+                .succinct_verifier_parameters
+                .as_ref()
+                .map(Digestible::digest),
+            InnerReceipt::Groth16(_) => self
+                .ctx
+                .groth16_verifier_parameters
+                .as_ref()
+                .map(Digestible::digest),
+            InnerReceipt::Composite(_) => self
+                .ctx
+                .composite_verifier_parameters()
+                .as_ref()
+                .map(Digestible::digest),
+            // Fake receipts have no parameters and only verify in dev mode.
+            _ => None,
+        };
+        if pinned.is_some_and(|pinned| pinned != receipt.inner.verifier_parameters()) {
+            return Err(ArmError::IncompatibleReceipt(
+                risc0_zkvm::VERSION.to_string(),
+            ));
+        }
+
+        receipt
+            .verify_with_context(&self.ctx, *image_id)
+            .map_err(|err| {
+                ArmError::ProofVerificationFailed(format!("Proof verification failed: {}", err))
+            })
+    }
+}
+
+/// Verifies a serialized [`Receipt`] (e.g. received from a remote prover)
+/// against the expected image id, and returns it.
+///
+/// The verifier parameters of the receipt are checked against those pinned
+/// by the risc0 version this crate is linked with, which accepts the
+/// receipts of every release of its major version. Receipts produced under
+/// other verifier parameters are rejected with
+/// [`ArmError::IncompatibleReceipt`].
+pub fn verify_receipt(
+    receipt_bytes: &[u8],
+    expected_image_id: &Digest,
+) -> Result<Receipt, ArmError> {
+    let receipt: Receipt = bincode::deserialize(receipt_bytes)
+        .map_err(|_| ArmError::InnerReceiptDeserializationError)?;
+    ProofVerifier::new().verify_receipt(&receipt, expected_image_id)?;
+    Ok(receipt)
+}
+
+/// Rebuilds the receipt of a proof and its instance, e.g. to use it as an
+/// assumption of [`prove_with_assumptions`].
+pub fn to_receipt(proof: &[u8], instance: &[u8]) -> Result<Receipt, ArmError> {
     let inner: InnerReceipt =
         bincode::deserialize(proof).map_err(|_| ArmError::InnerReceiptDeserializationError)?;
-    let receipt = Receipt::new(inner, instance.to_vec());
-
-    receipt.verify(*verifying_key).map_err(|err| {
-        ArmError::ProofVerificationFailed(format!("Proof verification failed: {}", err))
-    })
+    Ok(Receipt::new(inner, instance.to_vec()))
 }
 
 /// Converts a serialized journal into an instance of the specified type.
@@ -81,6 +437,50 @@ fn prove_inner<T: Serialize>(
         .map_err(|_| ArmError::WriteWitnessFailed)?
         .build()
         .map_err(|_| ArmError::BuildProverEnvFailed)?;
+    prove_with_env(env, proving_key, proof_type)
+}
+
+/// Internal function to prove a statement with a prepared executor environment.
+#[cfg(feature = "prove")]
+fn prove_with_env(
+    env: ExecutorEnv<'_>,
+    proving_key: &[u8],
+    proof_type: ProofType,
+) -> Result<Receipt, ArmError> {
+    prove_with_prover(selected_prover()?, env, proving_key, proof_type)
+}
+
+/// Internal function returning the prover of the backend selected with
+/// [`set_prover_backend`].
+#[cfg(feature = "prove")]
+pub(crate) fn selected_prover() -> Result<Rc<dyn Prover>, ArmError> {
+    prover(prover_backend())
+}
+
+/// Internal function returning the prover of a backend.
+#[cfg(feature = "prove")]
+fn prover(backend: ProverBackend) -> Result<Rc<dyn Prover>, ArmError> {
+    match backend {
+        ProverBackend::Default => Ok(default_prover()),
+        ProverBackend::Local => Ok(Rc::new(LocalProver::new("local"))),
+        #[cfg(feature = "bonsai")]
+        ProverBackend::Bonsai => Ok(Rc::new(risc0_zkvm::BonsaiProver::new("bonsai"))),
+        #[cfg(not(feature = "bonsai"))]
+        ProverBackend::Bonsai => Err(ArmError::InvalidConfig(
+            "the bonsai backend requires the bonsai feature".to_string(),
+        )),
+    }
+}
+
+/// Internal function to prove a statement with the given prover.
+#[cfg(feature = "prove")]
+fn prove_with_prover(
+    prover: Rc<dyn Prover>,
+    env: ExecutorEnv<'_>,
+    proving_key: &[u8],
+    proof_type: ProofType,
+) -> Result<Receipt, ArmError> {
+    check_witness_schema(proving_key)?;
 
     let prover_opts = match proof_type {
         ProofType::Succinct => {
@@ -91,8 +491,19 @@ fn prove_inner<T: Serialize>(
         }
     };
 
-    let prove_info = default_prover()
+    let prove_info = prover
         .prove_with_ctx(env, &VerifierContext::default(), proving_key, &prover_opts)
         .map_err(|err| ArmError::ProveFailed(format!("Proof generation failed: {}", err)))?;
     Ok(prove_info.receipt)
 }
+
+#[cfg(feature = "prove")]
+#[test]
+fn test_set_prover_backend() {
+    assert_eq!(prover_backend(), ProverBackend::Default);
+    // Selecting an unavailable backend keeps the current one.
+    #[cfg(not(feature = "bonsai"))]
+    assert!(set_prover_backend(ProverBackend::Bonsai).is_err());
+    set_prover_backend(ProverBackend::Default).unwrap();
+    assert_eq!(prover_backend(), ProverBackend::Default);
+}
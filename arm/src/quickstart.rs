@@ -0,0 +1,133 @@
+//! An executable reference of the API: a transfer built, proven and verified
+//! in one call.
+//!
+//! [`demo_transfer`] goes through the steps of a wallet sending a resource
+//! and of a node accepting it, with in-memory keys, commitment tree and
+//! nullifier set, and returns every intermediate artifact. The trivial logic
+//! is the only resource logic embedded in this crate, so the transferred
+//! resources are ephemeral and have zero quantity; applications replace
+//! [`PaddingResourceLogic`] with the
+//! [`LogicProver`](crate::logic_proof::LogicProver) of their own circuit.
+//! Set `RISC0_DEV_MODE=1` to prove with fake receipts.
+
+use crate::{
+    commitment_tree::CommitmentTree,
+    compliance::ComplianceWitness,
+    config::ArmConfig,
+    error::ArmError,
+    logic_proof::PaddingResourceLogic,
+    merkle_path::MerklePath,
+    nullifier_key::NullifierKey,
+    nullifier_set::{NonMembershipProof, NullifierSet},
+    resource::Resource,
+    transaction::{Transaction, TransactionBuilder},
+};
+
+/// The artifacts of [`demo_transfer`].
+#[derive(Clone)]
+pub struct DemoTransfer {
+    /// The nullifier key of the sender, owning the consumed resource.
+    pub sender_key: NullifierKey,
+    /// The nullifier key of the recipient, owning the created resource.
+    pub recipient_key: NullifierKey,
+    /// The consumed resource.
+    pub consumed: Resource,
+    /// The created resource.
+    pub created: Resource,
+    /// The proof that the consumed resource was unspent before the transfer.
+    pub unspent_proof: NonMembershipProof,
+    /// The proven transaction.
+    pub transaction: Transaction,
+    /// The node's commitment tree, with the created commitment.
+    pub commitment_tree: CommitmentTree,
+    /// The node's nullifier set, with the consumed nullifier.
+    pub nullifier_set: NullifierSet,
+}
+
+/// Builds, proves and verifies a transfer of a resource from a fresh sender
+/// to a fresh recipient, and applies it to an empty node state.
+pub fn demo_transfer() -> Result<DemoTransfer, ArmError> {
+    // The wallets' keys.
+    let (sender_key, sender_commitment) = NullifierKey::random_pair();
+    let (recipient_key, recipient_commitment) = NullifierKey::random_pair();
+
+    // The node's state.
+    let mut commitment_tree = CommitmentTree::new();
+    let mut nullifier_set = NullifierSet::new();
+
+    // The sender consumes its resource and creates one owned by the
+    // recipient, whose nonce is derived from the consumed nullifier.
+    let consumed = PaddingResourceLogic::create_padding_resource(sender_commitment);
+    let mut created = PaddingResourceLogic::create_padding_resource(recipient_commitment);
+    created.set_nonce_from_nf(&consumed, &sender_key)?;
+    let nullifier = consumed.nullifier(&sender_key)?;
+    let commitment = created.commitment();
+    let unspent_proof = nullifier_set.prove_non_membership(&nullifier)?;
+
+    // Ephemeral resources are consumed against the latest root; the others
+    // against their path in the commitment tree.
+    let witness = ComplianceWitness::from_resources(
+        consumed,
+        commitment_tree.root(),
+        sender_key.clone(),
+        created,
+    );
+    let logic_key = sender_key.clone();
+    let transaction = TransactionBuilder::new(ArmConfig::default())
+        .add_unit(
+            witness,
+            move |path: MerklePath| {
+                PaddingResourceLogic::new(consumed, path.root(&nullifier), logic_key, true)
+            },
+            // The tags of created resources don't depend on a key.
+            move |path: MerklePath| {
+                PaddingResourceLogic::new(
+                    created,
+                    path.root(&commitment),
+                    NullifierKey::default(),
+                    false,
+                )
+            },
+        )
+        .build()?;
+
+    // The node verifies the transaction, checks that its nullifiers are
+    // unspent and applies it.
+    transaction.clone().verify()?;
+    unspent_proof.verify(&nullifier_set.root())?;
+    for action in &transaction.actions {
+        for unit in action.get_compliance_units() {
+            let instance = unit.get_instance()?;
+            nullifier_set.insert(instance.consumed_nullifier)?;
+            commitment_tree.append(instance.created_commitment)?;
+        }
+    }
+
+    Ok(DemoTransfer {
+        sender_key,
+        recipient_key,
+        consumed,
+        created,
+        unspent_proof,
+        transaction,
+        commitment_tree,
+        nullifier_set,
+    })
+}
+
+#[test]
+fn test_demo_transfer() {
+    let demo = demo_transfer().unwrap();
+    let nullifier = demo.consumed.nullifier(&demo.sender_key).unwrap();
+    assert!(demo.nullifier_set.contains(&nullifier));
+    assert_eq!(demo.commitment_tree.leaves(), &[demo.created.commitment()]);
+    assert!(demo
+        .created
+        .nk_commitment
+        .is_commitment_of(&demo.recipient_key));
+    assert!(demo
+        .unspent_proof
+        .verify(&demo.nullifier_set.root())
+        .is_err());
+    demo.transaction.verify().unwrap();
+}
@@ -19,20 +19,25 @@ const RESOURCE_BYTES: usize = DIGEST_BYTES
 use crate::{
     error::ArmError,
     nullifier_key::{NullifierKey, NullifierKeyCommitment},
+    rng::ArmRng,
+    utils::{short_hex, Redacted},
 };
 
 use k256::{
     elliptic_curve::hash2curve::{ExpandMsgXmd, GroupDigest},
     ProjectivePoint, Scalar, Secp256k1,
 };
-use rand::rngs::OsRng;
-use rand::Rng;
 use risc0_zkvm::sha::{rust_crypto::Sha256 as Sha256Type, Impl, Sha256, DIGEST_BYTES};
 use risc0_zkvm::Digest;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 /// Resource representation in the ARM system.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
 pub struct Resource {
     /// a succinct representation of the predicate associated with the resource
     pub logic_ref: Digest,
@@ -74,7 +79,7 @@ impl Resource {
                 .try_into()
                 .expect("it can not fail since the digest length is always 32 bytes"),
             nk_commitment,
-            rand_seed: OsRng.gen(),
+            rand_seed: ArmRng::new().bytes(),
         }
     }
 
@@ -188,7 +193,7 @@ impl Resource {
         cm: &Digest,
     ) -> Result<Digest, ArmError> {
         // Make sure that the nullifier public key corresponds to the secret key
-        if self.nk_commitment == nf_key.commit() {
+        if self.nk_commitment.is_commitment_of(nf_key) {
             let mut bytes = [0u8; 4 * DIGEST_BYTES];
             let mut offset: usize = 0;
             // Write the nullifier secret key
@@ -234,7 +239,7 @@ impl Resource {
 
     /// Reset the randomness seed of the resource
     pub fn reset_randomness(&mut self) {
-        self.rand_seed = OsRng.gen();
+        self.rand_seed = ArmRng::new().bytes();
     }
 
     /// Set the nonce of the resource
@@ -285,3 +290,36 @@ impl Default for Resource {
         }
     }
 }
+
+/// Shows every field but the randomness seed, from which the commitment
+/// blinding is derived.
+impl fmt::Debug for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Resource")
+            .field("logic_ref", &self.logic_ref)
+            .field("label_ref", &self.label_ref)
+            .field("quantity", &self.quantity)
+            .field("value_ref", &self.value_ref)
+            .field("is_ephemeral", &self.is_ephemeral)
+            .field("nonce", &hex::encode(self.nonce))
+            .field("nk_commitment", &self.nk_commitment)
+            .field("rand_seed", &Redacted)
+            .finish()
+    }
+}
+
+/// A one-line summary, e.g. `resource 1a2b3c4d… (logic 5e6f7a8b…, label
+/// 9c0d1e2f…, quantity 10)`, identified by its commitment.
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "resource {} (logic {}, label {}, quantity {}{})",
+            short_hex(&self.commitment()),
+            short_hex(&self.logic_ref),
+            short_hex(&self.label_ref),
+            self.quantity,
+            if self.is_ephemeral { ", ephemeral" } else { "" }
+        )
+    }
+}
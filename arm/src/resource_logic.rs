@@ -16,6 +16,74 @@ pub trait LogicCircuit: Default + Clone + Serialize + for<'de> Deserialize<'de>
 
     /// Logic constraints implementation
     fn constrain(&self) -> Result<LogicInstance, ArmError>;
+
+    /// Indicates whether the resource of the witness is ephemeral. Only
+    /// instances of ephemeral resources may carry external payloads (see
+    /// [`AppData::check_visibility`]); the default treats the resource as
+    /// persistent.
+    fn is_ephemeral(&self) -> bool {
+        false
+    }
+}
+
+/// Runs the constraints of a logic circuit in a guest, aborting the
+/// execution (and so the proof) if they fail or if the instance breaks the
+/// app data visibility rules. Used by
+/// [`arm_logic_main!`](crate::arm_logic_main).
+pub fn constrain_or_abort<W: LogicCircuit>(witness: &W) -> LogicInstance {
+    witness
+        .constrain()
+        .and_then(|instance| {
+            instance.app_data.check_visibility(witness.is_ephemeral())?;
+            Ok(instance)
+        })
+        .unwrap_or_else(|err| panic!("{} constraints failed: {err}", std::any::type_name::<W>()))
+}
+
+/// Defines the `main` of a logic circuit guest: it reads the witness of the
+/// given [`LogicCircuit`] type, runs its constraints and checks the app data
+/// visibility rules (aborting if either fails) and commits the [`LogicInstance`] as the journal. It also declares the
+/// witness schema (see [`declare_witness_schema!`](crate::declare_witness_schema)).
+///
+/// The guest's `main.rs` is then:
+///
+/// ```ignore
+/// use my_app::MyLogicWitness;
+///
+/// anoma_rm_risc0::arm_logic_main!(MyLogicWitness);
+/// ```
+///
+/// The guest must depend on `risc0-zkvm`.
+#[macro_export]
+macro_rules! arm_logic_main {
+    ($witness:ty) => {
+        $crate::declare_witness_schema!();
+
+        fn main() {
+            let witness: $witness = ::risc0_zkvm::guest::env::read();
+            let instance = $crate::resource_logic::constrain_or_abort(&witness);
+            ::risc0_zkvm::guest::env::commit(&instance);
+        }
+    };
+}
+
+/// Defines the `main` of a logic circuit guest like
+/// [`arm_logic_main!`](crate::arm_logic_main), binding the committed
+/// instance to the circuit's image id: it reads the image id and then the
+/// witness, and commits the [`BoundLogicInstance`](crate::logic_instance::BoundLogicInstance).
+/// Such circuits are proven with [`LogicProver::prove_bound`](crate::logic_proof::LogicProver::prove_bound).
+#[macro_export]
+macro_rules! arm_bound_logic_main {
+    ($witness:ty) => {
+        $crate::declare_witness_schema!();
+
+        fn main() {
+            let (image_id, witness): (::risc0_zkvm::Digest, $witness) =
+                ::risc0_zkvm::guest::env::read();
+            let instance = $crate::resource_logic::constrain_or_abort(&witness);
+            ::risc0_zkvm::guest::env::commit(&instance.bind_image_id(image_id));
+        }
+    };
 }
 
 /// Trivial logic witness for resources that do not require complex logic proofs.
@@ -49,6 +117,10 @@ impl LogicCircuit for TrivialLogicWitness {
             app_data: AppData::default(), // No app data for trivial logic
         })
     }
+
+    fn is_ephemeral(&self) -> bool {
+        self.resource.is_ephemeral
+    }
 }
 
 impl TrivialLogicWitness {
@@ -67,3 +139,60 @@ impl TrivialLogicWitness {
         }
     }
 }
+
+#[test]
+fn test_constrain_or_abort() {
+    let mut resource = Resource {
+        is_ephemeral: true,
+        ..Default::default()
+    };
+    let witness =
+        TrivialLogicWitness::new(resource, Digest::default(), NullifierKey::default(), true);
+    assert_eq!(constrain_or_abort(&witness), witness.constrain().unwrap());
+
+    resource.quantity = 1;
+    let witness =
+        TrivialLogicWitness::new(resource, Digest::default(), NullifierKey::default(), true);
+    assert!(std::panic::catch_unwind(|| constrain_or_abort(&witness)).is_err());
+}
+
+#[test]
+fn test_constrain_or_abort_checks_visibility() {
+    use crate::logic_instance::ExpirableBlob;
+
+    // A circuit attaching an external payload to its resource.
+    #[derive(Clone, Default, Serialize, Deserialize)]
+    struct ExternalPayloadWitness(TrivialLogicWitness);
+
+    impl LogicCircuit for ExternalPayloadWitness {
+        fn constrain(&self) -> Result<LogicInstance, ArmError> {
+            let mut instance = self.0.constrain()?;
+            instance.app_data.external_payload = vec![ExpirableBlob {
+                blob: vec![1],
+                deletion_criterion: 0,
+            }];
+            Ok(instance)
+        }
+
+        fn is_ephemeral(&self) -> bool {
+            self.0.is_ephemeral()
+        }
+    }
+
+    let resource = Resource {
+        is_ephemeral: true,
+        ..Default::default()
+    };
+    let mut witness = ExternalPayloadWitness(TrivialLogicWitness::new(
+        resource,
+        Digest::default(),
+        NullifierKey::default(),
+        true,
+    ));
+    assert_eq!(constrain_or_abort(&witness), witness.constrain().unwrap());
+
+    // The persistent resource's instance can't be committed, even though its
+    // constraints pass.
+    witness.0.resource.is_ephemeral = false;
+    assert!(std::panic::catch_unwind(|| constrain_or_abort(&witness)).is_err());
+}
@@ -0,0 +1,125 @@
+//! The randomness source of the crate.
+//!
+//! Everything random (rcvs, nonces, rand seeds, keys, encryption nonces) is
+//! drawn from [`ArmRng`], which reads OS entropy. The `try_*` methods report
+//! unavailable entropy as [`ArmError::EntropyUnavailable`] instead of
+//! panicking. Tests can make a thread's draws reproducible with
+//! [`ArmRng::seed_thread`] (feature `test-rng`); release builds without the
+//! feature always use OS entropy.
+
+use crate::error::ArmError;
+use k256::{
+    elliptic_curve::{Field, PrimeField},
+    Scalar,
+};
+use rand::{rngs::OsRng, CryptoRng, RngCore};
+#[cfg(any(test, feature = "test-rng"))]
+use rand::{rngs::StdRng, SeedableRng};
+#[cfg(any(test, feature = "test-rng"))]
+use std::cell::RefCell;
+
+#[cfg(any(test, feature = "test-rng"))]
+thread_local! {
+    static SEEDED: RefCell<Option<StdRng>> = const { RefCell::new(None) };
+}
+
+/// A handle to the crate's randomness source.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ArmRng;
+
+impl ArmRng {
+    /// Returns a handle to the randomness source.
+    pub fn new() -> Self {
+        ArmRng
+    }
+
+    /// Fills `dest` with random bytes.
+    pub fn try_fill(&mut self, dest: &mut [u8]) -> Result<(), ArmError> {
+        self.try_fill_bytes(dest)
+            .map_err(|_| ArmError::EntropyUnavailable)
+    }
+
+    /// Returns `N` random bytes.
+    pub fn try_bytes<const N: usize>(&mut self) -> Result<[u8; N], ArmError> {
+        let mut bytes = [0u8; N];
+        self.try_fill(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Returns a uniformly random scalar.
+    pub fn try_scalar(&mut self) -> Result<Scalar, ArmError> {
+        loop {
+            let bytes = self.try_bytes::<32>()?;
+            // Rejection sampling; out-of-range bytes are negligibly rare.
+            if let Some(scalar) = Scalar::from_repr(bytes.into()).into_option() {
+                return Ok(scalar);
+            }
+        }
+    }
+
+    /// Returns `N` random bytes, panicking if entropy is unavailable.
+    pub fn bytes<const N: usize>(&mut self) -> [u8; N] {
+        let mut bytes = [0u8; N];
+        self.fill_bytes(&mut bytes);
+        bytes
+    }
+
+    /// Returns a uniformly random scalar, panicking if entropy is
+    /// unavailable.
+    pub fn scalar(&mut self) -> Scalar {
+        Scalar::random(self)
+    }
+
+    /// Makes the draws of the current thread deterministic, seeded with
+    /// `seed`. Only meant for tests.
+    #[cfg(any(test, feature = "test-rng"))]
+    pub fn seed_thread(seed: u64) {
+        SEEDED.with(|rng| *rng.borrow_mut() = Some(StdRng::seed_from_u64(seed)));
+    }
+
+    /// Makes the current thread draw from OS entropy again.
+    #[cfg(any(test, feature = "test-rng"))]
+    pub fn unseed_thread() {
+        SEEDED.with(|rng| *rng.borrow_mut() = None);
+    }
+}
+
+impl RngCore for ArmRng {
+    fn next_u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.bytes())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.bytes())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.try_fill_bytes(dest).expect("OS entropy unavailable")
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        #[cfg(any(test, feature = "test-rng"))]
+        if SEEDED
+            .with(|rng| rng.borrow_mut().as_mut().map(|rng| rng.fill_bytes(dest)))
+            .is_some()
+        {
+            return Ok(());
+        }
+        OsRng.try_fill_bytes(dest)
+    }
+}
+
+impl CryptoRng for ArmRng {}
+
+#[test]
+fn test_arm_rng_seeded() {
+    ArmRng::seed_thread(7);
+    let first: [u8; 32] = ArmRng::new().bytes();
+    let scalar = ArmRng::new().try_scalar().unwrap();
+    ArmRng::seed_thread(7);
+    assert_eq!(ArmRng::new().bytes::<32>(), first);
+    assert_eq!(ArmRng::new().try_scalar().unwrap(), scalar);
+
+    ArmRng::unseed_thread();
+    assert_ne!(ArmRng::new().try_bytes::<32>().unwrap(), first);
+}
@@ -1,23 +1,71 @@
 //! Transaction structure and associated methods.
 
+pub mod encoding;
+
+#[cfg(feature = "aggregation")]
+use crate::aggregation::{
+    batch::BatchAggregation, sequential::SequentialAggregation, AggregationProof,
+};
+#[cfg(feature = "prove")]
+use crate::config::ArmConfig;
 use crate::{
-    action::Action,
-    delta_proof::{DeltaInstance, DeltaProof, DeltaWitness},
+    action::{Action, ResourceRef},
+    action_tree::MerkleTree,
+    aggregation_verifier::AggregationVerifier,
+    compat::{self, PROTOCOL_VERSION},
+    compliance::ComplianceInstance,
+    constants::COMPLIANCE_VK,
+    delta_proof::{DeltaInstance, DeltaOpeningProof, DeltaProof, DeltaProofMode, DeltaWitness},
     error::ArmError,
+    kind_registry::KindRegistry,
+    proving_system::ProofVerifier,
+    resource::Resource,
+    utils::{format_size, hash_bytes, Elided},
+    verification::RejectionReason,
 };
-#[cfg(feature = "aggregation")]
+#[cfg(all(feature = "aggregation", feature = "prove"))]
 use crate::{
     aggregation::{
-        batch::BatchAggregation, sequential::SequentialAggregation, AggregationProof,
-        AggregationStrategy,
+        recommend_strategy, AggregationRecommendation, AggregationStrategy, SettlementTarget,
+        TxShape,
     },
     proving_system::ProofType,
 };
+#[cfg(feature = "prove")]
+use crate::{
+    compliance::{ComplianceWitness, Rcv},
+    compliance_unit::ComplianceUnit,
+    dust_policy::DustPolicy,
+    logic_proof::{LogicProver, LogicVerifier},
+    merkle_path::MerklePath,
+};
+use k256::{
+    elliptic_curve::sec1::{FromEncodedPoint, ToEncodedPoint},
+    EncodedPoint, ProjectivePoint,
+};
+use risc0_zkvm::Digest;
 use serde::{Deserialize, Serialize};
+use std::fmt;
+#[cfg(feature = "prove")]
+use std::{
+    sync::mpsc::{self, Receiver, SyncSender},
+    thread::{self, JoinHandle},
+};
+
+// Separates the expiry from the action delta messages.
+const VALID_UNTIL_DOMAIN: &[u8] = b"ARM_VALID_UNTIL";
+// Separates the provenance digest from the action delta messages.
+const PROVENANCE_DOMAIN: &[u8] = b"ARM_PROVENANCE";
+// Separates the tags root from the action delta messages.
+const TAGS_ROOT_DOMAIN: &[u8] = b"ARM_TAGS_ROOT";
 
 /// Represents a transaction consisting of actions, delta proof, expected balance,
 /// and optional aggregation proof.
-#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
 pub struct Transaction {
     /// The actions included in the transaction.
     pub actions: Vec<Action>,
@@ -27,18 +75,321 @@ pub struct Transaction {
     pub expected_balance: Option<Vec<u8>>,
     /// The aggregation proof, if present, attesting to the validity of all individual proofs.
     pub aggregation_proof: Option<Vec<u8>>,
+    /// The last time (as a unix timestamp in seconds) the transaction may be
+    /// settled. It's bound into the delta message, so it can't be changed
+    /// once the delta proof is generated. Added in protocol version 2, see
+    /// [`compat`].
+    pub valid_until: Option<u64>,
+    /// The crate version and circuit image ids the transaction was built
+    /// with. Like the expiry, it's bound into the delta message.
+    pub provenance: Option<Provenance>,
+    /// The root of the tree of the action tree roots, committing to the
+    /// exact set of tags of the transaction. Like the expiry, it's bound
+    /// into the delta message.
+    pub tags_root: Option<Digest>,
+}
+
+impl fmt::Debug for Transaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Transaction")
+            .field("actions", &self.actions)
+            .field("delta_proof", &self.delta_proof)
+            .field(
+                "expected_balance",
+                &self.expected_balance.as_deref().map(Elided::bytes),
+            )
+            .field(
+                "aggregation_proof",
+                &self.aggregation_proof.as_deref().map(Elided::bytes),
+            )
+            .field("valid_until", &self.valid_until)
+            .field("provenance", &self.provenance)
+            .field("tags_root", &self.tags_root)
+            .finish()
+    }
+}
+
+/// A one-line summary, e.g. `transaction (1 action(s), 2 unit(s), delta
+/// proof, aggregation 214.8 KiB)`.
+impl fmt::Display for Transaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let units: usize = self
+            .actions
+            .iter()
+            .map(|action| action.compliance_units.len())
+            .sum();
+        let delta = match self.delta_proof {
+            Delta::Witness(_) => "delta witness",
+            Delta::Unbalanced { .. } => "unbalanced delta witness",
+            Delta::Proof(_) => "delta proof",
+            Delta::OpeningProof(_) => "delta opening proof",
+        };
+        write!(
+            f,
+            "transaction ({} action(s), {units} unit(s), {delta}, ",
+            self.actions.len()
+        )?;
+        match &self.aggregation_proof {
+            Some(proof) => write!(f, "aggregation {})", format_size(proof.len())),
+            None => write!(f, "no aggregation)"),
+        }
+    }
+}
+
+/// The instances of a transaction flattened in the canonical order shared by
+/// the aggregation guests and the EVM adapter: actions in order, and within an
+/// action, for each compliance unit, the consumed and then the created
+/// resource. `logic_refs`, `tags` and `arm_app_data_digests` are indexed
+/// alike.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct AdapterInstances {
+    /// The compliance instances, one per compliance unit.
+    pub compliance_instances: Vec<ComplianceInstance>,
+    /// The logic verifying keys of the resources.
+    pub logic_refs: Vec<Digest>,
+    /// The nullifiers and commitments of the resources.
+    pub tags: Vec<Digest>,
+    /// The ARM digests of the resources' app data, see [`AppData::digest`].
+    /// They are domain-separated for the ARM and are not the adapter's
+    /// encoding of the app data, which adapters derive from the app data
+    /// itself.
+    ///
+    /// [`AppData::digest`]: crate::logic_instance::AppData::digest
+    pub arm_app_data_digests: Vec<Digest>,
+}
+
+/// Records which crate version and circuits a transaction was built with, so
+/// settlement operators can route its verification.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
+pub struct Provenance {
+    /// The version of this crate.
+    pub arm_version: String,
+    /// The compliance circuit image id.
+    pub compliance_vk: Digest,
+    /// The sorted, deduplicated logic circuit image ids of the transaction.
+    pub logic_vks: Vec<Digest>,
+}
+
+impl Provenance {
+    /// Records the provenance of the given transaction with this crate.
+    pub fn of(tx: &Transaction) -> Self {
+        let mut logic_vks: Vec<Digest> = tx
+            .actions
+            .iter()
+            .flat_map(|action| action.logic_verifier_inputs.iter())
+            .map(|input| input.verifying_key)
+            .collect();
+        logic_vks.sort();
+        logic_vks.dedup();
+        Provenance {
+            arm_version: env!("CARGO_PKG_VERSION").to_string(),
+            compliance_vk: *COMPLIANCE_VK,
+            logic_vks,
+        }
+    }
+
+    /// Computes the digest bound into the delta message.
+    pub fn digest(&self) -> Result<Digest, ArmError> {
+        let bytes = bincode::serialize(self).map_err(|_| ArmError::SerializationError)?;
+        Ok(hash_bytes(&bytes))
+    }
+
+    /// Merges the provenance of composed transactions. Returns `None` if they
+    /// were built with different crate versions or compliance circuits.
+    pub fn merge(&self, other: &Provenance) -> Option<Provenance> {
+        if self.arm_version != other.arm_version || self.compliance_vk != other.compliance_vk {
+            return None;
+        }
+        let mut logic_vks = [self.logic_vks.clone(), other.logic_vks.clone()].concat();
+        logic_vks.sort();
+        logic_vks.dedup();
+        Some(Provenance {
+            logic_vks,
+            ..self.clone()
+        })
+    }
 }
 
 /// Represents either a delta witness for proving or a delta proof for verification.
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "borsh",
+    derive(borsh::BorshSerialize, borsh::BorshDeserialize)
+)]
 pub enum Delta {
     /// The delta witness used for proving the delta proof.
     Witness(DeltaWitness),
     /// The delta proof used for verification.
     Proof(DeltaProof),
+    /// The delta opening proof used for verification, in deployments using
+    /// [`DeltaProofMode::Opening`].
+    OpeningProof(DeltaOpeningProof),
+    /// The delta witness of actions that aren't balanced yet, e.g. an
+    /// intent waiting for a solver, with their imbalance.
+    Unbalanced {
+        /// The delta witness of the actions.
+        witness: DeltaWitness,
+        /// The SEC1-compressed imbalance of the actions (see
+        /// [`Delta::imbalance`]), never the identity.
+        imbalance: Vec<u8>,
+    },
+}
+
+// Encodes an imbalance like the `imbalance` of `Delta::Unbalanced`.
+fn encode_imbalance(imbalance: &ProjectivePoint) -> Vec<u8> {
+    imbalance.to_encoded_point(true).as_bytes().to_vec()
+}
+
+// Returns the imbalance of the actions with respect to the delta witness.
+fn witness_imbalance(
+    witness: &DeltaWitness,
+    actions: &[Action],
+) -> Result<ProjectivePoint, ArmError> {
+    let delta = actions
+        .iter()
+        .try_fold(ProjectivePoint::IDENTITY, |acc, action| {
+            Ok::<_, ArmError>(acc + action.delta()?)
+        })?;
+    let randomness = ProjectivePoint::from(*witness.signing_key.verifying_key().as_affine());
+    Ok(delta - randomness)
+}
+
+// Decodes the `imbalance` of `Delta::Unbalanced`.
+fn decode_imbalance(bytes: &[u8]) -> Result<ProjectivePoint, ArmError> {
+    let encoded = EncodedPoint::from_bytes(bytes).map_err(|_| ArmError::InvalidDelta)?;
+    ProjectivePoint::from_encoded_point(&encoded)
+        .into_option()
+        .ok_or(ArmError::InvalidDelta)
+}
+
+impl Delta {
+    /// Wraps the delta witness of the actions, recording their imbalance if
+    /// they aren't balanced.
+    pub fn from_witness(witness: DeltaWitness, actions: &[Action]) -> Result<Delta, ArmError> {
+        let imbalance = witness_imbalance(&witness, actions)?;
+        if imbalance == ProjectivePoint::IDENTITY {
+            return Ok(Delta::Witness(witness));
+        }
+        Ok(Delta::Unbalanced {
+            witness,
+            imbalance: encode_imbalance(&imbalance),
+        })
+    }
+
+    /// Combines the deltas of two sets of actions, e.g. an intent and the
+    /// solver's actions balancing it. The result is a witness if the
+    /// imbalances cancel out and is unbalanced otherwise. Fails if either
+    /// delta is already proven.
+    pub fn balance_against(self, other: Delta) -> Result<Delta, ArmError> {
+        let (witness1, imbalance1) = self.into_witness()?;
+        let (witness2, imbalance2) = other.into_witness()?;
+        let witness = witness1.compose(&witness2);
+        let imbalance = imbalance1 + imbalance2;
+        if imbalance == ProjectivePoint::IDENTITY {
+            Ok(Delta::Witness(witness))
+        } else {
+            Ok(Delta::Unbalanced {
+                witness,
+                imbalance: encode_imbalance(&imbalance),
+            })
+        }
+    }
+
+    /// Proves the delta witness over the delta message with the given mode.
+    /// Unbalanced witnesses can't be proven and proofs are kept as they are.
+    pub fn into_proof(self, message: &[u8], mode: DeltaProofMode) -> Result<Delta, ArmError> {
+        match self {
+            Delta::Witness(witness) => match mode {
+                DeltaProofMode::Signature => {
+                    Ok(Delta::Proof(DeltaProof::prove(message, &witness)?))
+                }
+                DeltaProofMode::Opening => Ok(Delta::OpeningProof(DeltaOpeningProof::prove(
+                    message, &witness,
+                )?)),
+            },
+            Delta::Unbalanced { .. } => Err(ArmError::UnbalancedTransaction),
+            Delta::Proof(_) | Delta::OpeningProof(_) => Ok(self),
+        }
+    }
+
+    // Splits a witness into the delta witness and the recorded imbalance.
+    fn into_witness(self) -> Result<(DeltaWitness, ProjectivePoint), ArmError> {
+        match self {
+            Delta::Witness(witness) => Ok((witness, ProjectivePoint::IDENTITY)),
+            Delta::Unbalanced { witness, imbalance } => {
+                Ok((witness, decode_imbalance(&imbalance)?))
+            }
+            Delta::Proof(_) | Delta::OpeningProof(_) => Err(ArmError::ExpectedDeltaWitness),
+        }
+    }
+
+    /// Returns the imbalance of the actions with respect to the delta
+    /// witness: the sum of the actions' deltas minus the witness randomness
+    /// commitment. It is the identity exactly when the actions are balanced,
+    /// and otherwise tells a solver which kinds and quantities are missing.
+    pub fn imbalance(&self, actions: &[Action]) -> Result<ProjectivePoint, ArmError> {
+        match self {
+            Delta::Witness(witness) | Delta::Unbalanced { witness, .. } => {
+                witness_imbalance(witness, actions)
+            }
+            Delta::Proof(_) | Delta::OpeningProof(_) => Err(ArmError::ExpectedDeltaWitness),
+        }
+    }
+
+    /// Returns how the delta is proven, or `None` for a witness.
+    pub fn mode(&self) -> Option<DeltaProofMode> {
+        match self {
+            Delta::Witness(_) | Delta::Unbalanced { .. } => None,
+            Delta::Proof(_) => Some(DeltaProofMode::Signature),
+            Delta::OpeningProof(_) => Some(DeltaProofMode::Opening),
+        }
+    }
+
+    /// Verifies the delta proof against the delta message and instance.
+    pub fn verify(&self, message: &[u8], instance: DeltaInstance) -> Result<(), ArmError> {
+        match self {
+            Delta::Witness(_) | Delta::Unbalanced { .. } => Err(ArmError::ExpectedDeltaProof),
+            Delta::Proof(proof) => DeltaProof::verify(message, proof, instance),
+            Delta::OpeningProof(proof) => DeltaOpeningProof::verify(message, proof, instance),
+        }
+    }
+
+    /// Checks whether the delta balances the given actions, either by
+    /// comparing a witness against the actions' deltas or by verifying a
+    /// proof over the actions' delta message. Proofs of transactions with an
+    /// expiry, a provenance or a tags root commit to them as well and are
+    /// checked by [`Transaction::is_balanced`].
+    pub fn is_balanced_with(&self, actions: &[Action]) -> Result<bool, ArmError> {
+        Transaction::create(actions.to_vec(), self.clone()).is_balanced()
+    }
 }
 
 impl Transaction {
+    /// Encodes the transaction, prefixed with the [`PROTOCOL_VERSION`] it's
+    /// encoded with.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ArmError> {
+        self.to_bytes_with_version(PROTOCOL_VERSION.major)
+    }
+
+    /// Encodes the transaction in the format of an earlier major protocol
+    /// version, for peers that haven't upgraded yet. Version 1 transactions
+    /// are encoded without a header, as the 1.0 crates do. Fails if the
+    /// version isn't supported or can't represent the transaction.
+    pub fn to_bytes_with_version(&self, major: u16) -> Result<Vec<u8>, ArmError> {
+        compat::encode(self, major)
+    }
+
+    /// Decodes a transaction encoded with [`Transaction::to_bytes`] or by a
+    /// 1.0 peer, translating the formats of earlier supported versions. Fails
+    /// if it was encoded with another major version.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ArmError> {
+        compat::decode(bytes)
+    }
+
     /// Create a new transaction with the given actions and delta.
     /// Delta proof is a deterministic process, no proving key is needed.
     /// Delta instance can be constructed from the actions.
@@ -48,115 +399,474 @@ impl Transaction {
             delta_proof: delta,
             expected_balance: None,
             aggregation_proof: None,
+            valid_until: None,
+            provenance: None,
+            tags_root: None,
+        }
+    }
+
+    /// Records the provenance of the transaction. Like the expiry, it must be
+    /// set before generating the delta proof.
+    pub fn with_provenance(mut self) -> Self {
+        self.provenance = Some(Provenance::of(&self));
+        self
+    }
+
+    /// Commits the transaction to its tags root, see [`Self::tags_root`].
+    /// Like the expiry, it must be set before generating the delta proof,
+    /// after which the actions can't change.
+    pub fn with_tags_root(mut self) -> Result<Self, ArmError> {
+        self.tags_root = Some(self.tags_root()?);
+        Ok(self)
+    }
+
+    /// Returns the root of the tree whose leaves are the action tree roots,
+    /// in action order. It's a compact reference to the exact set of state
+    /// changes of the transaction, e.g. for bridges and receipts.
+    pub fn tags_root(&self) -> Result<Digest, ArmError> {
+        let roots = self
+            .actions
+            .iter()
+            .map(Action::action_tree_root)
+            .collect::<Result<Vec<_>, _>>()?;
+        MerkleTree::from(roots).root()
+    }
+
+    /// Sets the expiry of the transaction. It must be set before generating
+    /// the delta proof, which commits to it.
+    pub fn with_valid_until(mut self, valid_until: u64) -> Self {
+        self.valid_until = Some(valid_until);
+        self
+    }
+
+    /// Checks whether the transaction has expired at the given unix time.
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.valid_until
+            .is_some_and(|valid_until| now > valid_until)
+    }
+
+    /// Checks whether the delta balances the actions like
+    /// [`Delta::is_balanced_with`], verifying proofs over the whole delta
+    /// message, including the expiry, the provenance and the tags root.
+    pub fn is_balanced(&self) -> Result<bool, ArmError> {
+        match &self.delta_proof {
+            Delta::Witness(_) | Delta::Unbalanced { .. } => {
+                Ok(self.delta_proof.imbalance(&self.actions)? == ProjectivePoint::IDENTITY)
+            }
+            Delta::Proof(_) | Delta::OpeningProof(_) => {
+                let msg = self.get_delta_msg()?;
+                let instance = match self.delta() {
+                    Ok(instance) => instance,
+                    Err(_) => return Ok(false),
+                };
+                Ok(self.delta_proof.verify(&msg, instance).is_ok())
+            }
         }
     }
 
     /// Generates the delta proof for the transaction if it contains a delta witness.
     pub fn generate_delta_proof(self) -> Result<Transaction, ArmError> {
+        self.generate_delta_proof_with_mode(DeltaProofMode::Signature)
+    }
+
+    /// Generates the delta proof of the given mode for the transaction if it
+    /// contains a delta witness. Fails if the delta is unbalanced.
+    pub fn generate_delta_proof_with_mode(
+        self,
+        mode: DeltaProofMode,
+    ) -> Result<Transaction, ArmError> {
         match self.delta_proof {
-            Delta::Witness(ref witness) => {
+            Delta::Witness(_) | Delta::Unbalanced { .. } => {
                 let msg = self.get_delta_msg()?;
-                let proof = DeltaProof::prove(&msg, witness)?;
-                let delta_proof = Delta::Proof(proof);
                 Ok(Transaction {
-                    actions: self.actions,
-                    delta_proof,
-                    expected_balance: self.expected_balance,
-                    aggregation_proof: self.aggregation_proof,
+                    delta_proof: self.delta_proof.into_proof(&msg, mode)?,
+                    ..self
                 })
             }
-            Delta::Proof(_) => Ok(self),
+            Delta::Proof(_) | Delta::OpeningProof(_) => Ok(self),
         }
     }
 
+    /// Generates the delta proof of the transaction like
+    /// [`Transaction::generate_delta_proof`], returning it as a
+    /// [`ProvenTransaction`].
+    pub fn into_proven(self) -> Result<ProvenTransaction, ArmError> {
+        ProvenTransaction::try_from(self.generate_delta_proof()?)
+    }
+
     /// Verifies all the proofs and corresponding checks in the transaction.
     pub fn verify(self) -> Result<(), ArmError> {
-        match &self.delta_proof {
-            Delta::Proof(ref proof) => {
-                let msg = self.get_delta_msg()?;
-                let instance = self.delta()?;
-                DeltaProof::verify(&msg, proof, instance)?;
-
-                // Check for nullifier duplication across all compliance units
-                self.nf_duplication_check()?;
-
-                if self.aggregation_proof.is_some() {
-                    #[cfg(not(feature = "aggregation"))]
-                    return Err(ArmError::ProofVerificationFailed(
-                        "feature `aggregation` is not enabled".into(),
-                    ));
-
-                    #[cfg(feature = "aggregation")]
-                    self.verify_aggregation()?;
-                } else {
-                    // Try verifying individually.
-                    for action in self.actions {
-                        action.verify()?;
-                    }
-                }
-                Ok(())
+        self.verify_inner(None)
+    }
+
+    /// Verifies the transaction like [`Transaction::verify`], checking its
+    /// aggregation proof, if any, with the given verifier.
+    pub fn verify_with_aggregation_verifier(
+        self,
+        verifier: &dyn AggregationVerifier,
+    ) -> Result<(), ArmError> {
+        self.verify_inner(Some(verifier))
+    }
+
+    fn verify_inner(
+        self,
+        aggregation_verifier: Option<&dyn AggregationVerifier>,
+    ) -> Result<(), ArmError> {
+        let result = self.verify_proofs(aggregation_verifier);
+        #[cfg(feature = "metrics")]
+        if let Err(err) = &result {
+            crate::metrics::record_verification_error(err);
+        }
+        result
+    }
+
+    fn verify_proofs(
+        self,
+        aggregation_verifier: Option<&dyn AggregationVerifier>,
+    ) -> Result<(), ArmError> {
+        self.verify_checked(aggregation_verifier, |_, err| err)
+    }
+
+    // Runs the checks of [`Transaction::verify`], reporting a failure with
+    // `reject` given the reason and the error of the failed check. Shared
+    // with [`Transaction::verify_with_context`], which reports the reason.
+    pub(crate) fn verify_checked<E>(
+        self,
+        aggregation_verifier: Option<&dyn AggregationVerifier>,
+        reject: impl Fn(RejectionReason, ArmError) -> E,
+    ) -> Result<(), E> {
+        let delta_mismatch = |err| reject(RejectionReason::DeltaMismatch, err);
+        if let Delta::Witness(_) | Delta::Unbalanced { .. } = self.delta_proof {
+            return Err(delta_mismatch(ArmError::ExpectedDeltaProof));
+        }
+        let msg = self
+            .get_delta_msg()
+            .map_err(|err| reject(RejectionReason::Malformed, err))?;
+        let instance = self.delta().map_err(delta_mismatch)?;
+        self.delta_proof
+            .verify(&msg, instance)
+            .map_err(delta_mismatch)?;
+
+        // Check for nullifier duplication across all compliance units
+        self.nf_duplication_check()
+            .map_err(|err| reject(RejectionReason::DuplicateNullifier, err))?;
+        self.check_resource_refs()
+            .map_err(|err| reject(RejectionReason::Malformed, err))?;
+        self.check_tags_root()
+            .map_err(|err| reject(RejectionReason::Malformed, err))?;
+        for (index, action) in self.actions.iter().enumerate() {
+            action
+                .check_canonical_order()
+                .map_err(|err| reject(RejectionReason::Malformed, err.at_action(index)))?;
+        }
+
+        if let Some(proof) = &self.aggregation_proof {
+            let invalid_aggregation = |err| reject(RejectionReason::InvalidAggregationProof, err);
+            return match aggregation_verifier {
+                Some(verifier) => verifier
+                    .verify_aggregation(&self, proof)
+                    .map_err(invalid_aggregation),
+                #[cfg(not(feature = "aggregation"))]
+                None => Err(invalid_aggregation(ArmError::ProofVerificationFailed(
+                    "feature `aggregation` is not enabled".into(),
+                ))),
+                #[cfg(feature = "aggregation")]
+                None => self.verify_aggregation().map_err(invalid_aggregation),
+            };
+        }
+
+        // Try verifying individually.
+        let verifier = ProofVerifier::new();
+        for (index, action) in self.actions.iter().enumerate() {
+            action.verify_compliance_units(&verifier).map_err(|err| {
+                reject(
+                    RejectionReason::InvalidComplianceProof,
+                    err.at_action(index),
+                )
+            })?;
+            action
+                .verify_logic_proofs(&verifier)
+                .map_err(|err| reject(RejectionReason::InvalidLogicProof, err.at_action(index)))?;
+        }
+        Ok(())
+    }
+
+    // Replaces all compliance and resource logic proofs with `None`.
+    #[cfg(any(
+        all(feature = "aggregation", feature = "prove"),
+        test,
+        feature = "test-aggregation"
+    ))]
+    pub(crate) fn erase_base_proofs(&mut self) {
+        for a in self.actions.iter_mut() {
+            for cu in a.compliance_units.iter_mut() {
+                cu.proof = None;
+            }
+            for lp in a.logic_verifier_inputs.iter_mut() {
+                lp.proof = None;
             }
-            Delta::Witness(_) => Err(ArmError::ExpectedDeltaProof),
+        }
+    }
+
+    /// Checks that the committed tags root, if any, is the one of the
+    /// actions.
+    pub fn check_tags_root(&self) -> Result<(), ArmError> {
+        match self.tags_root {
+            Some(tags_root) if tags_root != self.tags_root()? => Err(ArmError::TagsRootMismatch),
+            _ => Ok(()),
         }
     }
 
     /// Inner check for nullifier duplication across all compliance units
     pub fn nf_duplication_check(&self) -> Result<(), ArmError> {
         let mut seen_nullifiers = std::collections::HashSet::new();
-        for action in &self.actions {
+        for (index, action) in self.actions.iter().enumerate() {
             for cu in action.get_compliance_units() {
                 let instance = cu.get_instance()?;
                 if !seen_nullifiers.insert(instance.consumed_nullifier) {
-                    return Err(ArmError::NullifierDuplication);
+                    return Err(
+                        ArmError::NullifierDuplication.with_tag(index, instance.consumed_nullifier)
+                    );
                 }
             }
         }
         Ok(())
     }
 
+    /// Returns the index of the action holding the referenced resource,
+    /// trying the hinted action first.
+    pub fn resolve_resource_ref(&self, resource_ref: &ResourceRef) -> Option<usize> {
+        if self
+            .actions
+            .get(resource_ref.action_hint)
+            .is_some_and(|action| action.contains_tag(&resource_ref.tag))
+        {
+            return Some(resource_ref.action_hint);
+        }
+        self.actions
+            .iter()
+            .position(|action| action.contains_tag(&resource_ref.tag))
+    }
+
+    /// Checks that the resource references declared by every action resolve
+    /// to a resource of the transaction.
+    pub fn check_resource_refs(&self) -> Result<(), ArmError> {
+        for (index, action) in self.actions.iter().enumerate() {
+            for resource_ref in &action.resource_refs {
+                if self.resolve_resource_ref(resource_ref).is_none() {
+                    return Err(ArmError::UnresolvedResourceRef.with_tag(index, resource_ref.tag));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Describes the transaction in human-readable form, one line per
+    /// compliance unit. Created resources among `known_resources` (e.g. the
+    /// wallet's own outputs) are shown with their quantity formatted by the
+    /// registry.
+    pub fn describe(&self, registry: &KindRegistry, known_resources: &[Resource]) -> String {
+        let mut lines = vec![format!(
+            "transaction: {} action(s), delta {}, aggregation {}",
+            self.actions.len(),
+            match self.delta_proof {
+                Delta::Witness(_) => "witness",
+                Delta::Unbalanced { .. } => "unbalanced witness",
+                Delta::Proof(_) => "proof",
+                Delta::OpeningProof(_) => "opening proof",
+            },
+            if self.aggregation_proof.is_some() {
+                "yes"
+            } else {
+                "no"
+            }
+        )];
+        for (index, action) in self.actions.iter().enumerate() {
+            lines.push(format!(
+                "  action {index}: {} unit(s), {} logic input(s)",
+                action.compliance_units.len(),
+                action.logic_verifier_inputs.len()
+            ));
+            for cu in action.get_compliance_units() {
+                let Ok(instance) = cu.get_instance() else {
+                    lines.push("    <malformed compliance instance>".to_string());
+                    continue;
+                };
+                let mut line = format!(
+                    "    consumed {} -> created {}",
+                    instance.consumed_nullifier, instance.created_commitment
+                );
+                if let Some(resource) = known_resources
+                    .iter()
+                    .find(|r| r.commitment() == instance.created_commitment)
+                {
+                    line.push_str(&format!(" ({})", registry.format_resource(resource)));
+                    if let Some(metadata) = registry.get_for_resource(resource) {
+                        line.push_str(&format!(" {}", metadata.display_name));
+                    }
+                }
+                lines.push(line);
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// Returns the instances of the transaction in the canonical adapter
+    /// order (see [`AdapterInstances`]).
+    pub fn to_adapter_instances(&self) -> Result<AdapterInstances, ArmError> {
+        let mut instances = AdapterInstances::default();
+        for (index, action) in self.actions.iter().enumerate() {
+            let (compliance_instances, inputs) = action
+                .ordered_inputs()
+                .map_err(|err| err.at_action(index))?;
+            instances.compliance_instances.extend(compliance_instances);
+            for input in inputs {
+                instances.logic_refs.push(input.verifying_key);
+                instances.tags.push(input.tag);
+                instances
+                    .arm_app_data_digests
+                    .push(input.app_data.digest()?);
+            }
+        }
+        Ok(instances)
+    }
+
     /// Returns the DeltaInstance constructed from the sum of all actions' deltas.
     pub fn delta(&self) -> Result<DeltaInstance, ArmError> {
-        let mut points = Vec::with_capacity(self.actions.len());
-        for action in &self.actions {
-            points.push(action.delta()?);
-        }
-        DeltaInstance::from_deltas(&points)
+        let instances = self
+            .actions
+            .iter()
+            .flat_map(|action| action.compliance_units.iter())
+            .map(|unit| unit.get_instance())
+            .collect::<Result<Vec<_>, _>>()?;
+        DeltaProof::expected_key(&instances)
     }
 
     /// Constructs the delta message by concatenating the delta messages
-    /// of each action.
+    /// of each action, followed by the expiry, the provenance digest and the
+    /// tags root if the transaction has them.
     pub fn get_delta_msg(&self) -> Result<Vec<u8>, ArmError> {
         let mut msg = Vec::new();
         for action in &self.actions {
             msg.extend(action.get_delta_msg()?);
         }
+        if let Some(valid_until) = self.valid_until {
+            msg.extend_from_slice(VALID_UNTIL_DOMAIN);
+            msg.extend_from_slice(&valid_until.to_be_bytes());
+        }
+        if let Some(provenance) = &self.provenance {
+            msg.extend_from_slice(PROVENANCE_DOMAIN);
+            msg.extend_from_slice(provenance.digest()?.as_bytes());
+        }
+        if let Some(tags_root) = &self.tags_root {
+            msg.extend_from_slice(TAGS_ROOT_DOMAIN);
+            msg.extend_from_slice(tags_root.as_bytes());
+        }
         Ok(msg)
     }
 
     /// Composes two transactions by concatenating their actions and combining their delta witnesses.
+    /// The composed transaction expires with the earlier of the two and keeps
+    /// their merged provenance if both have one. The tags root is dropped, as
+    /// the actions changed.
+    ///
+    /// Panics where [`Transaction::try_compose`] fails, e.g. if both
+    /// transactions reveal the same nullifier.
     pub fn compose(tx1: Transaction, tx2: Transaction) -> Transaction {
-        let mut actions = tx1.actions;
-        actions.extend(tx2.actions);
-        let delta = match (&tx1.delta_proof, &tx2.delta_proof) {
-            (Delta::Witness(witness1), Delta::Witness(witness2)) => {
-                Delta::Witness(witness1.compose(witness2))
-            }
-            _ => panic!("Cannot compose transactions with different delta types"),
+        tx1.try_compose(tx2)
+            .expect("Cannot compose transactions with different delta types or shared nullifiers")
+    }
+
+    /// Composes the transaction with a counterparty's like
+    /// [`Transaction::compose`], e.g. an intent with the solver's actions,
+    /// returning an error instead of panicking. The deltas stay unproven: the
+    /// result is a witness if the imbalances cancel out and is unbalanced
+    /// otherwise, so it can be composed further and only balanced
+    /// compositions can be proven with [`Transaction::generate_delta_proof`].
+    /// Fails if either delta is already proven, or if both transactions
+    /// reveal the same nullifier.
+    pub fn try_compose(self, other: Transaction) -> Result<Transaction, ArmError> {
+        let valid_until = match (self.valid_until, other.valid_until) {
+            (Some(t1), Some(t2)) => Some(t1.min(t2)),
+            (t1, t2) => t1.or(t2),
+        };
+        let provenance = match (&self.provenance, &other.provenance) {
+            (Some(p1), Some(p2)) => p1.merge(p2),
+            _ => None,
+        };
+        let mut actions = self.actions;
+        actions.extend(other.actions);
+        let delta = self.delta_proof.balance_against(other.delta_proof)?;
+        let composed = Transaction {
+            valid_until,
+            provenance,
+            ..Transaction::create(actions, delta)
         };
-        Transaction::create(actions, delta)
+        composed.nf_duplication_check()?;
+        Ok(composed)
+    }
+}
+
+/// A transaction whose delta is proven.
+///
+/// [`Transaction::verify`] fails at runtime on transactions still carrying a
+/// delta witness; services taking a `ProvenTransaction` instead can only be
+/// handed transactions with a delta proof, obtained with
+/// [`Transaction::into_proven`] or checked with `TryFrom`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProvenTransaction(Transaction);
+
+impl ProvenTransaction {
+    /// Verifies all the proofs and corresponding checks in the transaction.
+    pub fn verify(self) -> Result<(), ArmError> {
+        self.0.verify()
+    }
+
+    /// Returns the transaction.
+    pub fn transaction(&self) -> &Transaction {
+        &self.0
+    }
+
+    /// Returns the transaction, e.g. to encode it.
+    pub fn into_inner(self) -> Transaction {
+        self.0
+    }
+}
+
+/// Fails with [`ArmError::ExpectedDeltaProof`] if the delta isn't proven.
+impl TryFrom<Transaction> for ProvenTransaction {
+    type Error = ArmError;
+
+    fn try_from(tx: Transaction) -> Result<Self, ArmError> {
+        match tx.delta_proof {
+            Delta::Proof(_) | Delta::OpeningProof(_) => Ok(ProvenTransaction(tx)),
+            Delta::Witness(_) | Delta::Unbalanced { .. } => Err(ArmError::ExpectedDeltaProof),
+        }
     }
 }
 
 #[cfg(feature = "aggregation")]
 impl Transaction {
     /// Aggregates all the transaction proofs with the default strategy.
+    #[cfg(feature = "prove")]
     pub fn aggregate(&mut self, proof_type: ProofType) -> Result<(), ArmError> {
         self.aggregate_with_strategy(AggregationStrategy::Batch, proof_type)
     }
 
+    /// Aggregates all the transaction proofs with the strategy and proof type
+    /// of the given configuration.
+    #[cfg(feature = "prove")]
+    pub fn aggregate_with_config(&mut self, config: &ArmConfig) -> Result<(), ArmError> {
+        self.aggregate_with_strategy(
+            config.aggregation_strategy.clone(),
+            config.default_proof_type,
+        )
+    }
+
     /// Aggregates all the transaction proofs using the passed aggregation strategy.
     /// If aggregation is successful, `self` contains an aggregation proof and its
     /// compliance and logic proofs are set to `None`. Else proofs are untouched.
+    #[cfg(feature = "prove")]
     pub fn aggregate_with_strategy(
         &mut self,
         strategy: AggregationStrategy,
@@ -180,6 +890,22 @@ impl Transaction {
         Ok(())
     }
 
+    /// Aggregates all the transaction proofs as recommended by
+    /// [`recommend_strategy`] for the settlement target, returning the
+    /// recommendation. Transactions that aren't worth aggregating are left
+    /// unchanged.
+    #[cfg(feature = "prove")]
+    pub fn aggregate_auto(
+        &mut self,
+        target: &SettlementTarget,
+    ) -> Result<AggregationRecommendation, ArmError> {
+        let recommendation = recommend_strategy(&TxShape::of(self), target)?;
+        if let Some(strategy) = &recommendation.strategy {
+            self.aggregate_with_strategy(strategy.clone(), recommendation.proof_type)?;
+        }
+        Ok(recommendation)
+    }
+
     /// Verifies the aggregated proof of the transaction.
     pub fn verify_aggregation(&self) -> Result<(), ArmError> {
         if let Some(agg_proof) = &self.aggregation_proof {
@@ -211,16 +937,1096 @@ impl Transaction {
             None
         }
     }
+}
 
-    // Replaces all compliance and resource logic proofs with `None`.
-    fn erase_base_proofs(&mut self) {
-        for a in self.actions.iter_mut() {
-            for cu in a.compliance_units.iter_mut() {
-                cu.proof = None;
+/// Builds balanced transactions end-to-end from compliance witnesses and the
+/// logics of their resources: the builder computes the action trees, proves
+/// the compliance units and logics, and composes the delta witness.
+///
+/// The builder is generic over the constructors of the logic proofs, which
+/// must be `Send` for the builds of [`TransactionBuilder::parallel`].
+#[cfg(feature = "prove")]
+pub struct TransactionBuilder<L = LogicConstructor> {
+    // The units of each action, the last one being filled
+    actions: Vec<Vec<BuilderUnit<L>>>,
+    config: ArmConfig,
+    dust_policy: DustPolicy,
+    // The number of threads proving the units of parallel builds
+    #[cfg(feature = "parallel")]
+    max_jobs: usize,
+}
+
+// Proves a resource logic from its path in the action tree.
+#[cfg(feature = "prove")]
+type LogicConstructor = Box<dyn FnOnce(MerklePath, &ArmConfig) -> Result<LogicVerifier, ArmError>>;
+
+#[cfg(feature = "prove")]
+struct BuilderUnit<L> {
+    witness: ComplianceWitness,
+    consumed_logic: L,
+    created_logic: L,
+}
+
+#[cfg(feature = "prove")]
+impl<L> BuilderUnit<L> {
+    fn tags(&self) -> Result<(Digest, Digest), ArmError> {
+        let nullifier = self
+            .witness
+            .consumed_resource
+            .nullifier(&self.witness.nf_key)?;
+        Ok((nullifier, self.witness.created_resource.commitment()))
+    }
+}
+
+// A unit with the paths of its resources in the action tree, ready to be
+// proven.
+#[cfg(feature = "prove")]
+struct ProvingJob<L> {
+    unit: BuilderUnit<L>,
+    consumed_path: MerklePath,
+    created_path: MerklePath,
+}
+
+#[cfg(feature = "prove")]
+impl<L> ProvingJob<L>
+where
+    L: FnOnce(MerklePath, &ArmConfig) -> Result<LogicVerifier, ArmError>,
+{
+    // Proves the compliance unit and the logics of the job in order, and
+    // checks the visibility rules of their app data.
+    fn prove(self, config: &ArmConfig) -> Result<ProvenUnit, ArmError> {
+        let BuilderUnit {
+            witness,
+            consumed_logic,
+            created_logic,
+        } = self.unit;
+        let compliance_unit = ComplianceUnit::create_with_config(&witness, config)?;
+        let consumed_logic = consumed_logic(self.consumed_path, config)?;
+        let created_logic = created_logic(self.created_path, config)?;
+        ProvenUnit::checked(&witness, compliance_unit, consumed_logic, created_logic)
+    }
+}
+
+#[cfg(feature = "prove")]
+struct ProvenUnit {
+    compliance_unit: ComplianceUnit,
+    consumed_logic: LogicVerifier,
+    created_logic: LogicVerifier,
+    rcv: Rcv,
+}
+
+#[cfg(feature = "prove")]
+impl ProvenUnit {
+    // Checks the visibility rules of the app data of the logics against the
+    // resources of the unit, also for logic guests not defined with
+    // `arm_logic_main!`, which may not check them.
+    fn checked(
+        witness: &ComplianceWitness,
+        compliance_unit: ComplianceUnit,
+        consumed_logic: LogicVerifier,
+        created_logic: LogicVerifier,
+    ) -> Result<Self, ArmError> {
+        for (logic, resource) in [
+            (&consumed_logic, &witness.consumed_resource),
+            (&created_logic, &witness.created_resource),
+        ] {
+            let instance = logic.get_instance()?;
+            instance
+                .app_data
+                .check_visibility(resource.is_ephemeral)
+                .map_err(|err| err.with_tag(0, instance.tag))?;
+        }
+        Ok(ProvenUnit {
+            compliance_unit,
+            consumed_logic,
+            created_logic,
+            rcv: witness.rcv,
+        })
+    }
+}
+
+// Checks that the units balance without creating dust, computes their paths
+// in the action trees and returns the proving jobs with the number of units
+// of each action.
+#[cfg(feature = "prove")]
+fn proving_jobs<L>(
+    actions: Vec<Vec<BuilderUnit<L>>>,
+    dust_policy: &DustPolicy,
+) -> Result<(Vec<ProvingJob<L>>, Vec<usize>), ArmError> {
+    for unit in actions.iter().flatten() {
+        dust_policy.check_resource(&unit.witness.created_resource)?;
+    }
+    let imbalance = actions.iter().flatten().try_fold(
+        ProjectivePoint::IDENTITY,
+        |acc, unit| -> Result<_, ArmError> {
+            let consumed = &unit.witness.consumed_resource;
+            let created = &unit.witness.created_resource;
+            Ok(acc + created.kind()? * created.quantity_scalar()
+                - consumed.kind()? * consumed.quantity_scalar())
+        },
+    )?;
+    if imbalance != ProjectivePoint::IDENTITY {
+        return Err(ArmError::UnbalancedTransaction);
+    }
+
+    let mut jobs = Vec::new();
+    let mut action_sizes = Vec::with_capacity(actions.len());
+    for units in actions.into_iter().filter(|units| !units.is_empty()) {
+        let tags = units
+            .iter()
+            .map(BuilderUnit::tags)
+            .collect::<Result<Vec<_>, _>>()?;
+        let tree = MerkleTree::from_units(tags.iter().copied());
+        action_sizes.push(units.len());
+        for (unit, (nullifier, commitment)) in units.into_iter().zip(tags) {
+            jobs.push(ProvingJob {
+                unit,
+                consumed_path: tree.generate_path(&nullifier)?,
+                created_path: tree.generate_path(&commitment)?,
+            });
+        }
+    }
+    Ok((jobs, action_sizes))
+}
+
+// Assembles the transaction of the proven units and proves its delta.
+#[cfg(feature = "prove")]
+fn assemble_transaction(
+    units: Vec<ProvenUnit>,
+    action_sizes: &[usize],
+) -> Result<Transaction, ArmError> {
+    let rcvs: Vec<Rcv> = units.iter().map(|unit| unit.rcv).collect();
+    let mut units = units.into_iter();
+    let actions = action_sizes
+        .iter()
+        .map(|size| {
+            let mut compliance_units = Vec::with_capacity(*size);
+            let mut logic_verifiers = Vec::with_capacity(2 * size);
+            for unit in units.by_ref().take(*size) {
+                compliance_units.push(unit.compliance_unit);
+                logic_verifiers.push(unit.consumed_logic);
+                logic_verifiers.push(unit.created_logic);
             }
-            for lp in a.logic_verifier_inputs.iter_mut() {
-                lp.proof = None;
+            Action::new(compliance_units, logic_verifiers)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let delta_witness = DeltaWitness::from_rcvs(&rcvs)?;
+    Transaction::create(actions, Delta::Witness(delta_witness)).generate_delta_proof()
+}
+
+#[cfg(feature = "prove")]
+impl<L> TransactionBuilder<L> {
+    /// Rejects the transaction at build time if it creates resources below
+    /// the policy's minimum quantities.
+    pub fn with_dust_policy(mut self, dust_policy: DustPolicy) -> Self {
+        self.dust_policy = dust_policy;
+        self
+    }
+
+    /// Closes the current action. Units added afterwards belong to the next
+    /// action.
+    pub fn next_action(mut self) -> Self {
+        if !self.actions.last().unwrap().is_empty() {
+            self.actions.push(Vec::new());
+        }
+        self
+    }
+
+    fn with_config(config: ArmConfig) -> Self {
+        TransactionBuilder {
+            actions: vec![Vec::new()],
+            config,
+            dust_policy: DustPolicy::default(),
+            #[cfg(feature = "parallel")]
+            max_jobs: 1,
+        }
+    }
+
+    fn push_unit(mut self, unit: BuilderUnit<L>) -> Self {
+        // `actions` always holds the action being filled
+        self.actions.last_mut().unwrap().push(unit);
+        self
+    }
+}
+
+#[cfg(feature = "prove")]
+impl TransactionBuilder {
+    /// Creates a builder proving with the given configuration.
+    pub fn new(config: ArmConfig) -> Self {
+        Self::with_config(config)
+    }
+
+    /// Adds a compliance unit to the current action. The logic provers of
+    /// its consumed and created resources are constructed from their paths
+    /// in the action tree, which are only known once the action is complete.
+    pub fn add_unit<C, D>(
+        self,
+        witness: ComplianceWitness,
+        consumed_logic: impl FnOnce(MerklePath) -> C + 'static,
+        created_logic: impl FnOnce(MerklePath) -> D + 'static,
+    ) -> Self
+    where
+        C: LogicProver,
+        D: LogicProver,
+    {
+        self.push_unit(BuilderUnit {
+            witness,
+            consumed_logic: Box::new(move |path, config: &ArmConfig| {
+                consumed_logic(path).prove_with_config(config)
+            }),
+            created_logic: Box::new(move |path, config: &ArmConfig| {
+                created_logic(path).prove_with_config(config)
+            }),
+        })
+    }
+
+    /// Proves the actions and returns the transaction with its delta proof.
+    /// Fails before proving anything if the units don't balance or create
+    /// dust under the builder's policy.
+    pub fn build(self) -> Result<Transaction, ArmError> {
+        let (jobs, action_sizes) = proving_jobs(self.actions, &self.dust_policy)?;
+        let units = jobs
+            .into_iter()
+            .map(|job| job.prove(&self.config))
+            .collect::<Result<Vec<_>, _>>()?;
+        assemble_transaction(units, &action_sizes)
+    }
+
+    /// Starts a pipelined build: compliance proofs are generated in the
+    /// background as soon as their witnesses are pushed, while the caller is
+    /// still selecting or signing later resources. At most `max_in_flight`
+    /// witnesses wait for proving; pushing more blocks until the prover
+    /// catches up.
+    pub fn streaming(config: ArmConfig, max_in_flight: usize) -> StreamingTransactionBuilder {
+        StreamingTransactionBuilder::new(config, max_in_flight)
+    }
+
+    /// Starts a build proving all the compliance units and logics of the
+    /// transaction concurrently, on at most `max_jobs` threads. The logic
+    /// constructors must then be `Send`.
+    #[cfg(feature = "parallel")]
+    pub fn parallel(config: ArmConfig, max_jobs: usize) -> ParallelTransactionBuilder {
+        ParallelTransactionBuilder {
+            max_jobs,
+            ..TransactionBuilder::with_config(config)
+        }
+    }
+}
+
+// Proves a resource logic from its path in the action tree, on any thread.
+#[cfg(feature = "parallel")]
+type SendLogicConstructor =
+    Box<dyn FnOnce(MerklePath, &ArmConfig) -> Result<LogicVerifier, ArmError> + Send>;
+
+/// A transaction builder proving all its proofs concurrently. See
+/// [`TransactionBuilder::parallel`].
+#[cfg(feature = "parallel")]
+pub type ParallelTransactionBuilder = TransactionBuilder<SendLogicConstructor>;
+
+#[cfg(feature = "parallel")]
+impl ParallelTransactionBuilder {
+    /// Adds a compliance unit to the current action, like
+    /// [`TransactionBuilder::add_unit`].
+    pub fn add_unit<C, D>(
+        self,
+        witness: ComplianceWitness,
+        consumed_logic: impl FnOnce(MerklePath) -> C + Send + 'static,
+        created_logic: impl FnOnce(MerklePath) -> D + Send + 'static,
+    ) -> Self
+    where
+        C: LogicProver,
+        D: LogicProver,
+    {
+        self.push_unit(BuilderUnit {
+            witness,
+            consumed_logic: Box::new(move |path, config: &ArmConfig| {
+                consumed_logic(path).prove_with_config(config)
+            }),
+            created_logic: Box::new(move |path, config: &ArmConfig| {
+                created_logic(path).prove_with_config(config)
+            }),
+        })
+    }
+
+    /// Proves the compliance units and logics of all the actions
+    /// concurrently and returns the transaction with its delta proof. Fails
+    /// before proving anything if the units don't balance or create dust.
+    pub fn build(self) -> Result<Transaction, ArmError> {
+        use rayon::prelude::*;
+
+        let (jobs, action_sizes) = proving_jobs(self.actions, &self.dust_policy)?;
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.max_jobs.max(1))
+            .build()
+            .map_err(|err| ArmError::ProveFailed(format!("can't start the provers: {err}")))?;
+        let config = &self.config;
+        let units = pool.install(|| {
+            jobs.into_par_iter()
+                .map(|job| {
+                    let ProvingJob {
+                        unit,
+                        consumed_path,
+                        created_path,
+                    } = job;
+                    let witness = &unit.witness;
+                    let (compliance_unit, (consumed_logic, created_logic)) = rayon::join(
+                        || ComplianceUnit::create_with_config(witness, config),
+                        || {
+                            rayon::join(
+                                || (unit.consumed_logic)(consumed_path, config),
+                                || (unit.created_logic)(created_path, config),
+                            )
+                        },
+                    );
+                    ProvenUnit::checked(witness, compliance_unit?, consumed_logic?, created_logic?)
+                })
+                .collect::<Result<Vec<_>, ArmError>>()
+        })?;
+        assemble_transaction(units, &action_sizes)
+    }
+}
+
+/// A transaction builder proving compliance units while it's being filled.
+/// See [`TransactionBuilder::streaming`].
+#[cfg(feature = "prove")]
+pub struct StreamingTransactionBuilder {
+    jobs: Option<SyncSender<ComplianceWitness>>,
+    results: Option<Receiver<Result<ComplianceUnit, ArmError>>>,
+    worker: Option<JoinHandle<()>>,
+    // The number of compliance units of each finished action with its logic verifiers
+    actions: Vec<(usize, Vec<LogicVerifier>)>,
+    submitted: usize,
+    action_start: usize,
+    rcvs: Vec<Rcv>,
+    dust_policy: DustPolicy,
+    #[cfg(feature = "aggregation")]
+    auto_aggregation: Option<SettlementTarget>,
+}
+
+#[cfg(feature = "prove")]
+impl StreamingTransactionBuilder {
+    fn new(config: ArmConfig, max_in_flight: usize) -> Self {
+        let (jobs, job_queue) = mpsc::sync_channel::<ComplianceWitness>(max_in_flight.max(1));
+        let (result_sink, results) = mpsc::channel();
+        let worker = thread::spawn(move || {
+            for witness in job_queue {
+                let unit = ComplianceUnit::create_with_config(&witness, &config);
+                if result_sink.send(unit).is_err() {
+                    break;
+                }
+            }
+        });
+        StreamingTransactionBuilder {
+            jobs: Some(jobs),
+            results: Some(results),
+            worker: Some(worker),
+            actions: Vec::new(),
+            submitted: 0,
+            action_start: 0,
+            rcvs: Vec::new(),
+            dust_policy: DustPolicy::default(),
+            #[cfg(feature = "aggregation")]
+            auto_aggregation: None,
+        }
+    }
+
+    /// Rejects pushed witnesses creating resources below the policy's
+    /// minimum quantities.
+    pub fn with_dust_policy(mut self, dust_policy: DustPolicy) -> Self {
+        self.dust_policy = dust_policy;
+        self
+    }
+
+    /// Aggregates the finished transaction as recommended for the settlement
+    /// target. See [`Transaction::aggregate_auto`].
+    #[cfg(feature = "aggregation")]
+    pub fn with_auto_aggregation(mut self, target: SettlementTarget) -> Self {
+        self.auto_aggregation = Some(target);
+        self
+    }
+
+    /// Queues a finalized compliance witness of the current action for
+    /// proving. Blocks while `max_in_flight` witnesses are already queued.
+    /// Fails if the created resource is dust under the builder's policy.
+    pub fn push_compliance(&mut self, witness: ComplianceWitness) -> Result<(), ArmError> {
+        self.dust_policy.check_resource(&witness.created_resource)?;
+        self.rcvs.push(witness.rcv);
+        self.jobs
+            .as_ref()
+            .ok_or(ArmError::ProveFailed("streaming prover stopped".into()))?
+            .send(witness)
+            .map_err(|_| ArmError::ProveFailed("streaming prover stopped".into()))?;
+        self.submitted += 1;
+        Ok(())
+    }
+
+    /// Returns the number of compliance witnesses pushed so far.
+    pub fn submitted(&self) -> usize {
+        self.submitted
+    }
+
+    /// Closes the current action with its logic verifiers. Compliance
+    /// witnesses pushed afterwards belong to the next action.
+    pub fn finish_action(&mut self, logic_verifiers: Vec<LogicVerifier>) {
+        self.actions
+            .push((self.submitted - self.action_start, logic_verifiers));
+        self.action_start = self.submitted;
+    }
+
+    /// Waits for the outstanding proofs and assembles the transaction. Its
+    /// delta witness is composed from the pushed compliance witnesses. In
+    /// auto aggregation mode, the transaction is aggregated as recommended.
+    pub fn finish(mut self) -> Result<Transaction, ArmError> {
+        if self.action_start != self.submitted {
+            return Err(ArmError::ProveFailed(
+                "compliance units pushed after the last action".into(),
+            ));
+        }
+        // Closing the queue lets the worker terminate after the last job
+        drop(self.jobs.take());
+
+        let results = self.results.take();
+        let units = (0..self.submitted)
+            .map(|_| {
+                results
+                    .as_ref()
+                    .and_then(|results| results.recv().ok())
+                    .ok_or_else(|| ArmError::ProveFailed("streaming prover stopped".into()))?
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(worker) = self.worker.take() {
+            worker
+                .join()
+                .map_err(|_| ArmError::ProveFailed("streaming prover panicked".into()))?;
+        }
+
+        let mut units = units.into_iter();
+        let actions = std::mem::take(&mut self.actions)
+            .into_iter()
+            .map(|(n, logic_verifiers)| {
+                Action::new(units.by_ref().take(n).collect(), logic_verifiers)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let delta_witness = DeltaWitness::from_rcvs(&self.rcvs)?;
+        let tx = Transaction::create(actions, Delta::Witness(delta_witness));
+        #[cfg(feature = "aggregation")]
+        let tx = match &self.auto_aggregation {
+            Some(target) => {
+                let mut tx = tx;
+                tx.aggregate_auto(target)?;
+                tx
             }
+            None => tx,
+        };
+        Ok(tx)
+    }
+}
+
+#[cfg(feature = "prove")]
+impl Drop for StreamingTransactionBuilder {
+    fn drop(&mut self) {
+        // Without a receiver the worker's next send fails, so it exits after
+        // its current job instead of proving the whole queue
+        drop(self.jobs.take());
+        drop(self.results.take());
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
         }
     }
 }
+
+#[test]
+fn test_delta_is_balanced_with() {
+    use crate::{action::unproven_action, compliance::ComplianceWitness};
+
+    // The default witness consumes and creates the same quantity of the same
+    // kind with rcv = 1.
+    let action = unproven_action(&ComplianceWitness::default());
+
+    let mut one = [0u8; 32];
+    one[31] = 1;
+    let delta = Delta::Witness(DeltaWitness::from_bytes(&one).unwrap());
+    assert!(delta
+        .is_balanced_with(std::slice::from_ref(&action))
+        .unwrap());
+    assert!(!delta.is_balanced_with(&[]).unwrap());
+
+    let tx = Transaction::create(vec![action.clone()], delta)
+        .generate_delta_proof()
+        .unwrap();
+    assert!(tx
+        .delta_proof
+        .is_balanced_with(std::slice::from_ref(&action))
+        .unwrap());
+    assert!(!tx
+        .delta_proof
+        .is_balanced_with(&[action.clone(), action])
+        .unwrap());
+    assert_eq!(
+        tx.delta_proof.imbalance(&[]),
+        Err(ArmError::ExpectedDeltaWitness)
+    );
+}
+
+#[test]
+fn test_unbalanced_delta() {
+    use crate::{action::unproven_action, compliance::ComplianceWitness};
+
+    // Each action's delta is the rcv = 1 commitment.
+    let action = unproven_action(&ComplianceWitness::default());
+    let mut one = [0u8; 32];
+    one[31] = 1;
+    let witness = || DeltaWitness::from_bytes(&one).unwrap();
+
+    let balanced = Delta::from_witness(witness(), std::slice::from_ref(&action)).unwrap();
+    assert_eq!(balanced, Delta::Witness(witness()));
+    let intent = Delta::from_witness(witness(), &[action.clone(), action.clone()]).unwrap();
+    assert!(matches!(intent, Delta::Unbalanced { .. }));
+    let solution = Delta::from_witness(witness(), &[]).unwrap();
+    let actions = vec![action.clone(), action];
+    assert!(!intent.is_balanced_with(&actions).unwrap());
+
+    // Unbalanced deltas can't be proven.
+    let unbalanced = Transaction::create(actions.clone(), intent.clone());
+    assert_eq!(
+        unbalanced.clone().generate_delta_proof(),
+        Err(ArmError::UnbalancedTransaction)
+    );
+    assert_eq!(unbalanced.verify(), Err(ArmError::ExpectedDeltaProof));
+
+    let combined = intent.balance_against(solution).unwrap();
+    assert!(matches!(combined, Delta::Witness(_)));
+    assert!(combined.is_balanced_with(&actions).unwrap());
+
+    let tx = Transaction::create(actions, combined);
+    assert_eq!(
+        ProvenTransaction::try_from(tx.clone()),
+        Err(ArmError::ExpectedDeltaProof)
+    );
+    let proven = tx.into_proven().unwrap();
+    assert_eq!(
+        proven.transaction().delta_proof.mode(),
+        Some(DeltaProofMode::Signature)
+    );
+    assert_eq!(
+        proven.into_inner().delta_proof.balance_against(balanced),
+        Err(ArmError::ExpectedDeltaWitness)
+    );
+}
+
+#[test]
+fn test_try_compose() {
+    use crate::{action::unproven_action, compliance::ComplianceWitness, padding::PaddingUnit};
+
+    let balanced_tx = |witness: &ComplianceWitness| {
+        let action = unproven_action(witness);
+        let delta = DeltaWitness::from_rcvs(&[witness.rcv]).unwrap();
+        let delta = Delta::from_witness(delta, std::slice::from_ref(&action)).unwrap();
+        Transaction::create(vec![action], delta)
+    };
+    let default_witness = ComplianceWitness::default();
+    let padding_witness = PaddingUnit::new().unwrap().witness;
+
+    let composed = balanced_tx(&default_witness)
+        .try_compose(balanced_tx(&padding_witness))
+        .unwrap();
+    assert_eq!(composed.actions.len(), 2);
+    assert!(matches!(composed.delta_proof, Delta::Witness(_)));
+    let proven = composed.generate_delta_proof().unwrap();
+    assert!(proven
+        .delta_proof
+        .is_balanced_with(&proven.actions)
+        .unwrap());
+
+    // Unbalanced intents stay unproven until they're balanced.
+    let mut two = [0u8; 32];
+    two[31] = 2;
+    let action = unproven_action(&default_witness);
+    let delta = Delta::from_witness(
+        DeltaWitness::from_bytes(&two).unwrap(),
+        std::slice::from_ref(&action),
+    )
+    .unwrap();
+    let intent = Transaction::create(vec![action], delta);
+    let composed = intent.try_compose(balanced_tx(&padding_witness)).unwrap();
+    assert!(matches!(composed.delta_proof, Delta::Unbalanced { .. }));
+    assert_eq!(
+        composed.generate_delta_proof(),
+        Err(ArmError::UnbalancedTransaction)
+    );
+
+    assert!(matches!(
+        balanced_tx(&default_witness).try_compose(balanced_tx(&default_witness)),
+        Err(ArmError::WithTag { source, .. }) if *source == ArmError::NullifierDuplication
+    ));
+    // The infallible composition goes through the same checks.
+    let (tx1, tx2) = (balanced_tx(&default_witness), balanced_tx(&default_witness));
+    assert!(std::panic::catch_unwind(|| Transaction::compose(tx1, tx2)).is_err());
+    assert_eq!(
+        proven.try_compose(balanced_tx(&default_witness)),
+        Err(ArmError::ExpectedDeltaWitness)
+    );
+}
+
+#[cfg(feature = "borsh")]
+#[test]
+fn test_borsh_round_trip() {
+    use crate::{
+        action::unproven_action,
+        compliance::ComplianceWitness,
+        logic_instance::{AppData, ExpirableBlob},
+        logic_proof::LogicVerifierInputs,
+    };
+    use borsh::BorshDeserialize;
+
+    // Decodes the borsh encoding of the value and checks that it has the
+    // same bincode representation.
+    fn round_trip<T>(value: &T) -> T
+    where
+        T: borsh::BorshSerialize + BorshDeserialize + Serialize,
+    {
+        let decoded = T::try_from_slice(&borsh::to_vec(value).unwrap()).unwrap();
+        assert_eq!(
+            bincode::serialize(&decoded).unwrap(),
+            bincode::serialize(value).unwrap()
+        );
+        decoded
+    }
+
+    let witness = ComplianceWitness::default();
+    round_trip(&witness);
+    let instance = witness.constrain().unwrap();
+    let app_data = AppData {
+        resource_payload: vec![ExpirableBlob {
+            blob: vec![1, 2, 3],
+            deletion_criterion: 1,
+        }],
+        ..Default::default()
+    };
+    let mut action = Action {
+        logic_verifier_inputs: vec![LogicVerifierInputs {
+            tag: instance.consumed_nullifier,
+            verifying_key: Digest::from_bytes([6u8; 32]),
+            app_data,
+            proof: None,
+            app_data_by_digest: false,
+            binds_image_id: true,
+            logic_ref_opening: Some([7u8; 32]),
+        }],
+        resource_refs: vec![ResourceRef::new(0, instance.consumed_nullifier)],
+        ..unproven_action(&witness)
+    };
+    action.compliance_units[0].proof = Some(vec![4, 5]);
+
+    let mut one = [0u8; 32];
+    one[31] = 1;
+    let tx = Transaction::create(
+        vec![action],
+        Delta::Witness(DeltaWitness::from_bytes(&one).unwrap()),
+    )
+    .with_valid_until(100)
+    .with_provenance();
+    assert_eq!(round_trip(&tx), tx);
+    let tx = tx.generate_delta_proof().unwrap();
+    assert_eq!(round_trip(&tx), tx);
+    let tx = Transaction {
+        delta_proof: Delta::OpeningProof(
+            DeltaOpeningProof::prove(b"message", &DeltaWitness::from_bytes(&one).unwrap()).unwrap(),
+        ),
+        ..tx
+    };
+    assert_eq!(round_trip(&tx), tx);
+    assert_eq!(
+        round_trip(&witness.consumed_resource),
+        witness.consumed_resource
+    );
+
+    // Non-canonical scalars are rejected like in the serde encoding.
+    let invalid = Delta::Witness(DeltaWitness::from_bytes(&one).unwrap());
+    let mut bytes = borsh::to_vec(&invalid).unwrap();
+    bytes[1..].fill(0xff);
+    assert!(Delta::try_from_slice(&bytes).is_err());
+}
+
+#[test]
+fn test_transaction_formatting() {
+    use crate::{action::unproven_action, compliance::ComplianceWitness};
+
+    let mut action = unproven_action(&ComplianceWitness::default());
+    action.compliance_units[0].proof = Some(vec![7u8; 300_000]);
+    let mut tx = Transaction::create(
+        vec![action],
+        Delta::Witness(DeltaWitness::from_bytes(&[1u8; 32]).unwrap()),
+    );
+    tx.aggregation_proof = Some(vec![7u8; 4096]);
+
+    assert_eq!(
+        tx.to_string(),
+        "transaction (1 action(s), 1 unit(s), delta witness, aggregation 4.0 KiB)"
+    );
+    let debug = format!("{tx:?}");
+    assert!(debug.len() < 2_000);
+    assert!(debug.contains("<redacted>"));
+    assert!(debug.contains("<4096 bytes>"));
+
+    let resource = Resource {
+        rand_seed: [0xab; 32],
+        ..Resource::default()
+    };
+    let debug = format!("{resource:?}");
+    assert!(!debug.contains("abab"));
+    assert!(!debug.contains("171, 171"));
+}
+
+#[cfg(feature = "prove")]
+#[test]
+fn test_streaming_transaction_builder() {
+    use crate::compliance::ComplianceWitness;
+
+    let mut builder = TransactionBuilder::streaming(ArmConfig::default(), 1);
+    builder
+        .push_compliance(ComplianceWitness::default())
+        .unwrap();
+    builder
+        .push_compliance(ComplianceWitness::default())
+        .unwrap();
+    builder.finish_action(vec![]);
+    builder
+        .push_compliance(ComplianceWitness::default())
+        .unwrap();
+    builder.finish_action(vec![]);
+    assert_eq!(builder.submitted(), 3);
+
+    let tx = builder.finish().unwrap();
+    assert_eq!(tx.actions.len(), 2);
+    assert_eq!(tx.actions[0].compliance_units.len(), 2);
+    assert_eq!(tx.actions[1].compliance_units.len(), 1);
+    for unit in tx.actions.iter().flat_map(|a| a.compliance_units.iter()) {
+        unit.verify().unwrap();
+    }
+    assert!(tx.delta_proof.is_balanced_with(&tx.actions).unwrap());
+
+    let mut unfinished = TransactionBuilder::streaming(ArmConfig::default(), 1);
+    unfinished
+        .push_compliance(ComplianceWitness::default())
+        .unwrap();
+    assert!(unfinished.finish().is_err());
+
+    // Dropping a builder with queued jobs stops the worker
+    let mut dropped = TransactionBuilder::streaming(ArmConfig::default(), 2);
+    for _ in 0..2 {
+        dropped
+            .push_compliance(ComplianceWitness::default())
+            .unwrap();
+    }
+    drop(dropped);
+}
+
+#[cfg(feature = "prove")]
+#[test]
+fn test_transaction_builder() {
+    use crate::{logic_proof::PaddingResourceLogic, padding::PaddingUnit};
+
+    let add_padding_unit = |builder: TransactionBuilder| {
+        let unit = PaddingUnit::new().unwrap();
+        let [nullifier, commitment] = unit.tags().unwrap();
+        let witness = unit.witness.clone();
+        let (consumed, created, nf_key) = (
+            witness.consumed_resource,
+            witness.created_resource,
+            witness.nf_key.clone(),
+        );
+        let created_nf_key = nf_key.clone();
+        builder.add_unit(
+            witness,
+            move |path: MerklePath| {
+                PaddingResourceLogic::new(consumed, path.root(&nullifier), nf_key, true)
+            },
+            move |path: MerklePath| {
+                PaddingResourceLogic::new(created, path.root(&commitment), created_nf_key, false)
+            },
+        )
+    };
+
+    let builder = TransactionBuilder::new(ArmConfig::default());
+    let builder = add_padding_unit(add_padding_unit(builder)).next_action();
+    let tx = add_padding_unit(builder).next_action().build().unwrap();
+    assert_eq!(tx.actions.len(), 2);
+    assert_eq!(tx.actions[0].compliance_units.len(), 2);
+    assert_eq!(tx.actions[0].logic_verifier_inputs.len(), 4);
+    assert_eq!(tx.actions[1].compliance_units.len(), 1);
+    tx.verify().unwrap();
+
+    // Unbalanced units are rejected before proving.
+    let mut unit = PaddingUnit::new().unwrap();
+    unit.witness.created_resource.quantity = 1;
+    let builder = TransactionBuilder::new(ArmConfig::default()).add_unit(
+        unit.witness,
+        |_| PaddingResourceLogic::default(),
+        |_| PaddingResourceLogic::default(),
+    );
+    assert!(matches!(
+        builder.build(),
+        Err(ArmError::UnbalancedTransaction)
+    ));
+
+    // So is dust under the builder's policy.
+    let mut unit = PaddingUnit::new().unwrap();
+    unit.witness.created_resource.is_ephemeral = false;
+    let created = &unit.witness.created_resource;
+    let mut dust_policy = DustPolicy::new();
+    dust_policy.set_min_quantity(created.logic_ref, created.label_ref, 1);
+    let builder = TransactionBuilder::new(ArmConfig::default())
+        .with_dust_policy(dust_policy)
+        .add_unit(
+            unit.witness,
+            |_| PaddingResourceLogic::default(),
+            |_| PaddingResourceLogic::default(),
+        );
+    assert!(matches!(builder.build(), Err(ArmError::DustQuantity)));
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn test_parallel_transaction_builder() {
+    use crate::{logic_proof::PaddingResourceLogic, padding::PaddingUnit};
+
+    let add_padding_unit = |builder: ParallelTransactionBuilder| {
+        let unit = PaddingUnit::new().unwrap();
+        let [nullifier, commitment] = unit.tags().unwrap();
+        let witness = unit.witness.clone();
+        let (consumed, created, nf_key) = (
+            witness.consumed_resource,
+            witness.created_resource,
+            witness.nf_key.clone(),
+        );
+        let created_nf_key = nf_key.clone();
+        builder.add_unit(
+            witness,
+            move |path: MerklePath| {
+                PaddingResourceLogic::new(consumed, path.root(&nullifier), nf_key, true)
+            },
+            move |path: MerklePath| {
+                PaddingResourceLogic::new(created, path.root(&commitment), created_nf_key, false)
+            },
+        )
+    };
+
+    let builder = TransactionBuilder::parallel(ArmConfig::default(), 2);
+    let builder = add_padding_unit(add_padding_unit(builder)).next_action();
+    let tx = add_padding_unit(builder).build().unwrap();
+    assert_eq!(tx.actions.len(), 2);
+    assert_eq!(tx.actions[0].compliance_units.len(), 2);
+    assert_eq!(tx.actions[0].logic_verifier_inputs.len(), 4);
+    assert_eq!(tx.actions[1].compliance_units.len(), 1);
+    tx.verify().unwrap();
+}
+
+#[test]
+fn test_describe_transaction() {
+    use crate::{
+        action::unproven_action, compliance::ComplianceWitness, kind_registry::KindMetadata,
+    };
+
+    let witness = ComplianceWitness::default();
+    let action = unproven_action(&witness);
+    let mut one = [0u8; 32];
+    one[31] = 1;
+    let tx = Transaction::create(
+        vec![action],
+        Delta::Witness(DeltaWitness::from_bytes(&one).unwrap()),
+    );
+
+    let created = witness.created_resource;
+    let mut registry = KindRegistry::new();
+    registry.register(
+        created.logic_ref,
+        created.label_ref,
+        KindMetadata {
+            decimals: 2,
+            symbol: "XAN".to_string(),
+            display_name: "Test token".to_string(),
+        },
+    );
+    let description = tx.describe(&registry, &[created]);
+    assert_eq!(description.lines().count(), 3);
+    assert!(description.contains("(0.01 XAN) Test token"));
+    assert!(!tx.describe(&registry, &[]).contains("XAN"));
+}
+
+#[test]
+fn test_transaction_expiry() {
+    use crate::{action::unproven_action, compliance::ComplianceWitness};
+
+    let mut one = [0u8; 32];
+    one[31] = 1;
+    let delta = || Delta::Witness(DeltaWitness::from_bytes(&one).unwrap());
+
+    let tx = Transaction::create(vec![], delta());
+    assert!(!tx.is_expired(u64::MAX));
+    assert!(tx.get_delta_msg().unwrap().is_empty());
+
+    let tx = tx.with_valid_until(100);
+    assert!(!tx.is_expired(100));
+    assert!(tx.is_expired(101));
+    assert!(!tx.get_delta_msg().unwrap().is_empty());
+
+    let other = Transaction::create(vec![], delta()).with_valid_until(50);
+    assert_eq!(Transaction::compose(tx, other).valid_until, Some(50));
+
+    // The delta proof of an expiring transaction only balances it with its
+    // expiry.
+    let action = unproven_action(&ComplianceWitness::default());
+    let mut tx = Transaction::create(vec![action], delta())
+        .with_valid_until(100)
+        .generate_delta_proof()
+        .unwrap();
+    assert!(tx.is_balanced().unwrap());
+    assert!(!tx.delta_proof.is_balanced_with(&tx.actions).unwrap());
+    tx.valid_until = Some(200);
+    assert!(!tx.is_balanced().unwrap());
+}
+
+#[test]
+fn test_transaction_provenance() {
+    let mut one = [0u8; 32];
+    one[31] = 1;
+    let delta = || Delta::Witness(DeltaWitness::from_bytes(&one).unwrap());
+
+    let tx = Transaction::create(vec![], delta()).with_provenance();
+    let provenance = tx.provenance.clone().unwrap();
+    assert_eq!(provenance.arm_version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(provenance.compliance_vk, *COMPLIANCE_VK);
+    assert!(provenance.logic_vks.is_empty());
+    assert!(!tx.get_delta_msg().unwrap().is_empty());
+
+    let mut other = provenance.clone();
+    other.logic_vks.push(Digest::default());
+    assert_eq!(provenance.merge(&other), Some(other.clone()));
+    other.arm_version = "0.0.0".to_string();
+    assert_eq!(provenance.merge(&other), None);
+
+    let composed = Transaction::compose(tx, Transaction::create(vec![], delta()));
+    assert_eq!(composed.provenance, None);
+}
+
+#[test]
+fn test_transaction_tags_root() {
+    use crate::{action::unproven_action, compliance::ComplianceWitness};
+
+    let instance = ComplianceWitness::default().constrain().unwrap();
+    let action = unproven_action(&ComplianceWitness::default());
+    assert_eq!(
+        action.tags().unwrap(),
+        vec![instance.consumed_nullifier, instance.created_commitment]
+    );
+
+    let mut one = [0u8; 32];
+    one[31] = 1;
+    let delta = || Delta::Witness(DeltaWitness::from_bytes(&one).unwrap());
+    let tx = Transaction::create(vec![action.clone()], delta());
+    let msg = tx.get_delta_msg().unwrap();
+    let mut tx = tx.with_tags_root().unwrap();
+    assert_eq!(
+        tx.tags_root,
+        Some(
+            MerkleTree::from(vec![action.action_tree_root().unwrap()])
+                .root()
+                .unwrap()
+        )
+    );
+    assert_ne!(tx.get_delta_msg().unwrap(), msg);
+    assert_eq!(tx.check_tags_root(), Ok(()));
+
+    tx.actions.push(action);
+    assert_eq!(tx.check_tags_root(), Err(ArmError::TagsRootMismatch));
+    assert!(Transaction::create(vec![], delta())
+        .with_tags_root()
+        .is_err());
+}
+
+#[test]
+fn test_to_adapter_instances() {
+    use crate::{
+        action::unproven_action, compliance::ComplianceWitness, logic_instance::AppData,
+        logic_proof::LogicVerifierInputs,
+    };
+
+    let witness = ComplianceWitness::default();
+    let instance = witness.constrain().unwrap();
+    let input = |tag: Digest, verifying_key: Digest| LogicVerifierInputs {
+        tag,
+        verifying_key,
+        app_data: AppData::new(),
+        proof: None,
+        app_data_by_digest: false,
+        binds_image_id: false,
+        logic_ref_opening: None,
+    };
+    // The inputs are out of order; the adapter order follows the compliance
+    // instance.
+    let action = Action {
+        logic_verifier_inputs: vec![
+            input(instance.created_commitment, instance.created_logic_ref),
+            input(instance.consumed_nullifier, instance.consumed_logic_ref),
+        ],
+        ..unproven_action(&witness)
+    };
+    let mut one = [0u8; 32];
+    one[31] = 1;
+    let tx = Transaction::create(
+        vec![action.clone(), action],
+        Delta::Witness(DeltaWitness::from_bytes(&one).unwrap()),
+    );
+
+    let instances = tx.to_adapter_instances().unwrap();
+    assert_eq!(
+        instances.compliance_instances,
+        vec![instance.clone(), instance.clone()]
+    );
+    assert_eq!(
+        instances.tags,
+        [instance.consumed_nullifier, instance.created_commitment].repeat(2)
+    );
+    assert_eq!(
+        instances.logic_refs,
+        [instance.consumed_logic_ref, instance.created_logic_ref].repeat(2)
+    );
+    assert_eq!(
+        instances.arm_app_data_digests,
+        vec![AppData::new().digest().unwrap(); 4]
+    );
+}
+
+#[test]
+fn test_check_resource_refs() {
+    use crate::{action::unproven_action, compliance::ComplianceWitness};
+
+    let instance = ComplianceWitness::default().constrain().unwrap();
+    let solver = unproven_action(&ComplianceWitness::default());
+    let intent = Action::new(vec![], vec![])
+        .unwrap()
+        .with_resource_refs(vec![ResourceRef::new(0, instance.consumed_nullifier)]);
+
+    let mut one = [0u8; 32];
+    one[31] = 1;
+    let delta = || Delta::Witness(DeltaWitness::from_bytes(&one).unwrap());
+    let tx = Transaction::create(vec![solver.clone(), intent.clone()], delta());
+    assert!(tx.check_resource_refs().is_ok());
+
+    // After composition the hint is stale, but the reference still resolves.
+    let composed = Transaction::compose(
+        Transaction::create(vec![intent.clone()], delta()),
+        Transaction::create(vec![solver], delta()),
+    );
+    let resource_ref = &composed.actions[0].resource_refs[0];
+    assert_eq!(composed.resolve_resource_ref(resource_ref), Some(1));
+    assert!(composed.check_resource_refs().is_ok());
+
+    let tx = Transaction::create(vec![intent], delta());
+    assert_eq!(
+        tx.check_resource_refs(),
+        Err(ArmError::UnresolvedResourceRef.with_tag(0, instance.consumed_nullifier))
+    );
+}
@@ -0,0 +1,621 @@
+//! Canonical, versioned binary encoding of transactions.
+//!
+//! [`Transaction::to_bytes`] is the bincode encoding of the structs of this
+//! crate, which changes whenever they are refactored. The canonical encoding
+//! has a documented byte layout instead, which other implementations (the
+//! SDKs, the EVM adapter, indexers) can decode without this crate.
+//!
+//! # Layout
+//!
+//! An encoded value starts with the [`CANONICAL_FORMAT_VERSION`] byte,
+//! followed by the value:
+//!
+//! - integers are little-endian, `u32` for lengths and counts, `u64` for
+//!   action indices and timestamps; blob words are `u32`s,
+//! - booleans are a `0` or `1` byte,
+//! - digests and fixed-size keys and proofs are their raw bytes,
+//! - byte strings and strings are their `u32` length followed by the bytes
+//!   (strings are UTF-8),
+//! - sequences are their `u32` count followed by the items,
+//! - optional values are a `0` byte if absent, or a `1` byte followed by the
+//!   value,
+//! - structs are records: their `u32` length followed by their fields in
+//!   declaration order,
+//! - a [`Delta`] is a variant byte (`0` witness, `1` proof, `2` opening
+//!   proof, `3` unbalanced) followed by the record of the variant.
+//!
+//! # Compatibility
+//!
+//! Fields are only ever added at the end of a record, and decoders skip the
+//! bytes of a record past the fields they know, so a decoder reads the
+//! values encoded by later encoders of its format version. Any other change
+//! of the layout bumps the format version, and decoders reject the versions
+//! they don't know with [`ArmError::UnsupportedEncodingVersion`]. Encoding
+//! is deterministic, and the encoding of values decoded without skipped
+//! bytes is the original encoding.
+//!
+//! # Versions
+//!
+//! The format version is independent of the
+//! [`PROTOCOL_VERSION`](crate::compat::PROTOCOL_VERSION) of
+//! [`Transaction::to_bytes`]. The protocol version follows the structs, and
+//! its major version is bumped when bincode can't decode them anymore, e.g.
+//! when a field is added. The format version follows the documented layout,
+//! so the fields added by a protocol version are appended to the records
+//! without bumping it. All transactions of protocol versions 1 and 2 (once
+//! translated by [`Transaction::from_bytes`]) have a format version 1
+//! encoding.
+
+use crate::{
+    action::{Action, ResourceRef},
+    compliance_unit::ComplianceUnit,
+    delta_proof::{DeltaOpeningProof, DeltaProof, DeltaWitness},
+    error::ArmError,
+    logic_instance::{AppData, ExpirableBlob},
+    logic_proof::LogicVerifierInputs,
+    transaction::{Delta, Provenance, Transaction},
+};
+use risc0_zkvm::Digest;
+
+/// The version of the canonical encoding.
+pub const CANONICAL_FORMAT_VERSION: u8 = 1;
+
+// A value with a canonical encoding.
+trait Canonical: Sized {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), ArmError>;
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, ArmError>;
+}
+
+macro_rules! canonical_bytes {
+    ($($ty:ty),*) => {
+        $(
+            impl $ty {
+                /// Encodes the value in the [canonical encoding](crate::transaction::encoding).
+                pub fn to_canonical_bytes(&self) -> Result<Vec<u8>, ArmError> {
+                    let mut out = vec![CANONICAL_FORMAT_VERSION];
+                    self.encode(&mut out)?;
+                    Ok(out)
+                }
+
+                /// Decodes a value encoded with `to_canonical_bytes`, by this
+                /// or a later encoder of the same format version.
+                pub fn from_canonical_bytes(bytes: &[u8]) -> Result<Self, ArmError> {
+                    let mut reader = Reader { bytes };
+                    let version = reader.u8()?;
+                    if version != CANONICAL_FORMAT_VERSION {
+                        return Err(ArmError::UnsupportedEncodingVersion(version));
+                    }
+                    let value = Self::decode(&mut reader)?;
+                    reader.finish()?;
+                    Ok(value)
+                }
+            }
+        )*
+    };
+}
+
+canonical_bytes!(Transaction, Action, ComplianceUnit, LogicVerifierInputs);
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ArmError> {
+        if self.bytes.len() < len {
+            return Err(ArmError::DeserializationError);
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ArmError> {
+        Ok(self.take(N)?.try_into().expect("taken N bytes"))
+    }
+
+    fn u8(&mut self) -> Result<u8, ArmError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, ArmError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, ArmError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn bool(&mut self) -> Result<bool, ArmError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(ArmError::DeserializationError),
+        }
+    }
+
+    fn len(&mut self) -> Result<usize, ArmError> {
+        usize::try_from(self.u32()?).map_err(|_| ArmError::DeserializationError)
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>, ArmError> {
+        let len = self.len()?;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    fn seq<T: Canonical>(&mut self) -> Result<Vec<T>, ArmError> {
+        // The count isn't trusted to preallocate.
+        let count = self.len()?;
+        let mut items = Vec::new();
+        for _ in 0..count {
+            items.push(T::decode(self)?);
+        }
+        Ok(items)
+    }
+
+    // Decodes a record, skipping the fields appended by later encoders.
+    fn record<T>(
+        &mut self,
+        decode: impl FnOnce(&mut Reader<'a>) -> Result<T, ArmError>,
+    ) -> Result<T, ArmError> {
+        let len = self.len()?;
+        let mut record = Reader {
+            bytes: self.take(len)?,
+        };
+        decode(&mut record)
+    }
+
+    fn finish(&self) -> Result<(), ArmError> {
+        if !self.bytes.is_empty() {
+            return Err(ArmError::DeserializationError);
+        }
+        Ok(())
+    }
+}
+
+fn write_len(out: &mut Vec<u8>, len: usize) -> Result<(), ArmError> {
+    let len = u32::try_from(len).map_err(|_| ArmError::SerializationError)?;
+    out.extend_from_slice(&len.to_le_bytes());
+    Ok(())
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) -> Result<(), ArmError> {
+    write_len(out, bytes.len())?;
+    out.extend_from_slice(bytes);
+    Ok(())
+}
+
+// Encodes a record, prefixing its fields with their length.
+fn write_record(
+    out: &mut Vec<u8>,
+    encode: impl FnOnce(&mut Vec<u8>) -> Result<(), ArmError>,
+) -> Result<(), ArmError> {
+    let start = out.len();
+    out.extend_from_slice(&[0; 4]);
+    encode(out)?;
+    let len = u32::try_from(out.len() - start - 4).map_err(|_| ArmError::SerializationError)?;
+    out[start..start + 4].copy_from_slice(&len.to_le_bytes());
+    Ok(())
+}
+
+impl Canonical for bool {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), ArmError> {
+        out.push(u8::from(*self));
+        Ok(())
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, ArmError> {
+        reader.bool()
+    }
+}
+
+impl Canonical for u32 {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), ArmError> {
+        out.extend_from_slice(&self.to_le_bytes());
+        Ok(())
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, ArmError> {
+        reader.u32()
+    }
+}
+
+impl Canonical for u64 {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), ArmError> {
+        out.extend_from_slice(&self.to_le_bytes());
+        Ok(())
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, ArmError> {
+        reader.u64()
+    }
+}
+
+impl Canonical for [u8; 32] {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), ArmError> {
+        out.extend_from_slice(self);
+        Ok(())
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, ArmError> {
+        reader.array()
+    }
+}
+
+impl Canonical for Digest {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), ArmError> {
+        out.extend_from_slice(self.as_bytes());
+        Ok(())
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, ArmError> {
+        Ok(Digest::from_bytes(reader.array()?))
+    }
+}
+
+impl Canonical for String {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), ArmError> {
+        write_bytes(out, self.as_bytes())
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, ArmError> {
+        String::from_utf8(reader.bytes()?).map_err(|_| ArmError::DeserializationError)
+    }
+}
+
+// Byte strings are `Vec<u8>`, so sequences of other items are encoded with
+// `write_seq` and decoded with `Reader::seq`.
+impl Canonical for Vec<u8> {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), ArmError> {
+        write_bytes(out, self)
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, ArmError> {
+        reader.bytes()
+    }
+}
+
+impl<T: Canonical> Canonical for Option<T> {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), ArmError> {
+        match self {
+            None => {
+                out.push(0);
+                Ok(())
+            }
+            Some(value) => {
+                out.push(1);
+                value.encode(out)
+            }
+        }
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, ArmError> {
+        match reader.bool()? {
+            false => Ok(None),
+            true => T::decode(reader).map(Some),
+        }
+    }
+}
+
+fn write_seq<T: Canonical>(out: &mut Vec<u8>, items: &[T]) -> Result<(), ArmError> {
+    write_len(out, items.len())?;
+    items.iter().try_for_each(|item| item.encode(out))
+}
+
+impl Canonical for ExpirableBlob {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), ArmError> {
+        write_record(out, |out| {
+            write_seq(out, &self.blob)?;
+            self.deletion_criterion.encode(out)
+        })
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, ArmError> {
+        reader.record(|r| {
+            Ok(ExpirableBlob {
+                blob: r.seq()?,
+                deletion_criterion: r.u32()?,
+            })
+        })
+    }
+}
+
+impl Canonical for AppData {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), ArmError> {
+        write_record(out, |out| {
+            write_seq(out, &self.resource_payload)?;
+            write_seq(out, &self.discovery_payload)?;
+            write_seq(out, &self.external_payload)?;
+            write_seq(out, &self.application_payload)
+        })
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, ArmError> {
+        reader.record(|r| {
+            Ok(AppData {
+                resource_payload: r.seq()?,
+                discovery_payload: r.seq()?,
+                external_payload: r.seq()?,
+                application_payload: r.seq()?,
+            })
+        })
+    }
+}
+
+impl Canonical for LogicVerifierInputs {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), ArmError> {
+        write_record(out, |out| {
+            self.tag.encode(out)?;
+            self.verifying_key.encode(out)?;
+            self.app_data.encode(out)?;
+            self.proof.encode(out)?;
+            self.app_data_by_digest.encode(out)?;
+            self.binds_image_id.encode(out)?;
+            self.logic_ref_opening.encode(out)
+        })
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, ArmError> {
+        reader.record(|r| {
+            Ok(LogicVerifierInputs {
+                tag: Canonical::decode(r)?,
+                verifying_key: Canonical::decode(r)?,
+                app_data: Canonical::decode(r)?,
+                proof: Canonical::decode(r)?,
+                app_data_by_digest: r.bool()?,
+                binds_image_id: r.bool()?,
+                logic_ref_opening: Canonical::decode(r)?,
+            })
+        })
+    }
+}
+
+impl Canonical for ComplianceUnit {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), ArmError> {
+        write_record(out, |out| {
+            self.proof.encode(out)?;
+            self.instance.encode(out)
+        })
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, ArmError> {
+        reader.record(|r| {
+            Ok(ComplianceUnit {
+                proof: Canonical::decode(r)?,
+                instance: r.bytes()?,
+            })
+        })
+    }
+}
+
+impl Canonical for ResourceRef {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), ArmError> {
+        write_record(out, |out| {
+            (self.action_hint as u64).encode(out)?;
+            self.tag.encode(out)
+        })
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, ArmError> {
+        reader.record(|r| {
+            let action_hint =
+                usize::try_from(r.u64()?).map_err(|_| ArmError::DeserializationError)?;
+            Ok(ResourceRef::new(action_hint, Canonical::decode(r)?))
+        })
+    }
+}
+
+impl Canonical for Action {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), ArmError> {
+        write_record(out, |out| {
+            write_seq(out, &self.compliance_units)?;
+            write_seq(out, &self.logic_verifier_inputs)?;
+            write_seq(out, &self.resource_refs)
+        })
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, ArmError> {
+        reader.record(|r| {
+            Ok(Action {
+                compliance_units: r.seq()?,
+                logic_verifier_inputs: r.seq()?,
+                resource_refs: r.seq()?,
+            })
+        })
+    }
+}
+
+impl Canonical for Delta {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), ArmError> {
+        match self {
+            Delta::Witness(witness) => {
+                out.push(0);
+                write_record(out, |out| witness.to_bytes().encode(out))
+            }
+            Delta::Proof(proof) => {
+                out.push(1);
+                write_record(out, |out| {
+                    out.extend_from_slice(&proof.to_bytes());
+                    Ok(())
+                })
+            }
+            Delta::OpeningProof(proof) => {
+                out.push(2);
+                write_record(out, |out| {
+                    out.extend_from_slice(&proof.to_bytes());
+                    Ok(())
+                })
+            }
+            Delta::Unbalanced { witness, imbalance } => {
+                out.push(3);
+                write_record(out, |out| {
+                    witness.to_bytes().encode(out)?;
+                    imbalance.encode(out)
+                })
+            }
+        }
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, ArmError> {
+        match reader.u8()? {
+            0 => reader.record(|r| Ok(Delta::Witness(DeltaWitness::from_bytes(r.take(32)?)?))),
+            1 => reader.record(|r| Ok(Delta::Proof(DeltaProof::from_bytes(r.take(65)?)?))),
+            2 => reader.record(|r| {
+                Ok(Delta::OpeningProof(DeltaOpeningProof::from_bytes(
+                    r.take(65)?,
+                )?))
+            }),
+            3 => reader.record(|r| {
+                Ok(Delta::Unbalanced {
+                    witness: DeltaWitness::from_bytes(r.take(32)?)?,
+                    imbalance: r.bytes()?,
+                })
+            }),
+            _ => Err(ArmError::DeserializationError),
+        }
+    }
+}
+
+impl Canonical for Provenance {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), ArmError> {
+        write_record(out, |out| {
+            self.arm_version.encode(out)?;
+            self.compliance_vk.encode(out)?;
+            write_seq(out, &self.logic_vks)
+        })
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, ArmError> {
+        reader.record(|r| {
+            Ok(Provenance {
+                arm_version: Canonical::decode(r)?,
+                compliance_vk: Canonical::decode(r)?,
+                logic_vks: r.seq()?,
+            })
+        })
+    }
+}
+
+impl Canonical for Transaction {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), ArmError> {
+        write_record(out, |out| {
+            write_seq(out, &self.actions)?;
+            self.delta_proof.encode(out)?;
+            self.expected_balance.encode(out)?;
+            self.aggregation_proof.encode(out)?;
+            self.valid_until.encode(out)?;
+            self.provenance.encode(out)?;
+            self.tags_root.encode(out)
+        })
+    }
+
+    fn decode(reader: &mut Reader<'_>) -> Result<Self, ArmError> {
+        reader.record(|r| {
+            Ok(Transaction {
+                actions: r.seq()?,
+                delta_proof: Canonical::decode(r)?,
+                expected_balance: Canonical::decode(r)?,
+                aggregation_proof: Canonical::decode(r)?,
+                valid_until: Canonical::decode(r)?,
+                provenance: Canonical::decode(r)?,
+                tags_root: Canonical::decode(r)?,
+            })
+        })
+    }
+}
+
+#[test]
+fn test_canonical_encoding() {
+    let input = LogicVerifierInputs {
+        tag: Digest::from_bytes([1u8; 32]),
+        verifying_key: Digest::from_bytes([2u8; 32]),
+        app_data: AppData {
+            external_payload: vec![ExpirableBlob {
+                blob: vec![3, 4],
+                deletion_criterion: 1,
+            }],
+            ..Default::default()
+        },
+        proof: Some(vec![5, 6, 7]),
+        app_data_by_digest: false,
+        binds_image_id: true,
+        logic_ref_opening: Some([8u8; 32]),
+    };
+    let action = Action {
+        compliance_units: vec![ComplianceUnit {
+            proof: None,
+            instance: vec![9; 12],
+        }],
+        logic_verifier_inputs: vec![input.clone()],
+        resource_refs: vec![ResourceRef::new(1, input.tag)],
+    };
+    let tx = Transaction {
+        actions: vec![action.clone()],
+        delta_proof: Delta::Witness(DeltaWitness::from_bytes(&[1u8; 32]).unwrap()),
+        expected_balance: None,
+        aggregation_proof: Some(vec![10]),
+        valid_until: Some(1_700_000_000),
+        provenance: Some(Provenance {
+            arm_version: "2.0.0".into(),
+            compliance_vk: Digest::from_bytes([11u8; 32]),
+            logic_vks: vec![input.verifying_key],
+        }),
+        tags_root: None,
+    };
+
+    let bytes = tx.to_canonical_bytes().unwrap();
+    assert_eq!(bytes[0], CANONICAL_FORMAT_VERSION);
+    assert!(Transaction::from_canonical_bytes(&bytes).unwrap() == tx);
+    let bytes = action.to_canonical_bytes().unwrap();
+    assert!(Action::from_canonical_bytes(&bytes).unwrap() == action);
+    let bytes = input.to_canonical_bytes().unwrap();
+    assert!(LogicVerifierInputs::from_canonical_bytes(&bytes).unwrap() == input);
+
+    // The layout of a compliance unit: version, record length, absent proof,
+    // instance length and instance.
+    let unit = &action.compliance_units[0];
+    let bytes = unit.to_canonical_bytes().unwrap();
+    assert_eq!(bytes[..10], [1, 17, 0, 0, 0, 0, 12, 0, 0, 0]);
+    assert_eq!(&ComplianceUnit::from_canonical_bytes(&bytes).unwrap(), unit);
+
+    // Fields appended to a record by later encoders are skipped.
+    let mut extended = bytes.clone();
+    extended[1] += 2;
+    extended.extend_from_slice(&[0xaa, 0xbb]);
+    assert_eq!(
+        &ComplianceUnit::from_canonical_bytes(&extended).unwrap(),
+        unit
+    );
+
+    // Unknown versions, non-canonical booleans and trailing bytes are rejected.
+    let mut future = bytes.clone();
+    future[0] = 2;
+    assert_eq!(
+        ComplianceUnit::from_canonical_bytes(&future),
+        Err(ArmError::UnsupportedEncodingVersion(2))
+    );
+    let mut non_canonical = bytes.clone();
+    non_canonical[5] = 2;
+    assert!(ComplianceUnit::from_canonical_bytes(&non_canonical).is_err());
+    let trailing = [&bytes[..], &[0]].concat();
+    assert!(ComplianceUnit::from_canonical_bytes(&trailing).is_err());
+
+    // Every delta variant round-trips.
+    let witness = DeltaWitness::from_bytes(&[1u8; 32]).unwrap();
+    for delta in [
+        Delta::Proof(DeltaProof::prove(b"message", &witness).unwrap()),
+        Delta::OpeningProof(DeltaOpeningProof::prove(b"message", &witness).unwrap()),
+        Delta::Unbalanced {
+            witness: witness.clone(),
+            imbalance: vec![12, 13],
+        },
+    ] {
+        let tx = Transaction {
+            delta_proof: delta,
+            ..tx.clone()
+        };
+        let bytes = tx.to_canonical_bytes().unwrap();
+        assert!(Transaction::from_canonical_bytes(&bytes).unwrap() == tx);
+    }
+}
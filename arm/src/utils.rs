@@ -1,6 +1,8 @@
-//! Utility functions for byte and word conversions and hashing.
+//! Utility functions for byte and word conversions, hashing and log
+//! formatting.
 
 use risc0_zkvm::sha::{Digest, Impl, Sha256, DIGEST_WORDS};
+use std::fmt;
 
 /// Converts a byte slice to a vector of u32 words.
 pub fn bytes_to_words(bytes: &[u8]) -> Vec<u32> {
@@ -45,6 +47,89 @@ pub fn hash_bytes(bytes: &[u8]) -> Digest {
     *Impl::hash_bytes(bytes)
 }
 
+/// Formats the first four bytes of a digest in hex, e.g. `1a2b3c4d…`, for
+/// log lines.
+pub fn short_hex(digest: &Digest) -> String {
+    format!("{}…", hex::encode(&digest.as_bytes()[..4]))
+}
+
+/// Formats a size in bytes, e.g. `224 B` or `215.3 KiB`, for log lines.
+pub fn format_size(bytes: usize) -> String {
+    match bytes {
+        0..1024 => format!("{bytes} B"),
+        1024..1048576 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1048576.0),
+    }
+}
+
+/// Stands in for a blob in `Debug` output, showing only its length.
+pub(crate) struct Elided {
+    len: usize,
+    unit: &'static str,
+}
+
+impl Elided {
+    #[cfg(feature = "transaction")]
+    pub(crate) fn bytes(bytes: &[u8]) -> Self {
+        Elided {
+            len: bytes.len(),
+            unit: "bytes",
+        }
+    }
+
+    pub(crate) fn words(words: &[u32]) -> Self {
+        Elided {
+            len: words.len(),
+            unit: "words",
+        }
+    }
+}
+
+impl fmt::Debug for Elided {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<{} {}>", self.len, self.unit)
+    }
+}
+
+/// Stands in for a secret in `Debug` output.
+pub(crate) struct Redacted;
+
+impl fmt::Debug for Redacted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+/// Implements borsh for a type as the fixed-size array returned by its
+/// `to_bytes`, decoded with its `from_bytes`.
+#[cfg(all(
+    feature = "borsh",
+    any(feature = "compliance_circuit", feature = "aggregation_circuit")
+))]
+macro_rules! borsh_via_bytes {
+    ($ty:ty, $len:expr) => {
+        impl borsh::BorshSerialize for $ty {
+            fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+                writer.write_all(&self.to_bytes())
+            }
+        }
+
+        impl borsh::BorshDeserialize for $ty {
+            fn deserialize_reader<R: std::io::Read>(reader: &mut R) -> std::io::Result<Self> {
+                let bytes = <[u8; $len] as borsh::BorshDeserialize>::deserialize_reader(reader)?;
+                <$ty>::from_bytes(&bytes).map_err(|err| {
+                    std::io::Error::new(std::io::ErrorKind::InvalidData, err.to_string())
+                })
+            }
+        }
+    };
+}
+#[cfg(all(
+    feature = "borsh",
+    any(feature = "compliance_circuit", feature = "aggregation_circuit")
+))]
+pub(crate) use borsh_via_bytes;
+
 #[test]
 fn test_bytes_to_words() {
     let bytes = [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07];
@@ -0,0 +1,630 @@
+//! Context-aware transaction verification with stable rejection reasons.
+
+use crate::{
+    chain::ChainContext,
+    compliance::ComplianceInstance,
+    delta_proof::DeltaProofMode,
+    error::ArmError,
+    logic_instance::{AppData, LogicInstance},
+    proving_system::ProofVerifier,
+    transaction::{Delta, Transaction},
+};
+use risc0_zkvm::Digest;
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+    sync::Arc,
+};
+use thiserror::Error;
+
+/// The reason a transaction was rejected.
+///
+/// The numeric codes are stable and shared with the settlement adapters, so
+/// rejections can be reported consistently on-chain and off-chain; the EVM
+/// adapter reverts with them in `TransactionRejected` of the gadgets' `evm`
+/// module. New reasons are only ever appended.
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+#[non_exhaustive]
+pub enum RejectionReason {
+    /// A compliance proof is missing or invalid.
+    #[error("Invalid compliance proof")]
+    InvalidComplianceProof = 1,
+    /// A logic proof is missing, invalid, or doesn't match the compliance units.
+    #[error("Invalid logic proof")]
+    InvalidLogicProof = 2,
+    /// A consumed resource refers to a commitment tree root the verifier doesn't know.
+    #[error("Unknown commitment tree root")]
+    UnknownRoot = 3,
+    /// The same nullifier is revealed more than once.
+    #[error("Duplicate nullifier")]
+    DuplicateNullifier = 4,
+    /// The delta proof is missing or doesn't verify against the transaction delta.
+    #[error("Delta mismatch")]
+    DeltaMismatch = 5,
+    /// The transaction exceeds the size limits of the verifier.
+    #[error("Transaction too large")]
+    Oversize = 6,
+    /// A resource logic is not accepted by the verifier.
+    #[error("Unknown logic")]
+    UnknownLogic = 7,
+    /// The aggregation proof is invalid or aggregation is not supported.
+    #[error("Invalid aggregation proof")]
+    InvalidAggregationProof = 8,
+    /// The transaction can't be decoded.
+    #[error("Malformed transaction")]
+    Malformed = 9,
+    /// The transaction's validity period is over.
+    #[error("Transaction expired")]
+    Expired = 10,
+    /// The transaction is rejected by the verifier's policy, e.g. because it
+    /// creates dust resources.
+    #[error("Policy violation")]
+    PolicyViolation = 11,
+}
+
+impl RejectionReason {
+    /// Returns the stable numeric code of the reason.
+    pub fn code(&self) -> u8 {
+        *self as u8
+    }
+
+    /// Returns the reason corresponding to a numeric code.
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            1 => Some(RejectionReason::InvalidComplianceProof),
+            2 => Some(RejectionReason::InvalidLogicProof),
+            3 => Some(RejectionReason::UnknownRoot),
+            4 => Some(RejectionReason::DuplicateNullifier),
+            5 => Some(RejectionReason::DeltaMismatch),
+            6 => Some(RejectionReason::Oversize),
+            7 => Some(RejectionReason::UnknownLogic),
+            8 => Some(RejectionReason::InvalidAggregationProof),
+            9 => Some(RejectionReason::Malformed),
+            10 => Some(RejectionReason::Expired),
+            11 => Some(RejectionReason::PolicyViolation),
+            _ => None,
+        }
+    }
+}
+
+/// The verifier's view of the world used by [`Transaction::verify_with_context`].
+///
+/// Every check is optional; the default context only performs the
+/// context-free checks of [`Transaction::verify`].
+#[derive(Debug, Clone, Default)]
+pub struct VerificationContext {
+    /// The commitment tree roots known by the verifier.
+    pub known_roots: Option<HashSet<Digest>>,
+    /// The resource logic refs accepted by the verifier.
+    pub known_logics: Option<HashSet<Digest>>,
+    /// The maximum serialized size of a transaction in bytes.
+    pub max_transaction_bytes: Option<usize>,
+    /// The current unix time in seconds, used to reject expired transactions
+    /// when there is no chain context.
+    pub now: Option<u64>,
+    /// The state of the chain the transaction is verified against. Its
+    /// timestamp takes precedence over `now`.
+    pub chain: Option<ChainContext>,
+    /// Application-specific rules checked on every instance of the
+    /// transaction, in order.
+    pub policies: Vec<Arc<dyn VerificationPolicy>>,
+    /// The root of the forwarder contracts external payloads may call, for
+    /// policies checking the forwarder calls of a transaction.
+    pub forwarder_allowlist_root: Option<Digest>,
+    /// The root of the issuers authorized to create permissioned resources,
+    /// for policies checking the issuer roots published by their logics.
+    pub issuer_allowlist_root: Option<Digest>,
+    /// The only delta proof mode accepted by the verifier, if any.
+    pub delta_proof_mode: Option<DeltaProofMode>,
+}
+
+impl VerificationContext {
+    /// Creates a context without any constraint.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Verifies against the given state of the chain.
+    pub fn with_chain(mut self, chain: ChainContext) -> Self {
+        self.chain = Some(chain);
+        self
+    }
+
+    /// Returns the current unix time in seconds, from the chain context if
+    /// there is one.
+    pub fn now(&self) -> Option<u64> {
+        self.chain
+            .as_ref()
+            .map(|chain| chain.timestamp)
+            .or(self.now)
+    }
+
+    /// Adds an application-specific policy to the context.
+    pub fn with_policy(mut self, policy: impl VerificationPolicy + 'static) -> Self {
+        self.policies.push(Arc::new(policy));
+        self
+    }
+}
+
+/// Application-specific rules, e.g. blob size limits or allowed deletion
+/// criteria, enforced by [`Transaction::verify_with_context`] through
+/// [`VerificationContext::policies`].
+///
+/// The hooks run before any proof is verified, with the context being
+/// verified against. Each one accepts by default and rejects with the reason
+/// to report, typically [`RejectionReason::PolicyViolation`].
+pub trait VerificationPolicy: fmt::Debug + Send + Sync {
+    /// Checks the whole transaction, e.g. a dust check of the created
+    /// resources the verifier can decrypt.
+    fn on_transaction(
+        &self,
+        _ctx: &VerificationContext,
+        _tx: &Transaction,
+    ) -> Result<(), RejectionReason> {
+        Ok(())
+    }
+
+    /// Checks the compliance instance of a unit of the action at index
+    /// `action`.
+    fn on_compliance_instance(
+        &self,
+        _ctx: &VerificationContext,
+        _action: usize,
+        _instance: &ComplianceInstance,
+    ) -> Result<(), RejectionReason> {
+        Ok(())
+    }
+
+    /// Checks the instance of a logic proof of the action at index `action`,
+    /// verified against `logic_ref`.
+    fn on_logic_instance(
+        &self,
+        _ctx: &VerificationContext,
+        _action: usize,
+        _logic_ref: &Digest,
+        _instance: &LogicInstance,
+    ) -> Result<(), RejectionReason> {
+        Ok(())
+    }
+
+    /// Checks the app data carried for the resource `tag` of the action at
+    /// index `action`. Unlike the logic instance, it holds the full app data
+    /// even if the proof only commits to its digest.
+    fn on_app_data(
+        &self,
+        _ctx: &VerificationContext,
+        _action: usize,
+        _tag: &Digest,
+        _app_data: &AppData,
+    ) -> Result<(), RejectionReason> {
+        Ok(())
+    }
+}
+
+impl Transaction {
+    /// Verifies the transaction like [`Transaction::verify`], additionally
+    /// checking it against the verifier's context first, and reports the
+    /// reason of the first failed check. Both run the same proof checks.
+    pub fn verify_with_context(self, ctx: &VerificationContext) -> Result<(), RejectionReason> {
+        let result = self.check_with_context(ctx);
+        #[cfg(feature = "metrics")]
+        if let Err(reason) = &result {
+            crate::metrics::record_verification_failure(reason);
+        }
+        result
+    }
+
+    fn check_with_context(self, ctx: &VerificationContext) -> Result<(), RejectionReason> {
+        if ctx.now().is_some_and(|now| self.is_expired(now)) {
+            return Err(RejectionReason::Expired);
+        }
+
+        if let Some(max_bytes) = ctx.max_transaction_bytes {
+            let size =
+                bincode::serialized_size(&self).map_err(|_| RejectionReason::Malformed)? as usize;
+            if size > max_bytes {
+                return Err(RejectionReason::Oversize);
+            }
+        }
+
+        let instances = self
+            .actions
+            .iter()
+            .flat_map(|action| action.get_compliance_units())
+            .map(|unit| unit.get_instance())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| RejectionReason::Malformed)?;
+
+        if let Some(known_roots) = &ctx.known_roots {
+            if instances
+                .iter()
+                .any(|instance| !known_roots.contains(&instance.consumed_commitment_tree_root))
+            {
+                return Err(RejectionReason::UnknownRoot);
+            }
+        }
+
+        // Compliance instances may only commit to the logic refs, so they're
+        // checked on the verifying keys of the logic verifier inputs.
+        if let Some(known_logics) = &ctx.known_logics {
+            if self
+                .actions
+                .iter()
+                .flat_map(|action| action.get_logic_verifier_inputs())
+                .any(|input| !known_logics.contains(&input.verifying_key))
+            {
+                return Err(RejectionReason::UnknownLogic);
+            }
+        }
+
+        if !ctx.policies.is_empty() {
+            self.check_policies(ctx)?;
+        }
+
+        if ctx
+            .delta_proof_mode
+            .is_some_and(|mode| self.delta_proof.mode() != Some(mode))
+        {
+            return Err(RejectionReason::DeltaMismatch);
+        }
+
+        self.verify_checked(None, |reason, _| reason)
+    }
+}
+
+impl Transaction {
+    // Runs the hooks of the context's policies on the transaction and every
+    // instance of it.
+    fn check_policies(&self, ctx: &VerificationContext) -> Result<(), RejectionReason> {
+        let policies = &ctx.policies;
+        for policy in policies {
+            policy.on_transaction(ctx, self)?;
+        }
+        for (index, action) in self.actions.iter().enumerate() {
+            for unit in action.get_compliance_units() {
+                let instance = unit
+                    .get_instance()
+                    .map_err(|_| RejectionReason::Malformed)?;
+                for policy in policies {
+                    policy.on_compliance_instance(ctx, index, &instance)?;
+                }
+            }
+            for input in action.get_logic_verifier_inputs() {
+                for policy in policies {
+                    policy.on_app_data(ctx, index, &input.tag, &input.app_data)?;
+                }
+            }
+            let logic_verifiers = action
+                .get_logic_verifiers()
+                .map_err(|_| RejectionReason::InvalidLogicProof)?;
+            for verifier in &logic_verifiers {
+                let instance = verifier
+                    .get_instance()
+                    .map_err(|_| RejectionReason::Malformed)?;
+                for policy in policies {
+                    policy.on_logic_instance(ctx, index, &verifier.verifying_key, &instance)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What was checked when verifying an action.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ActionReport {
+    /// The number of compliance proofs verified.
+    pub compliance_proofs: usize,
+    /// The number of logic proofs verified.
+    pub logic_proofs: usize,
+    /// The number of logic proofs skipped.
+    pub skipped_logic_proofs: usize,
+}
+
+/// What was checked by [`Transaction::verify_actions`]. The delta proof and
+/// the nullifier uniqueness are checked for the whole transaction regardless
+/// of the selected actions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerificationReport {
+    /// The reports of the verified actions, by action index.
+    pub verified_actions: BTreeMap<usize, ActionReport>,
+    /// The indices of the actions whose proofs were skipped.
+    pub skipped_actions: Vec<usize>,
+    /// Whether the proofs were verified through the aggregation proof, which
+    /// covers all actions.
+    pub aggregated: bool,
+}
+
+impl Transaction {
+    /// Verifies the delta proof, the nullifier uniqueness, and the proofs of
+    /// the selected actions only, e.g. for services that verified the other
+    /// actions upstream. An aggregated transaction has its aggregation proof
+    /// verified, which covers all actions.
+    pub fn verify_actions(&self, indices: &[usize]) -> Result<VerificationReport, ArmError> {
+        if let Some(index) = indices.iter().find(|index| **index >= self.actions.len()) {
+            return Err(ArmError::ActionIndexOutOfRange(*index));
+        }
+
+        match &self.delta_proof {
+            Delta::Proof(_) | Delta::OpeningProof(_) => {
+                self.delta_proof
+                    .verify(&self.get_delta_msg()?, self.delta()?)?;
+            }
+            Delta::Witness(_) | Delta::Unbalanced { .. } => {
+                return Err(ArmError::ExpectedDeltaProof)
+            }
+        }
+        self.nf_duplication_check()?;
+        self.check_resource_refs()?;
+        self.check_tags_root()?;
+
+        let mut report = VerificationReport::default();
+        if self.aggregation_proof.is_some() {
+            #[cfg(not(feature = "aggregation"))]
+            return Err(ArmError::ProofVerificationFailed(
+                "feature `aggregation` is not enabled".into(),
+            ));
+
+            #[cfg(feature = "aggregation")]
+            {
+                self.verify_aggregation()?;
+                report.aggregated = true;
+                report.verified_actions = self
+                    .actions
+                    .iter()
+                    .enumerate()
+                    .map(|(index, action)| (index, action.full_report()))
+                    .collect();
+                return Ok(report);
+            }
+        }
+
+        let verifier = ProofVerifier::new();
+        for (index, action) in self.actions.iter().enumerate() {
+            if indices.contains(&index) {
+                action
+                    .clone()
+                    .verify_with(&verifier)
+                    .map_err(|err| err.at_action(index))?;
+                report.verified_actions.insert(index, action.full_report());
+            } else {
+                report.skipped_actions.push(index);
+            }
+        }
+        Ok(report)
+    }
+}
+
+impl From<RejectionReason> for ArmError {
+    fn from(reason: RejectionReason) -> Self {
+        ArmError::TransactionRejected(reason.code())
+    }
+}
+
+#[test]
+fn test_rejection_reason_codes() {
+    for code in 1..=11 {
+        let reason = RejectionReason::from_code(code).unwrap();
+        assert_eq!(reason.code(), code);
+    }
+    assert_eq!(RejectionReason::from_code(0), None);
+    assert_eq!(RejectionReason::from_code(12), None);
+}
+
+#[test]
+fn test_verify_with_context_rejects() {
+    use crate::delta_proof::DeltaWitness;
+
+    let witness = DeltaWitness::from_bytes(&[1u8; 32]).unwrap();
+    let tx = Transaction::create(vec![], Delta::Witness(witness));
+    assert_eq!(
+        tx.clone().verify_with_context(&VerificationContext::new()),
+        Err(RejectionReason::DeltaMismatch)
+    );
+
+    let ctx = VerificationContext {
+        max_transaction_bytes: Some(1),
+        ..Default::default()
+    };
+    assert_eq!(
+        tx.clone().verify_with_context(&ctx),
+        Err(RejectionReason::Oversize)
+    );
+
+    #[derive(Debug)]
+    struct NonEmpty;
+
+    impl VerificationPolicy for NonEmpty {
+        fn on_transaction(
+            &self,
+            _ctx: &VerificationContext,
+            tx: &Transaction,
+        ) -> Result<(), RejectionReason> {
+            if tx.actions.is_empty() {
+                return Err(RejectionReason::PolicyViolation);
+            }
+            Ok(())
+        }
+    }
+
+    let ctx = VerificationContext::new().with_policy(NonEmpty);
+    assert_eq!(
+        tx.clone().verify_with_context(&ctx),
+        Err(RejectionReason::PolicyViolation)
+    );
+
+    let ctx = VerificationContext {
+        now: Some(11),
+        ..Default::default()
+    };
+    let tx = tx.with_valid_until(10);
+    assert_eq!(
+        tx.clone().verify_with_context(&ctx),
+        Err(RejectionReason::Expired)
+    );
+    // The chain time takes precedence over the local clock.
+    let ctx = ctx.with_chain(ChainContext::new("testnet", 1, 10));
+    assert_eq!(
+        tx.verify_with_context(&ctx),
+        Err(RejectionReason::DeltaMismatch)
+    );
+}
+
+#[test]
+fn test_verify_actions() {
+    use crate::{
+        action::unproven_action, compliance::ComplianceWitness, delta_proof::DeltaWitness,
+    };
+
+    // An action without proofs, balanced with rcv = 1.
+    let action = unproven_action(&ComplianceWitness::default());
+    let mut one = [0u8; 32];
+    one[31] = 1;
+    let tx = Transaction::create(
+        vec![action.clone()],
+        Delta::Witness(DeltaWitness::from_bytes(&one).unwrap()),
+    )
+    .generate_delta_proof()
+    .unwrap();
+
+    // Only the balance is checked when no action is selected.
+    let report = tx.verify_actions(&[]).unwrap();
+    assert!(report.verified_actions.is_empty());
+    assert_eq!(report.skipped_actions, vec![0]);
+    assert!(!report.aggregated);
+
+    assert!(tx.verify_actions(&[0]).is_err());
+    assert!(action.verify_compliance_only().is_err());
+    assert_eq!(
+        tx.verify_actions(&[1]),
+        Err(ArmError::ActionIndexOutOfRange(1))
+    );
+}
+
+#[test]
+fn test_delta_proof_modes() {
+    use crate::{
+        action::unproven_action, compliance::ComplianceWitness, delta_proof::DeltaWitness,
+    };
+
+    // An action without proofs, balanced with rcv = 1.
+    let action = unproven_action(&ComplianceWitness::default());
+    let mut one = [0u8; 32];
+    one[31] = 1;
+    let tx = Transaction::create(
+        vec![action.clone()],
+        Delta::Witness(DeltaWitness::from_bytes(&one).unwrap()),
+    )
+    .generate_delta_proof_with_mode(DeltaProofMode::Opening)
+    .unwrap();
+    assert_eq!(tx.delta_proof.mode(), Some(DeltaProofMode::Opening));
+    assert!(tx.verify_actions(&[]).is_ok());
+    assert_eq!(
+        tx.delta_proof
+            .is_balanced_with(std::slice::from_ref(&action)),
+        Ok(true)
+    );
+    assert_eq!(
+        tx.delta_proof.is_balanced_with(&[action.clone(), action]),
+        Ok(false)
+    );
+
+    let ctx = VerificationContext {
+        delta_proof_mode: Some(DeltaProofMode::Signature),
+        ..Default::default()
+    };
+    assert_eq!(
+        tx.clone().verify_with_context(&ctx),
+        Err(RejectionReason::DeltaMismatch)
+    );
+    // The delta is accepted; the units have no proofs.
+    let ctx = VerificationContext {
+        delta_proof_mode: Some(DeltaProofMode::Opening),
+        ..Default::default()
+    };
+    assert!(!matches!(
+        tx.verify_with_context(&ctx),
+        Ok(()) | Err(RejectionReason::DeltaMismatch)
+    ));
+}
+
+#[test]
+fn test_verification_policy_hooks() {
+    use crate::{
+        action::{unproven_action, Action},
+        compliance::ComplianceWitness,
+        delta_proof::DeltaWitness,
+        logic_instance::ExpirableBlob,
+        logic_proof::LogicVerifierInputs,
+    };
+
+    #[derive(Debug)]
+    struct MaxBlobWords(usize);
+
+    impl VerificationPolicy for MaxBlobWords {
+        fn on_app_data(
+            &self,
+            _ctx: &VerificationContext,
+            _action: usize,
+            _tag: &Digest,
+            app_data: &AppData,
+        ) -> Result<(), RejectionReason> {
+            if app_data
+                .to_map()
+                .values()
+                .flatten()
+                .any(|blob| blob.blob.len() > self.0)
+            {
+                return Err(RejectionReason::PolicyViolation);
+            }
+            Ok(())
+        }
+    }
+
+    // An action without proofs, balanced with rcv = 1.
+    let instance = ComplianceWitness::default().constrain().unwrap();
+    let input = |tag, verifying_key| LogicVerifierInputs {
+        tag,
+        verifying_key,
+        app_data: AppData::default(),
+        proof: None,
+        app_data_by_digest: false,
+        binds_image_id: false,
+        logic_ref_opening: None,
+    };
+    let mut created = input(instance.created_commitment, instance.created_logic_ref);
+    created.app_data.add_application_payload(ExpirableBlob {
+        blob: vec![0u32; 8],
+        deletion_criterion: 0,
+    });
+    let mut logic_verifier_inputs = vec![
+        input(instance.consumed_nullifier, instance.consumed_logic_ref),
+        created,
+    ];
+    logic_verifier_inputs.sort_by_key(|input| <[u8; 32]>::from(input.tag));
+    let action = Action {
+        logic_verifier_inputs,
+        ..unproven_action(&ComplianceWitness::default())
+    };
+    let mut one = [0u8; 32];
+    one[31] = 1;
+    let tx = Transaction::create(
+        vec![action],
+        Delta::Witness(DeltaWitness::from_bytes(&one).unwrap()),
+    )
+    .generate_delta_proof()
+    .unwrap();
+
+    let ctx = VerificationContext::new().with_policy(MaxBlobWords(4));
+    assert_eq!(
+        tx.clone().verify_with_context(&ctx),
+        Err(RejectionReason::PolicyViolation)
+    );
+    // The policy accepts, so verification goes on to the missing proofs.
+    let ctx = VerificationContext::new().with_policy(MaxBlobWords(8));
+    assert_eq!(
+        tx.verify_with_context(&ctx),
+        Err(RejectionReason::InvalidComplianceProof)
+    );
+}
@@ -0,0 +1,97 @@
+//! Versioning of the witness layout shared by hosts and guests.
+//!
+//! Witnesses are serialized by the host and deserialized inside the zkVM, so a
+//! host built against a different witness layout than a guest ELF only fails
+//! deep inside the guest with a deserialization panic. Guests declare the
+//! schema they were built with using [`declare_witness_schema!`], which embeds
+//! a tagged version in the ELF (and so in its image id), and provers check it
+//! before proving. ELFs built before versioning carry no tag and are accepted.
+
+use crate::error::ArmError;
+
+/// The version of the witness layouts of this crate. Bump it whenever the
+/// serialized layout of a guest-consumed witness changes.
+pub const WITNESS_SCHEMA_VERSION: u32 = 1;
+
+/// The tag preceding the big-endian schema version in guest ELFs.
+pub const WITNESS_SCHEMA_TAG: &[u8; 20] = b"ARM_WITNESS_SCHEMA_V";
+
+/// Length of the marker embedded by [`declare_witness_schema!`].
+pub const WITNESS_SCHEMA_MARKER_LEN: usize = WITNESS_SCHEMA_TAG.len() + 4;
+
+/// Builds the marker embedded in guest ELFs for the given version.
+pub const fn witness_schema_marker(version: u32) -> [u8; WITNESS_SCHEMA_MARKER_LEN] {
+    let mut marker = [0u8; WITNESS_SCHEMA_MARKER_LEN];
+    let mut i = 0;
+    while i < WITNESS_SCHEMA_TAG.len() {
+        marker[i] = WITNESS_SCHEMA_TAG[i];
+        i += 1;
+    }
+    let version = version.to_be_bytes();
+    let mut j = 0;
+    while j < 4 {
+        marker[i + j] = version[j];
+        j += 1;
+    }
+    marker
+}
+
+/// Embeds the witness schema version of this crate in a guest ELF. Invoke it
+/// once at the top level of the guest's `main.rs`.
+#[macro_export]
+macro_rules! declare_witness_schema {
+    () => {
+        #[used]
+        #[no_mangle]
+        pub static ARM_WITNESS_SCHEMA: [u8; $crate::witness_schema::WITNESS_SCHEMA_MARKER_LEN] =
+            $crate::witness_schema::witness_schema_marker(
+                $crate::witness_schema::WITNESS_SCHEMA_VERSION,
+            );
+    };
+}
+
+/// Reads the witness schema version declared by a guest ELF, if any.
+pub fn guest_schema_version(elf: &[u8]) -> Option<u32> {
+    let start = elf
+        .windows(WITNESS_SCHEMA_TAG.len())
+        .position(|window| window == WITNESS_SCHEMA_TAG)?
+        + WITNESS_SCHEMA_TAG.len();
+    let version = elf.get(start..start + 4)?;
+    Some(u32::from_be_bytes(version.try_into().ok()?))
+}
+
+/// Checks that a guest ELF expects the witness schema of this crate.
+pub fn check_witness_schema(elf: &[u8]) -> Result<(), ArmError> {
+    match guest_schema_version(elf) {
+        Some(guest) if guest != WITNESS_SCHEMA_VERSION => Err(ArmError::WitnessSchemaMismatch {
+            host: WITNESS_SCHEMA_VERSION,
+            guest,
+        }),
+        _ => Ok(()),
+    }
+}
+
+#[test]
+fn test_witness_schema() {
+    let elf = [
+        &b"\x7fELF..."[..],
+        &witness_schema_marker(WITNESS_SCHEMA_VERSION)[..],
+        &b"..."[..],
+    ]
+    .concat();
+    assert_eq!(guest_schema_version(&elf), Some(WITNESS_SCHEMA_VERSION));
+    assert!(check_witness_schema(&elf).is_ok());
+
+    let drifted = witness_schema_marker(WITNESS_SCHEMA_VERSION + 1);
+    assert_eq!(
+        check_witness_schema(&drifted),
+        Err(ArmError::WitnessSchemaMismatch {
+            host: WITNESS_SCHEMA_VERSION,
+            guest: WITNESS_SCHEMA_VERSION + 1,
+        })
+    );
+
+    // Unversioned ELFs are accepted.
+    assert_eq!(guest_schema_version(b"\x7fELF"), None);
+    assert!(check_witness_schema(b"\x7fELF").is_ok());
+}
@@ -0,0 +1,147 @@
+//! Equivalence of the host-side constraints and the guests of the shipped
+//! circuits.
+//!
+//! Every witness below is run through its native `constrain()` and through
+//! the pinned guest in the executor, and both must produce the same
+//! instance, byte for byte. The aggregation guests run on the instances of
+//! an unproven transaction and must commit the aggregation instance its
+//! verifier expects. Execution doesn't depend on `RISC0_DEV_MODE`.
+//!
+//! The other shipped guests aren't covered:
+//! - the test logic guest, pinned in `arm_tests/arm_test_app`, is built
+//!   against the published `anoma-rm-risc0` release rather than this tree,
+//!   as is the test app;
+//! - the example circuits (shielded swap, voting, streaming payment and
+//!   lending) ship no pinned ELF, only sources to build with
+//!   `cargo risczero build`; their guests only call the example crate's
+//!   `constrain()`;
+//! - the private and multi compliance guests ship no pinned ELF either.
+
+#![cfg(all(feature = "transaction", feature = "prove"))]
+
+#[cfg(feature = "aggregation")]
+use anoma_rm_risc0::{
+    action::Action,
+    action_tree::MerkleTree,
+    compliance_unit::ComplianceUnit,
+    delta_proof::DeltaWitness,
+    logic_proof::{LogicVerifier, PaddingResourceLogic},
+    resource_logic::LogicCircuit,
+    transaction::{Delta, Transaction},
+    utils::words_to_bytes,
+};
+use anoma_rm_risc0::{
+    compliance::ComplianceWitness, constants::COMPLIANCE_PK, error::ArmError,
+    logic_proof::LogicProver, merkle_path::MerklePath, nullifier_key::NullifierKey,
+    padding::PaddingUnit, prelude::Digest, proving_system::check_guest_equivalence,
+    resource::Resource,
+};
+
+// Compliance witnesses covering the default unit, random padding units and a
+// unit consuming a resource from a commitment tree path.
+fn compliance_witnesses() -> Vec<ComplianceWitness> {
+    let mut witnesses = vec![ComplianceWitness::default()];
+    for _ in 0..2 {
+        witnesses.push(PaddingUnit::new().unwrap().witness);
+    }
+
+    let (nf_key, nk_commitment) = NullifierKey::random_pair();
+    let consumed = Resource {
+        quantity: 5,
+        nk_commitment,
+        ..Default::default()
+    };
+    let mut created = consumed;
+    created.set_nonce_from_nf(&consumed, &nf_key).unwrap();
+    let path = MerklePath::from_path(&[
+        (Digest::from_bytes([1u8; 32]), false),
+        (Digest::from_bytes([2u8; 32]), true),
+    ]);
+    witnesses.push(ComplianceWitness::from_resources_with_path(
+        consumed, nf_key, path, created,
+    ));
+    witnesses
+}
+
+#[test]
+fn compliance_guest_matches_host() {
+    for witness in compliance_witnesses() {
+        let instance = witness.constrain().unwrap();
+        check_guest_equivalence(COMPLIANCE_PK, &witness, &instance).unwrap();
+    }
+}
+
+#[test]
+fn trivial_logic_guest_matches_host() {
+    for unit in [PaddingUnit::new().unwrap(), PaddingUnit::new().unwrap()] {
+        let root = Digest::from_bytes([7u8; 32]);
+        for logic in unit.logics(root) {
+            logic.check_guest().unwrap();
+        }
+    }
+}
+
+#[test]
+fn divergent_instance_is_detected() {
+    let witness = ComplianceWitness::default();
+    let mut instance = witness.constrain().unwrap();
+    instance.created_commitment = Digest::from_bytes([3u8; 32]);
+    assert!(matches!(
+        check_guest_equivalence(COMPLIANCE_PK, &witness, &instance),
+        Err(ArmError::GuestDivergence)
+    ));
+}
+
+// An unproven transaction of padding units. The aggregation guests verify
+// its compliance and logic instances by their claims.
+#[cfg(feature = "aggregation")]
+fn unproven_padding_transaction(units: usize) -> Transaction {
+    let padding_units: Vec<_> = (0..units).map(|_| PaddingUnit::new().unwrap()).collect();
+    let root = MerkleTree::from_units(padding_units.iter().map(|unit| {
+        let [nullifier, commitment] = unit.tags().unwrap();
+        (nullifier, commitment)
+    }))
+    .root()
+    .unwrap();
+
+    let mut compliance_units = Vec::new();
+    let mut logic_verifiers = Vec::new();
+    for unit in padding_units.iter() {
+        let instance = unit.witness.constrain().unwrap();
+        compliance_units.push(ComplianceUnit::unproven(&instance).unwrap());
+        for logic in unit.logics(root) {
+            let instance = logic.witness().constrain().unwrap();
+            logic_verifiers.push(LogicVerifier {
+                proof: None,
+                instance: words_to_bytes(&risc0_zkvm::serde::to_vec(&instance).unwrap()).to_vec(),
+                verifying_key: PaddingResourceLogic::verifying_key(),
+            });
+        }
+    }
+
+    let rcvs: Vec<_> = padding_units.iter().map(|unit| unit.witness.rcv).collect();
+    Transaction::create(
+        vec![Action::new(compliance_units, logic_verifiers).unwrap()],
+        Delta::Witness(DeltaWitness::from_rcvs(&rcvs).unwrap()),
+    )
+}
+
+#[cfg(feature = "aggregation")]
+#[test]
+fn batch_aggregation_guest_matches_host() {
+    use anoma_rm_risc0::aggregation::batch::BatchAggregation;
+
+    for units in [1, 2] {
+        BatchAggregation::check_guest(&unproven_padding_transaction(units)).unwrap();
+    }
+}
+
+#[cfg(feature = "aggregation")]
+#[test]
+fn sequential_aggregation_guest_matches_host() {
+    use anoma_rm_risc0::aggregation::sequential::SequentialAggregation;
+
+    for units in [1, 2] {
+        SequentialAggregation::check_guest(&unproven_padding_transaction(units)).unwrap();
+    }
+}
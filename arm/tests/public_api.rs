@@ -0,0 +1,127 @@
+//! Semver guards of the public API.
+//!
+//! The guards below pin the shape of the types downstream code uses most:
+//! they stop compiling when a field or a signature changes, so breaking
+//! changes are noticed before a release.
+//!
+//! `public_api_snapshot` additionally compares the whole public API with
+//! `tests/public-api.txt`, which covers the default features. It requires
+//! `cargo-public-api` and a nightly toolchain, so it's ignored by default:
+//!
+//! ```text
+//! cargo install cargo-public-api
+//! cargo test -p anoma-rm-risc0 --test public_api -- --ignored
+//! ```
+//!
+//! Set `UPDATE_PUBLIC_API=1` to write the snapshot after an intended change.
+
+#![cfg(feature = "transaction")]
+
+use anoma_rm_risc0::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{path::Path, process::Command};
+
+const SNAPSHOT: &str = "tests/public-api.txt";
+
+#[test]
+fn resource_fields_are_stable() {
+    let Resource {
+        logic_ref,
+        label_ref,
+        quantity,
+        value_ref,
+        is_ephemeral,
+        nonce,
+        nk_commitment,
+        rand_seed,
+    } = Resource::default();
+    let _: (Digest, Digest, u128, Digest, bool) =
+        (logic_ref, label_ref, quantity, value_ref, is_ephemeral);
+    let _: ([u8; 32], NullifierKeyCommitment, [u8; 32]) = (nonce, nk_commitment, rand_seed);
+
+    let _: fn(&Resource) -> Digest = Resource::commitment;
+    let _: fn(&Resource, &NullifierKey) -> Result<Digest, ArmError> = Resource::nullifier;
+}
+
+#[test]
+fn transaction_api_is_stable() {
+    let tx = Transaction::create(
+        vec![],
+        Delta::Witness(DeltaWitness::from_bytes(&[1u8; 32]).unwrap()),
+    );
+    let Transaction {
+        actions,
+        delta_proof,
+        expected_balance,
+        aggregation_proof,
+        valid_until,
+        provenance,
+        tags_root,
+    } = tx;
+    let _: (Vec<Action>, Delta) = (actions, delta_proof);
+    let _: (Option<Vec<u8>>, Option<Vec<u8>>) = (expected_balance, aggregation_proof);
+    let _ = (valid_until, provenance, tags_root);
+
+    let _: fn(Vec<Action>, Delta) -> Transaction = Transaction::create;
+    let _: fn(Transaction, Transaction) -> Transaction = Transaction::compose;
+    let _: fn(Transaction) -> Result<Transaction, ArmError> = Transaction::generate_delta_proof;
+    let _: fn(Transaction) -> Result<(), ArmError> = Transaction::verify;
+    let _: fn(Transaction, &VerificationContext) -> Result<(), RejectionReason> =
+        Transaction::verify_with_context;
+    let _: fn(&Transaction) -> Result<Vec<u8>, ArmError> = Transaction::get_delta_msg;
+}
+
+#[test]
+fn logic_prover_api_is_stable() {
+    #[derive(Clone, Default, Serialize, Deserialize)]
+    struct Guard(());
+
+    impl LogicProver for Guard {
+        type Witness = ();
+
+        fn proving_key() -> &'static [u8] {
+            &[]
+        }
+
+        fn verifying_key() -> Digest {
+            Digest::default()
+        }
+
+        fn witness(&self) -> &Self::Witness {
+            &self.0
+        }
+    }
+
+    assert_eq!(Guard::verifying_key_as_bytes(), vec![0u8; 32]);
+    #[cfg(feature = "prove")]
+    {
+        let _: fn(&Guard, ProofType) -> Result<LogicVerifier, ArmError> = Guard::prove;
+    }
+}
+
+#[test]
+#[ignore = "requires cargo-public-api and a nightly toolchain"]
+fn public_api_snapshot() {
+    let output = Command::new(env!("CARGO"))
+        .args(["public-api", "--simplified", "-p", env!("CARGO_PKG_NAME")])
+        .output()
+        .expect("failed to run cargo public-api");
+    assert!(
+        output.status.success(),
+        "cargo public-api failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let api = String::from_utf8(output.stdout).unwrap();
+
+    let snapshot = Path::new(env!("CARGO_MANIFEST_DIR")).join(SNAPSHOT);
+    if std::env::var_os("UPDATE_PUBLIC_API").is_some() {
+        std::fs::write(&snapshot, api).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&snapshot)
+        .unwrap_or_else(|_| panic!("missing {SNAPSHOT}, run with UPDATE_PUBLIC_API=1"));
+    assert!(
+        api == expected,
+        "the public API changed, review the diff and run with UPDATE_PUBLIC_API=1"
+    );
+}
@@ -0,0 +1,108 @@
+//! Build-script helpers embedding guest ELFs and image ids into the ARM crate.
+//!
+//! Methods crates call [`embed_guest!`] from their `build.rs` instead of
+//! `risc0_build::embed_methods()`. When `ARM_EMBED_GUESTS=1` is set, the named
+//! guests are built reproducibly in docker and copied to `arm/elfs/<name>.bin`
+//! along with their image id in `arm/elfs/<name>.id`, which the ARM constants
+//! read at compile time. Otherwise the guests are built as usual and the
+//! pinned ELFs are left untouched, so local builds can't change the image ids
+//! by accident.
+
+use std::{collections::HashMap, env, fs, path::PathBuf};
+
+use risc0_build::{DockerOptionsBuilder, GuestListEntry, GuestOptionsBuilder};
+
+/// Opts in to overwriting the ELFs and image ids in `arm/elfs`.
+pub const EMBED_GUESTS_ENV: &str = "ARM_EMBED_GUESTS";
+
+/// Overrides the destination directory, `arm/elfs` by default.
+pub const ELFS_DIR_ENV: &str = "ARM_ELFS_DIR";
+
+/// Builds the methods of the calling crate and embeds the named guests into
+/// the ARM crate, e.g. `arm_build::embed_guest!("compliance-guest");`.
+#[macro_export]
+macro_rules! embed_guest {
+    ($($name:literal),+ $(,)?) => {{
+        let names = [$($name),+];
+        let guests = $crate::embed_methods(&names);
+        for name in names {
+            $crate::embed(&guests, name);
+        }
+    }};
+}
+
+/// Builds the methods of the calling crate, in docker for the named guest
+/// packages if enabled by [`EMBED_GUESTS_ENV`].
+pub fn embed_methods(names: &[&str]) -> Vec<GuestListEntry> {
+    if !embedding_enabled() {
+        return risc0_build::embed_methods();
+    }
+
+    // The guests depend on the ARM crate, so the docker context is the
+    // repository root.
+    let docker = DockerOptionsBuilder::default()
+        .root_dir(repo_root())
+        .build()
+        .expect("Failed to build the docker options");
+    let options = GuestOptionsBuilder::default()
+        .use_docker(docker)
+        .build()
+        .expect("Failed to build the guest options");
+    risc0_build::embed_methods_with_options(
+        names
+            .iter()
+            .map(|name| (*name, options.clone()))
+            .collect::<HashMap<_, _>>(),
+    )
+}
+
+/// Embeds the guest `name` among `guests` into the ARM crate, if enabled by
+/// [`EMBED_GUESTS_ENV`].
+pub fn embed(guests: &[GuestListEntry], name: &str) {
+    println!("cargo:rerun-if-env-changed={EMBED_GUESTS_ENV}");
+    println!("cargo:rerun-if-env-changed={ELFS_DIR_ENV}");
+
+    let guest = guests
+        .iter()
+        .find(|guest| guest.name == name)
+        .unwrap_or_else(|| panic!("unknown guest `{name}`"));
+
+    if !embedding_enabled() {
+        return;
+    }
+
+    let dir = elfs_dir();
+    fs::create_dir_all(&dir).expect("Failed to create the ELF directory");
+    write_if_changed(dir.join(format!("{name}.bin")), &guest.elf);
+    write_if_changed(
+        dir.join(format!("{name}.id")),
+        format!("{}\n", to_hex(guest.image_id.as_bytes())).as_bytes(),
+    );
+}
+
+fn embedding_enabled() -> bool {
+    env::var(EMBED_GUESTS_ENV).as_deref() == Ok("1")
+}
+
+fn repo_root() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../..")
+}
+
+fn elfs_dir() -> PathBuf {
+    env::var_os(ELFS_DIR_ENV)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| repo_root().join("arm/elfs"))
+}
+
+// Leaves unchanged files alone, so their mtime doesn't trigger rebuilds.
+fn write_if_changed(path: PathBuf, contents: &[u8]) {
+    if fs::read(&path).is_ok_and(|current| current == contents) {
+        return;
+    }
+    fs::write(&path, contents)
+        .unwrap_or_else(|err| panic!("Failed to write {}: {err}", path.display()));
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
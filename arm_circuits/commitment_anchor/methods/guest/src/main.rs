@@ -0,0 +1,15 @@
+use risc0_zkvm::guest::env;
+
+use anoma_rm_risc0::commitment_tree::AnchorWitness;
+
+///  The commitment anchor circuit.
+fn main() {
+    // Read the witness.
+    let witness: AnchorWitness = env::read();
+
+    // Append the commitments to the frontier.
+    let instance = witness.constrain().unwrap();
+
+    // The output.
+    env::commit(&instance);
+}
@@ -0,0 +1,14 @@
+use anoma_rm_risc0::compliance::ComplianceWitness;
+use risc0_zkvm::guest::env;
+
+// Profiling-only variant of the compliance guest skipping the Merkle path
+// check. Its proofs never verify against the compliance verifying key.
+pub fn main() {
+    let compliance_witness: ComplianceWitness = env::read();
+
+    let compliance_instance = compliance_witness
+        .constrain_without_merkle_check()
+        .unwrap();
+
+    env::commit(&compliance_instance);
+}
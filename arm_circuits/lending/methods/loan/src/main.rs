@@ -0,0 +1,3 @@
+use anoma_rm_risc0_lending::LoanWitness;
+
+anoma_rm_risc0::arm_logic_main!(LoanWitness);
@@ -0,0 +1,15 @@
+use risc0_zkvm::guest::env;
+
+use anoma_rm_risc0::compliance::MultiComplianceWitness;
+
+///  The variable-arity compliance circuit.
+fn main() {
+    // Read the witness.
+    let witness: MultiComplianceWitness = env::read();
+
+    // Check the consumed and created resources.
+    let instance = witness.constrain().unwrap();
+
+    // The output.
+    env::commit(&instance);
+}
@@ -0,0 +1,15 @@
+use risc0_zkvm::guest::env;
+
+use anoma_rm_risc0::compliance::PrivateComplianceWitness;
+
+///  The compliance circuit committing to the logic refs.
+fn main() {
+    // Read the witness.
+    let witness: PrivateComplianceWitness = env::read();
+
+    // Check the resources and commit to their logic refs.
+    let instance = witness.constrain().unwrap();
+
+    // The output.
+    env::commit(&instance);
+}
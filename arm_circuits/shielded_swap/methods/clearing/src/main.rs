@@ -0,0 +1,3 @@
+use anoma_rm_risc0_shielded_swap::ClearingWitness;
+
+anoma_rm_risc0::arm_logic_main!(ClearingWitness);
@@ -0,0 +1,3 @@
+use anoma_rm_risc0_streaming_payment::ClaimWitness;
+
+anoma_rm_risc0::arm_logic_main!(ClaimWitness);
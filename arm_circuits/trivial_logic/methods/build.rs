@@ -1,3 +1,3 @@
 fn main() {
-    risc0_build::embed_methods();
+    arm_build::embed_guest!("trivial-logic-guest");
 }
@@ -0,0 +1,169 @@
+//! A local devnet simulating a settlement layer, for integration tests.
+//!
+//! A [`Devnet`] plays the role of the protocol adapter: it verifies the
+//! transactions it's given against the roots of its commitment tree, rejects
+//! nullifiers that were already revealed, and settles the accepted
+//! transactions by appending their commitments to the tree and recording
+//! their nullifiers. Tests can drive it directly, or through the HTTP/JSON
+//! API of a [`DevnetServer`] with a [`DevnetClient`], like they would a real
+//! settlement layer.
+
+pub mod server;
+
+pub use server::{DevnetClient, DevnetError, DevnetServer};
+
+use anoma_rm_risc0::{
+    commitment_tree::{CommitmentTree, MAX_TREE_DEPTH},
+    compliance::INITIAL_ROOT,
+    error::ArmError,
+    merkle_path::MerklePath,
+    transaction::Transaction,
+    verification::{RejectionReason, VerificationContext},
+    Digest,
+};
+use std::collections::{HashMap, HashSet};
+
+/// The state of the simulated settlement layer.
+#[derive(Clone, Debug)]
+pub struct Devnet {
+    tree: CommitmentTree,
+    positions: HashMap<Digest, u64>,
+    roots: HashSet<Digest>,
+    nullifiers: HashSet<Digest>,
+    context: VerificationContext,
+}
+
+impl Default for Devnet {
+    fn default() -> Self {
+        Devnet::with_context(VerificationContext::new())
+    }
+}
+
+impl Devnet {
+    /// Creates a devnet with an empty commitment tree.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a devnet verifying transactions with the given context, e.g.
+    /// to only accept some logics. Its known roots are replaced by the roots
+    /// of the devnet.
+    pub fn with_context(context: VerificationContext) -> Self {
+        let tree = CommitmentTree::new();
+        // Ephemeral resources are consumed against the initial root
+        let roots = HashSet::from([*INITIAL_ROOT, tree.root()]);
+        Devnet {
+            tree,
+            positions: HashMap::new(),
+            roots,
+            nullifiers: HashSet::new(),
+            context,
+        }
+    }
+
+    /// Verifies and settles a transaction, returning the new root of the
+    /// commitment tree.
+    pub fn submit(&mut self, tx: Transaction) -> Result<Digest, RejectionReason> {
+        let mut nullifiers = Vec::new();
+        let mut commitments = Vec::new();
+        for unit in tx.actions.iter().flat_map(|a| a.get_compliance_units()) {
+            let instance = unit
+                .get_instance()
+                .map_err(|_| RejectionReason::Malformed)?;
+            nullifiers.push(instance.consumed_nullifier);
+            commitments.push(instance.created_commitment);
+        }
+        if nullifiers.iter().any(|nf| self.nullifiers.contains(nf)) {
+            return Err(RejectionReason::DuplicateNullifier);
+        }
+
+        let mut ctx = self.context.clone();
+        ctx.known_roots = Some(self.roots.clone());
+        tx.verify_with_context(&ctx)?;
+
+        if self.tree.size() + commitments.len() as u64 > 1 << MAX_TREE_DEPTH {
+            return Err(RejectionReason::Oversize);
+        }
+        for commitment in commitments {
+            // The capacity of the tree was checked above
+            let position = self.tree.append(commitment).unwrap();
+            self.positions.entry(commitment).or_insert(position);
+        }
+        let root = self.tree.root();
+        self.nullifiers.extend(nullifiers);
+        self.roots.insert(root);
+        Ok(root)
+    }
+
+    /// Returns the current root of the commitment tree.
+    pub fn root(&self) -> Digest {
+        self.tree.root()
+    }
+
+    /// Returns whether the root is a current or past root of the commitment
+    /// tree.
+    pub fn is_known_root(&self, root: &Digest) -> bool {
+        self.roots.contains(root)
+    }
+
+    /// Returns the number of commitments in the tree.
+    pub fn size(&self) -> u64 {
+        self.tree.size()
+    }
+
+    /// Returns the path of a commitment to the current root.
+    pub fn merkle_path(&self, commitment: &Digest) -> Result<MerklePath, ArmError> {
+        let position = self
+            .positions
+            .get(commitment)
+            .ok_or(ArmError::InvalidLeaf)?;
+        self.tree.witness(*position)
+    }
+
+    /// Returns the commitment tree.
+    pub fn commitment_tree(&self) -> &CommitmentTree {
+        &self.tree
+    }
+
+    /// Returns whether the nullifier was revealed by a settled transaction.
+    pub fn is_spent(&self, nullifier: &Digest) -> bool {
+        self.nullifiers.contains(nullifier)
+    }
+}
+
+#[cfg(test)]
+fn padding_transaction() -> Transaction {
+    use anoma_rm_risc0::{padding::padding_action, proving_system::ProofType, transaction::Delta};
+
+    let (action, delta_witness) = padding_action(1, ProofType::Succinct).unwrap();
+    Transaction::create(vec![action], Delta::Witness(delta_witness))
+        .generate_delta_proof()
+        .unwrap()
+}
+
+#[test]
+fn test_devnet_submit() {
+    let mut devnet = Devnet::new();
+    let tx = padding_transaction();
+    let instance = tx.actions[0].get_compliance_units()[0]
+        .get_instance()
+        .unwrap();
+
+    let root = devnet.submit(tx.clone()).unwrap();
+    assert_eq!(devnet.root(), root);
+    assert_eq!(devnet.size(), 1);
+    assert!(devnet.is_known_root(&root));
+    assert!(devnet.is_spent(&instance.consumed_nullifier));
+    let path = devnet.merkle_path(&instance.created_commitment).unwrap();
+    assert_eq!(path.root(&instance.created_commitment), root);
+
+    // Replays are double spends.
+    assert_eq!(devnet.submit(tx), Err(RejectionReason::DuplicateNullifier));
+    assert_eq!(devnet.size(), 1);
+
+    // Only roots of the devnet are accepted.
+    let mut ctx = VerificationContext::new();
+    ctx.known_roots = Some(HashSet::new());
+    let mut devnet = Devnet::with_context(ctx);
+    assert!(devnet.submit(padding_transaction()).is_ok());
+}
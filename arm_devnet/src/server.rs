@@ -0,0 +1,364 @@
+//! The HTTP/JSON API of a devnet.
+//!
+//! A [`DevnetServer`] serves a [`Devnet`] on a background thread:
+//!
+//! - `POST /transactions` submits a JSON-encoded [`Transaction`] and returns
+//!   the new root, or the code and message of the [`RejectionReason`] with
+//!   status 422;
+//! - `GET /root` returns the current root and size of the commitment tree;
+//! - `GET /paths/<commitment>` returns the [`MerklePath`] of a commitment to
+//!   the current root;
+//! - `GET /nullifiers/<nullifier>` returns whether a nullifier is spent.
+//!
+//! Digests are hex-encoded. Each request is served on its own connection, so
+//! any HTTP client works; [`DevnetClient`] is a minimal one for tests.
+
+use crate::Devnet;
+use anoma_rm_risc0::{
+    digest_encoding::DigestEncoding, merkle_path::MerklePath, transaction::Transaction,
+    verification::RejectionReason, Digest,
+};
+use serde_json::{json, Value};
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+use thiserror::Error;
+
+/// The maximum size of a request body, in bytes.
+pub const MAX_BODY_BYTES: usize = 64 << 20;
+
+/// How long the server waits on a read from a client before dropping the
+/// connection, so that a silent client can't stall the devnet.
+pub const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// An error talking to a devnet.
+#[derive(Debug, Error)]
+pub enum DevnetError {
+    /// The devnet can't be reached.
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    /// A request or response can't be encoded or decoded.
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    /// The devnet rejected the transaction.
+    #[error("Transaction rejected: {0}")]
+    Rejected(RejectionReason),
+    /// The devnet failed the request, e.g. because the commitment is unknown.
+    #[error("HTTP error {status}: {message}")]
+    Http { status: u16, message: String },
+    /// The response of the devnet can't be parsed.
+    #[error("Invalid response")]
+    InvalidResponse,
+}
+
+/// A devnet served over HTTP on a background thread. The server stops when
+/// it's dropped.
+pub struct DevnetServer {
+    addr: SocketAddr,
+    devnet: Arc<Mutex<Devnet>>,
+    shutdown: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl DevnetServer {
+    /// Serves the devnet on the address, e.g. `127.0.0.1:0` to pick a free
+    /// port.
+    pub fn spawn(devnet: Devnet, addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let devnet = Arc::new(Mutex::new(devnet));
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let handle = {
+            let devnet = devnet.clone();
+            let shutdown = shutdown.clone();
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if shutdown.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    // A broken connection only fails its own request
+                    if let Ok(stream) = stream {
+                        let _ = handle_connection(&devnet, stream);
+                    }
+                }
+            })
+        };
+
+        Ok(DevnetServer {
+            addr,
+            devnet,
+            shutdown,
+            handle: Some(handle),
+        })
+    }
+
+    /// Returns the address the server listens on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns a client of the server.
+    pub fn client(&self) -> DevnetClient {
+        DevnetClient::new(self.addr)
+    }
+
+    /// Locks the state of the devnet, e.g. to inspect it in tests.
+    pub fn devnet(&self) -> MutexGuard<'_, Devnet> {
+        lock(&self.devnet)
+    }
+}
+
+impl Drop for DevnetServer {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // Wake up the listener so that it sees the shutdown
+        let _ = TcpStream::connect(self.addr);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn lock(devnet: &Mutex<Devnet>) -> MutexGuard<'_, Devnet> {
+    devnet.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn handle_connection(devnet: &Mutex<Devnet>, stream: TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let (status, body) = match read_request(&mut reader)? {
+        Some((method, path, body)) => route(devnet, &method, &path, &body),
+        None => (400, json!({ "error": "malformed request" })),
+    };
+    write_response(stream, status, &body)
+}
+
+// Reads the method, path and body of a request, or `None` if the request
+// can't be parsed.
+fn read_request(reader: &mut impl BufRead) -> io::Result<Option<(String, String, Vec<u8>)>> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Ok(None);
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+
+    let mut content_length = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                match value.trim().parse() {
+                    Ok(len) if len <= MAX_BODY_BYTES => content_length = len,
+                    _ => return Ok(None),
+                }
+            }
+        }
+    }
+
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(Some((method, path, body)))
+}
+
+fn route(devnet: &Mutex<Devnet>, method: &str, path: &str, body: &[u8]) -> (u16, Value) {
+    let not_found = (404, json!({ "error": "not found" }));
+    let parse_digest = |hex: &str| Digest::from_hex(hex).ok();
+
+    match (method, path) {
+        ("POST", "/transactions") => {
+            let Ok(tx) = serde_json::from_slice::<Transaction>(body) else {
+                return rejection(RejectionReason::Malformed);
+            };
+            match lock(devnet).submit(tx) {
+                Ok(root) => (200, json!({ "root": root.to_hex() })),
+                Err(reason) => rejection(reason),
+            }
+        }
+        ("GET", "/root") => {
+            let devnet = lock(devnet);
+            (
+                200,
+                json!({ "root": devnet.root().to_hex(), "size": devnet.size() }),
+            )
+        }
+        ("GET", _) => {
+            if let Some(hex) = path.strip_prefix("/paths/") {
+                let Some(commitment) = parse_digest(hex) else {
+                    return (400, json!({ "error": "invalid commitment" }));
+                };
+                match lock(devnet).merkle_path(&commitment) {
+                    Ok(path) => (200, json!({ "path": path })),
+                    Err(_) => not_found,
+                }
+            } else if let Some(hex) = path.strip_prefix("/nullifiers/") {
+                let Some(nullifier) = parse_digest(hex) else {
+                    return (400, json!({ "error": "invalid nullifier" }));
+                };
+                (200, json!({ "spent": lock(devnet).is_spent(&nullifier) }))
+            } else {
+                not_found
+            }
+        }
+        _ => not_found,
+    }
+}
+
+fn rejection(reason: RejectionReason) -> (u16, Value) {
+    (
+        422,
+        json!({ "code": reason.code(), "error": reason.to_string() }),
+    )
+}
+
+fn write_response(mut stream: TcpStream, status: u16, body: &Value) -> io::Result<()> {
+    let body = body.to_string();
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        _ => "Unprocessable Entity",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+/// A blocking client of the devnet API.
+#[derive(Clone, Copy, Debug)]
+pub struct DevnetClient {
+    addr: SocketAddr,
+}
+
+impl DevnetClient {
+    /// Creates a client of the devnet served at the address.
+    pub fn new(addr: SocketAddr) -> Self {
+        DevnetClient { addr }
+    }
+
+    /// Submits a transaction, returning the new root of the commitment tree.
+    pub fn submit(&self, tx: &Transaction) -> Result<Digest, DevnetError> {
+        let body = serde_json::to_vec(tx)?;
+        let response = self.request("POST", "/transactions", &body)?;
+        digest_field(&response, "root")
+    }
+
+    /// Returns the current root of the commitment tree.
+    pub fn root(&self) -> Result<Digest, DevnetError> {
+        digest_field(&self.request("GET", "/root", &[])?, "root")
+    }
+
+    /// Returns the path of a commitment to the current root.
+    pub fn merkle_path(&self, commitment: &Digest) -> Result<MerklePath, DevnetError> {
+        let mut response = self.request("GET", &format!("/paths/{}", commitment.to_hex()), &[])?;
+        Ok(serde_json::from_value(response["path"].take())?)
+    }
+
+    /// Returns whether the nullifier is spent.
+    pub fn is_spent(&self, nullifier: &Digest) -> Result<bool, DevnetError> {
+        let response = self.request("GET", &format!("/nullifiers/{}", nullifier.to_hex()), &[])?;
+        response["spent"]
+            .as_bool()
+            .ok_or(DevnetError::InvalidResponse)
+    }
+
+    fn request(&self, method: &str, path: &str, body: &[u8]) -> Result<Value, DevnetError> {
+        let mut stream = TcpStream::connect(self.addr)?;
+        write!(
+            stream,
+            "{method} {path} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.addr,
+            body.len()
+        )?;
+        stream.write_all(body)?;
+        stream.flush()?;
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response)?;
+        let split = response
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .ok_or(DevnetError::InvalidResponse)?;
+        let status: u16 = std::str::from_utf8(&response[..split])
+            .ok()
+            .and_then(|head| head.split_whitespace().nth(1))
+            .and_then(|status| status.parse().ok())
+            .ok_or(DevnetError::InvalidResponse)?;
+        let body: Value = serde_json::from_slice(&response[split + 4..])?;
+
+        match status {
+            200 => Ok(body),
+            422 => Err(body["code"]
+                .as_u64()
+                .and_then(|code| RejectionReason::from_code(code as u8))
+                .map(DevnetError::Rejected)
+                .unwrap_or(DevnetError::InvalidResponse)),
+            _ => Err(DevnetError::Http {
+                status,
+                message: body["error"].as_str().unwrap_or_default().to_string(),
+            }),
+        }
+    }
+}
+
+fn digest_field(response: &Value, field: &str) -> Result<Digest, DevnetError> {
+    response[field]
+        .as_str()
+        .and_then(|hex| Digest::from_hex(hex).ok())
+        .ok_or(DevnetError::InvalidResponse)
+}
+
+#[test]
+fn test_devnet_server() {
+    let server = DevnetServer::spawn(Devnet::new(), "127.0.0.1:0").unwrap();
+    let client = server.client();
+    let tx = crate::padding_transaction();
+    let instance = tx.actions[0].get_compliance_units()[0]
+        .get_instance()
+        .unwrap();
+
+    assert_eq!(client.root().unwrap(), server.devnet().root());
+    assert!(!client.is_spent(&instance.consumed_nullifier).unwrap());
+    let root = client.submit(&tx).unwrap();
+    assert_eq!(client.root().unwrap(), root);
+    assert!(client.is_spent(&instance.consumed_nullifier).unwrap());
+    let path = client.merkle_path(&instance.created_commitment).unwrap();
+    assert_eq!(path.root(&instance.created_commitment), root);
+
+    assert!(matches!(
+        client.submit(&tx),
+        Err(DevnetError::Rejected(RejectionReason::DuplicateNullifier))
+    ));
+    assert!(matches!(
+        client.merkle_path(&Digest::default()),
+        Err(DevnetError::Http { status: 404, .. })
+    ));
+    assert!(matches!(
+        client.request("GET", "/paths/zz", &[]),
+        Err(DevnetError::Http { status: 400, .. })
+    ));
+
+    // A client that never sends its request times out instead of blocking
+    // the others.
+    let _silent = TcpStream::connect(server.addr()).unwrap();
+    assert_eq!(client.root().unwrap(), root);
+}
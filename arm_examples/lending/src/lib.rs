@@ -0,0 +1,253 @@
+//! Loans collateralized at an oracle price.
+//!
+//! A loan resource holds the collateral as its quantity, and its
+//! `value_ref` commits to the [`Loan`] terms: the borrower, the debt and
+//! the minimum collateral ratio. Its `label_ref` commits to the verifying
+//! key of the price oracle ([`loan_label`]), so every loan of the kind is
+//! valued by the same oracle.
+//!
+//! The loan logic ([`LoanWitness`]) only lets loans be created (opened, or
+//! adjusted by consuming the old loan) if the collateral covers the debt at
+//! the minimum ratio, at the price of an [`OracleAttestation`] of the
+//! [`PRICE_FEED`]. It publishes the time of the attestation, so verifiers
+//! reject stale prices with [`check_loan`].
+
+use anoma_rm_risc0::{
+    error::ArmError,
+    logic_instance::{AppData, ExpirableBlob, LogicInstance},
+    merkle_path::MerklePath,
+    nullifier_key::{NullifierKey, NullifierKeyCommitment},
+    resource::Resource,
+    resource_logic::LogicCircuit,
+    utils::hash_bytes,
+    Digest,
+};
+use anoma_rm_risc0_gadgets::{authority::AuthorityVerifyingKey, oracle::OracleAttestation};
+use serde::{Deserialize, Serialize};
+
+/// The feed pricing a unit of collateral in units of debt.
+pub const PRICE_FEED: &str = "COLLATERAL/DEBT";
+/// The fixed-point scale of [`PRICE_FEED`] prices.
+pub const PRICE_SCALE: u128 = 100_000_000;
+/// The scale of collateral ratios: ratios are in basis points.
+pub const RATIO_SCALE: u128 = 10_000;
+
+const LOAN_LABEL_DOMAIN: &[u8] = b"ARM_LENDING_ORACLE";
+
+/// The label of loans valued by the given oracle.
+pub fn loan_label(oracle_vk: &AuthorityVerifyingKey) -> Digest {
+    hash_bytes(&[LOAN_LABEL_DOMAIN, &oracle_vk.to_bytes()[..]].concat())
+}
+
+/// The terms of a loan.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Loan {
+    /// The owner of the loan.
+    pub borrower: NullifierKeyCommitment,
+    /// The borrowed quantity.
+    pub debt: u128,
+    /// The minimum value of the collateral, relative to the debt, in basis
+    /// points (e.g. 15000 for 150%).
+    pub min_ratio: u128,
+    /// Blinds the loan commitment.
+    pub salt: [u8; 32],
+}
+
+impl Loan {
+    /// Computes the commitment to the terms stored in the loan's `value_ref`.
+    pub fn commitment(&self) -> Digest {
+        let bytes = [
+            self.borrower.as_bytes(),
+            &self.debt.to_be_bytes()[..],
+            &self.min_ratio.to_be_bytes()[..],
+            &self.salt,
+        ]
+        .concat();
+        hash_bytes(&bytes)
+    }
+
+    /// Returns whether `collateral` covers the debt at the minimum ratio at
+    /// the given price.
+    pub fn is_collateralized(&self, collateral: u128, price: u128) -> bool {
+        let value = collateral.saturating_mul(price) / PRICE_SCALE;
+        value.saturating_mul(RATIO_SCALE) >= self.debt.saturating_mul(self.min_ratio)
+    }
+
+    /// Creates the loan resource holding `collateral`, valued by the oracle.
+    pub fn resource(
+        &self,
+        loan_logic_ref: Digest,
+        oracle_vk: &AuthorityVerifyingKey,
+        collateral: u128,
+        nonce: Digest,
+    ) -> Resource {
+        Resource::create(
+            loan_logic_ref,
+            loan_label(oracle_vk),
+            collateral,
+            self.commitment(),
+            false,
+            nonce,
+            self.borrower,
+        )
+    }
+}
+
+/// The logic witness of loan resources.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct LoanWitness {
+    /// The resource.
+    pub resource: Resource,
+    /// Indicates whether the resource is consumed or created.
+    pub is_consumed: bool,
+    /// The nullifier key of the resource.
+    pub nf_key: NullifierKey,
+    /// The path from the resource's tag to the action tree root.
+    pub existence_path: MerklePath,
+    /// The terms opening the resource's `value_ref`.
+    pub loan: Loan,
+    /// The oracle the resource's `label_ref` commits to.
+    pub oracle_vk: AuthorityVerifyingKey,
+    /// The price of the collateral (only checked when creating a loan).
+    pub price: OracleAttestation,
+}
+
+impl LoanWitness {
+    /// Creates the witness of a created loan, at the attested price.
+    pub fn created(
+        resource: Resource,
+        existence_path: MerklePath,
+        loan: Loan,
+        oracle_vk: AuthorityVerifyingKey,
+        price: OracleAttestation,
+    ) -> Self {
+        LoanWitness {
+            resource,
+            existence_path,
+            loan,
+            oracle_vk,
+            price,
+            ..Default::default()
+        }
+    }
+}
+
+impl LogicCircuit for LoanWitness {
+    fn constrain(&self) -> Result<LogicInstance, ArmError> {
+        let tag = self.resource.tag(self.is_consumed, &self.nf_key)?;
+        let root = self.existence_path.root(&tag);
+        let mut app_data = AppData::new();
+
+        assert!(!self.resource.is_ephemeral);
+        assert_eq!(self.resource.label_ref, loan_label(&self.oracle_vk));
+        assert_eq!(self.resource.value_ref, self.loan.commitment());
+
+        if !self.is_consumed {
+            // New loans must be collateralized at the attested price
+            let datum = self.price.verify(&self.oracle_vk, PRICE_FEED)?;
+            let price = datum.as_price().ok_or(ArmError::DeserializationError)?;
+            assert!(self.loan.is_collateralized(self.resource.quantity, price));
+
+            // The verifier checks the price is recent
+            let timestamp = datum.timestamp;
+            app_data.add_application_payload(ExpirableBlob {
+                blob: vec![(timestamp >> 32) as u32, timestamp as u32],
+                deletion_criterion: 1,
+            });
+        }
+
+        Ok(LogicInstance {
+            tag,
+            is_consumed: self.is_consumed,
+            root,
+            app_data,
+        })
+    }
+
+    fn is_ephemeral(&self) -> bool {
+        self.resource.is_ephemeral
+    }
+}
+
+/// Checks a loan logic instance against the verifier's current unix time
+/// (e.g. `VerificationContext::now`): the prices of created loans must be at
+/// most `max_age` seconds old.
+pub fn check_loan(instance: &LogicInstance, now: u64, max_age: u64) -> Result<(), ArmError> {
+    for blob in &instance.app_data.application_payload {
+        let [high, low] = blob.blob[..] else {
+            return Err(ArmError::DeserializationError);
+        };
+        let timestamp = (u64::from(high) << 32) | u64::from(low);
+        if timestamp > now || now - timestamp > max_age {
+            return Err(ArmError::StaleAttestation);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+use anoma_rm_risc0_gadgets::{
+    authority::AuthoritySigningKey,
+    oracle::{LocalOracle, OracleDatum, OracleSource},
+};
+
+#[cfg(test)]
+fn test_loan() -> (LocalOracle, Loan, Resource) {
+    let oracle = LocalOracle::new(AuthoritySigningKey::new());
+    let (_, borrower) = NullifierKey::random_pair();
+    let loan = Loan {
+        borrower,
+        debt: 1_000,
+        min_ratio: 15_000,
+        salt: [5u8; 32],
+    };
+    let resource = loan.resource(
+        Digest::default(),
+        &oracle.verifying_key(),
+        1_000,
+        Digest::default(),
+    );
+    (oracle, loan, resource)
+}
+
+#[test]
+fn test_loan_collateralized() {
+    let (mut oracle, loan, resource) = test_loan();
+    // 1000 units of collateral at 2 are worth 2000, 200% of the debt.
+    oracle.publish(OracleDatum::price(PRICE_FEED, 2 * PRICE_SCALE, 1_000));
+    let witness = LoanWitness::created(
+        resource,
+        MerklePath::default(),
+        loan,
+        oracle.verifying_key(),
+        oracle.fetch(PRICE_FEED).unwrap(),
+    );
+    let instance = witness.constrain().unwrap();
+    assert!(check_loan(&instance, 1_030, 60).is_ok());
+    assert_eq!(
+        check_loan(&instance, 1_100, 60),
+        Err(ArmError::StaleAttestation)
+    );
+
+    // Another oracle's price is rejected.
+    let mut other = witness;
+    other.price = OracleAttestation::sign(other.price.datum.clone(), &AuthoritySigningKey::new());
+    assert!(other.constrain().is_err());
+}
+
+#[test]
+#[should_panic]
+fn test_loan_rejects_undercollateralized() {
+    let (mut oracle, loan, resource) = test_loan();
+    // Worth 1400, 140% of the debt.
+    oracle.publish(OracleDatum::price(PRICE_FEED, 14 * PRICE_SCALE / 10, 1_000));
+    LoanWitness::created(
+        resource,
+        MerklePath::default(),
+        loan,
+        oracle.verifying_key(),
+        oracle.fetch(PRICE_FEED).unwrap(),
+    )
+    .constrain()
+    .unwrap();
+}
@@ -0,0 +1,424 @@
+//! A sealed-bid two-party swap.
+//!
+//! Each party creates an ephemeral intent resource whose `value_ref` commits
+//! to its [`Order`]; the order itself (including the limit price) only
+//! travels encrypted to the solver in the application payload. The solver
+//! consumes both intents together with a clearing resource whose logic
+//! ([`ClearingWitness`]) checks that the public clearing price crosses both
+//! limit prices. The intent logic ([`SwapIntentWitness`]) checks, when the
+//! intent is consumed, that the action creates a clearing resource of the
+//! order's clearing logic committing to the same clearing price, and that its
+//! limit is respected by that price.
+
+use anoma_rm_risc0::{
+    error::ArmError,
+    logic_instance::{AppData, ExpirableBlob, LogicInstance},
+    merkle_path::MerklePath,
+    nullifier_key::{NullifierKey, NullifierKeyCommitment},
+    resource::Resource,
+    resource_logic::LogicCircuit,
+    utils::{bytes_to_words, hash_bytes},
+    Digest,
+};
+use anoma_rm_risc0_gadgets::encryption::{Ciphertext, SecretKey};
+use k256::AffinePoint;
+use serde::{Deserialize, Serialize};
+
+/// The side of an order.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Side {
+    /// Buys the base asset, paying at most the limit price.
+    #[default]
+    Buy,
+    /// Sells the base asset, receiving at least the limit price.
+    Sell,
+}
+
+/// A sealed order.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Order {
+    /// The side of the order.
+    pub side: Side,
+    /// The limit price in quote units per base unit.
+    pub limit_price: u128,
+    /// The quantity of the base asset.
+    pub quantity: u128,
+    /// The logic of the clearing resource the order settles against.
+    pub clearing_logic_ref: Digest,
+    /// Blinds the order commitment.
+    pub salt: [u8; 32],
+}
+
+impl Order {
+    /// Computes the commitment to the order stored in the intent's `value_ref`.
+    pub fn commitment(&self) -> Digest {
+        let mut bytes = Vec::with_capacity(1 + 16 + 16 + 32 + 32);
+        bytes.push(self.side as u8);
+        bytes.extend_from_slice(&self.limit_price.to_be_bytes());
+        bytes.extend_from_slice(&self.quantity.to_be_bytes());
+        bytes.extend_from_slice(self.clearing_logic_ref.as_bytes());
+        bytes.extend_from_slice(&self.salt);
+        hash_bytes(&bytes)
+    }
+
+    /// Checks whether the order accepts the clearing price.
+    pub fn accepts(&self, clearing_price: u128) -> bool {
+        match self.side {
+            Side::Buy => clearing_price <= self.limit_price,
+            Side::Sell => clearing_price >= self.limit_price,
+        }
+    }
+
+    /// Encrypts the order to the solver.
+    pub fn seal(
+        &self,
+        solver_pk: &AffinePoint,
+        sender_sk: &SecretKey,
+        nonce: [u8; 12],
+    ) -> Result<Ciphertext, ArmError> {
+        let bytes = bincode::serialize(self).map_err(|_| ArmError::SerializationError)?;
+        Ciphertext::encrypt_with_nonce(&bytes, solver_pk, sender_sk, nonce)
+    }
+}
+
+/// Creates the ephemeral intent resource committing to the order.
+pub fn intent_resource(
+    intent_logic_ref: Digest,
+    pair_label: Digest,
+    order: &Order,
+    nonce: Digest,
+    nk_commitment: NullifierKeyCommitment,
+) -> Resource {
+    Resource::create(
+        intent_logic_ref,
+        pair_label,
+        order.quantity,
+        order.commitment(),
+        true,
+        nonce,
+        nk_commitment,
+    )
+}
+
+/// Computes the clearing price of two crossing orders (the midpoint of the
+/// limits), or `None` if they don't cross.
+pub fn clearing_price(buy: &Order, sell: &Order) -> Option<u128> {
+    if buy.side != Side::Buy || sell.side != Side::Sell || buy.quantity != sell.quantity {
+        return None;
+    }
+    if buy.limit_price < sell.limit_price {
+        return None;
+    }
+    Some(sell.limit_price + (buy.limit_price - sell.limit_price) / 2)
+}
+
+/// The logic witness of a swap intent.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct SwapIntentWitness {
+    /// The intent resource.
+    pub resource: Resource,
+    /// Indicates whether the intent is consumed (settled) or created.
+    pub is_consumed: bool,
+    /// The nullifier key of the intent.
+    pub nf_key: NullifierKey,
+    /// The path from the intent's tag to the action tree root.
+    pub existence_path: MerklePath,
+    /// The order opening the intent's `value_ref`.
+    pub order: Order,
+    /// The order encrypted to the solver.
+    pub sealed_order: Ciphertext,
+    /// The clearing price set by the solver (only checked when consumed).
+    pub clearing_price: u128,
+    /// The commitment to the counterparty's order (only checked when
+    /// consumed).
+    pub counter_order: Digest,
+    /// The clearing resource created in the same action (only checked when
+    /// consumed).
+    pub clearing: Resource,
+    /// The path from the clearing resource's commitment to the action tree
+    /// root (only checked when consumed).
+    pub clearing_path: MerklePath,
+}
+
+impl LogicCircuit for SwapIntentWitness {
+    fn constrain(&self) -> Result<LogicInstance, ArmError> {
+        let tag = self.resource.tag(self.is_consumed, &self.nf_key)?;
+        let root = self.existence_path.root(&tag);
+
+        // The intent is ephemeral and commits to the sealed order
+        assert!(self.resource.is_ephemeral);
+        assert_eq!(self.resource.value_ref, self.order.commitment());
+        assert_eq!(self.resource.quantity, self.order.quantity);
+
+        let mut application_payload = vec![ExpirableBlob {
+            blob: self.sealed_order.as_words(),
+            deletion_criterion: 1,
+        }];
+        if self.is_consumed {
+            // The action creates a clearing resource of the order's clearing
+            // logic that commits to both orders and the clearing price
+            let (buy, sell) = match self.order.side {
+                Side::Buy => (self.order.commitment(), self.counter_order),
+                Side::Sell => (self.counter_order, self.order.commitment()),
+            };
+            assert_eq!(self.clearing.logic_ref, self.order.clearing_logic_ref);
+            assert_eq!(
+                self.clearing.value_ref,
+                ClearingWitness::value_ref_from_commitments(&buy, &sell, self.clearing_price)
+            );
+            assert_eq!(self.clearing_path.root(&self.clearing.commitment()), root);
+
+            // Settlement must respect the limit price
+            assert!(self.order.accepts(self.clearing_price));
+            application_payload.push(ExpirableBlob {
+                blob: bytes_to_words(&self.clearing_price.to_be_bytes()),
+                deletion_criterion: 0,
+            });
+        }
+
+        Ok(LogicInstance {
+            tag,
+            is_consumed: self.is_consumed,
+            root,
+            app_data: AppData {
+                application_payload,
+                ..Default::default()
+            },
+        })
+    }
+
+    fn is_ephemeral(&self) -> bool {
+        self.resource.is_ephemeral
+    }
+}
+
+/// The logic witness of the solver's clearing resource.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ClearingWitness {
+    /// The ephemeral clearing resource.
+    pub resource: Resource,
+    /// Indicates whether the clearing resource is consumed or created.
+    pub is_consumed: bool,
+    /// The nullifier key of the clearing resource.
+    pub nf_key: NullifierKey,
+    /// The path from the clearing resource's tag to the action tree root.
+    pub existence_path: MerklePath,
+    /// The buy order.
+    pub buy: Order,
+    /// The sell order.
+    pub sell: Order,
+    /// The public clearing price.
+    pub clearing_price: u128,
+}
+
+impl ClearingWitness {
+    /// Computes the `value_ref` binding the clearing resource to both orders
+    /// and the clearing price.
+    pub fn value_ref(buy: &Order, sell: &Order, clearing_price: u128) -> Digest {
+        Self::value_ref_from_commitments(&buy.commitment(), &sell.commitment(), clearing_price)
+    }
+
+    /// Computes the clearing resource's `value_ref` from the order
+    /// commitments, as the intent logic sees them.
+    pub fn value_ref_from_commitments(buy: &Digest, sell: &Digest, clearing_price: u128) -> Digest {
+        let bytes = [
+            buy.as_bytes(),
+            sell.as_bytes(),
+            &clearing_price.to_be_bytes()[..],
+        ]
+        .concat();
+        hash_bytes(&bytes)
+    }
+}
+
+impl LogicCircuit for ClearingWitness {
+    fn constrain(&self) -> Result<LogicInstance, ArmError> {
+        let tag = self.resource.tag(self.is_consumed, &self.nf_key)?;
+        let root = self.existence_path.root(&tag);
+
+        assert!(self.resource.is_ephemeral);
+        assert_eq!(
+            self.resource.value_ref,
+            Self::value_ref(&self.buy, &self.sell, self.clearing_price)
+        );
+
+        // The orders cross at the clearing price
+        assert_eq!(self.buy.side, Side::Buy);
+        assert_eq!(self.sell.side, Side::Sell);
+        assert_eq!(self.buy.quantity, self.sell.quantity);
+        assert!(self.buy.accepts(self.clearing_price));
+        assert!(self.sell.accepts(self.clearing_price));
+
+        Ok(LogicInstance {
+            tag,
+            is_consumed: self.is_consumed,
+            root,
+            app_data: AppData {
+                application_payload: vec![ExpirableBlob {
+                    blob: bytes_to_words(&self.clearing_price.to_be_bytes()),
+                    deletion_criterion: 0,
+                }],
+                ..Default::default()
+            },
+        })
+    }
+
+    fn is_ephemeral(&self) -> bool {
+        self.resource.is_ephemeral
+    }
+}
+
+#[cfg(test)]
+fn test_orders() -> (Order, Order) {
+    let buy = Order {
+        side: Side::Buy,
+        limit_price: 110,
+        quantity: 5,
+        clearing_logic_ref: Digest::new([7u32; 8]),
+        salt: [1u8; 32],
+    };
+    let sell = Order {
+        side: Side::Sell,
+        limit_price: 100,
+        quantity: 5,
+        clearing_logic_ref: Digest::new([7u32; 8]),
+        salt: [2u8; 32],
+    };
+    (buy, sell)
+}
+
+/// Builds the intent witnesses of both orders and the clearing witness of a
+/// swap at the given price, all in one action tree.
+#[cfg(test)]
+fn test_swap(
+    buy: &Order,
+    sell: &Order,
+    price: u128,
+) -> (SwapIntentWitness, SwapIntentWitness, ClearingWitness) {
+    use anoma_rm_risc0::action_tree::MerkleTree;
+    use anoma_rm_risc0_gadgets::encryption::random_keypair;
+
+    let (_, solver_pk) = random_keypair();
+    let (intent_nf_key, intent_nk_commitment) = NullifierKey::random_pair();
+    let (clearing_nf_key, clearing_nk_commitment) = NullifierKey::random_pair();
+    let clearing = Resource::create(
+        buy.clearing_logic_ref,
+        Digest::default(),
+        0,
+        ClearingWitness::value_ref(buy, sell, price),
+        true,
+        Digest::default(),
+        clearing_nk_commitment,
+    );
+    let intent = |order: &Order, counter: &Order, nonce: u32| SwapIntentWitness {
+        resource: intent_resource(
+            Digest::default(),
+            Digest::default(),
+            order,
+            Digest::new([nonce; 8]),
+            intent_nk_commitment,
+        ),
+        is_consumed: true,
+        nf_key: intent_nf_key.clone(),
+        existence_path: MerklePath::empty(),
+        order: order.clone(),
+        sealed_order: order
+            .seal(&solver_pk, &SecretKey::random(), [0u8; 12])
+            .unwrap(),
+        clearing_price: price,
+        counter_order: counter.commitment(),
+        clearing,
+        clearing_path: MerklePath::empty(),
+    };
+    let mut buy_intent = intent(buy, sell, 1);
+    let mut sell_intent = intent(sell, buy, 2);
+
+    let buy_nf = buy_intent.resource.nullifier(&intent_nf_key).unwrap();
+    let sell_nf = sell_intent.resource.nullifier(&intent_nf_key).unwrap();
+    let tree = MerkleTree::new(vec![buy_nf, sell_nf, clearing.commitment()]);
+    let clearing_path = tree.generate_path(&clearing.commitment()).unwrap();
+    buy_intent.existence_path = tree.generate_path(&buy_nf).unwrap();
+    buy_intent.clearing_path = clearing_path.clone();
+    sell_intent.existence_path = tree.generate_path(&sell_nf).unwrap();
+    sell_intent.clearing_path = clearing_path.clone();
+
+    let clearing = ClearingWitness {
+        resource: clearing,
+        is_consumed: false,
+        nf_key: clearing_nf_key,
+        existence_path: clearing_path,
+        buy: buy.clone(),
+        sell: sell.clone(),
+        clearing_price: price,
+    };
+    (buy_intent, sell_intent, clearing)
+}
+
+#[test]
+fn test_swap_settlement() {
+    let (buy, sell) = test_orders();
+    let price = clearing_price(&buy, &sell).unwrap();
+    assert_eq!(price, 105);
+
+    let (buy_intent, sell_intent, clearing) = test_swap(&buy, &sell, price);
+    let buy_instance = buy_intent.constrain().unwrap();
+    let sell_instance = sell_intent.constrain().unwrap();
+    let clearing_instance = clearing.constrain().unwrap();
+    assert_eq!(buy_instance.root, clearing_instance.root);
+    assert_eq!(sell_instance.root, clearing_instance.root);
+    assert_eq!(buy_instance.app_data.application_payload.len(), 2);
+    assert_eq!(
+        buy_instance.app_data.application_payload[1],
+        sell_instance.app_data.application_payload[1]
+    );
+}
+
+#[test]
+#[should_panic]
+fn test_swap_rejects_price_beyond_limit() {
+    let (buy, sell) = test_orders();
+    let (buy_intent, _, _) = test_swap(&buy, &sell, 111);
+    let _ = buy_intent.constrain();
+}
+
+#[test]
+fn test_intent_binds_clearing_price() {
+    let (buy, sell) = test_orders();
+    let (buy_intent, _, _) = test_swap(&buy, &sell, 105);
+
+    // A price within the limit that the clearing resource doesn't commit to
+    let mut other_price = buy_intent.clone();
+    other_price.clearing_price = 101;
+    assert!(std::panic::catch_unwind(|| other_price.constrain()).is_err());
+
+    // A clearing resource of a logic the order didn't name
+    let mut other_logic = buy_intent.clone();
+    other_logic.clearing.logic_ref = Digest::default();
+    assert!(std::panic::catch_unwind(|| other_logic.constrain()).is_err());
+
+    // A clearing resource outside the intent's action
+    let mut other_action = buy_intent;
+    other_action.clearing_path = MerklePath::empty();
+    assert!(std::panic::catch_unwind(|| other_action.constrain()).is_err());
+}
+
+#[test]
+fn test_orders_must_cross() {
+    let (mut buy, sell) = test_orders();
+    buy.limit_price = 99;
+    assert_eq!(clearing_price(&buy, &sell), None);
+}
+
+#[test]
+fn test_sealed_order_decrypts_for_solver() {
+    use anoma_rm_risc0_gadgets::encryption::random_keypair;
+
+    let (buy, _) = test_orders();
+    let (solver_sk, solver_pk) = random_keypair();
+    let sealed = buy
+        .seal(&solver_pk, &SecretKey::random(), [0u8; 12])
+        .unwrap();
+    let plaintext = sealed.decrypt(&solver_sk).unwrap();
+    let opened: Order = bincode::deserialize(plaintext.as_bytes()).unwrap();
+    assert_eq!(opened, buy);
+}
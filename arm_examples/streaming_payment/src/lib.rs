@@ -0,0 +1,432 @@
+//! Subscription-style streaming payments.
+//!
+//! A stream resource holds the remaining deposit as its quantity, and its
+//! `value_ref` commits to the [`Stream`] terms: the recipient, the rate per
+//! epoch, the start epoch and the epoch up to which the stream was claimed.
+//! A claim consumes the stream and creates, in the same kind, its successor
+//! (with the deposit reduced and the claimed epoch advanced) and a payout to
+//! the recipient. The claim is balanced by a second compliance unit consuming
+//! an ephemeral, empty filler resource.
+//!
+//! The claim logic ([`ClaimWitness`]) checks the accounting in-circuit and
+//! publishes a [`Timelock`] at the claimed epoch, so verifiers reject claims
+//! ahead of their current epoch with [`check_claim`].
+
+use anoma_rm_risc0::{
+    action_tree::MerkleTree,
+    compliance::ComplianceWitness,
+    error::ArmError,
+    logic_instance::{AppData, LogicInstance},
+    merkle_path::MerklePath,
+    nullifier_key::{NullifierKey, NullifierKeyCommitment},
+    resource::Resource,
+    resource_logic::LogicCircuit,
+    utils::hash_bytes,
+    Digest,
+};
+use anoma_rm_risc0_gadgets::timelock::{check_timelocks, epoch_at, Timelock};
+use serde::{Deserialize, Serialize};
+
+/// Marks payout (and filler) resources in the resource's `value_ref`.
+pub const PAYOUT_VALUE_REF: Digest = Digest::ZERO;
+
+/// The terms of a payment stream.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Stream {
+    /// Owner of the payouts.
+    pub recipient: NullifierKeyCommitment,
+    /// Quantity released per epoch.
+    pub rate: u128,
+    /// The first epoch of the stream.
+    pub start_epoch: u64,
+    /// The epoch up to which the stream was claimed.
+    pub claimed_epoch: u64,
+    /// Blinds the stream commitment.
+    pub salt: [u8; 32],
+}
+
+impl Stream {
+    /// Creates a stream starting at `start_epoch`, not claimed yet.
+    pub fn new(
+        recipient: NullifierKeyCommitment,
+        rate: u128,
+        start_epoch: u64,
+        salt: [u8; 32],
+    ) -> Self {
+        Stream {
+            recipient,
+            rate,
+            start_epoch,
+            claimed_epoch: start_epoch,
+            salt,
+        }
+    }
+
+    /// Computes the commitment to the terms stored in the stream's `value_ref`.
+    pub fn commitment(&self) -> Digest {
+        let bytes = [
+            self.recipient.as_bytes(),
+            &self.rate.to_be_bytes()[..],
+            &self.start_epoch.to_be_bytes()[..],
+            &self.claimed_epoch.to_be_bytes()[..],
+            &self.salt,
+        ]
+        .concat();
+        hash_bytes(&bytes)
+    }
+
+    /// Computes the quantity released between the claimed epoch and `epoch`,
+    /// capped by the remaining deposit.
+    pub fn accrued(&self, epoch: u64, deposit: u128) -> u128 {
+        let epochs = epoch.saturating_sub(self.claimed_epoch) as u128;
+        self.rate.saturating_mul(epochs).min(deposit)
+    }
+
+    /// Returns the terms after a claim up to `epoch`.
+    pub fn advance(&self, epoch: u64) -> Self {
+        Stream {
+            claimed_epoch: epoch,
+            ..*self
+        }
+    }
+
+    /// Creates the stream resource holding `deposit`.
+    pub fn resource(
+        &self,
+        claim_logic_ref: Digest,
+        label_ref: Digest,
+        deposit: u128,
+        nonce: Digest,
+        nk_commitment: NullifierKeyCommitment,
+    ) -> Resource {
+        Resource::create(
+            claim_logic_ref,
+            label_ref,
+            deposit,
+            self.commitment(),
+            false,
+            nonce,
+            nk_commitment,
+        )
+    }
+}
+
+/// The logic witness of stream, payout and filler resources.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ClaimWitness {
+    /// The resource.
+    pub resource: Resource,
+    /// Indicates whether the resource is consumed or created.
+    pub is_consumed: bool,
+    /// The nullifier key of the resource.
+    pub nf_key: NullifierKey,
+    /// The path from the resource's tag to the action tree root.
+    pub existence_path: MerklePath,
+    /// The terms opening a stream resource's `value_ref`.
+    pub stream: Stream,
+    /// The claimed epoch (only checked when consuming a stream).
+    pub claim_epoch: u64,
+    /// The successor stream and its path (only checked when consuming a
+    /// stream).
+    pub successor: Resource,
+    /// The path from the successor's commitment to the action tree root.
+    pub successor_path: MerklePath,
+    /// The payout to the recipient (only checked when consuming a stream).
+    pub payout: Resource,
+    /// The path from the payout's commitment to the action tree root.
+    pub payout_path: MerklePath,
+}
+
+impl ClaimWitness {
+    fn same_kind(&self, other: &Resource) -> bool {
+        self.resource.logic_ref == other.logic_ref && self.resource.label_ref == other.label_ref
+    }
+}
+
+impl LogicCircuit for ClaimWitness {
+    fn constrain(&self) -> Result<LogicInstance, ArmError> {
+        let tag = self.resource.tag(self.is_consumed, &self.nf_key)?;
+        let root = self.existence_path.root(&tag);
+        let mut app_data = AppData::new();
+
+        if self.resource.value_ref == PAYOUT_VALUE_REF {
+            // Fillers are empty; payouts are plain balances checked by the claim
+            assert!(!self.resource.is_ephemeral || self.resource.quantity == 0);
+        } else {
+            assert!(!self.resource.is_ephemeral);
+            assert_eq!(self.resource.value_ref, self.stream.commitment());
+
+            if self.is_consumed {
+                // The claim advances the stream and pays the accrued quantity
+                assert!(self.claim_epoch > self.stream.claimed_epoch);
+                let accrued = self
+                    .stream
+                    .accrued(self.claim_epoch, self.resource.quantity);
+
+                assert!(self.same_kind(&self.successor));
+                assert_eq!(
+                    self.successor.value_ref,
+                    self.stream.advance(self.claim_epoch).commitment()
+                );
+                assert_eq!(self.successor.quantity, self.resource.quantity - accrued);
+                assert_eq!(self.successor.nk_commitment, self.resource.nk_commitment);
+                assert_eq!(self.successor_path.root(&self.successor.commitment()), root);
+
+                assert!(self.same_kind(&self.payout));
+                assert!(!self.payout.is_ephemeral);
+                assert_eq!(self.payout.value_ref, PAYOUT_VALUE_REF);
+                assert_eq!(self.payout.quantity, accrued);
+                assert_eq!(self.payout.nk_commitment, self.stream.recipient);
+                assert_eq!(self.payout_path.root(&self.payout.commitment()), root);
+
+                // The claimed epoch must have been reached
+                app_data.add_application_payload(Timelock::new(self.claim_epoch).to_blob());
+            }
+        }
+
+        Ok(LogicInstance {
+            tag,
+            is_consumed: self.is_consumed,
+            root,
+            app_data,
+        })
+    }
+
+    fn is_ephemeral(&self) -> bool {
+        self.resource.is_ephemeral
+    }
+}
+
+/// Checks a claim logic instance against the verifier's current unix time
+/// (e.g. `VerificationContext::now`) for epochs of `epoch_seconds`.
+pub fn check_claim(instance: &LogicInstance, now: u64, epoch_seconds: u64) -> Result<(), ArmError> {
+    check_timelocks(&instance.app_data, epoch_at(now, epoch_seconds))
+}
+
+/// The witnesses of a claim: two compliance units (stream to successor and
+/// filler to payout) and the claim logic witnesses of the four resources, in
+/// action tree order.
+#[derive(Clone)]
+pub struct Claim {
+    /// The compliance witnesses.
+    pub compliance: [ComplianceWitness; 2],
+    /// The claim logic witnesses of the stream, successor, filler and payout.
+    pub logics: [ClaimWitness; 4],
+}
+
+/// Builds the witnesses claiming a stream.
+pub struct ClaimBuilder {
+    stream: Stream,
+    resource: Resource,
+    nf_key: NullifierKey,
+    merkle_path: MerklePath,
+    latest_root: Digest,
+}
+
+impl ClaimBuilder {
+    /// Creates a builder for the given stream resource, its nullifier key,
+    /// its path in the commitment tree and the latest root (used for the
+    /// ephemeral filler).
+    pub fn new(
+        stream: Stream,
+        resource: Resource,
+        nf_key: NullifierKey,
+        merkle_path: MerklePath,
+        latest_root: Digest,
+    ) -> Self {
+        ClaimBuilder {
+            stream,
+            resource,
+            nf_key,
+            merkle_path,
+            latest_root,
+        }
+    }
+
+    /// Claims the stream up to `epoch`.
+    pub fn claim(&self, epoch: u64) -> Result<Claim, ArmError> {
+        if self.resource.value_ref != self.stream.commitment() {
+            return Err(ArmError::InvalidResourceValueRef);
+        }
+        if self.resource.nk_commitment != self.nf_key.commit() {
+            return Err(ArmError::InvalidNullifierKey);
+        }
+        if epoch <= self.stream.claimed_epoch {
+            return Err(ArmError::TimelockNotOpen);
+        }
+        let accrued = self.stream.accrued(epoch, self.resource.quantity);
+
+        let mut successor = self.resource;
+        successor.set_value_ref(self.stream.advance(epoch).commitment());
+        successor.quantity = self.resource.quantity - accrued;
+        successor.set_nonce_from_nf(&self.resource, &self.nf_key)?;
+        successor.reset_randomness();
+
+        let (filler_nf_key, filler_nk_commitment) = NullifierKey::random_pair();
+        let filler = Resource::create(
+            self.resource.logic_ref,
+            self.resource.label_ref,
+            0,
+            PAYOUT_VALUE_REF,
+            true,
+            Digest::default(),
+            filler_nk_commitment,
+        );
+        let mut payout = filler;
+        payout.is_ephemeral = false;
+        payout.quantity = accrued;
+        payout.set_nf_commitment(self.stream.recipient);
+        payout.set_nonce_from_nf(&filler, &filler_nf_key)?;
+        payout.reset_randomness();
+
+        let compliance = [
+            ComplianceWitness::from_resources_with_path(
+                self.resource,
+                self.nf_key.clone(),
+                self.merkle_path.clone(),
+                successor,
+            ),
+            ComplianceWitness::from_resources(
+                filler,
+                self.latest_root,
+                filler_nf_key.clone(),
+                payout,
+            ),
+        ];
+
+        let stream_nf = self.resource.nullifier(&self.nf_key)?;
+        let filler_nf = filler.nullifier(&filler_nf_key)?;
+        let tags = [
+            stream_nf,
+            successor.commitment(),
+            filler_nf,
+            payout.commitment(),
+        ];
+        let action_tree = MerkleTree::from_units([(tags[0], tags[1]), (tags[2], tags[3])]);
+        let paths = tags
+            .iter()
+            .map(|tag| action_tree.generate_path(tag))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let stream = ClaimWitness {
+            resource: self.resource,
+            is_consumed: true,
+            nf_key: self.nf_key.clone(),
+            existence_path: paths[0].clone(),
+            stream: self.stream,
+            claim_epoch: epoch,
+            successor,
+            successor_path: paths[1].clone(),
+            payout,
+            payout_path: paths[3].clone(),
+        };
+        let successor = ClaimWitness {
+            resource: successor,
+            existence_path: paths[1].clone(),
+            stream: self.stream.advance(epoch),
+            ..Default::default()
+        };
+        let filler = ClaimWitness {
+            resource: filler,
+            is_consumed: true,
+            nf_key: filler_nf_key,
+            existence_path: paths[2].clone(),
+            ..Default::default()
+        };
+        let payout = ClaimWitness {
+            resource: payout,
+            existence_path: paths[3].clone(),
+            ..Default::default()
+        };
+        Ok(Claim {
+            compliance,
+            logics: [stream, successor, filler, payout],
+        })
+    }
+}
+
+#[cfg(test)]
+fn test_builder(deposit: u128) -> (ClaimBuilder, NullifierKeyCommitment) {
+    let (nf_key, nk_commitment) = NullifierKey::random_pair();
+    let (_, recipient) = NullifierKey::random_pair();
+    let stream = Stream::new(recipient, 10, 100, [3u8; 32]);
+    let resource = stream.resource(
+        Digest::default(),
+        Digest::default(),
+        deposit,
+        Digest::default(),
+        nk_commitment,
+    );
+    let builder = ClaimBuilder::new(
+        stream,
+        resource,
+        nf_key,
+        MerklePath::default(),
+        Digest::default(),
+    );
+    (builder, recipient)
+}
+
+#[test]
+fn test_stream_claim() {
+    let (builder, recipient) = test_builder(1000);
+    let claim = builder.claim(104).unwrap();
+
+    let instances = claim
+        .logics
+        .iter()
+        .map(|witness| witness.constrain().unwrap())
+        .collect::<Vec<_>>();
+    let compliance = claim
+        .compliance
+        .iter()
+        .map(|witness| witness.constrain().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(instances[0].tag, compliance[0].consumed_nullifier);
+    assert_eq!(instances[1].tag, compliance[0].created_commitment);
+    assert_eq!(instances[2].tag, compliance[1].consumed_nullifier);
+    assert_eq!(instances[3].tag, compliance[1].created_commitment);
+    assert!(instances.iter().all(|i| i.root == instances[0].root));
+
+    let payout = &claim.logics[3].resource;
+    assert_eq!(payout.quantity, 40);
+    assert_eq!(payout.nk_commitment, recipient);
+    assert_eq!(claim.logics[1].resource.quantity, 960);
+
+    // The claim is only valid from epoch 104 on
+    let epoch_seconds = 60;
+    assert_eq!(
+        check_claim(&instances[0], 103 * epoch_seconds, epoch_seconds),
+        Err(ArmError::TimelockNotOpen)
+    );
+    assert!(check_claim(&instances[0], 104 * epoch_seconds, epoch_seconds).is_ok());
+
+    // The successor stream can be claimed again later
+    let next = ClaimBuilder::new(
+        claim.logics[1].stream,
+        claim.logics[1].resource,
+        builder.nf_key.clone(),
+        MerklePath::default(),
+        Digest::default(),
+    );
+    assert_eq!(next.claim(104).err(), Some(ArmError::TimelockNotOpen));
+    assert_eq!(next.claim(110).unwrap().logics[3].resource.quantity, 60);
+}
+
+#[test]
+fn test_stream_claim_capped_by_deposit() {
+    let (builder, _) = test_builder(25);
+    let claim = builder.claim(200).unwrap();
+    assert_eq!(claim.logics[3].resource.quantity, 25);
+    assert_eq!(claim.logics[1].resource.quantity, 0);
+    assert!(claim.logics[0].constrain().is_ok());
+}
+
+#[test]
+#[should_panic]
+fn test_stream_rejects_overpayment() {
+    let (builder, _) = test_builder(1000);
+    let mut claim = builder.claim(104).unwrap();
+    claim.logics[0].payout.quantity += 1;
+    claim.logics[0].constrain().unwrap();
+}
@@ -0,0 +1,360 @@
+//! One-resource-one-vote private voting.
+//!
+//! An [`Election`] is a resource kind: every voting right is a persistent
+//! resource of quantity one whose `label_ref` commits to the election. Casting
+//! a vote consumes the right and creates a ballot resource of the same kind,
+//! owned by the tallier, whose `value_ref` commits to the [`Ballot`]. The
+//! ballot itself only travels encrypted to the tallier in the resource
+//! payload, with a discovery hint so the tallier can find it. Since the right
+//! is consumed, its nullifier prevents voting twice, and a ballot can never be
+//! consumed as a right again.
+//!
+//! The vote logic ([`VoteWitness`]) checks the election, the ballot choice and
+//! that the published ciphertexts encrypt the committed ballot.
+
+use anoma_rm_risc0::{
+    action_tree::MerkleTree,
+    compliance::ComplianceWitness,
+    error::ArmError,
+    logic_instance::{AppData, ExpirableBlob, LogicInstance},
+    merkle_path::MerklePath,
+    nullifier_key::{NullifierKey, NullifierKeyCommitment},
+    resource::Resource,
+    resource_logic::LogicCircuit,
+    rng::ArmRng,
+    utils::hash_bytes,
+    Digest,
+};
+use anoma_rm_risc0_gadgets::encryption::{Ciphertext, SecretKey};
+use k256::{elliptic_curve::group::GroupEncoding, AffinePoint};
+use serde::{Deserialize, Serialize};
+
+/// Marks an unused voting right in the resource's `value_ref`.
+pub const VOTING_RIGHT_VALUE_REF: Digest = Digest::ZERO;
+
+/// Discovery hint encrypted to the tallier along with each ballot.
+pub const DISCOVERY_HINT: &[u8] = b"ARM_VOTING_BALLOT";
+
+/// The public parameters of an election.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Election {
+    /// Distinguishes elections sharing the same tallier.
+    pub id: Digest,
+    /// Number of choices; valid choices are `0..num_choices`.
+    pub num_choices: u32,
+    /// The tallier's encryption key.
+    pub tally_pk: AffinePoint,
+    /// The tallier's nullifier key commitment, owning all ballots.
+    pub tallier_nk_commitment: NullifierKeyCommitment,
+}
+
+impl Election {
+    /// Computes the `label_ref` shared by the election's rights and ballots.
+    pub fn label_ref(&self) -> Digest {
+        let bytes = [
+            self.id.as_bytes(),
+            &self.num_choices.to_be_bytes()[..],
+            &self.tally_pk.to_bytes()[..],
+            self.tallier_nk_commitment.as_bytes(),
+        ]
+        .concat();
+        hash_bytes(&bytes)
+    }
+
+    /// Creates a voting right owned by `nk_commitment`.
+    pub fn voting_right(
+        &self,
+        vote_logic_ref: Digest,
+        nonce: Digest,
+        nk_commitment: NullifierKeyCommitment,
+    ) -> Resource {
+        Resource::create(
+            vote_logic_ref,
+            self.label_ref(),
+            1,
+            VOTING_RIGHT_VALUE_REF,
+            false,
+            nonce,
+            nk_commitment,
+        )
+    }
+}
+
+/// A ballot.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Ballot {
+    /// The chosen option.
+    pub choice: u32,
+    /// Blinds the ballot commitment.
+    pub salt: [u8; 32],
+}
+
+impl Ballot {
+    /// Computes the commitment to the ballot stored in the ballot resource's
+    /// `value_ref`.
+    pub fn commitment(&self) -> Digest {
+        hash_bytes(&[&self.choice.to_be_bytes()[..], &self.salt].concat())
+    }
+
+    /// Encrypts the ballot to the tallier.
+    pub fn seal(
+        &self,
+        tally_pk: &AffinePoint,
+        sender_sk: &SecretKey,
+        nonce: [u8; 12],
+    ) -> Result<Ciphertext, ArmError> {
+        let bytes = bincode::serialize(self).map_err(|_| ArmError::SerializationError)?;
+        Ciphertext::encrypt_with_nonce(&bytes, tally_pk, sender_sk, nonce)
+    }
+}
+
+/// The logic witness of voting rights and ballots.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct VoteWitness {
+    /// The voting right (consumed) or the ballot (created).
+    pub resource: Resource,
+    /// Indicates whether the resource is consumed or created.
+    pub is_consumed: bool,
+    /// The nullifier key of the resource.
+    pub nf_key: NullifierKey,
+    /// The path from the resource's tag to the action tree root.
+    pub existence_path: MerklePath,
+    /// The election the resource belongs to.
+    pub election: Election,
+    /// The ballot (only checked for created ballots).
+    pub ballot: Ballot,
+    /// The ephemeral encryption key of the voter.
+    pub sender_sk: SecretKey,
+    /// The encryption nonce of the ballot.
+    pub ballot_nonce: [u8; 12],
+    /// The encryption nonce of the discovery hint.
+    pub discovery_nonce: [u8; 12],
+}
+
+impl LogicCircuit for VoteWitness {
+    fn constrain(&self) -> Result<LogicInstance, ArmError> {
+        let tag = self.resource.tag(self.is_consumed, &self.nf_key)?;
+        let root = self.existence_path.root(&tag);
+
+        // Rights and ballots are persistent, one per voter, of the election's kind
+        assert!(!self.resource.is_ephemeral);
+        assert_eq!(self.resource.quantity, 1);
+        assert_eq!(self.resource.label_ref, self.election.label_ref());
+
+        if self.is_consumed {
+            // Only unused rights can be consumed; ballots are final
+            assert_eq!(self.resource.value_ref, VOTING_RIGHT_VALUE_REF);
+            return Ok(LogicInstance {
+                tag,
+                is_consumed: self.is_consumed,
+                root,
+                app_data: AppData::default(),
+            });
+        }
+
+        // The ballot is valid, owned by the tallier and encrypted to the tallier
+        assert!(self.ballot.choice < self.election.num_choices);
+        assert_eq!(self.resource.value_ref, self.ballot.commitment());
+        assert_eq!(
+            self.resource.nk_commitment,
+            self.election.tallier_nk_commitment
+        );
+        let sealed_ballot =
+            self.ballot
+                .seal(&self.election.tally_pk, &self.sender_sk, self.ballot_nonce)?;
+        let discovery = Ciphertext::encrypt_with_nonce(
+            &DISCOVERY_HINT.to_vec(),
+            &self.election.tally_pk,
+            &self.sender_sk,
+            self.discovery_nonce,
+        )?;
+
+        let mut app_data = AppData::new();
+        app_data.add_resource_payload(ExpirableBlob {
+            blob: sealed_ballot.as_words(),
+            deletion_criterion: 1,
+        });
+        app_data.add_discovery_payload(ExpirableBlob {
+            blob: discovery.as_words(),
+            deletion_criterion: 1,
+        });
+        Ok(LogicInstance {
+            tag,
+            is_consumed: self.is_consumed,
+            root,
+            app_data,
+        })
+    }
+
+    fn is_ephemeral(&self) -> bool {
+        self.resource.is_ephemeral
+    }
+}
+
+/// The witnesses of a cast vote: one compliance unit consuming the right and
+/// creating the ballot, and the vote logic witnesses of both resources.
+#[derive(Clone)]
+pub struct CastVote {
+    /// The compliance witness.
+    pub compliance: ComplianceWitness,
+    /// The vote logic witness of the consumed right.
+    pub right: VoteWitness,
+    /// The vote logic witness of the created ballot.
+    pub ballot: VoteWitness,
+}
+
+/// Builds the witnesses casting a vote with a voting right.
+pub struct VoteBuilder {
+    election: Election,
+    right: Resource,
+    nf_key: NullifierKey,
+    merkle_path: MerklePath,
+}
+
+impl VoteBuilder {
+    /// Creates a builder for the given right, its nullifier key and its path
+    /// in the commitment tree.
+    pub fn new(
+        election: Election,
+        right: Resource,
+        nf_key: NullifierKey,
+        merkle_path: MerklePath,
+    ) -> Self {
+        VoteBuilder {
+            election,
+            right,
+            nf_key,
+            merkle_path,
+        }
+    }
+
+    /// Casts a vote for `choice`.
+    pub fn cast(&self, choice: u32) -> Result<CastVote, ArmError> {
+        if self.right.label_ref != self.election.label_ref() {
+            return Err(ArmError::InvalidResourceKind);
+        }
+        if self.right.value_ref != VOTING_RIGHT_VALUE_REF || choice >= self.election.num_choices {
+            return Err(ArmError::InvalidResourceValueRef);
+        }
+        if self.right.nk_commitment != self.nf_key.commit() {
+            return Err(ArmError::InvalidNullifierKey);
+        }
+
+        let ballot = Ballot {
+            choice,
+            salt: ArmRng::new().try_bytes()?,
+        };
+        let mut ballot_resource = self.right;
+        ballot_resource.set_value_ref(ballot.commitment());
+        ballot_resource.set_nf_commitment(self.election.tallier_nk_commitment);
+        ballot_resource.set_nonce_from_nf(&self.right, &self.nf_key)?;
+        ballot_resource.reset_randomness();
+
+        let compliance = ComplianceWitness::from_resources_with_path(
+            self.right,
+            self.nf_key.clone(),
+            self.merkle_path.clone(),
+            ballot_resource,
+        );
+
+        let nf = self.right.nullifier(&self.nf_key)?;
+        let cm = ballot_resource.commitment();
+        let action_tree = MerkleTree::new(vec![nf, cm]);
+
+        let sender_sk = SecretKey::random();
+        let right = VoteWitness {
+            resource: self.right,
+            is_consumed: true,
+            nf_key: self.nf_key.clone(),
+            existence_path: action_tree.generate_path(&nf)?,
+            election: self.election.clone(),
+            ..Default::default()
+        };
+        let ballot = VoteWitness {
+            resource: ballot_resource,
+            is_consumed: false,
+            nf_key: NullifierKey::default(),
+            existence_path: action_tree.generate_path(&cm)?,
+            election: self.election.clone(),
+            ballot,
+            sender_sk,
+            ballot_nonce: ArmRng::new().try_bytes()?,
+            discovery_nonce: ArmRng::new().try_bytes()?,
+        };
+        Ok(CastVote {
+            compliance,
+            right,
+            ballot,
+        })
+    }
+}
+
+#[cfg(test)]
+fn test_election() -> (Election, SecretKey) {
+    use anoma_rm_risc0_gadgets::encryption::random_keypair;
+
+    let (tally_sk, tally_pk) = random_keypair();
+    let (_, tallier_nk_commitment) = NullifierKey::random_pair();
+    let election = Election {
+        id: Digest::new([1u32; 8]),
+        num_choices: 3,
+        tally_pk,
+        tallier_nk_commitment,
+    };
+    (election, tally_sk)
+}
+
+#[cfg(test)]
+fn test_builder(election: &Election) -> VoteBuilder {
+    let (nf_key, nk_commitment) = NullifierKey::random_pair();
+    let right = election.voting_right(Digest::default(), Digest::default(), nk_commitment);
+    VoteBuilder::new(election.clone(), right, nf_key, MerklePath::default())
+}
+
+#[test]
+fn test_cast_vote() {
+    let (election, tally_sk) = test_election();
+    let vote = test_builder(&election).cast(2).unwrap();
+
+    let compliance = vote.compliance.constrain().unwrap();
+    let right = vote.right.constrain().unwrap();
+    let ballot = vote.ballot.constrain().unwrap();
+    assert_eq!(right.tag, compliance.consumed_nullifier);
+    assert_eq!(ballot.tag, compliance.created_commitment);
+    assert_eq!(right.root, ballot.root);
+
+    // Only the tallier can open the ballot
+    let sealed = Ciphertext::from_words(&ballot.app_data.resource_payload[0].blob);
+    let opened: Ballot =
+        bincode::deserialize(sealed.decrypt(&tally_sk).unwrap().as_bytes()).unwrap();
+    assert_eq!(opened.choice, 2);
+    assert_eq!(opened.commitment(), vote.ballot.resource.value_ref);
+    let hint = Ciphertext::from_words(&ballot.app_data.discovery_payload[0].blob);
+    assert_eq!(hint.decrypt(&tally_sk).unwrap().as_bytes(), DISCOVERY_HINT);
+}
+
+#[test]
+fn test_vote_validation() {
+    let (election, _) = test_election();
+    let builder = test_builder(&election);
+    assert_eq!(
+        builder.cast(3).err(),
+        Some(ArmError::InvalidResourceValueRef)
+    );
+
+    let (other, _) = test_election();
+    let builder = VoteBuilder::new(other, builder.right, builder.nf_key, MerklePath::default());
+    assert_eq!(builder.cast(0).err(), Some(ArmError::InvalidResourceKind));
+}
+
+#[test]
+#[should_panic]
+fn test_ballot_cannot_vote_again() {
+    let (election, _) = test_election();
+    let vote = test_builder(&election).cast(0).unwrap();
+
+    // A ballot consumed as a right fails the vote logic
+    let mut recast = vote.ballot.clone();
+    recast.is_consumed = true;
+    recast.constrain().unwrap();
+}
@@ -0,0 +1,51 @@
+//! Measures building issuances to many recipients.
+//!
+//! Run with `cargo bench -p anoma-rm-risc0-gadgets --features transaction`.
+
+use anoma_rm_risc0::{nullifier_key::NullifierKey, Digest};
+use anoma_rm_risc0_gadgets::{
+    encryption::random_keypair,
+    issuance::{IssuanceBuilder, Recipient},
+};
+use std::time::{Duration, Instant};
+
+const RECIPIENTS: [usize; 3] = [10, 100, 1000];
+const ROUNDS: u32 = 5;
+
+fn time_issuance(recipients: &[Recipient]) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        let issuance = IssuanceBuilder::new(
+            Digest::from_bytes([1u8; 32]),
+            Digest::from_bytes([2u8; 32]),
+            NullifierKey::random_pair().0,
+        )
+        .recipients(recipients.iter().cloned())
+        .build()
+        .unwrap();
+        for action in &issuance.actions {
+            action.action_tree_root().unwrap();
+        }
+        issuance.delta_witness().unwrap();
+    }
+    start.elapsed() / ROUNDS
+}
+
+fn main() {
+    for count in RECIPIENTS {
+        let recipients: Vec<Recipient> = (0..count)
+            .map(|_| Recipient {
+                nk_commitment: NullifierKey::random_pair().1,
+                value_ref: Digest::default(),
+                quantity: 1,
+                encryption_pk: random_keypair().1,
+                memo: None,
+            })
+            .collect();
+        let elapsed = time_issuance(&recipients);
+        println!(
+            "{count} recipients: {elapsed:?} ({:?} per recipient)",
+            elapsed / count as u32
+        );
+    }
+}
@@ -1,15 +1,98 @@
 //! Authority signing and verification using ECDSA over secp256k1.
+//!
+//! Signing keys are only compared in constant time, through
+//! [`ConstantTimeEq`]; `==` on them does the same.
 
-use anoma_rm_risc0::error::ArmError;
+use anoma_rm_risc0::{error::ArmError, key_encoding::KeyEncoding, rng::ArmRng, Digest};
 use k256::{
     ecdsa::{
         signature::{Signer, Verifier},
         Signature, SigningKey, VerifyingKey,
     },
-    elliptic_curve::{rand_core::OsRng, sec1::ToEncodedPoint},
+    elliptic_curve::sec1::ToEncodedPoint,
     AffinePoint,
 };
 use serde::{Deserialize, Serialize};
+use subtle::{Choice, ConstantTimeEq};
+
+/// The version of the [`SigningPayload`] encoding.
+pub const SIGNING_PAYLOAD_VERSION: u8 = 1;
+
+/// The domain used when signing a [`SigningPayload`].
+const SIGNING_PAYLOAD_DOMAIN: &[u8] = b"ARM_SIGNING_PAYLOAD";
+
+/// The purpose of an authorization signature. The discriminants are the
+/// signed encoding of the purpose and never change.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[repr(u8)]
+pub enum SigningPurpose {
+    /// Authorizes consuming a resource.
+    Consume = 0,
+    /// Authorizes creating a resource.
+    Create = 1,
+    /// Authorizes an intent to be settled by a solver.
+    Intent = 2,
+    /// Authorizes rotating the key bound to a resource.
+    Rotate = 3,
+}
+
+/// A structured message to be signed by an authority.
+///
+/// The application domain is mandatory and length-prefixed in the encoding,
+/// so a signature produced for one application (or action, or purpose) can't
+/// be replayed in another.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SigningPayload {
+    /// The application domain tag.
+    pub domain: Vec<u8>,
+    /// The encoding version.
+    pub version: u8,
+    /// The action tree root being authorized.
+    pub root: Digest,
+    /// The index of the action in the transaction.
+    pub action_index: u32,
+    /// The purpose of the signature.
+    pub purpose: SigningPurpose,
+}
+
+impl SigningPayload {
+    /// Creates a new signing payload with the current version.
+    pub fn new(
+        domain: &[u8],
+        root: Digest,
+        action_index: u32,
+        purpose: SigningPurpose,
+    ) -> Result<Self, ArmError> {
+        if domain.is_empty() {
+            return Err(ArmError::InvalidSigningPayload);
+        }
+        Ok(SigningPayload {
+            domain: domain.to_vec(),
+            version: SIGNING_PAYLOAD_VERSION,
+            root,
+            action_index,
+            purpose,
+        })
+    }
+
+    /// Encodes the payload into the bytes to be signed.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ArmError> {
+        if self.domain.is_empty() || self.version != SIGNING_PAYLOAD_VERSION {
+            return Err(ArmError::InvalidSigningPayload);
+        }
+        let domain_len =
+            u32::try_from(self.domain.len()).map_err(|_| ArmError::InvalidSigningPayload)?;
+
+        let mut bytes = Vec::with_capacity(1 + 4 + self.domain.len() + 32 + 4 + 1);
+        bytes.push(self.version);
+        bytes.extend_from_slice(&domain_len.to_be_bytes());
+        bytes.extend_from_slice(&self.domain);
+        bytes.extend_from_slice(self.root.as_bytes());
+        bytes.extend_from_slice(&self.action_index.to_be_bytes());
+        bytes.push(self.purpose as u8);
+        Ok(bytes)
+    }
+}
 
 /// The authority's signing key.
 #[derive(Clone)]
@@ -23,10 +106,24 @@ pub struct AuthorityVerifyingKey(AffinePoint);
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct AuthoritySignature(Signature);
 
+impl ConstantTimeEq for AuthoritySigningKey {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
+
+impl PartialEq for AuthoritySigningKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for AuthoritySigningKey {}
+
 impl AuthoritySigningKey {
     /// Generates a new random authority signing key.
     pub fn new() -> Self {
-        let signing_key = SigningKey::random(&mut OsRng);
+        let signing_key = SigningKey::random(&mut ArmRng::new());
         AuthoritySigningKey(signing_key)
     }
 
@@ -43,6 +140,11 @@ impl AuthoritySigningKey {
         AuthoritySignature(self.0.sign(&msg_with_domain))
     }
 
+    /// Signs a structured payload with the authority signing key.
+    pub fn sign_payload(&self, payload: &SigningPayload) -> Result<AuthoritySignature, ArmError> {
+        Ok(self.sign(SIGNING_PAYLOAD_DOMAIN, &payload.to_bytes()?))
+    }
+
     /// Serializes the signing key to a byte array.
     pub fn to_bytes(&self) -> [u8; 32] {
         self.0.to_bytes().into()
@@ -110,6 +212,16 @@ impl AuthorityVerifyingKey {
             .map_err(|_| ArmError::InvalidSignature)
     }
 
+    /// Verifies a signature against a structured payload. It can be used in
+    /// logic circuits as well as on the host.
+    pub fn verify_payload(
+        &self,
+        payload: &SigningPayload,
+        signature: &AuthoritySignature,
+    ) -> Result<(), ArmError> {
+        self.verify(SIGNING_PAYLOAD_DOMAIN, &payload.to_bytes()?, signature)
+    }
+
     /// Creates an AuthorityVerifyingKey from an AffinePoint.
     pub fn from_affine(point: AffinePoint) -> Self {
         AuthorityVerifyingKey(point)
@@ -124,6 +236,37 @@ impl AuthorityVerifyingKey {
     pub fn to_bytes(&self) -> Vec<u8> {
         self.0.to_encoded_point(false).as_bytes().to_vec()
     }
+
+    /// Deserializes the verifying key from SEC1 bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ArmError> {
+        let verifying_key =
+            VerifyingKey::from_sec1_bytes(bytes).map_err(|_| ArmError::InvalidPublicKey)?;
+        Ok(Self::from_affine(*verifying_key.as_affine()))
+    }
+}
+
+impl KeyEncoding for AuthoritySigningKey {
+    const HRP: &'static str = "armauthsk";
+
+    fn key_bytes(&self) -> Vec<u8> {
+        self.to_bytes().to_vec()
+    }
+
+    fn from_key_bytes(bytes: &[u8]) -> Result<Self, ArmError> {
+        Self::from_bytes(bytes)
+    }
+}
+
+impl KeyEncoding for AuthorityVerifyingKey {
+    const HRP: &'static str = "armauth";
+
+    fn key_bytes(&self) -> Vec<u8> {
+        self.to_bytes()
+    }
+
+    fn from_key_bytes(bytes: &[u8]) -> Result<Self, ArmError> {
+        Self::from_bytes(bytes)
+    }
 }
 
 impl AuthoritySignature {
@@ -169,3 +312,84 @@ fn test_authorization() {
 
     assert!(verifying_key.verify(domain, message, &signature).is_ok());
 }
+
+#[test]
+fn test_payload_authorization() {
+    let signing_key = AuthoritySigningKey::new();
+    let verifying_key = AuthorityVerifyingKey::from_signing_key(&signing_key);
+
+    let root = Digest::from_bytes([7u8; 32]);
+    let payload = SigningPayload::new(b"test_app", root, 0, SigningPurpose::Consume).unwrap();
+    let signature = signing_key.sign_payload(&payload).unwrap();
+    assert!(verifying_key.verify_payload(&payload, &signature).is_ok());
+
+    // Replays with a different action, purpose or application are rejected.
+    let other_action = SigningPayload::new(b"test_app", root, 1, SigningPurpose::Consume).unwrap();
+    assert!(verifying_key
+        .verify_payload(&other_action, &signature)
+        .is_err());
+    let other_purpose = SigningPayload::new(b"test_app", root, 0, SigningPurpose::Create).unwrap();
+    assert!(verifying_key
+        .verify_payload(&other_purpose, &signature)
+        .is_err());
+    let other_app = SigningPayload::new(b"other_app", root, 0, SigningPurpose::Consume).unwrap();
+    assert!(verifying_key
+        .verify_payload(&other_app, &signature)
+        .is_err());
+
+    assert_eq!(
+        SigningPayload::new(b"", root, 0, SigningPurpose::Consume),
+        Err(ArmError::InvalidSigningPayload)
+    );
+}
+
+#[test]
+fn test_signing_payload_encoding() {
+    let root = Digest::from_bytes([0xab; 32]);
+    let payload = SigningPayload::new(b"app", root, 2, SigningPurpose::Rotate).unwrap();
+    let expected = [
+        &[SIGNING_PAYLOAD_VERSION, 0, 0, 0, 3][..],
+        b"app",
+        &[0xab; 32],
+        &[0, 0, 0, 2, 3],
+    ]
+    .concat();
+    assert_eq!(payload.to_bytes().unwrap(), expected);
+
+    let purposes = [
+        SigningPurpose::Consume,
+        SigningPurpose::Create,
+        SigningPurpose::Intent,
+        SigningPurpose::Rotate,
+    ];
+    for (byte, purpose) in purposes.into_iter().enumerate() {
+        assert_eq!(purpose as u8, byte as u8);
+    }
+}
+
+#[test]
+fn test_authority_key_encoding() {
+    let signing_key = AuthoritySigningKey::new();
+    let verifying_key = AuthorityVerifyingKey::from_signing_key(&signing_key);
+
+    let decoded = AuthoritySigningKey::from_encoded(&signing_key.to_encoded()).unwrap();
+    assert_eq!(decoded.to_bytes(), signing_key.to_bytes());
+    assert_eq!(
+        AuthorityVerifyingKey::from_encoded(&verifying_key.to_encoded()).unwrap(),
+        verifying_key
+    );
+    assert_eq!(
+        AuthorityVerifyingKey::from_checked_hex(&verifying_key.to_checked_hex()).unwrap(),
+        verifying_key
+    );
+    assert!(AuthorityVerifyingKey::from_encoded(&signing_key.to_encoded()).is_err());
+}
+
+#[test]
+fn test_signing_key_ct_eq() {
+    let signing_key = AuthoritySigningKey::new();
+    let same = AuthoritySigningKey::from_bytes(&signing_key.to_bytes()).unwrap();
+    assert!(bool::from(signing_key.ct_eq(&same)));
+    assert!(!bool::from(signing_key.ct_eq(&AuthoritySigningKey::new())));
+    assert!(signing_key == same);
+}
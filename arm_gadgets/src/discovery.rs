@@ -0,0 +1,440 @@
+//! Scanning transactions for discovery payloads.
+//!
+//! Trial-decrypting every discovery blob of every transaction for every user
+//! doesn't scale for an indexer. A [`DiscoveryBlob`] therefore carries a
+//! short public hint of its recipient's viewing key, shared by many keys so
+//! it doesn't identify the recipient. The [`Scanner`] indexes the hints of
+//! each transaction in a [`BloomFilter`], and only trial-decrypts the blobs
+//! whose hint matches a subscriber's, at most `max_trials_per_tx` per
+//! subscriber and transaction. Matches are streamed to the subscribers as
+//! transactions are ingested, through a blocking channel or, with the
+//! `async` feature, an asynchronous stream. The scanner keeps the filters of
+//! the last [`DEFAULT_INDEX_CAPACITY`] transactions by default, evicting the
+//! oldest ones first.
+
+use crate::encryption::{generate_public_key, Ciphertext, SecretKey, SecurePlaintext};
+use anoma_rm_risc0::{
+    error::ArmError,
+    logic_instance::{ExpirableBlob, LintIssue, PayloadCategory, PayloadLinter},
+    transaction::Transaction,
+    utils::hash_bytes,
+    Digest,
+};
+use k256::{elliptic_curve::group::GroupEncoding, AffinePoint};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::mpsc::{self, Receiver, Sender},
+};
+
+const DISCOVERY_HINT_DOMAIN: &[u8] = b"ARM_DISCOVERY_HINT";
+const BLOOM_DOMAIN: &[u8] = b"ARM_DISCOVERY_BLOOM";
+
+/// The length of a discovery hint. About one in 65536 viewing keys shares a
+/// hint.
+pub const DISCOVERY_HINT_BYTES: usize = 2;
+
+/// The bits set per item in a [`BloomFilter`].
+pub const BLOOM_HASHES: u32 = 4;
+
+// The bits reserved per item in a `BloomFilter`, for about 1% false positives.
+const BLOOM_BITS_PER_ITEM: usize = 10;
+
+/// The number of transactions whose filters a [`Scanner`] keeps by default.
+pub const DEFAULT_INDEX_CAPACITY: usize = 10_000;
+
+/// Returns the discovery hint of a viewing public key.
+pub fn discovery_hint(viewing_pk: &AffinePoint) -> [u8; DISCOVERY_HINT_BYTES] {
+    let hash = hash_bytes(&[DISCOVERY_HINT_DOMAIN, &viewing_pk.to_bytes()[..]].concat());
+    let mut hint = [0u8; DISCOVERY_HINT_BYTES];
+    hint.copy_from_slice(&hash.as_bytes()[..DISCOVERY_HINT_BYTES]);
+    hint
+}
+
+/// A discovery payload blob: a ciphertext prefixed with its recipient's hint.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DiscoveryBlob {
+    /// The hint of the recipient's viewing key.
+    pub hint: [u8; DISCOVERY_HINT_BYTES],
+    /// The discovery message encrypted to the recipient.
+    pub ciphertext: Ciphertext,
+}
+
+impl DiscoveryBlob {
+    /// Encrypts a discovery message to the recipient's viewing public key.
+    pub fn encrypt(
+        message: &Vec<u8>,
+        viewing_pk: &AffinePoint,
+        sender_sk: &SecretKey,
+    ) -> Result<Self, ArmError> {
+        Ok(DiscoveryBlob {
+            hint: discovery_hint(viewing_pk),
+            ciphertext: Ciphertext::encrypt(message, viewing_pk, sender_sk)?,
+        })
+    }
+
+    /// Encodes the blob as a discovery payload blob. The first word holds the
+    /// hint.
+    pub fn to_blob(&self, deletion_criterion: u32) -> ExpirableBlob {
+        let mut hint = [0u8; 4];
+        hint[..DISCOVERY_HINT_BYTES].copy_from_slice(&self.hint);
+        let mut blob = vec![u32::from_be_bytes(hint)];
+        blob.extend(self.ciphertext.as_words());
+        ExpirableBlob {
+            blob,
+            deletion_criterion,
+        }
+    }
+
+    /// Decodes a blob produced by [`DiscoveryBlob::to_blob`]. The
+    /// ciphertext keeps the zero padding of its last word, which decryption
+    /// ignores.
+    pub fn from_blob(blob: &ExpirableBlob) -> Option<Self> {
+        let (first, words) = blob.blob.split_first()?;
+        let mut hint = [0u8; DISCOVERY_HINT_BYTES];
+        hint.copy_from_slice(&first.to_be_bytes()[..DISCOVERY_HINT_BYTES]);
+        Some(DiscoveryBlob {
+            hint,
+            ciphertext: Ciphertext::from_words(words),
+        })
+    }
+}
+
+/// Lints discovery payloads as [`DiscoveryBlob`]s, before proving.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DiscoveryBlobLinter;
+
+impl PayloadLinter for DiscoveryBlobLinter {
+    fn lint_blob(&self, category: PayloadCategory, blob: &ExpirableBlob) -> Option<LintIssue> {
+        if category != PayloadCategory::Discovery {
+            return None;
+        }
+        let Some(Ok(len)) =
+            DiscoveryBlob::from_blob(blob).map(|blob| blob.ciphertext.encoded_len())
+        else {
+            return Some(LintIssue::MalformedDiscoveryBlob);
+        };
+        let expected = 1 + len.div_ceil(4);
+        (blob.blob.len() != expected).then_some(LintIssue::WordLengthMismatch {
+            expected,
+            actual: blob.blob.len(),
+        })
+    }
+}
+
+/// A Bloom filter over the discovery hints of a transaction.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+}
+
+impl BloomFilter {
+    /// Creates an empty filter sized for `items` items.
+    pub fn with_capacity(items: usize) -> Self {
+        let words = (items.max(1) * BLOOM_BITS_PER_ITEM).div_ceil(64);
+        BloomFilter {
+            bits: vec![0; words],
+        }
+    }
+
+    /// Adds an item to the filter.
+    pub fn insert(&mut self, item: &[u8]) {
+        for bit in bit_indices(self.bits.len() * 64, item) {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    /// Returns whether the item may have been added. False positives are
+    /// possible, false negatives aren't.
+    pub fn may_contain(&self, item: &[u8]) -> bool {
+        bit_indices(self.bits.len() * 64, item)
+            .all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+}
+
+// The bits of an item in a filter of `len` bits.
+fn bit_indices(len: usize, item: &[u8]) -> impl Iterator<Item = usize> + '_ {
+    (0..BLOOM_HASHES).map(move |i| {
+        let hash = hash_bytes(&[BLOOM_DOMAIN, &i.to_be_bytes()[..], item].concat());
+        let mut word = [0u8; 8];
+        word.copy_from_slice(&hash.as_bytes()[..8]);
+        (u64::from_be_bytes(word) % len as u64) as usize
+    })
+}
+
+/// A discovery blob decrypted by a subscriber.
+pub struct DiscoveryMatch {
+    /// The id of the transaction holding the blob, as given to
+    /// [`Scanner::ingest`].
+    pub tx_id: Digest,
+    /// The index of the action holding the blob.
+    pub action: usize,
+    /// The tag of the resource whose app data holds the blob.
+    pub tag: Digest,
+    /// The decrypted discovery message.
+    pub plaintext: SecurePlaintext,
+}
+
+// Where the matches of a subscription are sent.
+enum MatchSink {
+    Blocking(Sender<DiscoveryMatch>),
+    #[cfg(feature = "async")]
+    Async(futures_channel::mpsc::UnboundedSender<DiscoveryMatch>),
+}
+
+impl MatchSink {
+    // Returns whether the match was sent, i.e. the subscriber is still
+    // listening.
+    fn send(&self, matched: DiscoveryMatch) -> bool {
+        match self {
+            MatchSink::Blocking(sender) => sender.send(matched).is_ok(),
+            #[cfg(feature = "async")]
+            MatchSink::Async(sender) => sender.unbounded_send(matched).is_ok(),
+        }
+    }
+}
+
+struct Subscription {
+    viewing_key: SecretKey,
+    hint: [u8; DISCOVERY_HINT_BYTES],
+    matches: MatchSink,
+}
+
+/// Scans ingested transactions for the discovery blobs of its subscribers.
+pub struct Scanner {
+    subscriptions: Vec<Subscription>,
+    index: BTreeMap<Digest, BloomFilter>,
+    // The indexed transactions, oldest first
+    ingested: VecDeque<Digest>,
+    index_capacity: usize,
+    max_trials_per_tx: usize,
+}
+
+impl Scanner {
+    /// Creates a scanner trying at most `max_trials_per_tx` decryptions per
+    /// subscriber and transaction, and keeping the filters of the last
+    /// [`DEFAULT_INDEX_CAPACITY`] transactions.
+    pub fn new(max_trials_per_tx: usize) -> Self {
+        Scanner {
+            subscriptions: Vec::new(),
+            index: BTreeMap::new(),
+            ingested: VecDeque::new(),
+            index_capacity: DEFAULT_INDEX_CAPACITY,
+            max_trials_per_tx,
+        }
+    }
+
+    /// Keeps the filters of the last `index_capacity` transactions only,
+    /// evicting the oldest ones right away if there are more.
+    pub fn with_index_capacity(mut self, index_capacity: usize) -> Self {
+        self.index_capacity = index_capacity;
+        self.evict();
+        self
+    }
+
+    /// Subscribes a viewing key, returning the stream of its matches in the
+    /// transactions ingested from now on. Dropping the receiver cancels the
+    /// subscription.
+    pub fn subscribe(&mut self, viewing_key: SecretKey) -> Receiver<DiscoveryMatch> {
+        let (matches, receiver) = mpsc::channel();
+        self.add_subscription(viewing_key, MatchSink::Blocking(matches));
+        receiver
+    }
+
+    /// Subscribes a viewing key like [`Scanner::subscribe`], returning its
+    /// matches as an asynchronous stream.
+    #[cfg(feature = "async")]
+    pub fn subscribe_async(
+        &mut self,
+        viewing_key: SecretKey,
+    ) -> futures_channel::mpsc::UnboundedReceiver<DiscoveryMatch> {
+        let (matches, receiver) = futures_channel::mpsc::unbounded();
+        self.add_subscription(viewing_key, MatchSink::Async(matches));
+        receiver
+    }
+
+    fn add_subscription(&mut self, viewing_key: SecretKey, matches: MatchSink) {
+        self.subscriptions.push(Subscription {
+            hint: discovery_hint(&generate_public_key(viewing_key.inner())),
+            viewing_key,
+            matches,
+        });
+    }
+
+    /// Returns the number of active subscriptions.
+    pub fn subscriptions(&self) -> usize {
+        self.subscriptions.len()
+    }
+
+    /// Returns the hint filter of an ingested transaction, unless it was
+    /// evicted or forgotten.
+    pub fn filter(&self, tx_id: &Digest) -> Option<&BloomFilter> {
+        self.index.get(tx_id)
+    }
+
+    /// Returns the number of indexed transactions.
+    pub fn indexed(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Removes the filter of a transaction, e.g. one dropped by a reorg,
+    /// returning it.
+    pub fn forget(&mut self, tx_id: &Digest) -> Option<BloomFilter> {
+        let filter = self.index.remove(tx_id)?;
+        self.ingested.retain(|ingested| ingested != tx_id);
+        Some(filter)
+    }
+
+    // Evicts the oldest filters beyond the capacity of the index.
+    fn evict(&mut self) {
+        while self.index.len() > self.index_capacity {
+            match self.ingested.pop_front() {
+                Some(oldest) => self.index.remove(&oldest),
+                None => break,
+            };
+        }
+    }
+
+    /// Indexes the discovery blobs of a transaction and streams the matches
+    /// to the subscribers. Returns the number of trial decryptions.
+    pub fn ingest(&mut self, tx_id: Digest, tx: &Transaction) -> usize {
+        let blobs: Vec<(usize, Digest, DiscoveryBlob)> = tx
+            .actions
+            .iter()
+            .enumerate()
+            .flat_map(|(index, action)| {
+                action
+                    .get_logic_verifier_inputs()
+                    .iter()
+                    .flat_map(move |input| {
+                        input
+                            .app_data
+                            .discovery_payload
+                            .iter()
+                            .filter_map(move |blob| {
+                                DiscoveryBlob::from_blob(blob).map(|blob| (index, input.tag, blob))
+                            })
+                    })
+            })
+            .collect();
+
+        let mut filter = BloomFilter::with_capacity(blobs.len());
+        for (_, _, blob) in &blobs {
+            filter.insert(&blob.hint);
+        }
+
+        let mut trials = 0;
+        self.subscriptions.retain(|subscription| {
+            if !filter.may_contain(&subscription.hint) {
+                return true;
+            }
+            let candidates = blobs
+                .iter()
+                .filter(|(_, _, blob)| blob.hint == subscription.hint)
+                .take(self.max_trials_per_tx);
+            for (action, tag, blob) in candidates {
+                trials += 1;
+                if let Ok(plaintext) = blob.ciphertext.decrypt(&subscription.viewing_key) {
+                    let matched = DiscoveryMatch {
+                        tx_id,
+                        action: *action,
+                        tag: *tag,
+                        plaintext,
+                    };
+                    if !subscription.matches.send(matched) {
+                        return false;
+                    }
+                }
+            }
+            true
+        });
+        if self.index.insert(tx_id, filter).is_none() {
+            self.ingested.push_back(tx_id);
+        }
+        self.evict();
+        trials
+    }
+}
+
+#[test]
+fn test_scanner_subscribe() {
+    use crate::encryption::random_keypair;
+    use anoma_rm_risc0::{
+        action::Action, delta_proof::DeltaWitness, logic_instance::AppData,
+        logic_proof::LogicVerifierInputs, transaction::Delta,
+    };
+
+    let (alice_sk, alice_pk) = random_keypair();
+    let (bob_sk, _) = random_keypair();
+    let (sender_sk, _) = random_keypair();
+
+    let mut scanner = Scanner::new(4);
+    let alice = scanner.subscribe(alice_sk.clone());
+    let bob = scanner.subscribe(bob_sk);
+
+    let blob = DiscoveryBlob::encrypt(&b"for alice".to_vec(), &alice_pk, &sender_sk).unwrap();
+    assert_eq!(
+        DiscoveryBlob::from_blob(&blob.to_blob(0)).unwrap().hint,
+        blob.hint
+    );
+    let mut app_data = AppData::default();
+    app_data.add_discovery_payload(blob.to_blob(0));
+    assert!(app_data.lint(true, &[&DiscoveryBlobLinter]).is_empty());
+    let mut malformed = app_data.clone();
+    malformed.add_discovery_payload(ExpirableBlob::default());
+    assert_eq!(
+        malformed.lint(true, &[&DiscoveryBlobLinter])[0].issue,
+        LintIssue::MalformedDiscoveryBlob
+    );
+    let tag = Digest::from_bytes([7u8; 32]);
+    let action = Action {
+        compliance_units: vec![],
+        logic_verifier_inputs: vec![LogicVerifierInputs {
+            tag,
+            verifying_key: Digest::default(),
+            app_data,
+            proof: None,
+            app_data_by_digest: false,
+            binds_image_id: false,
+            logic_ref_opening: None,
+        }],
+        resource_refs: vec![],
+    };
+    let tx = Transaction::create(
+        vec![action],
+        Delta::Witness(DeltaWitness::from_bytes(&[1u8; 32]).unwrap()),
+    );
+
+    let tx_id = Digest::from_bytes([1u8; 32]);
+    let trials = scanner.ingest(tx_id, &tx);
+    let matched = alice.try_recv().unwrap();
+    assert_eq!(
+        (matched.tx_id, matched.action, matched.tag),
+        (tx_id, 0, tag)
+    );
+    assert_eq!(matched.plaintext.as_bytes(), b"for alice");
+    assert!(bob.try_recv().is_err());
+    // Only subscribers sharing Alice's hint try to decrypt.
+    assert!((1..=2).contains(&trials));
+    assert!(scanner.filter(&tx_id).unwrap().may_contain(&blob.hint));
+
+    drop(alice);
+    scanner.ingest(Digest::from_bytes([2u8; 32]), &tx);
+    assert_eq!(scanner.subscriptions(), 1);
+
+    // The index is bounded, evicting the oldest filters first.
+    let mut scanner = scanner.with_index_capacity(2);
+    scanner.ingest(Digest::from_bytes([3u8; 32]), &tx);
+    assert_eq!(scanner.indexed(), 2);
+    assert!(scanner.filter(&tx_id).is_none());
+    assert!(scanner.forget(&Digest::from_bytes([2u8; 32])).is_some());
+    assert_eq!(scanner.indexed(), 1);
+
+    #[cfg(feature = "async")]
+    {
+        let mut alice = scanner.subscribe_async(alice_sk);
+        scanner.ingest(Digest::from_bytes([4u8; 32]), &tx);
+        let matched = alice.try_next().unwrap().unwrap();
+        assert_eq!(matched.plaintext.as_bytes(), b"for alice");
+    }
+}
@@ -1,24 +1,28 @@
 //! ARM encryption gadgets for resource logics(applications).
+//!
+//! Secret keys and plaintexts are only compared in constant time, through
+//! [`ConstantTimeEq`]; `==` on them does the same.
 
 use aes_gcm::{aead::Aead, Aes256Gcm, Key, KeyInit};
 use anoma_rm_risc0::{
     error::ArmError,
+    key_encoding::KeyEncoding,
+    rng::ArmRng,
     utils::{bytes_to_words, hash_bytes, words_to_bytes},
 };
 use k256::{
     elliptic_curve::{
         group::{prime::PrimeCurveAffine, Group, GroupEncoding},
-        Field,
+        PrimeField,
     },
     AffinePoint, ProjectivePoint, Scalar,
 };
-use rand::rngs::OsRng;
-use rand::Rng;
 use serde::{Deserialize, Serialize};
+use subtle::{Choice, ConstantTimeEq};
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
 /// The secret key used for encryption and decryption.
-#[derive(Clone, Serialize, Deserialize, Zeroize, ZeroizeOnDrop, PartialEq, Eq)]
+#[derive(Clone, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
 pub struct SecretKey(Scalar);
 
 impl SecretKey {
@@ -29,8 +33,7 @@ impl SecretKey {
 
     /// Generates a random SecretKey.
     pub fn random() -> Self {
-        let sk = Scalar::random(&mut OsRng);
-        SecretKey(sk)
+        SecretKey(ArmRng::new().scalar())
     }
 
     /// Returns a reference to the inner Scalar.
@@ -39,6 +42,38 @@ impl SecretKey {
     }
 }
 
+/// Secret keys double as viewing keys: they decrypt the payloads sent to
+/// their public key.
+impl KeyEncoding for SecretKey {
+    const HRP: &'static str = "armview";
+
+    fn key_bytes(&self) -> Vec<u8> {
+        self.0.to_bytes().to_vec()
+    }
+
+    fn from_key_bytes(bytes: &[u8]) -> Result<Self, ArmError> {
+        let bytes: [u8; 32] = bytes.try_into().map_err(|_| ArmError::InvalidKeyEncoding)?;
+        Scalar::from_repr(bytes.into())
+            .into_option()
+            .map(SecretKey)
+            .ok_or(ArmError::InvalidKeyEncoding)
+    }
+}
+
+impl ConstantTimeEq for SecretKey {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
+
+impl PartialEq for SecretKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for SecretKey {}
+
 impl Default for SecretKey {
     fn default() -> Self {
         SecretKey(Scalar::ONE)
@@ -76,7 +111,7 @@ impl Ciphertext {
         receiver_pk: &AffinePoint,
         sender_sk: &SecretKey,
     ) -> Result<Self, ArmError> {
-        let nonce: [u8; 12] = OsRng.gen();
+        let nonce: [u8; 12] = ArmRng::new().try_bytes()?;
         Self::encrypt_with_nonce(message, receiver_pk, sender_sk, nonce)
     }
 
@@ -107,11 +142,7 @@ impl Ciphertext {
 
     /// Decrypts the ciphertext using the receiver's secret key.
     pub fn decrypt(&self, sk: &SecretKey) -> Result<SecurePlaintext, ArmError> {
-        if self.inner().is_empty() {
-            return Err(ArmError::DecryptionFailed);
-        }
-        let cipher: InnerCiphert =
-            bincode::deserialize(self.inner()).map_err(|_| ArmError::DeserializationError)?;
+        let cipher = self.inner_ciphertext()?;
         // Generate the secret key using Diffie-Hellman exchange
         let inner_secret_key = InnerSecretKey::from_decryption(&cipher.pk, sk.inner())?;
 
@@ -125,6 +156,40 @@ impl Ciphertext {
 
         Ok(SecurePlaintext::new(plaintext))
     }
+
+    /// Decrypts the ciphertext with the sender's secret key and the
+    /// receiver's public key it was encrypted to. Senders use it to check
+    /// that the receiver will be able to decrypt.
+    pub fn decrypt_as_sender(
+        &self,
+        receiver_pk: &AffinePoint,
+        sender_sk: &SecretKey,
+    ) -> Result<SecurePlaintext, ArmError> {
+        let cipher = self.inner_ciphertext()?;
+        if cipher.pk != generate_public_key(sender_sk.inner()) {
+            return Err(ArmError::DecryptionFailed);
+        }
+        let inner_secret_key = InnerSecretKey::from_encryption(receiver_pk, sender_sk.inner())?;
+        let plaintext = Aes256Gcm::new(&inner_secret_key.inner())
+            .decrypt(&cipher.nonce.into(), cipher.cipher.as_ref())
+            .map_err(|_| ArmError::DecryptionFailed)?;
+        Ok(SecurePlaintext::new(plaintext))
+    }
+
+    /// Returns the length in bytes of the encoded ciphertext, without the
+    /// padding of [`Ciphertext::from_words`].
+    pub fn encoded_len(&self) -> Result<usize, ArmError> {
+        bincode::serialized_size(&self.inner_ciphertext()?)
+            .map(|len| len as usize)
+            .map_err(|_| ArmError::SerializationError)
+    }
+
+    fn inner_ciphertext(&self) -> Result<InnerCiphert, ArmError> {
+        if self.inner().is_empty() {
+            return Err(ArmError::DecryptionFailed);
+        }
+        bincode::deserialize(self.inner()).map_err(|_| ArmError::DeserializationError)
+    }
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
@@ -208,7 +273,7 @@ impl InnerSecretKey {
 }
 
 /// The plaintext produced by decryption.
-#[derive(Zeroize, ZeroizeOnDrop, PartialEq, Eq)]
+#[derive(Zeroize, ZeroizeOnDrop)]
 pub struct SecurePlaintext(Vec<u8>);
 
 impl SecurePlaintext {
@@ -223,6 +288,22 @@ impl SecurePlaintext {
     }
 }
 
+/// Plaintexts of different lengths compare unequal without inspecting their
+/// bytes; only the length is leaked.
+impl ConstantTimeEq for SecurePlaintext {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
+
+impl PartialEq for SecurePlaintext {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for SecurePlaintext {}
+
 /// Generates a public key (AffinePoint) from a given private key (Scalar).
 pub fn generate_public_key(sk: &Scalar) -> AffinePoint {
     // Compute public key as generator * private key
@@ -231,7 +312,7 @@ pub fn generate_public_key(sk: &Scalar) -> AffinePoint {
 
 /// Generates a random private key (Scalar) and its corresponding public key (ProjectivePoint)
 pub fn random_keypair() -> (SecretKey, AffinePoint) {
-    let sk = Scalar::random(&mut OsRng);
+    let sk = ArmRng::new().scalar();
     let pk = generate_public_key(&sk);
 
     (SecretKey::new(sk), pk)
@@ -246,7 +327,7 @@ fn test_encryption() {
 
     // Example message as Vec<u8>
     let message = b"Hello, AES-256-GCM encryption!".to_vec();
-    let nonce: [u8; 12] = rand::random();
+    let nonce: [u8; 12] = ArmRng::new().bytes();
 
     // Encryption
     let cipher = Ciphertext::encrypt_with_nonce(&message, &receiver_pk, &sender_sk, nonce).unwrap();
@@ -260,3 +341,29 @@ fn test_encryption() {
     let decrypted_from_words = cipher_from_words.decrypt(&receiver_sk).unwrap();
     assert_eq!(message, decrypted_from_words.as_bytes());
 }
+
+#[test]
+fn test_viewing_key_encoding() {
+    let (sk, _) = random_keypair();
+    let encoded = sk.to_encoded();
+    assert!(encoded.starts_with("armview1"));
+    assert!(SecretKey::from_encoded(&encoded).unwrap() == sk);
+    assert!(SecretKey::from_checked_hex(&sk.to_checked_hex()).unwrap() == sk);
+}
+
+#[test]
+fn test_secret_ct_eq() {
+    let (sk, _) = random_keypair();
+    let other = SecretKey::random();
+    assert!(bool::from(sk.ct_eq(&sk.clone())));
+    assert!(!bool::from(sk.ct_eq(&other)));
+
+    let plaintext = SecurePlaintext::new(b"secret".to_vec());
+    assert!(bool::from(
+        plaintext.ct_eq(&SecurePlaintext::new(b"secret".to_vec()))
+    ));
+    assert!(!bool::from(
+        plaintext.ct_eq(&SecurePlaintext::new(b"secreT".to_vec()))
+    ));
+    assert!(plaintext != SecurePlaintext::new(b"secrets".to_vec()));
+}
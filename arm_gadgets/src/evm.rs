@@ -1,8 +1,19 @@
 //! ARM EVM gadgets for resource logics(applications).
 
-use alloy_primitives::B256;
+use alloy_primitives::{Address, B256};
+#[cfg(feature = "transaction")]
+use alloy_sol_types::SolError;
 use alloy_sol_types::{sol, SolValue};
-use anoma_rm_risc0::resource::Resource as ArmResource;
+use anoma_rm_risc0::{
+    action_tree::MerkleTree, error::ArmError, merkle_path::MerklePath,
+    resource::Resource as ArmResource, utils::hash_bytes, Digest,
+};
+#[cfg(feature = "transaction")]
+use anoma_rm_risc0::{
+    logic_instance::AppData,
+    utils::words_to_bytes,
+    verification::{RejectionReason, VerificationContext, VerificationPolicy},
+};
 
 sol! {
     struct Resource {
@@ -96,6 +107,215 @@ impl ForwarderCalldata {
     pub fn decode(encoded: &[u8]) -> Option<Self> {
         Self::abi_decode_params(encoded).ok()
     }
+
+    /// In-circuit check that the forwarder is in the allowlist with the
+    /// given root, `path` being the path of the forwarder's leaf.
+    pub fn is_allowlisted(&self, allowlist_root: &Digest, path: &MerklePath) -> bool {
+        path.root(&forwarder_leaf(&self.untrustedForwarder)) == *allowlist_root
+    }
+}
+
+/// Returns the allowlist leaf of a forwarder address.
+pub fn forwarder_leaf(forwarder: &Address) -> Digest {
+    hash_bytes(forwarder.as_slice())
+}
+
+/// The forwarder contracts external payloads may call, committed to by the
+/// root of the tree of their leaves.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ForwarderAllowlist {
+    forwarders: Vec<Address>,
+}
+
+impl ForwarderAllowlist {
+    /// Creates an allowlist of the given forwarders, in order.
+    pub fn new(forwarders: Vec<Address>) -> Self {
+        ForwarderAllowlist { forwarders }
+    }
+
+    /// Returns whether the forwarder is allowed.
+    pub fn contains(&self, forwarder: &Address) -> bool {
+        self.forwarders.contains(forwarder)
+    }
+
+    fn tree(&self) -> MerkleTree {
+        MerkleTree::new(self.forwarders.iter().map(forwarder_leaf).collect())
+    }
+
+    /// Returns the root of the allowlist.
+    pub fn root(&self) -> Result<Digest, ArmError> {
+        self.tree().root()
+    }
+
+    /// Returns the path of an allowed forwarder, for
+    /// [`ForwarderCalldata::is_allowlisted`].
+    pub fn path(&self, forwarder: &Address) -> Result<MerklePath, ArmError> {
+        self.tree().generate_path(&forwarder_leaf(forwarder))
+    }
+}
+
+sol! {
+    struct FeeSplitUnwrap {
+        address token;
+        address receiver;
+        uint128 amount;
+        address feeCollector;
+        uint128 fee;
+    }
+}
+
+impl FeeSplitUnwrap {
+    /// Encodes the FeeSplitUnwrap struct into a byte vector using ABI encoding.
+    pub fn encode(&self) -> Vec<u8> {
+        self.abi_encode_params()
+    }
+
+    /// Decodes a byte slice into a FeeSplitUnwrap struct using ABI decoding.
+    pub fn decode(encoded: &[u8]) -> Option<Self> {
+        Self::abi_decode_params(encoded).ok()
+    }
+}
+
+/// The maximum fee of a [`FeeRoute`], in basis points.
+pub const MAX_FEE_BPS: u16 = 10_000;
+
+/// Pays a fee out of the unwrapped quantity of wrapped ERC20 resources: the
+/// forwarder call transfers `fee_bps` of the quantity to the fee collector
+/// (e.g. the relayer) and the rest to the receiver, so fees don't need a
+/// separate action.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FeeRoute {
+    /// The address the fee is transferred to.
+    pub collector: Address,
+    /// The fee, in basis points of the unwrapped quantity.
+    pub fee_bps: u16,
+}
+
+impl FeeRoute {
+    /// Creates a fee route, failing if the fee is over 100%.
+    pub fn new(collector: Address, fee_bps: u16) -> Result<Self, ArmError> {
+        if fee_bps > MAX_FEE_BPS {
+            return Err(ArmError::InvalidFeeRoute);
+        }
+        Ok(FeeRoute { collector, fee_bps })
+    }
+
+    /// Splits a quantity into the amount of the receiver and the fee, rounding
+    /// the fee down.
+    pub fn split(&self, quantity: u128) -> (u128, u128) {
+        let bps = u128::from(self.fee_bps.min(MAX_FEE_BPS));
+        let scale = u128::from(MAX_FEE_BPS);
+        // Doesn't overflow, unlike quantity * bps
+        let fee = quantity / scale * bps + quantity % scale * bps / scale;
+        (quantity - fee, fee)
+    }
+
+    /// Creates the forwarder call unwrapping `quantity` of the token, split
+    /// between the receiver and the fee collector.
+    pub fn calldata(
+        &self,
+        forwarder: &Address,
+        token: Address,
+        receiver: Address,
+        quantity: u128,
+    ) -> ForwarderCalldata {
+        let (amount, fee) = self.split(quantity);
+        let input = FeeSplitUnwrap {
+            token,
+            receiver,
+            amount,
+            feeCollector: self.collector,
+            fee,
+        };
+        ForwarderCalldata::from_bytes(forwarder.as_slice(), input.encode(), vec![])
+    }
+
+    /// In-circuit check that the forwarder call unwraps exactly `quantity`,
+    /// with the fee of the route paid to its collector.
+    pub fn check_calldata(&self, calldata: &ForwarderCalldata, quantity: u128) -> bool {
+        let Some(input) = FeeSplitUnwrap::decode(&calldata.input) else {
+            return false;
+        };
+        let (amount, fee) = self.split(quantity);
+        self.fee_bps <= MAX_FEE_BPS
+            && input.feeCollector == self.collector
+            && input.amount == amount
+            && input.fee == fee
+    }
+}
+
+/// Host-side check that every external payload is a call through an
+/// allowed forwarder. The allowlist must have the root of the verification
+/// context, so nodes agree on the allowlist they enforce.
+#[cfg(feature = "transaction")]
+#[derive(Debug, Clone)]
+pub struct ForwarderAllowlistPolicy {
+    allowlist: ForwarderAllowlist,
+    root: Digest,
+}
+
+#[cfg(feature = "transaction")]
+impl ForwarderAllowlistPolicy {
+    /// Creates the policy enforcing the allowlist.
+    pub fn new(allowlist: ForwarderAllowlist) -> Result<Self, ArmError> {
+        let root = allowlist.root()?;
+        Ok(ForwarderAllowlistPolicy { allowlist, root })
+    }
+}
+
+#[cfg(feature = "transaction")]
+impl VerificationPolicy for ForwarderAllowlistPolicy {
+    fn on_app_data(
+        &self,
+        ctx: &VerificationContext,
+        _action: usize,
+        _tag: &Digest,
+        app_data: &AppData,
+    ) -> Result<(), RejectionReason> {
+        if app_data.external_payload.is_empty() {
+            return Ok(());
+        }
+        if ctx.forwarder_allowlist_root != Some(self.root) {
+            return Err(RejectionReason::PolicyViolation);
+        }
+        for payload in &app_data.external_payload {
+            let calldata = ForwarderCalldata::decode(words_to_bytes(&payload.blob))
+                .ok_or(RejectionReason::Malformed)?;
+            if !self.allowlist.contains(&calldata.untrustedForwarder) {
+                return Err(RejectionReason::PolicyViolation);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "transaction")]
+sol! {
+    /// The error the protocol adapter reverts with when it rejects a
+    /// transaction, carrying the code of the [`RejectionReason`].
+    error TransactionRejected(uint8 code);
+}
+
+#[cfg(feature = "transaction")]
+impl From<RejectionReason> for TransactionRejected {
+    fn from(reason: RejectionReason) -> Self {
+        TransactionRejected {
+            code: reason.code(),
+        }
+    }
+}
+
+#[cfg(feature = "transaction")]
+impl TransactionRejected {
+    /// Decodes the revert data of a rejected transaction.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        <Self as SolError>::abi_decode(data).ok()
+    }
+
+    /// Returns the reason of the rejection, if the code is known.
+    pub fn reason(&self) -> Option<RejectionReason> {
+        RejectionReason::from_code(self.code)
+    }
 }
 
 #[test]
@@ -119,6 +339,113 @@ fn forward_call_data_test() {
     assert_eq!(data.output, decoded_data.output);
 }
 
+#[test]
+fn forwarder_allowlist_test() {
+    let allowed: Address = "0x1111111111111111111111111111111111111111"
+        .parse()
+        .unwrap();
+    let other: Address = "0x2222222222222222222222222222222222222222"
+        .parse()
+        .unwrap();
+    let allowlist = ForwarderAllowlist::new(vec![allowed, Address::ZERO]);
+    let root = allowlist.root().unwrap();
+    assert!(allowlist.contains(&allowed) && !allowlist.contains(&other));
+    assert_eq!(allowlist.path(&other), Err(ArmError::InvalidLeaf));
+
+    let path = allowlist.path(&allowed).unwrap();
+    let calldata = ForwarderCalldata::from_bytes(allowed.as_slice(), vec![0xab], vec![0xcd]);
+    assert!(calldata.is_allowlisted(&root, &path));
+    let calldata = ForwarderCalldata::from_bytes(other.as_slice(), vec![0xab], vec![0xcd]);
+    assert!(!calldata.is_allowlisted(&root, &path));
+}
+
+#[test]
+fn fee_route_test() {
+    let forwarder: Address = "0x1111111111111111111111111111111111111111"
+        .parse()
+        .unwrap();
+    let collector: Address = "0x2222222222222222222222222222222222222222"
+        .parse()
+        .unwrap();
+    let route = FeeRoute::new(collector, 30).unwrap();
+    assert_eq!(route.split(10_000), (9_970, 30));
+    assert_eq!(route.split(99), (99, 0));
+    assert_eq!(
+        route.split(u128::MAX).0 + route.split(u128::MAX).1,
+        u128::MAX
+    );
+    assert_eq!(
+        FeeRoute::new(collector, MAX_FEE_BPS + 1),
+        Err(ArmError::InvalidFeeRoute)
+    );
+
+    let calldata = route.calldata(&forwarder, Address::ZERO, Address::ZERO, 10_000);
+    let decoded = ForwarderCalldata::decode(&calldata.encode()).unwrap();
+    assert!(route.check_calldata(&decoded, 10_000));
+    assert!(!route.check_calldata(&decoded, 20_000));
+    let other = FeeRoute::new(forwarder, 30).unwrap();
+    assert!(!other.check_calldata(&decoded, 10_000));
+    let higher = FeeRoute::new(collector, 31).unwrap();
+    assert!(!higher.check_calldata(&decoded, 10_000));
+}
+
+#[cfg(feature = "transaction")]
+#[test]
+fn forwarder_allowlist_policy_test() {
+    use anoma_rm_risc0::{logic_instance::ExpirableBlob, utils::bytes_to_words};
+
+    let allowed: Address = "0x1111111111111111111111111111111111111111"
+        .parse()
+        .unwrap();
+    let allowlist = ForwarderAllowlist::new(vec![allowed]);
+    let policy = ForwarderAllowlistPolicy::new(allowlist.clone()).unwrap();
+    let ctx = VerificationContext {
+        forwarder_allowlist_root: Some(allowlist.root().unwrap()),
+        ..Default::default()
+    };
+    let app_data = |forwarder: &Address| {
+        let calldata = ForwarderCalldata::from_bytes(forwarder.as_slice(), vec![1], vec![2]);
+        let mut app_data = AppData::default();
+        app_data.add_external_payload(ExpirableBlob {
+            blob: bytes_to_words(&calldata.encode()),
+            deletion_criterion: 0,
+        });
+        app_data
+    };
+    let tag = Digest::default();
+
+    assert_eq!(
+        policy.on_app_data(&ctx, 0, &tag, &app_data(&allowed)),
+        Ok(())
+    );
+    assert_eq!(
+        policy.on_app_data(&ctx, 0, &tag, &app_data(&Address::ZERO)),
+        Err(RejectionReason::PolicyViolation)
+    );
+    assert_eq!(
+        policy.on_app_data(&VerificationContext::new(), 0, &tag, &app_data(&allowed)),
+        Err(RejectionReason::PolicyViolation)
+    );
+    assert_eq!(
+        policy.on_app_data(&VerificationContext::new(), 0, &tag, &AppData::default()),
+        Ok(())
+    );
+}
+
+#[cfg(feature = "transaction")]
+#[test]
+fn transaction_rejected_test() {
+    // The selector of `TransactionRejected(uint8)` checked by the adapter.
+    assert_eq!(TransactionRejected::SELECTOR, [0xff, 0x3a, 0x47, 0xf6]);
+    for code in 1..=11 {
+        let reason = RejectionReason::from_code(code).unwrap();
+        let data = TransactionRejected::from(reason).abi_encode();
+        let decoded = TransactionRejected::decode(&data).unwrap();
+        assert_eq!(decoded.reason(), Some(reason));
+    }
+    assert_eq!(TransactionRejected { code: 0 }.reason(), None);
+}
+
 #[test]
 fn evm_resource_test() {
     let arm_resource = ArmResource::default();
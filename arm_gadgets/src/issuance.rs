@@ -0,0 +1,309 @@
+//! Batch issuance of resources, e.g. for airdrops.
+//!
+//! Each compliance unit creates a single resource, so minting to N
+//! recipients takes N units, each consuming an ephemeral mint of the
+//! recipient's quantity so the kind balances. The [`IssuanceBuilder`] packs
+//! the units into as few actions as possible, at most
+//! `max_units_per_action` each, and pads the last action so that every
+//! action tree is full. It also encrypts a [`ResourceNote`] to each
+//! recipient under a single sender key, ready for the resource payload of
+//! the created resource. The resource logic of the kind must authorize
+//! consuming the mints.
+
+use crate::{
+    encryption::SecretKey,
+    memo::{Memo, ResourceNote, ResourceNoteLinter},
+};
+use anoma_rm_risc0::{
+    action_tree::MerkleTree,
+    compliance::{ComplianceWitness, Rcv, INITIAL_ROOT},
+    delta_proof::DeltaWitness,
+    dust_policy::DustPolicy,
+    error::ArmError,
+    logic_instance::{AppData, ExpirableBlob, LintSeverity, PayloadLint},
+    nullifier_key::{NullifierKey, NullifierKeyCommitment},
+    padding::PaddingUnit,
+    resource::Resource,
+    rng::ArmRng,
+    Digest,
+};
+use k256::AffinePoint;
+
+/// The default maximum number of compliance units per action.
+pub const DEFAULT_MAX_UNITS_PER_ACTION: usize = 64;
+
+/// A recipient of an issuance.
+#[derive(Clone, Debug)]
+pub struct Recipient {
+    /// The nullifier key commitment owning the created resource.
+    pub nk_commitment: NullifierKeyCommitment,
+    /// The value reference of the created resource.
+    pub value_ref: Digest,
+    /// The quantity issued.
+    pub quantity: u128,
+    /// The public key the resource note is encrypted to.
+    pub encryption_pk: AffinePoint,
+    /// A memo for the recipient, if any.
+    pub memo: Option<Memo>,
+}
+
+/// An action of an issuance.
+#[derive(Clone)]
+pub struct IssuedAction {
+    /// The compliance witnesses minting to the recipients of the action.
+    pub witnesses: Vec<ComplianceWitness>,
+    /// The resource payloads of the created resources, in witness order.
+    pub payloads: Vec<ExpirableBlob>,
+    /// The padding units filling the action tree.
+    pub padding: Vec<PaddingUnit>,
+}
+
+impl IssuedAction {
+    /// Returns the action tree leaves: the nullifier and commitment of each
+    /// unit, padding units included, in the canonical order.
+    pub fn tags(&self) -> Result<Vec<Digest>, ArmError> {
+        Ok(self.tree()?.leaves)
+    }
+
+    /// Returns the root of the action tree, needed to prove the logics.
+    pub fn action_tree_root(&self) -> Result<Digest, ArmError> {
+        self.tree()?.root()
+    }
+
+    fn tree(&self) -> Result<MerkleTree, ArmError> {
+        let mut units = Vec::with_capacity(self.witnesses.len() + self.padding.len());
+        for witness in &self.witnesses {
+            units.push((
+                witness.consumed_resource.nullifier(&witness.nf_key)?,
+                witness.created_resource.commitment(),
+            ));
+        }
+        for unit in &self.padding {
+            let [nullifier, commitment] = unit.tags()?;
+            units.push((nullifier, commitment));
+        }
+        Ok(MerkleTree::from_units(units))
+    }
+
+    /// Returns the rcvs of the units, the padding units last.
+    pub fn rcvs(&self) -> Vec<Rcv> {
+        self.witnesses
+            .iter()
+            .chain(self.padding.iter().map(|unit| &unit.witness))
+            .map(|witness| witness.rcv)
+            .collect()
+    }
+}
+
+/// The actions of an issuance.
+#[derive(Clone)]
+pub struct Issuance {
+    /// The actions, holding the recipients in order.
+    pub actions: Vec<IssuedAction>,
+    /// The warnings of linting the resource payloads.
+    pub warnings: Vec<PayloadLint>,
+}
+
+impl Issuance {
+    /// Returns the resources created for the recipients, in order.
+    pub fn created_resources(&self) -> Vec<Resource> {
+        self.actions
+            .iter()
+            .flat_map(|action| action.witnesses.iter())
+            .map(|witness| witness.created_resource)
+            .collect()
+    }
+
+    /// Returns the delta witness of all the units of the issuance.
+    pub fn delta_witness(&self) -> Result<DeltaWitness, ArmError> {
+        let rcvs: Vec<Rcv> = self.actions.iter().flat_map(IssuedAction::rcvs).collect();
+        DeltaWitness::from_rcvs(&rcvs)
+    }
+}
+
+/// Builds an [`Issuance`] of a resource kind.
+pub struct IssuanceBuilder {
+    logic_ref: Digest,
+    label_ref: Digest,
+    nf_key: NullifierKey,
+    recipients: Vec<Recipient>,
+    max_units_per_action: usize,
+    padding: bool,
+    deletion_criterion: u32,
+    dust_policy: DustPolicy,
+}
+
+impl IssuanceBuilder {
+    /// Starts an issuance of the kind, whose mints are consumed with the
+    /// issuer's `nf_key`.
+    pub fn new(logic_ref: Digest, label_ref: Digest, nf_key: NullifierKey) -> Self {
+        IssuanceBuilder {
+            logic_ref,
+            label_ref,
+            nf_key,
+            recipients: Vec::new(),
+            max_units_per_action: DEFAULT_MAX_UNITS_PER_ACTION,
+            padding: true,
+            deletion_criterion: 0,
+            dust_policy: DustPolicy::default(),
+        }
+    }
+
+    /// Adds a recipient.
+    pub fn recipient(mut self, recipient: Recipient) -> Self {
+        self.recipients.push(recipient);
+        self
+    }
+
+    /// Adds recipients.
+    pub fn recipients(mut self, recipients: impl IntoIterator<Item = Recipient>) -> Self {
+        self.recipients.extend(recipients);
+        self
+    }
+
+    /// Sets the maximum number of units per action, rounded down to a power
+    /// of two so full actions have full trees.
+    pub fn with_max_units_per_action(mut self, max_units_per_action: usize) -> Self {
+        self.max_units_per_action = max_units_per_action
+            .checked_ilog2()
+            .map_or(1, |log| 1 << log);
+        self
+    }
+
+    /// Sets whether the last action is padded to a full tree. Defaults to
+    /// `true`.
+    pub fn with_padding(mut self, padding: bool) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Sets the deletion criterion of the resource payloads.
+    pub fn with_deletion_criterion(mut self, deletion_criterion: u32) -> Self {
+        self.deletion_criterion = deletion_criterion;
+        self
+    }
+
+    /// Rejects recipients of quantities below the policy's minimum.
+    pub fn with_dust_policy(mut self, dust_policy: DustPolicy) -> Self {
+        self.dust_policy = dust_policy;
+        self
+    }
+
+    /// Creates the compliance witnesses, resource payloads and padding of the
+    /// actions. Fails if a recipient's quantity is dust.
+    pub fn build(self) -> Result<Issuance, ArmError> {
+        let sender_sk = SecretKey::random();
+        let nk_commitment = self.nf_key.commit();
+        let mut actions = Vec::with_capacity(self.recipients.len() / self.max_units_per_action + 1);
+        let mut warnings = Vec::new();
+        for chunk in self.recipients.chunks(self.max_units_per_action) {
+            let mut witnesses = Vec::with_capacity(chunk.len());
+            let mut payloads = Vec::with_capacity(chunk.len());
+            for recipient in chunk {
+                let mint = Resource::create(
+                    self.logic_ref,
+                    self.label_ref,
+                    recipient.quantity,
+                    Digest::default(),
+                    true,
+                    Digest::from_bytes(ArmRng::new().try_bytes()?),
+                    nk_commitment,
+                );
+                let mut created = Resource::create(
+                    self.logic_ref,
+                    self.label_ref,
+                    recipient.quantity,
+                    recipient.value_ref,
+                    false,
+                    Digest::default(),
+                    recipient.nk_commitment,
+                );
+                created.set_nonce_from_nf(&mint, &self.nf_key)?;
+                self.dust_policy.check_resource(&created)?;
+
+                let mut note = ResourceNote::new(created);
+                note.memo = recipient.memo.clone();
+                let payload = note.to_blob(
+                    &recipient.encryption_pk,
+                    &sender_sk,
+                    self.deletion_criterion,
+                )?;
+                let mut app_data = AppData::new();
+                app_data.add_resource_payload(payload.clone());
+                let linter = ResourceNoteLinter {
+                    receiver_pk: &recipient.encryption_pk,
+                    sender_sk: &sender_sk,
+                };
+                for lint in app_data.lint(false, &[&linter]) {
+                    if lint.severity == LintSeverity::Error {
+                        return Err(ArmError::PayloadLintFailed(lint.to_string()));
+                    }
+                    warnings.push(lint);
+                }
+                payloads.push(payload);
+                witnesses.push(ComplianceWitness::from_resources(
+                    mint,
+                    *INITIAL_ROOT,
+                    self.nf_key.clone(),
+                    created,
+                ));
+            }
+            let padding_units = if self.padding {
+                chunk.len().next_power_of_two() - chunk.len()
+            } else {
+                0
+            };
+            let padding = (0..padding_units)
+                .map(|_| PaddingUnit::new())
+                .collect::<Result<_, _>>()?;
+            actions.push(IssuedAction {
+                witnesses,
+                payloads,
+                padding,
+            });
+        }
+        Ok(Issuance { actions, warnings })
+    }
+}
+
+#[test]
+fn test_issuance_1k_recipients() {
+    use crate::encryption::random_keypair;
+
+    let (receiver_sk, receiver_pk) = random_keypair();
+    let (issuer_nf_key, _) = NullifierKey::random_pair();
+    let recipients = (0..1000u128).map(|i| Recipient {
+        nk_commitment: NullifierKey::random_pair().1,
+        value_ref: Digest::default(),
+        quantity: i + 1,
+        encryption_pk: receiver_pk,
+        memo: None,
+    });
+    let issuance = IssuanceBuilder::new(
+        Digest::from_bytes([1u8; 32]),
+        Digest::from_bytes([2u8; 32]),
+        issuer_nf_key,
+    )
+    .recipients(recipients)
+    .with_max_units_per_action(100)
+    .build()
+    .unwrap();
+
+    // 1000 recipients at 64 units per action, the last 40 padded to 64.
+    assert_eq!(issuance.actions.len(), 16);
+    assert!(issuance.warnings.is_empty());
+    let last = issuance.actions.last().unwrap();
+    assert_eq!((last.witnesses.len(), last.padding.len()), (40, 24));
+    assert_eq!(last.tags().unwrap().len(), 128);
+    assert!(issuance.actions[..15]
+        .iter()
+        .all(|action| action.witnesses.len() == 64 && action.padding.is_empty()));
+
+    let created = issuance.created_resources();
+    assert_eq!(created.len(), 1000);
+    assert_eq!(created[999].quantity, 1000);
+    let note = ResourceNote::from_blob(&last.payloads[39], &receiver_sk).unwrap();
+    assert_eq!(note.resource, created[999]);
+    assert!(issuance.actions[0].witnesses[0].constrain().is_ok());
+    assert!(issuance.delta_witness().is_ok());
+}
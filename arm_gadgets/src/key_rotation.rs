@@ -0,0 +1,254 @@
+//! Rotation of authorization keys bound to resources.
+//!
+//! A resource is bound to an authorization key when its `value_ref` is
+//! [`auth_key_commitment`] of that key. Rotating the key consumes the
+//! resource and creates a successor bound to the new key, authorized by the
+//! old key signing the action tree root with [`SigningPurpose::Rotate`].
+//! Logic circuits check the rotation with [`KeyRotationWitness::constrain`]
+//! and publish the resulting [`RotationRecord`] in the application payload,
+//! so the rotation history of a resource can be audited.
+
+use crate::authority::{
+    AuthoritySignature, AuthoritySigningKey, AuthorityVerifyingKey, SigningPayload, SigningPurpose,
+};
+use anoma_rm_risc0::{
+    error::ArmError,
+    logic_instance::ExpirableBlob,
+    nullifier_key::NullifierKey,
+    resource::Resource,
+    utils::{bytes_to_words, hash_bytes, words_to_bytes},
+    Digest,
+};
+use serde::{Deserialize, Serialize};
+
+const AUTH_KEY_DOMAIN: &[u8] = b"ARM_AUTH_KEY_V1";
+const RECORD_BYTES: usize = 4 * 32;
+
+/// Computes the `value_ref` binding a resource to an authorization key.
+pub fn auth_key_commitment(key: &AuthorityVerifyingKey) -> Digest {
+    hash_bytes(&[AUTH_KEY_DOMAIN, &key.to_bytes()[..]].concat())
+}
+
+/// An auditable record of a key rotation.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RotationRecord {
+    /// The commitment to the retired key.
+    pub old_key_commitment: Digest,
+    /// The commitment to the new key.
+    pub new_key_commitment: Digest,
+    /// The nullifier of the consumed resource.
+    pub consumed_nullifier: Digest,
+    /// The commitment of the successor resource.
+    pub created_commitment: Digest,
+}
+
+impl RotationRecord {
+    /// Encodes the record as an application payload blob.
+    pub fn to_blob(&self, deletion_criterion: u32) -> ExpirableBlob {
+        let bytes = [
+            self.old_key_commitment.as_bytes(),
+            self.new_key_commitment.as_bytes(),
+            self.consumed_nullifier.as_bytes(),
+            self.created_commitment.as_bytes(),
+        ]
+        .concat();
+        ExpirableBlob {
+            blob: bytes_to_words(&bytes),
+            deletion_criterion,
+        }
+    }
+
+    /// Decodes a record from an application payload blob.
+    pub fn from_blob(blob: &ExpirableBlob) -> Result<Self, ArmError> {
+        let bytes = words_to_bytes(&blob.blob);
+        if bytes.len() != RECORD_BYTES {
+            return Err(ArmError::InvalidKeyRotation);
+        }
+        let digest = |i: usize| {
+            Digest::try_from(&bytes[i * 32..(i + 1) * 32]).map_err(|_| ArmError::InvalidKeyRotation)
+        };
+        Ok(RotationRecord {
+            old_key_commitment: digest(0)?,
+            new_key_commitment: digest(1)?,
+            consumed_nullifier: digest(2)?,
+            created_commitment: digest(3)?,
+        })
+    }
+}
+
+/// The witness of a key rotation, checked inside the logic circuits of the
+/// consumed and the successor resource.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct KeyRotationWitness {
+    /// The resource bound to the old key.
+    pub consumed: Resource,
+    /// The nullifier key of the consumed resource.
+    pub nf_key: NullifierKey,
+    /// The successor resource bound to the new key.
+    pub created: Resource,
+    /// The retired key.
+    pub old_key: AuthorityVerifyingKey,
+    /// The new key.
+    pub new_key: AuthorityVerifyingKey,
+    /// The old key's signature authorizing the rotation.
+    pub signature: AuthoritySignature,
+}
+
+impl KeyRotationWitness {
+    /// Checks the rotation within the action with the given tree root and
+    /// index, and returns the record to publish.
+    pub fn constrain(
+        &self,
+        domain: &[u8],
+        root: Digest,
+        action_index: u32,
+    ) -> Result<RotationRecord, ArmError> {
+        let old_key_commitment = auth_key_commitment(&self.old_key);
+        let new_key_commitment = auth_key_commitment(&self.new_key);
+
+        // Both resources are bound to their keys
+        if self.consumed.value_ref != old_key_commitment
+            || self.created.value_ref != new_key_commitment
+            || old_key_commitment == new_key_commitment
+        {
+            return Err(ArmError::InvalidKeyRotation);
+        }
+
+        // The successor only differs in the key it's bound to
+        if self.created.logic_ref != self.consumed.logic_ref
+            || self.created.label_ref != self.consumed.label_ref
+            || self.created.quantity != self.consumed.quantity
+            || self.created.is_ephemeral != self.consumed.is_ephemeral
+        {
+            return Err(ArmError::InvalidKeyRotation);
+        }
+
+        // The old key authorized the rotation
+        let payload = SigningPayload::new(domain, root, action_index, SigningPurpose::Rotate)?;
+        self.old_key.verify_payload(&payload, &self.signature)?;
+
+        Ok(RotationRecord {
+            old_key_commitment,
+            new_key_commitment,
+            consumed_nullifier: self.consumed.nullifier(&self.nf_key)?,
+            created_commitment: self.created.commitment(),
+        })
+    }
+}
+
+/// Host-side flow for rotating the key of a resource.
+#[derive(Clone)]
+pub struct KeyRotationBuilder {
+    consumed: Resource,
+    nf_key: NullifierKey,
+    created: Resource,
+    old_key: AuthorityVerifyingKey,
+    new_key: AuthorityVerifyingKey,
+}
+
+impl KeyRotationBuilder {
+    /// Starts rotating the key of `consumed`, which must be bound to `old_key`.
+    pub fn new(
+        consumed: Resource,
+        nf_key: NullifierKey,
+        old_key: AuthorityVerifyingKey,
+        new_key: AuthorityVerifyingKey,
+    ) -> Result<Self, ArmError> {
+        if consumed.value_ref != auth_key_commitment(&old_key) {
+            return Err(ArmError::InvalidKeyRotation);
+        }
+        let mut created = consumed;
+        created.set_value_ref(auth_key_commitment(&new_key));
+        created.set_nonce_from_nf(&consumed, &nf_key)?;
+        created.reset_randomness();
+        Ok(KeyRotationBuilder {
+            consumed,
+            nf_key,
+            created,
+            old_key,
+            new_key,
+        })
+    }
+
+    /// Returns the successor resource to include in the action.
+    pub fn successor(&self) -> &Resource {
+        &self.created
+    }
+
+    /// Signs the rotation with the old key once the action tree root is known.
+    pub fn authorize(
+        self,
+        old_signing_key: &AuthoritySigningKey,
+        domain: &[u8],
+        root: Digest,
+        action_index: u32,
+    ) -> Result<KeyRotationWitness, ArmError> {
+        if AuthorityVerifyingKey::from_signing_key(old_signing_key) != self.old_key {
+            return Err(ArmError::InvalidKeyRotation);
+        }
+        let payload = SigningPayload::new(domain, root, action_index, SigningPurpose::Rotate)?;
+        Ok(KeyRotationWitness {
+            consumed: self.consumed,
+            nf_key: self.nf_key,
+            created: self.created,
+            old_key: self.old_key,
+            new_key: self.new_key,
+            signature: old_signing_key.sign_payload(&payload)?,
+        })
+    }
+}
+
+#[test]
+fn test_key_rotation() {
+    use anoma_rm_risc0::action_tree::MerkleTree;
+
+    let domain = b"wallet";
+    let old_sk = AuthoritySigningKey::new();
+    let old_key = AuthorityVerifyingKey::from_signing_key(&old_sk);
+    let new_key = AuthorityVerifyingKey::from_signing_key(&AuthoritySigningKey::new());
+
+    let (nf_key, nk_commitment) = NullifierKey::random_pair();
+    let consumed = Resource::create(
+        Digest::default(),
+        Digest::default(),
+        10,
+        auth_key_commitment(&old_key),
+        false,
+        Digest::default(),
+        nk_commitment,
+    );
+
+    let builder = KeyRotationBuilder::new(consumed, nf_key.clone(), old_key, new_key).unwrap();
+    let successor = *builder.successor();
+    assert_eq!(successor.value_ref, auth_key_commitment(&new_key));
+
+    let nf = consumed.nullifier(&nf_key).unwrap();
+    let root = MerkleTree::new(vec![nf, successor.commitment()])
+        .root()
+        .unwrap();
+    let witness = builder.authorize(&old_sk, domain, root, 0).unwrap();
+    let record = witness.constrain(domain, root, 0).unwrap();
+    assert_eq!(record.consumed_nullifier, nf);
+    assert_eq!(record.created_commitment, successor.commitment());
+    assert_eq!(
+        RotationRecord::from_blob(&record.to_blob(0)).unwrap(),
+        record
+    );
+
+    // The signature doesn't authorize another action
+    assert_eq!(
+        witness.constrain(domain, Digest::default(), 0),
+        Err(ArmError::InvalidSignature)
+    );
+
+    // The successor can't change the quantity
+    let mut inflated = witness.clone();
+    inflated.created.quantity = 11;
+    assert_eq!(
+        inflated.constrain(domain, root, 0),
+        Err(ArmError::InvalidKeyRotation)
+    );
+
+    // Only the bound key can start a rotation
+    assert!(KeyRotationBuilder::new(consumed, nf_key, new_key, old_key).is_err());
+}
@@ -3,5 +3,23 @@
 #![deny(missing_docs)]
 
 pub mod authority;
+#[cfg(feature = "transaction")]
+pub mod discovery;
 pub mod encryption;
 pub mod evm;
+#[cfg(feature = "transaction")]
+pub mod issuance;
+pub mod key_rotation;
+pub mod memo;
+pub mod oracle;
+pub mod permissioned_issuance;
+pub mod rate_limit;
+#[cfg(feature = "transaction")]
+pub mod receipt;
+pub mod recovery;
+#[cfg(feature = "transaction")]
+pub mod sealed_tx;
+#[cfg(feature = "transaction")]
+pub mod split;
+pub mod timelock;
+pub mod viewing;
@@ -0,0 +1,249 @@
+//! Encrypted resource notes with an optional memo.
+//!
+//! A [`ResourceNote`] is the standard plaintext of an encrypted resource
+//! payload: the created resource, which the receiver needs to spend it, and
+//! an optional [`Memo`] of up to [`MEMO_MAX_BYTES`] bytes for human-readable
+//! context such as an invoice id. The memo is padded to its maximum length
+//! before encryption, so the ciphertext doesn't reveal whether there is one
+//! or how long it is.
+
+use crate::encryption::{Ciphertext, SecretKey, SecurePlaintext};
+use anoma_rm_risc0::{
+    error::ArmError,
+    logic_instance::{ExpirableBlob, LintIssue, PayloadCategory, PayloadLinter},
+    resource::Resource,
+};
+use k256::AffinePoint;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+/// The maximum length of a memo in bytes.
+pub const MEMO_MAX_BYTES: usize = 512;
+
+/// A memo attached to a resource note.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Memo(Vec<u8>);
+
+impl Memo {
+    /// Creates a memo from bytes, failing if they're longer than
+    /// [`MEMO_MAX_BYTES`].
+    pub fn new(bytes: Vec<u8>) -> Result<Self, ArmError> {
+        if bytes.len() > MEMO_MAX_BYTES {
+            return Err(ArmError::MemoTooLong);
+        }
+        Ok(Memo(bytes))
+    }
+
+    /// Creates a memo from text.
+    pub fn from_text(text: &str) -> Result<Self, ArmError> {
+        Self::new(text.as_bytes().to_vec())
+    }
+
+    /// Returns the bytes of the memo.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the memo as text, if it's valid UTF-8.
+    pub fn as_text(&self) -> Option<&str> {
+        std::str::from_utf8(&self.0).ok()
+    }
+}
+
+/// The plaintext of an encrypted resource payload.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResourceNote {
+    /// The resource sent to the receiver.
+    pub resource: Resource,
+    /// The sender's memo, if any.
+    pub memo: Option<Memo>,
+}
+
+// The encoding of a note, with the memo padded to `MEMO_MAX_BYTES`.
+#[derive(Serialize, Deserialize)]
+struct PaddedNote {
+    resource: Resource,
+    has_memo: bool,
+    memo_len: u16,
+    memo: Vec<u8>,
+}
+
+impl ResourceNote {
+    /// Creates a note of the resource without a memo.
+    pub fn new(resource: Resource) -> Self {
+        ResourceNote {
+            resource,
+            memo: None,
+        }
+    }
+
+    /// Attaches a memo to the note.
+    pub fn with_memo(mut self, memo: Memo) -> Self {
+        self.memo = Some(memo);
+        self
+    }
+
+    /// Encrypts the note to the receiver's public key.
+    pub fn encrypt(
+        &self,
+        receiver_pk: &AffinePoint,
+        sender_sk: &SecretKey,
+    ) -> Result<Ciphertext, ArmError> {
+        let memo = self.memo.as_ref().map(Memo::as_bytes).unwrap_or_default();
+        let mut padded = memo.to_vec();
+        padded.resize(MEMO_MAX_BYTES, 0);
+        let note = PaddedNote {
+            resource: self.resource,
+            has_memo: self.memo.is_some(),
+            memo_len: memo.len() as u16,
+            memo: padded,
+        };
+        let mut plaintext = bincode::serialize(&note).map_err(|_| ArmError::SerializationError)?;
+        let ciphertext = Ciphertext::encrypt(&plaintext, receiver_pk, sender_sk);
+        plaintext.zeroize();
+        ciphertext
+    }
+
+    /// Decrypts a note with the receiver's secret key.
+    pub fn decrypt(ciphertext: &Ciphertext, sk: &SecretKey) -> Result<Self, ArmError> {
+        Self::from_plaintext(&ciphertext.decrypt(sk)?)
+    }
+
+    fn from_plaintext(plaintext: &SecurePlaintext) -> Result<Self, ArmError> {
+        let note: PaddedNote = bincode::deserialize(plaintext.as_bytes())
+            .map_err(|_| ArmError::DeserializationError)?;
+        let memo_len = note.memo_len as usize;
+        if note.memo.len() != MEMO_MAX_BYTES || memo_len > MEMO_MAX_BYTES {
+            return Err(ArmError::MemoTooLong);
+        }
+        let memo = note
+            .has_memo
+            .then(|| Memo::new(note.memo[..memo_len].to_vec()))
+            .transpose()?;
+        Ok(ResourceNote {
+            resource: note.resource,
+            memo,
+        })
+    }
+
+    /// Encrypts the note into a resource payload blob.
+    pub fn to_blob(
+        &self,
+        receiver_pk: &AffinePoint,
+        sender_sk: &SecretKey,
+        deletion_criterion: u32,
+    ) -> Result<ExpirableBlob, ArmError> {
+        Ok(ExpirableBlob {
+            blob: self.encrypt(receiver_pk, sender_sk)?.as_words(),
+            deletion_criterion,
+        })
+    }
+
+    /// Decrypts a note from a resource payload blob.
+    pub fn from_blob(blob: &ExpirableBlob, sk: &SecretKey) -> Result<Self, ArmError> {
+        Self::decrypt(&Ciphertext::from_words(&blob.blob), sk)
+    }
+}
+
+/// Lints resource payloads as [`ResourceNote`]s encrypted by the sender to
+/// the declared receiver key, before proving.
+pub struct ResourceNoteLinter<'a> {
+    /// The public key the notes are declared to be encrypted to.
+    pub receiver_pk: &'a AffinePoint,
+    /// The secret key the notes were encrypted with.
+    pub sender_sk: &'a SecretKey,
+}
+
+impl PayloadLinter for ResourceNoteLinter<'_> {
+    fn lint_blob(&self, category: PayloadCategory, blob: &ExpirableBlob) -> Option<LintIssue> {
+        if category != PayloadCategory::Resource {
+            return None;
+        }
+        let ciphertext = Ciphertext::from_words(&blob.blob);
+        let decrypted = ciphertext
+            .decrypt_as_sender(self.receiver_pk, self.sender_sk)
+            .and_then(|plaintext| ResourceNote::from_plaintext(&plaintext));
+        let (Ok(_), Ok(len)) = (decrypted, ciphertext.encoded_len()) else {
+            return Some(LintIssue::Undecryptable);
+        };
+        let expected = len.div_ceil(4);
+        (blob.blob.len() != expected).then_some(LintIssue::WordLengthMismatch {
+            expected,
+            actual: blob.blob.len(),
+        })
+    }
+}
+
+#[test]
+fn test_resource_note_memo() {
+    use crate::encryption::random_keypair;
+
+    let (receiver_sk, receiver_pk) = random_keypair();
+    let sender_sk = SecretKey::random();
+    let resource = Resource::default();
+
+    let note = ResourceNote::new(resource).with_memo(Memo::from_text("invoice #42").unwrap());
+    let blob = note.to_blob(&receiver_pk, &sender_sk, 0).unwrap();
+    let decrypted = ResourceNote::from_blob(&blob, &receiver_sk).unwrap();
+    assert_eq!(decrypted, note);
+    assert_eq!(decrypted.memo.unwrap().as_text(), Some("invoice #42"));
+    assert!(ResourceNote::from_blob(&blob, &SecretKey::random()).is_err());
+
+    // Notes with and without memos have the same size.
+    let plain = ResourceNote::new(resource);
+    let plain_blob = plain.to_blob(&receiver_pk, &sender_sk, 0).unwrap();
+    assert_eq!(plain_blob.blob.len(), blob.blob.len());
+    assert_eq!(
+        ResourceNote::from_blob(&plain_blob, &receiver_sk).unwrap(),
+        plain
+    );
+
+    assert_eq!(
+        Memo::new(vec![0u8; MEMO_MAX_BYTES + 1]),
+        Err(ArmError::MemoTooLong)
+    );
+}
+
+#[test]
+fn test_resource_note_lint() {
+    use crate::encryption::random_keypair;
+    use anoma_rm_risc0::logic_instance::AppData;
+
+    let (_, receiver_pk) = random_keypair();
+    let (_, other_pk) = random_keypair();
+    let sender_sk = SecretKey::random();
+    let note = ResourceNote::new(Resource::default());
+    let linter = ResourceNoteLinter {
+        receiver_pk: &receiver_pk,
+        sender_sk: &sender_sk,
+    };
+
+    let mut app_data = AppData::new();
+    let blob = note.to_blob(&receiver_pk, &sender_sk, 0).unwrap();
+    app_data.add_resource_payload(blob.clone());
+    assert!(app_data.lint(false, &[&linter]).is_empty());
+
+    // Encrypted to another key, and padded with an extra word.
+    app_data.add_resource_payload(note.to_blob(&other_pk, &sender_sk, 0).unwrap());
+    let mut padded = blob;
+    padded.blob.push(0);
+    app_data.add_resource_payload(padded.clone());
+    let issues: Vec<_> = app_data
+        .lint(false, &[&linter])
+        .into_iter()
+        .map(|lint| (lint.index, lint.issue))
+        .collect();
+    assert_eq!(
+        issues,
+        vec![
+            (1, LintIssue::Undecryptable),
+            (
+                2,
+                LintIssue::WordLengthMismatch {
+                    expected: padded.blob.len() - 1,
+                    actual: padded.blob.len()
+                }
+            )
+        ]
+    );
+}
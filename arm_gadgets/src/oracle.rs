@@ -0,0 +1,185 @@
+//! Signed external data for logic circuits.
+//!
+//! An oracle signs [`OracleDatum`]s (prices, FX rates, randomness beacons)
+//! with an [`AuthoritySigningKey`]. Hosts fetch the attestations they need
+//! from an [`OracleSource`] and put them in the logic witness, and the
+//! circuit checks them against the oracle key it is configured with (e.g.
+//! committed to by the label of its resources) with
+//! [`OracleAttestation::verify`]. The signature covers the feed, value and
+//! timestamp, so attestations can't be moved to another feed or replayed as
+//! newer data.
+
+use crate::authority::{AuthoritySignature, AuthoritySigningKey, AuthorityVerifyingKey};
+use anoma_rm_risc0::error::ArmError;
+use serde::{Deserialize, Serialize};
+
+/// The domain oracle attestations are signed under.
+const ORACLE_ATTESTATION_DOMAIN: &[u8] = b"ARM_ORACLE_ATTESTATION_V1";
+
+/// A value published by an oracle.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OracleDatum {
+    /// The feed of the value, e.g. `ETH/USD`.
+    pub feed: String,
+    /// The value, in the encoding of the feed.
+    pub value: Vec<u8>,
+    /// The time of the value, as a unix time in seconds.
+    pub timestamp: u64,
+}
+
+impl OracleDatum {
+    /// Creates the datum of a price feed, the price being big-endian encoded.
+    pub fn price(feed: &str, price: u128, timestamp: u64) -> Self {
+        OracleDatum {
+            feed: feed.to_string(),
+            value: price.to_be_bytes().to_vec(),
+            timestamp,
+        }
+    }
+
+    /// Decodes the value of a price feed.
+    pub fn as_price(&self) -> Option<u128> {
+        Some(u128::from_be_bytes(self.value.as_slice().try_into().ok()?))
+    }
+
+    /// Encodes the datum into the bytes signed by the oracle.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(8 + self.feed.len() + self.value.len() + 8);
+        bytes.extend_from_slice(&(self.feed.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(self.feed.as_bytes());
+        bytes.extend_from_slice(&(self.value.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&self.value);
+        bytes.extend_from_slice(&self.timestamp.to_be_bytes());
+        bytes
+    }
+}
+
+/// A datum signed by an oracle.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OracleAttestation {
+    /// The signed datum.
+    pub datum: OracleDatum,
+    /// The oracle's signature over the datum.
+    pub signature: AuthoritySignature,
+}
+
+impl OracleAttestation {
+    /// Signs a datum with the oracle's key.
+    pub fn sign(datum: OracleDatum, oracle_sk: &AuthoritySigningKey) -> Self {
+        let signature = oracle_sk.sign(ORACLE_ATTESTATION_DOMAIN, &datum.to_bytes());
+        OracleAttestation { datum, signature }
+    }
+
+    /// Checks that the attestation was signed by the oracle for the given
+    /// feed, returning the datum. It can be used in logic circuits as well as
+    /// on the host.
+    pub fn verify(
+        &self,
+        oracle_vk: &AuthorityVerifyingKey,
+        feed: &str,
+    ) -> Result<&OracleDatum, ArmError> {
+        if self.datum.feed != feed {
+            return Err(ArmError::OracleFeedMismatch);
+        }
+        oracle_vk.verify(
+            ORACLE_ATTESTATION_DOMAIN,
+            &self.datum.to_bytes(),
+            &self.signature,
+        )?;
+        Ok(&self.datum)
+    }
+
+    /// Checks that the datum is at most `max_age` seconds older than `now`.
+    /// Circuits can't observe the time, so verifiers check it on the host, or
+    /// circuits compare it with a bound published for the verifier.
+    pub fn check_fresh(&self, now: u64, max_age: u64) -> Result<(), ArmError> {
+        if self.datum.timestamp > now || now - self.datum.timestamp > max_age {
+            return Err(ArmError::StaleAttestation);
+        }
+        Ok(())
+    }
+}
+
+/// Where hosts fetch attestations from, e.g. an oracle's HTTP API.
+pub trait OracleSource {
+    /// Returns the latest attestation of the feed.
+    fn fetch(&self, feed: &str) -> Result<OracleAttestation, ArmError>;
+}
+
+/// An in-process oracle signing the values it's given, for tests and local
+/// deployments.
+pub struct LocalOracle {
+    signing_key: AuthoritySigningKey,
+    values: std::collections::BTreeMap<String, (Vec<u8>, u64)>,
+}
+
+impl LocalOracle {
+    /// Creates an oracle with the given key and no values.
+    pub fn new(signing_key: AuthoritySigningKey) -> Self {
+        LocalOracle {
+            signing_key,
+            values: Default::default(),
+        }
+    }
+
+    /// Returns the verifying key circuits are configured with.
+    pub fn verifying_key(&self) -> AuthorityVerifyingKey {
+        AuthorityVerifyingKey::from_signing_key(&self.signing_key)
+    }
+
+    /// Publishes the value of a feed at a time.
+    pub fn publish(&mut self, datum: OracleDatum) {
+        self.values
+            .insert(datum.feed.clone(), (datum.value, datum.timestamp));
+    }
+}
+
+impl OracleSource for LocalOracle {
+    fn fetch(&self, feed: &str) -> Result<OracleAttestation, ArmError> {
+        let (value, timestamp) = self.values.get(feed).ok_or(ArmError::OracleFeedMismatch)?;
+        let datum = OracleDatum {
+            feed: feed.to_string(),
+            value: value.clone(),
+            timestamp: *timestamp,
+        };
+        Ok(OracleAttestation::sign(datum, &self.signing_key))
+    }
+}
+
+/// Fetches the attestations of the feeds, in order, to package them into a
+/// witness.
+pub fn fetch_attestations(
+    source: &dyn OracleSource,
+    feeds: &[&str],
+) -> Result<Vec<OracleAttestation>, ArmError> {
+    feeds.iter().map(|feed| source.fetch(feed)).collect()
+}
+
+#[test]
+fn test_oracle_attestation() {
+    let mut oracle = LocalOracle::new(AuthoritySigningKey::new());
+    let oracle_vk = oracle.verifying_key();
+    oracle.publish(OracleDatum::price("ETH/USD", 3_000_000_000, 1_000));
+    oracle.publish(OracleDatum::price("BTC/USD", 60_000_000_000, 1_000));
+
+    let attestations = fetch_attestations(&oracle, &["ETH/USD", "BTC/USD"]).unwrap();
+    let datum = attestations[0].verify(&oracle_vk, "ETH/USD").unwrap();
+    assert_eq!(datum.as_price(), Some(3_000_000_000));
+    assert!(attestations[0].check_fresh(1_060, 60).is_ok());
+    assert_eq!(
+        attestations[0].check_fresh(1_061, 60),
+        Err(ArmError::StaleAttestation)
+    );
+    assert!(fetch_attestations(&oracle, &["EUR/USD"]).is_err());
+
+    // Moved to another feed, tampered, or signed by another key.
+    assert_eq!(
+        attestations[1].verify(&oracle_vk, "ETH/USD"),
+        Err(ArmError::OracleFeedMismatch)
+    );
+    let mut tampered = attestations[0].clone();
+    tampered.datum.timestamp += 1;
+    assert!(tampered.verify(&oracle_vk, "ETH/USD").is_err());
+    let other = OracleAttestation::sign(attestations[0].datum.clone(), &AuthoritySigningKey::new());
+    assert!(other.verify(&oracle_vk, "ETH/USD").is_err());
+}
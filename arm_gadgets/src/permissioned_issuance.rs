@@ -0,0 +1,368 @@
+//! Permissioned issuance of resources, e.g. for regulated assets.
+//!
+//! Only the issuers in an allowlist may create resources of a permissioned
+//! kind. The allowlist is committed to by the root of the tree of the
+//! [`issuer_leaf`]s of their keys, maintained by the [`IssuerRegistry`].
+//! The logic circuit of a created resource checks with
+//! [`PermissionedIssuanceWitness::constrain`] that the issuer is in the
+//! allowlist and signed the action tree root with [`SigningPurpose::Create`].
+//!
+//! The root is either bound to the kind, the `label_ref` of the resources
+//! being [`issuers_label_ref`] of the root and the rest of the label, or
+//! supplied by the verification context: the logic then publishes it in its
+//! application payload, and the [`IssuerAllowlistPolicy`] rejects the roots
+//! other than the `issuer_allowlist_root` of the context. Rotating the
+//! issuers of a kind bound to its root changes the kind; with the root of
+//! the context, it only takes an update of the verifiers' context.
+
+use crate::authority::{
+    AuthoritySignature, AuthoritySigningKey, AuthorityVerifyingKey, SigningPayload, SigningPurpose,
+};
+use anoma_rm_risc0::{
+    action_tree::MerkleTree,
+    error::ArmError,
+    logic_instance::ExpirableBlob,
+    merkle_path::MerklePath,
+    resource::Resource,
+    utils::{bytes_to_words, hash_bytes, words_to_bytes},
+    Digest,
+};
+#[cfg(feature = "transaction")]
+use anoma_rm_risc0::{
+    logic_instance::AppData,
+    verification::{RejectionReason, VerificationContext, VerificationPolicy},
+};
+use serde::{Deserialize, Serialize};
+
+const ISSUER_DOMAIN: &[u8] = b"ARM_ISSUER_V1";
+const ISSUERS_LABEL_DOMAIN: &[u8] = b"ARM_ISSUERS_LABEL_V1";
+const ISSUERS_ROOT_DOMAIN: &[u8] = b"ARM_ISSUERS_ROOT";
+
+/// Returns the allowlist leaf of an issuer's key.
+pub fn issuer_leaf(issuer: &AuthorityVerifyingKey) -> Digest {
+    hash_bytes(&[ISSUER_DOMAIN, &issuer.to_bytes()[..]].concat())
+}
+
+/// Computes the `label_ref` of a permissioned kind bound to the issuers
+/// with the given root, `label` being the rest of the kind's label.
+pub fn issuers_label_ref(issuers_root: &Digest, label: &[u8]) -> Digest {
+    hash_bytes(&[ISSUERS_LABEL_DOMAIN, issuers_root.as_bytes(), label].concat())
+}
+
+/// Encodes the issuers root published by a logic as an application payload
+/// blob.
+pub fn issuers_root_blob(issuers_root: &Digest, deletion_criterion: u32) -> ExpirableBlob {
+    ExpirableBlob {
+        blob: bytes_to_words(&[ISSUERS_ROOT_DOMAIN, issuers_root.as_bytes()].concat()),
+        deletion_criterion,
+    }
+}
+
+/// Decodes the issuers root of an application payload blob, if it's one.
+pub fn issuers_root_from_blob(blob: &ExpirableBlob) -> Option<Digest> {
+    words_to_bytes(&blob.blob)
+        .strip_prefix(ISSUERS_ROOT_DOMAIN)
+        .and_then(|root| Digest::try_from(root).ok())
+}
+
+/// The issuers authorized to create resources of a permissioned kind,
+/// committed to by the root of the tree of their leaves.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IssuerRegistry {
+    issuers: Vec<AuthorityVerifyingKey>,
+}
+
+impl IssuerRegistry {
+    /// Creates a registry of the given issuers, in order.
+    pub fn new(issuers: Vec<AuthorityVerifyingKey>) -> Self {
+        let mut registry = IssuerRegistry::default();
+        for issuer in issuers {
+            registry.add(issuer);
+        }
+        registry
+    }
+
+    /// Authorizes an issuer. Returns `false` if it already was.
+    pub fn add(&mut self, issuer: AuthorityVerifyingKey) -> bool {
+        if self.contains(&issuer) {
+            return false;
+        }
+        self.issuers.push(issuer);
+        true
+    }
+
+    /// Revokes an issuer, keeping the order of the others. Returns `false`
+    /// if it wasn't authorized.
+    pub fn remove(&mut self, issuer: &AuthorityVerifyingKey) -> bool {
+        let len = self.issuers.len();
+        self.issuers.retain(|key| key != issuer);
+        self.issuers.len() != len
+    }
+
+    /// Returns whether the issuer is authorized.
+    pub fn contains(&self, issuer: &AuthorityVerifyingKey) -> bool {
+        self.issuers.contains(issuer)
+    }
+
+    /// Returns the authorized issuers, in order.
+    pub fn issuers(&self) -> &[AuthorityVerifyingKey] {
+        &self.issuers
+    }
+
+    fn tree(&self) -> MerkleTree {
+        MerkleTree::new(self.issuers.iter().map(issuer_leaf).collect())
+    }
+
+    /// Returns the root of the allowlist. Fails if it's empty.
+    pub fn root(&self) -> Result<Digest, ArmError> {
+        self.tree().root()
+    }
+
+    /// Returns the path of an authorized issuer's leaf under the current
+    /// root. Paths change when issuers are added or revoked.
+    pub fn path(&self, issuer: &AuthorityVerifyingKey) -> Result<MerklePath, ArmError> {
+        if !self.contains(issuer) {
+            return Err(ArmError::UnauthorizedIssuer);
+        }
+        self.tree().generate_path(&issuer_leaf(issuer))
+    }
+
+    /// Signs the issuance of `created` in the action with the given tree
+    /// root and index, producing the witness of its logic.
+    pub fn authorize(
+        &self,
+        issuer_signing_key: &AuthoritySigningKey,
+        created: Resource,
+        source: IssuersRootSource,
+        domain: &[u8],
+        root: Digest,
+        action_index: u32,
+    ) -> Result<PermissionedIssuanceWitness, ArmError> {
+        let issuer = AuthorityVerifyingKey::from_signing_key(issuer_signing_key);
+        let payload = SigningPayload::new(domain, root, action_index, SigningPurpose::Create)?;
+        Ok(PermissionedIssuanceWitness {
+            created,
+            issuer,
+            issuer_path: self.path(&issuer)?,
+            issuers_root: self.root()?,
+            source,
+            signature: issuer_signing_key.sign_payload(&payload)?,
+        })
+    }
+}
+
+/// Where the logic of a permissioned kind takes the issuers root from.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum IssuersRootSource {
+    /// The root is bound to the kind by its `label_ref`, with the rest of the
+    /// kind's label (see [`issuers_label_ref`]).
+    Label(Vec<u8>),
+    /// The root is published in the application payload, for the
+    /// verification context (see [`IssuerAllowlistPolicy`]).
+    Context,
+}
+
+/// The witness of a permissioned issuance, checked inside the logic circuit
+/// of the created resource.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PermissionedIssuanceWitness {
+    /// The created resource.
+    pub created: Resource,
+    /// The issuer's key.
+    pub issuer: AuthorityVerifyingKey,
+    /// The path of the issuer's leaf in the allowlist.
+    pub issuer_path: MerklePath,
+    /// The root of the allowlist.
+    pub issuers_root: Digest,
+    /// Where the root comes from.
+    pub source: IssuersRootSource,
+    /// The issuer's signature authorizing the issuance.
+    pub signature: AuthoritySignature,
+}
+
+impl PermissionedIssuanceWitness {
+    /// Checks that an authorized issuer created the resource within the
+    /// action with the given tree root and index. Returns the blob to publish
+    /// in the application payload if the root comes from the verification
+    /// context.
+    pub fn constrain(
+        &self,
+        domain: &[u8],
+        root: Digest,
+        action_index: u32,
+    ) -> Result<Option<ExpirableBlob>, ArmError> {
+        // The issuer is in the allowlist
+        if self.issuer_path.root(&issuer_leaf(&self.issuer)) != self.issuers_root {
+            return Err(ArmError::UnauthorizedIssuer);
+        }
+
+        // The issuer authorized the issuance
+        let payload = SigningPayload::new(domain, root, action_index, SigningPurpose::Create)?;
+        self.issuer.verify_payload(&payload, &self.signature)?;
+
+        match &self.source {
+            IssuersRootSource::Label(label) => {
+                if self.created.label_ref != issuers_label_ref(&self.issuers_root, label) {
+                    return Err(ArmError::UnauthorizedIssuer);
+                }
+                Ok(None)
+            }
+            IssuersRootSource::Context => Ok(Some(issuers_root_blob(&self.issuers_root, 0))),
+        }
+    }
+}
+
+/// Host-side check that every issuers root published in the application
+/// payloads is the root of the verification context, so nodes agree on the
+/// issuers they accept.
+#[cfg(feature = "transaction")]
+#[derive(Debug, Clone, Default)]
+pub struct IssuerAllowlistPolicy;
+
+#[cfg(feature = "transaction")]
+impl VerificationPolicy for IssuerAllowlistPolicy {
+    fn on_app_data(
+        &self,
+        ctx: &VerificationContext,
+        _action: usize,
+        _tag: &Digest,
+        app_data: &AppData,
+    ) -> Result<(), RejectionReason> {
+        for blob in &app_data.application_payload {
+            if let Some(root) = issuers_root_from_blob(blob) {
+                if ctx.issuer_allowlist_root != Some(root) {
+                    return Err(RejectionReason::PolicyViolation);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_issuer_registry() {
+    let issuers: Vec<_> = (0..3)
+        .map(|_| AuthorityVerifyingKey::from_signing_key(&AuthoritySigningKey::new()))
+        .collect();
+    let mut registry = IssuerRegistry::new(issuers.clone());
+    assert!(!registry.add(issuers[0]));
+    let root = registry.root().unwrap();
+    for issuer in &issuers {
+        let path = registry.path(issuer).unwrap();
+        assert_eq!(path.root(&issuer_leaf(issuer)), root);
+    }
+
+    assert!(registry.remove(&issuers[1]));
+    assert!(!registry.remove(&issuers[1]));
+    assert_ne!(registry.root().unwrap(), root);
+    assert_eq!(registry.issuers(), &[issuers[0], issuers[2]]);
+    assert_eq!(
+        registry.path(&issuers[1]),
+        Err(ArmError::UnauthorizedIssuer)
+    );
+}
+
+#[test]
+fn test_permissioned_issuance() {
+    let domain = b"stablecoin";
+    let issuer_sk = AuthoritySigningKey::new();
+    let issuer = AuthorityVerifyingKey::from_signing_key(&issuer_sk);
+    let other_sk = AuthoritySigningKey::new();
+    let mut registry = IssuerRegistry::new(vec![
+        issuer,
+        AuthorityVerifyingKey::from_signing_key(&other_sk),
+    ]);
+
+    let label = b"USD".to_vec();
+    let created = Resource {
+        label_ref: issuers_label_ref(&registry.root().unwrap(), &label),
+        quantity: 100,
+        ..Default::default()
+    };
+    let root = Digest::from_bytes([1u8; 32]);
+    let source = IssuersRootSource::Label(label);
+    let witness = registry
+        .authorize(&issuer_sk, created, source.clone(), domain, root, 0)
+        .unwrap();
+    assert_eq!(witness.constrain(domain, root, 0), Ok(None));
+
+    // The signature doesn't authorize another action
+    assert_eq!(
+        witness.constrain(domain, Digest::default(), 0),
+        Err(ArmError::InvalidSignature)
+    );
+
+    // A revoked issuer isn't in the allowlist anymore, which changes the kind
+    registry.remove(&issuer);
+    assert!(registry
+        .authorize(&issuer_sk, created, source.clone(), domain, root, 0)
+        .is_err());
+    let witness = registry
+        .authorize(&other_sk, created, source, domain, root, 0)
+        .unwrap();
+    assert_eq!(
+        witness.constrain(domain, root, 0),
+        Err(ArmError::UnauthorizedIssuer)
+    );
+
+    // The root of the context is published instead
+    let witness = registry
+        .authorize(
+            &other_sk,
+            created,
+            IssuersRootSource::Context,
+            domain,
+            root,
+            0,
+        )
+        .unwrap();
+    let blob = witness.constrain(domain, root, 0).unwrap().unwrap();
+    assert_eq!(
+        issuers_root_from_blob(&blob),
+        Some(registry.root().unwrap())
+    );
+
+    // An issuer outside the allowlist can't forge a path
+    let mut forged = witness;
+    forged.issuer = issuer;
+    assert_eq!(
+        forged.constrain(domain, root, 0),
+        Err(ArmError::UnauthorizedIssuer)
+    );
+}
+
+#[cfg(feature = "transaction")]
+#[test]
+fn test_issuer_allowlist_policy() {
+    let registry = IssuerRegistry::new(vec![AuthorityVerifyingKey::from_signing_key(
+        &AuthoritySigningKey::new(),
+    )]);
+    let root = registry.root().unwrap();
+    let ctx = VerificationContext {
+        issuer_allowlist_root: Some(root),
+        ..Default::default()
+    };
+    let mut app_data = AppData::default();
+    app_data
+        .application_payload
+        .push(issuers_root_blob(&root, 0));
+    let tag = Digest::default();
+
+    assert_eq!(
+        IssuerAllowlistPolicy.on_app_data(&ctx, 0, &tag, &app_data),
+        Ok(())
+    );
+    assert_eq!(
+        IssuerAllowlistPolicy.on_app_data(&VerificationContext::new(), 0, &tag, &app_data),
+        Err(RejectionReason::PolicyViolation)
+    );
+    assert_eq!(
+        IssuerAllowlistPolicy.on_app_data(
+            &VerificationContext::new(),
+            0,
+            &tag,
+            &AppData::default()
+        ),
+        Ok(())
+    );
+}
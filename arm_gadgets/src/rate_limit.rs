@@ -0,0 +1,202 @@
+//! Rate-limited nullifiers for epoch-based spending limits (à la RLN).
+//!
+//! A key may produce at most `limit` shares per epoch, one per message id.
+//! Each share reveals a point on a line whose slope is derived from the key,
+//! the epoch and the message id, and whose intercept is the secret key. Two
+//! shares with the same message id in the same epoch have the same nullifier
+//! and reveal the secret key, so applications can detect (and punish)
+//! overspending while honest spends stay unlinkable.
+
+use anoma_rm_risc0::{error::ArmError, rng::ArmRng, utils::hash_bytes, Digest};
+use k256::{
+    elliptic_curve::{ops::Reduce, PrimeField},
+    FieldBytes, Scalar, U256,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use subtle::{Choice, ConstantTimeEq};
+
+const RATE_LIMIT_DOMAIN: &[u8] = b"ARM_RATE_LIMIT_V1";
+
+/// The secret key of a rate-limited identity.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RateLimitKey(Scalar);
+
+impl fmt::Debug for RateLimitKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("RateLimitKey")
+            .field(&format_args!("<redacted>"))
+            .finish()
+    }
+}
+
+impl ConstantTimeEq for RateLimitKey {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
+
+impl PartialEq for RateLimitKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for RateLimitKey {}
+
+impl RateLimitKey {
+    /// Generates a random rate-limit key.
+    pub fn random() -> Self {
+        RateLimitKey(ArmRng::new().scalar())
+    }
+
+    /// Returns the public commitment to the key, registered with the application.
+    pub fn commitment(&self) -> Digest {
+        hash_bytes(&[RATE_LIMIT_DOMAIN, &self.to_bytes()[..]].concat())
+    }
+
+    /// Serializes the key to bytes.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0.to_bytes().into()
+    }
+
+    /// Deserializes the key from bytes.
+    pub fn from_bytes(bytes: &[u8; 32]) -> Result<Self, ArmError> {
+        Scalar::from_repr((*bytes).into())
+            .into_option()
+            .map(RateLimitKey)
+            .ok_or(ArmError::InvalidSigningKey)
+    }
+
+    // The slope of the line used in the given epoch and message slot.
+    fn slope(&self, epoch: u64, message_id: u32) -> Scalar {
+        let bytes = [
+            RATE_LIMIT_DOMAIN,
+            &self.to_bytes()[..],
+            &epoch.to_be_bytes()[..],
+            &message_id.to_be_bytes()[..],
+        ]
+        .concat();
+        hash_to_scalar(&bytes)
+    }
+}
+
+/// A share revealed with every rate-limited spend.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RateLimitShare {
+    /// The epoch of the spend.
+    pub epoch: u64,
+    /// The rate-limited nullifier, identical for reused message slots.
+    pub nullifier: Digest,
+    /// The x coordinate, derived from the signal.
+    pub x: Scalar,
+    /// The y coordinate, `key + slope * x`.
+    pub y: Scalar,
+}
+
+/// The witness of a rate-limited spend, checked inside logic circuits.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RateLimitWitness {
+    /// The secret key.
+    pub key: RateLimitKey,
+    /// The current epoch.
+    pub epoch: u64,
+    /// The message slot used in this epoch, in `0..limit`.
+    pub message_id: u32,
+    /// The signal being authorized, e.g. the nullifier of the spent resource.
+    pub signal: Vec<u8>,
+}
+
+impl RateLimitWitness {
+    /// Creates a new witness.
+    pub fn new(key: RateLimitKey, epoch: u64, message_id: u32, signal: &[u8]) -> Self {
+        RateLimitWitness {
+            key,
+            epoch,
+            message_id,
+            signal: signal.to_vec(),
+        }
+    }
+
+    /// Checks the derivation against the key commitment and the per-epoch
+    /// limit, returning the share to be revealed.
+    pub fn constrain(
+        &self,
+        key_commitment: &Digest,
+        limit: u32,
+    ) -> Result<RateLimitShare, ArmError> {
+        if self.key.commitment() != *key_commitment {
+            return Err(ArmError::InvalidSigningKey);
+        }
+        if self.message_id >= limit {
+            return Err(ArmError::RateLimitExceeded);
+        }
+
+        let slope = self.key.slope(self.epoch, self.message_id);
+        let x = hash_to_scalar(&[RATE_LIMIT_DOMAIN, &self.signal[..]].concat());
+        Ok(RateLimitShare {
+            epoch: self.epoch,
+            nullifier: hash_bytes(&slope.to_bytes()),
+            x,
+            y: self.key.0 + slope * x,
+        })
+    }
+}
+
+/// Recovers the secret key from two shares of the same slot with different
+/// signals. Returns `None` if the shares don't reveal it.
+pub fn recover_key(first: &RateLimitShare, second: &RateLimitShare) -> Option<RateLimitKey> {
+    if first.epoch != second.epoch || first.nullifier != second.nullifier || first.x == second.x {
+        return None;
+    }
+    let slope = (first.y - second.y) * (first.x - second.x).invert().into_option()?;
+    Some(RateLimitKey(first.y - slope * first.x))
+}
+
+fn hash_to_scalar(bytes: &[u8]) -> Scalar {
+    let digest = FieldBytes::clone_from_slice(hash_bytes(bytes).as_bytes());
+    <Scalar as Reduce<U256>>::reduce_bytes(&digest)
+}
+
+#[test]
+fn test_rate_limited_nullifier() {
+    let key = RateLimitKey::random();
+    let commitment = key.commitment();
+    let limit = 2;
+
+    let first = RateLimitWitness::new(key.clone(), 7, 0, b"spend 1")
+        .constrain(&commitment, limit)
+        .unwrap();
+    let second = RateLimitWitness::new(key.clone(), 7, 1, b"spend 2")
+        .constrain(&commitment, limit)
+        .unwrap();
+    assert_ne!(first.nullifier, second.nullifier);
+    assert_eq!(recover_key(&first, &second), None);
+
+    // Exceeding the limit is rejected in-circuit.
+    assert_eq!(
+        RateLimitWitness::new(key.clone(), 7, 2, b"spend 3").constrain(&commitment, limit),
+        Err(ArmError::RateLimitExceeded)
+    );
+
+    // Reusing a slot reveals the key.
+    let reused = RateLimitWitness::new(key.clone(), 7, 0, b"spend 3")
+        .constrain(&commitment, limit)
+        .unwrap();
+    assert_eq!(reused.nullifier, first.nullifier);
+    assert_eq!(recover_key(&first, &reused), Some(key.clone()));
+
+    // Slots are fresh in the next epoch.
+    let next_epoch = RateLimitWitness::new(key.clone(), 8, 0, b"spend 1")
+        .constrain(&commitment, limit)
+        .unwrap();
+    assert_ne!(next_epoch.nullifier, first.nullifier);
+
+    let bytes = key.to_bytes();
+    assert_eq!(RateLimitKey::from_bytes(&bytes).unwrap(), key);
+
+    // The key is compared in constant time and never printed.
+    assert!(bool::from(key.ct_eq(&key.clone())));
+    assert!(!bool::from(key.ct_eq(&RateLimitKey::random())));
+    assert_eq!(format!("{key:?}"), "RateLimitKey(<redacted>)");
+}
@@ -0,0 +1,168 @@
+//! Settlement receipts: proofs that a tag was included in a settled
+//! transaction.
+//!
+//! An [`InclusionReceipt`] carries the path of a tag to its action tree root
+//! and the path of that root to the transaction's tags root (see
+//! `Transaction::tags_root`), along with a reference to the settlement. A
+//! third party who trusts the tags root of the referenced settlement, e.g. a
+//! merchant checking a payment was received, verifies the receipt without
+//! the transaction.
+
+use anoma_rm_risc0::{
+    action_tree::MerkleTree, error::ArmError, merkle_path::MerklePath, transaction::Transaction,
+    Digest,
+};
+use serde::{Deserialize, Serialize};
+
+/// Where a transaction was settled.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SettlementRef {
+    /// The id of the settlement chain.
+    pub chain_id: u64,
+    /// The block the transaction was settled in.
+    pub block_number: u64,
+    /// The hash of the settlement transaction.
+    pub tx_hash: [u8; 32],
+}
+
+/// A proof that a tag was included in a settled transaction.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct InclusionReceipt {
+    /// The included nullifier or commitment.
+    pub tag: Digest,
+    /// The path from the tag to its action tree root.
+    #[serde(with = "anoma_rm_risc0::merkle_path::compact")]
+    pub action_path: MerklePath,
+    /// The path from the action tree root to the tags root.
+    #[serde(with = "anoma_rm_risc0::merkle_path::compact")]
+    pub tags_path: MerklePath,
+    /// The tags root the transaction commits to.
+    pub tags_root: Digest,
+    /// Where the transaction was settled.
+    pub settlement: SettlementRef,
+}
+
+impl InclusionReceipt {
+    /// Creates the receipt of `tag` in a settled transaction. The
+    /// transaction must commit to its tags root, so that the receipt is
+    /// bound to the delta proof.
+    pub fn create(
+        tx: &Transaction,
+        tag: &Digest,
+        settlement: SettlementRef,
+    ) -> Result<Self, ArmError> {
+        let tags_root = tx.tags_root.ok_or(ArmError::MissingTagsRoot)?;
+        tx.check_tags_root()?;
+
+        let action = tx
+            .actions
+            .iter()
+            .find(|action| action.contains_tag(tag))
+            .ok_or(ArmError::TagNotFound)?;
+        let action_path = MerkleTree::from(action.tags()?).generate_path(tag)?;
+
+        let roots = tx
+            .actions
+            .iter()
+            .map(|action| action.action_tree_root())
+            .collect::<Result<Vec<_>, _>>()?;
+        let tags_path = MerkleTree::from(roots).generate_path(&action.action_tree_root()?)?;
+
+        Ok(InclusionReceipt {
+            tag: *tag,
+            action_path,
+            tags_path,
+            tags_root,
+            settlement,
+        })
+    }
+
+    /// Returns the action tree root of the tag.
+    pub fn action_root(&self) -> Digest {
+        self.action_path.root(&self.tag)
+    }
+
+    /// Checks that the receipt leads to `tags_root`, the tags root of the
+    /// settled transaction as known to the verifier.
+    pub fn verify(&self, tags_root: &Digest) -> Result<(), ArmError> {
+        if self.tags_root != *tags_root || self.tags_path.root(&self.action_root()) != *tags_root {
+            return Err(ArmError::InvalidInclusionReceipt);
+        }
+        Ok(())
+    }
+
+    /// Serializes the receipt.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ArmError> {
+        bincode::serialize(self).map_err(|_| ArmError::SerializationError)
+    }
+
+    /// Deserializes a receipt.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ArmError> {
+        bincode::deserialize(bytes).map_err(|_| ArmError::DeserializationError)
+    }
+}
+
+#[test]
+fn test_inclusion_receipt() {
+    use anoma_rm_risc0::{
+        action::Action, compliance_unit::ComplianceUnit, delta_proof::DeltaWitness,
+        padding::PaddingUnit, transaction::Delta,
+    };
+
+    // Unproved actions of two padding units.
+    let actions: Vec<_> = (0..3)
+        .map(|_| {
+            let compliance_units = (0..2)
+                .map(|_| {
+                    let instance = PaddingUnit::new().unwrap().witness.constrain().unwrap();
+                    ComplianceUnit::unproven(&instance).unwrap()
+                })
+                .collect();
+            Action {
+                compliance_units,
+                logic_verifier_inputs: vec![],
+                resource_refs: vec![],
+            }
+        })
+        .collect();
+    let tag = actions[1].tags().unwrap()[1];
+    let tx = Transaction::create(
+        actions,
+        Delta::Witness(DeltaWitness::from_bytes(&[1u8; 32]).unwrap()),
+    );
+    let settlement = SettlementRef {
+        chain_id: 1,
+        block_number: 100,
+        tx_hash: [7u8; 32],
+    };
+    assert_eq!(
+        InclusionReceipt::create(&tx, &tag, settlement.clone()),
+        Err(ArmError::MissingTagsRoot)
+    );
+
+    let tx = tx.with_tags_root().unwrap();
+    let tags_root = tx.tags_root.unwrap();
+    let receipt = InclusionReceipt::create(&tx, &tag, settlement.clone()).unwrap();
+    assert_eq!(
+        receipt.action_root(),
+        tx.actions[1].action_tree_root().unwrap()
+    );
+    assert_eq!(receipt.verify(&tags_root), Ok(()));
+    assert_eq!(
+        receipt.verify(&Digest::default()),
+        Err(ArmError::InvalidInclusionReceipt)
+    );
+    let decoded = InclusionReceipt::from_bytes(&receipt.to_bytes().unwrap()).unwrap();
+    assert_eq!(decoded, receipt);
+
+    let mut forged = receipt;
+    forged.tag = Digest::from_bytes([9u8; 32]);
+    assert_eq!(
+        forged.verify(&tags_root),
+        Err(ArmError::InvalidInclusionReceipt)
+    );
+    assert_eq!(
+        InclusionReceipt::create(&tx, &Digest::from_bytes([9u8; 32]), settlement),
+        Err(ArmError::TagNotFound)
+    );
+}
@@ -0,0 +1,245 @@
+//! Social recovery of nullifier keys through guardians.
+//!
+//! Losing a nullifier key permanently locks the resources it controls. A
+//! [`RecoveryBundle`] splits the key into Shamir shares over GF(2^8), one
+//! per guardian, each encrypted to the guardian's public key. Any
+//! `threshold` guardians can decrypt their shares and hand them to the owner,
+//! who reconstructs the key; fewer learn nothing about it. The bundle
+//! commits to the key, so a wrong reconstruction is detected.
+
+use crate::encryption::{Ciphertext, SecretKey};
+use anoma_rm_risc0::{
+    error::ArmError,
+    nullifier_key::{NullifierKey, NullifierKeyCommitment},
+    rng::ArmRng,
+};
+use k256::AffinePoint;
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+const KEY_BYTES: usize = 32;
+
+/// A guardian's share of a nullifier key.
+#[derive(Clone, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+pub struct KeyShare {
+    index: u8,
+    bytes: [u8; KEY_BYTES],
+}
+
+impl KeyShare {
+    /// Returns the index of the share, starting at 1.
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+}
+
+/// Splits a nullifier key into `shares` shares, any `threshold` of which
+/// reconstruct it.
+pub fn split_key(
+    nf_key: &NullifierKey,
+    threshold: u8,
+    shares: u8,
+) -> Result<Vec<KeyShare>, ArmError> {
+    if threshold == 0 || threshold > shares {
+        return Err(ArmError::InvalidRecoveryThreshold);
+    }
+    // The coefficients of the polynomials of each byte, the constant terms
+    // being the key bytes.
+    let mut coefficients = vec![[0u8; KEY_BYTES]; threshold as usize];
+    coefficients[0].copy_from_slice(nf_key.inner());
+    for coefficient in coefficients.iter_mut().skip(1) {
+        *coefficient = ArmRng::new().try_bytes()?;
+    }
+
+    let key_shares = (1..=shares)
+        .map(|index| {
+            let mut bytes = [0u8; KEY_BYTES];
+            for (i, byte) in bytes.iter_mut().enumerate() {
+                *byte = coefficients
+                    .iter()
+                    .rev()
+                    .fold(0, |acc, coefficient| gf_mul(acc, index) ^ coefficient[i]);
+            }
+            KeyShare { index, bytes }
+        })
+        .collect();
+    coefficients.zeroize();
+    Ok(key_shares)
+}
+
+/// Reconstructs a nullifier key from shares with distinct indices. With
+/// fewer shares than the threshold the result is unrelated to the key.
+pub fn combine_shares(shares: &[KeyShare]) -> Result<NullifierKey, ArmError> {
+    for (i, share) in shares.iter().enumerate() {
+        if share.index == 0 || shares[..i].iter().any(|s| s.index == share.index) {
+            return Err(ArmError::RecoveryFailed);
+        }
+    }
+    let mut key = [0u8; KEY_BYTES];
+    for share in shares {
+        // The Lagrange basis polynomial of the share evaluated at zero.
+        let basis = shares
+            .iter()
+            .filter(|other| other.index != share.index)
+            .fold(1, |acc, other| {
+                gf_mul(acc, gf_mul(other.index, gf_inv(other.index ^ share.index)))
+            });
+        for (byte, share_byte) in key.iter_mut().zip(share.bytes) {
+            *byte ^= gf_mul(share_byte, basis);
+        }
+    }
+    let nf_key = NullifierKey::from_bytes(key);
+    key.zeroize();
+    Ok(nf_key)
+}
+
+/// A key share encrypted to a guardian.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct GuardianShare {
+    /// The guardian's public key.
+    pub guardian_pk: AffinePoint,
+    /// The index of the share.
+    pub index: u8,
+    /// The share encrypted to the guardian.
+    pub ciphertext: Ciphertext,
+}
+
+/// The encrypted shares of a nullifier key, kept by the owner or the
+/// guardians until recovery.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RecoveryBundle {
+    /// The number of shares reconstructing the key.
+    pub threshold: u8,
+    /// The commitment to the key.
+    pub nk_commitment: NullifierKeyCommitment,
+    /// The shares, one per guardian.
+    pub shares: Vec<GuardianShare>,
+}
+
+impl RecoveryBundle {
+    /// Splits the key among the guardians, any `threshold` of whom can
+    /// recover it.
+    pub fn create(
+        nf_key: &NullifierKey,
+        guardian_pks: &[AffinePoint],
+        threshold: u8,
+    ) -> Result<Self, ArmError> {
+        let count =
+            u8::try_from(guardian_pks.len()).map_err(|_| ArmError::InvalidRecoveryThreshold)?;
+        let key_shares = split_key(nf_key, threshold, count)?;
+        // The shares are encrypted under a one-time sender key.
+        let sender_sk = SecretKey::random();
+        let shares = guardian_pks
+            .iter()
+            .zip(&key_shares)
+            .map(|(guardian_pk, share)| {
+                let mut plaintext =
+                    bincode::serialize(share).map_err(|_| ArmError::SerializationError)?;
+                let ciphertext = Ciphertext::encrypt(&plaintext, guardian_pk, &sender_sk);
+                plaintext.zeroize();
+                Ok(GuardianShare {
+                    guardian_pk: *guardian_pk,
+                    index: share.index,
+                    ciphertext: ciphertext?,
+                })
+            })
+            .collect::<Result<_, ArmError>>()?;
+        Ok(RecoveryBundle {
+            threshold,
+            nk_commitment: nf_key.commit(),
+            shares,
+        })
+    }
+
+    /// Decrypts the share of the guardian holding `guardian_sk`.
+    pub fn decrypt_share(&self, guardian_sk: &SecretKey) -> Result<KeyShare, ArmError> {
+        self.shares
+            .iter()
+            .find_map(|guardian_share| {
+                let plaintext = guardian_share.ciphertext.decrypt(guardian_sk).ok()?;
+                let share: KeyShare = bincode::deserialize(plaintext.as_bytes()).ok()?;
+                (share.index == guardian_share.index).then_some(share)
+            })
+            .ok_or(ArmError::DecryptionFailed)
+    }
+
+    /// Reconstructs the key from at least `threshold` shares, checking it
+    /// against the commitment.
+    pub fn recover(&self, shares: &[KeyShare]) -> Result<NullifierKey, ArmError> {
+        if shares.len() < self.threshold as usize {
+            return Err(ArmError::RecoveryFailed);
+        }
+        let nf_key = combine_shares(shares)?;
+        if !self.nk_commitment.is_commitment_of(&nf_key) {
+            return Err(ArmError::RecoveryFailed);
+        }
+        Ok(nf_key)
+    }
+
+    /// Serializes the bundle.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ArmError> {
+        bincode::serialize(self).map_err(|_| ArmError::SerializationError)
+    }
+
+    /// Deserializes a bundle.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ArmError> {
+        bincode::deserialize(bytes).map_err(|_| ArmError::DeserializationError)
+    }
+}
+
+// Multiplication in GF(2^8) modulo x^8 + x^4 + x^3 + x + 1, without branches
+// on the operands.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    for _ in 0..8 {
+        product ^= a & 0u8.wrapping_sub(b & 1);
+        a = (a << 1) ^ (0x1b & 0u8.wrapping_sub(a >> 7));
+        b >>= 1;
+    }
+    product
+}
+
+// The inverse in GF(2^8), as a^254.
+fn gf_inv(a: u8) -> u8 {
+    let mut result = 1;
+    let mut power = a;
+    for bit in 0..8 {
+        if (254u8 >> bit) & 1 == 1 {
+            result = gf_mul(result, power);
+        }
+        power = gf_mul(power, power);
+    }
+    result
+}
+
+#[test]
+fn test_recovery_bundle() {
+    use crate::encryption::random_keypair;
+
+    let (nf_key, _) = NullifierKey::random_pair();
+    let guardians: Vec<(SecretKey, AffinePoint)> = (0..5).map(|_| random_keypair()).collect();
+    let pks: Vec<AffinePoint> = guardians.iter().map(|(_, pk)| *pk).collect();
+    let bundle = RecoveryBundle::create(&nf_key, &pks, 3).unwrap();
+    let bundle = RecoveryBundle::from_bytes(&bundle.to_bytes().unwrap()).unwrap();
+
+    let shares: Vec<KeyShare> = [4, 0, 2]
+        .iter()
+        .map(|&i| bundle.decrypt_share(&guardians[i].0).unwrap())
+        .collect();
+    assert!(bundle.recover(&shares).unwrap() == nf_key);
+    assert_eq!(
+        bundle.recover(&shares[..2]).err(),
+        Some(ArmError::RecoveryFailed)
+    );
+    let duplicated = [shares[0].clone(), shares[0].clone(), shares[1].clone()];
+    assert_eq!(
+        bundle.recover(&duplicated).err(),
+        Some(ArmError::RecoveryFailed)
+    );
+    assert!(bundle.decrypt_share(&SecretKey::random()).is_err());
+
+    assert_eq!(
+        RecoveryBundle::create(&nf_key, &pks, 6).err(),
+        Some(ArmError::InvalidRecoveryThreshold)
+    );
+}
@@ -0,0 +1,106 @@
+//! Sealed transactions for front-running-resistant submission.
+//!
+//! The submitter first publishes the commitment of a transaction, then the
+//! transaction encrypted to the block proposer's key. Once the ordering is
+//! fixed the proposer opens it, and anyone can check that the revealed
+//! transaction matches the earlier commitment.
+
+use crate::encryption::{Ciphertext, SecretKey};
+use anoma_rm_risc0::{
+    error::ArmError, rng::ArmRng, transaction::Transaction, utils::hash_bytes, Digest,
+};
+use k256::AffinePoint;
+use serde::{Deserialize, Serialize};
+
+const SALT_BYTES: usize = 32;
+
+/// Domain separator of the transaction commitment.
+const SEALED_TX_COMMITMENT_DOMAIN: &[u8] = b"ARM_SEALED_TX_COMMITMENT";
+
+/// A transaction encrypted to a block proposer together with its commitment.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SealedTransaction {
+    /// The commitment to the transaction, published before the ciphertext.
+    pub commitment: Digest,
+    /// The salted transaction encrypted to the proposer's public key.
+    pub ciphertext: Ciphertext,
+}
+
+impl SealedTransaction {
+    /// Seals the transaction to the proposer's public key.
+    pub fn seal(
+        tx: &Transaction,
+        proposer_pk: &AffinePoint,
+        sender_sk: &SecretKey,
+    ) -> Result<Self, ArmError> {
+        let salt: [u8; SALT_BYTES] = ArmRng::new().try_bytes()?;
+        let tx_bytes = tx.to_bytes()?;
+        let commitment = commit(&salt, &tx_bytes);
+
+        let plaintext = [&salt[..], &tx_bytes[..]].concat();
+        let ciphertext = Ciphertext::encrypt(&plaintext, proposer_pk, sender_sk)?;
+        Ok(SealedTransaction {
+            commitment,
+            ciphertext,
+        })
+    }
+
+    /// Opens the sealed transaction with the proposer's secret key, returning
+    /// the transaction and the salt needed to check the commitment.
+    pub fn open(&self, proposer_sk: &SecretKey) -> Result<(Transaction, [u8; 32]), ArmError> {
+        let plaintext = self.ciphertext.decrypt(proposer_sk)?;
+        let bytes = plaintext.as_bytes();
+        if bytes.len() < SALT_BYTES {
+            return Err(ArmError::DecryptionFailed);
+        }
+        let (salt, tx_bytes) = bytes.split_at(SALT_BYTES);
+        if commit(salt, tx_bytes) != self.commitment {
+            return Err(ArmError::InvalidTransactionCommitment);
+        }
+
+        let tx = Transaction::from_bytes(tx_bytes)?;
+        let salt = salt.try_into().map_err(|_| ArmError::DecryptionFailed)?;
+        Ok((tx, salt))
+    }
+
+    /// Checks that a revealed transaction and salt match the commitment.
+    pub fn verify_commitment(&self, tx: &Transaction, salt: &[u8; 32]) -> Result<(), ArmError> {
+        let tx_bytes = tx.to_bytes()?;
+        if commit(salt, &tx_bytes) != self.commitment {
+            return Err(ArmError::InvalidTransactionCommitment);
+        }
+        Ok(())
+    }
+}
+
+/// Commits to the canonical encoding of a transaction, see
+/// [`Transaction::to_bytes`].
+fn commit(salt: &[u8], tx_bytes: &[u8]) -> Digest {
+    hash_bytes(&[SEALED_TX_COMMITMENT_DOMAIN, salt, tx_bytes].concat())
+}
+
+#[test]
+fn test_sealed_transaction() {
+    use crate::encryption::random_keypair;
+    use anoma_rm_risc0::{delta_proof::DeltaWitness, transaction::Delta};
+
+    let witness = DeltaWitness::from_bytes(&[1u8; 32]).unwrap();
+    let tx = Transaction::create(vec![], Delta::Witness(witness));
+    let sender_sk = SecretKey::random();
+    let (proposer_sk, proposer_pk) = random_keypair();
+
+    let sealed = SealedTransaction::seal(&tx, &proposer_pk, &sender_sk).unwrap();
+    let (opened, salt) = sealed.open(&proposer_sk).unwrap();
+    assert_eq!(opened, tx);
+    assert!(sealed.verify_commitment(&opened, &salt).is_ok());
+
+    let other = Transaction::create(
+        vec![],
+        Delta::Witness(DeltaWitness::from_bytes(&[2u8; 32]).unwrap()),
+    );
+    assert_eq!(
+        sealed.verify_commitment(&other, &salt),
+        Err(ArmError::InvalidTransactionCommitment)
+    );
+    assert!(sealed.open(&SecretKey::random()).is_err());
+}
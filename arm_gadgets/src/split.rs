@@ -0,0 +1,239 @@
+//! Splitting a resource into several resources of the same kind.
+//!
+//! A compliance unit consumes and creates a single resource, so a split of
+//! one resource into N spans N units: the first consumes the input, the
+//! others consume ephemeral zero-quantity fillers of the input's kind, and
+//! each creates one of the outputs. The quantities of the kind cancel across
+//! the units, and the rcvs are chosen to sum up to a single blinding, so the
+//! split balances like one 1→1 unit. The resource logic of the kind must
+//! accept consuming the fillers, which it can recognize with
+//! [`is_split_filler`].
+
+use anoma_rm_risc0::{
+    compliance::{ComplianceWitness, Rcv},
+    delta_proof::DeltaWitness,
+    dust_policy::DustPolicy,
+    error::ArmError,
+    merkle_path::MerklePath,
+    nullifier_key::{NullifierKey, NullifierKeyCommitment},
+    resource::Resource,
+    rng::ArmRng,
+    Digest,
+};
+use k256::Scalar;
+
+/// Checks whether a consumed resource is a filler of a split, i.e. an
+/// ephemeral resource of quantity zero.
+pub fn is_split_filler(resource: &Resource) -> bool {
+    resource.is_ephemeral && resource.quantity == 0
+}
+
+/// The compliance witnesses of a split, one per output.
+#[derive(Clone)]
+pub struct QuantitySplit {
+    /// The compliance witnesses, the first one consuming the input.
+    pub witnesses: Vec<ComplianceWitness>,
+}
+
+impl QuantitySplit {
+    /// Returns the created resources, in output order.
+    pub fn outputs(&self) -> Vec<Resource> {
+        self.witnesses
+            .iter()
+            .map(|witness| witness.created_resource)
+            .collect()
+    }
+
+    /// Returns the rcvs of the compliance units.
+    pub fn rcvs(&self) -> Vec<Rcv> {
+        self.witnesses.iter().map(|witness| witness.rcv).collect()
+    }
+
+    /// Returns the delta witness of the split's compliance units.
+    pub fn delta_witness(&self) -> Result<DeltaWitness, ArmError> {
+        DeltaWitness::from_rcvs(&self.rcvs())
+    }
+}
+
+/// Builds a [`QuantitySplit`] of a consumed resource.
+pub struct QuantitySplitBuilder {
+    input: Resource,
+    nf_key: NullifierKey,
+    merkle_path: MerklePath,
+    outputs: Vec<(NullifierKeyCommitment, u128)>,
+    rcv: Option<Rcv>,
+    dust_policy: DustPolicy,
+}
+
+impl QuantitySplitBuilder {
+    /// Starts a split of `input`, consumed with `nf_key` at `merkle_path`
+    /// (ignored for an ephemeral input).
+    pub fn new(input: Resource, nf_key: NullifierKey, merkle_path: MerklePath) -> Self {
+        QuantitySplitBuilder {
+            input,
+            nf_key,
+            merkle_path,
+            outputs: Vec::new(),
+            rcv: None,
+            dust_policy: DustPolicy::default(),
+        }
+    }
+
+    /// Adds an output of the given quantity, owned by `nk_commitment`.
+    pub fn output(mut self, nk_commitment: NullifierKeyCommitment, quantity: u128) -> Self {
+        self.outputs.push((nk_commitment, quantity));
+        self
+    }
+
+    /// Splits the input quantity evenly into `parts` outputs owned by
+    /// `nk_commitment`, the first outputs taking the remainder.
+    pub fn even(mut self, nk_commitment: NullifierKeyCommitment, parts: usize) -> Self {
+        if parts == 0 {
+            return self;
+        }
+        let parts_u128 = parts as u128;
+        let (share, remainder) = (
+            self.input.quantity / parts_u128,
+            self.input.quantity % parts_u128,
+        );
+        self.outputs
+            .extend((0..parts_u128).map(|i| (nk_commitment, share + u128::from(i < remainder))));
+        self
+    }
+
+    /// Sets the total blinding of the split's delta. Defaults to a random
+    /// rcv.
+    pub fn with_rcv(mut self, rcv: Rcv) -> Self {
+        self.rcv = Some(rcv);
+        self
+    }
+
+    /// Rejects splits creating outputs below the policy's minimum
+    /// quantities.
+    pub fn with_dust_policy(mut self, dust_policy: DustPolicy) -> Self {
+        self.dust_policy = dust_policy;
+        self
+    }
+
+    /// Creates the compliance witnesses. Fails if there are no outputs,
+    /// their quantities don't add up to the input quantity, or one of them
+    /// is dust.
+    pub fn build(self) -> Result<QuantitySplit, ArmError> {
+        let total = self
+            .outputs
+            .iter()
+            .try_fold(0u128, |acc, (_, quantity)| acc.checked_add(*quantity));
+        if self.outputs.is_empty() || total != Some(self.input.quantity) {
+            return Err(ArmError::QuantityMismatch);
+        }
+
+        let mut witnesses = Vec::with_capacity(self.outputs.len());
+        for (index, (nk_commitment, quantity)) in self.outputs.into_iter().enumerate() {
+            let consumed = if index == 0 {
+                self.input
+            } else {
+                Resource::create(
+                    self.input.logic_ref,
+                    self.input.label_ref,
+                    0,
+                    self.input.value_ref,
+                    true,
+                    Digest::from_bytes(ArmRng::new().try_bytes()?),
+                    self.nf_key.commit(),
+                )
+            };
+            let mut created = Resource::create(
+                self.input.logic_ref,
+                self.input.label_ref,
+                quantity,
+                self.input.value_ref,
+                false,
+                Digest::default(),
+                nk_commitment,
+            );
+            created.set_nonce_from_nf(&consumed, &self.nf_key)?;
+            self.dust_policy.check_resource(&created)?;
+            let merkle_path = if index == 0 {
+                self.merkle_path.clone()
+            } else {
+                MerklePath::empty()
+            };
+            witnesses.push(ComplianceWitness::from_resources_with_path(
+                consumed,
+                self.nf_key.clone(),
+                merkle_path,
+                created,
+            ));
+        }
+
+        // The first unit takes the part of the blinding the others don't.
+        let rcv = self.rcv.unwrap_or_else(Rcv::random);
+        let others = witnesses[1..]
+            .iter()
+            .fold(Scalar::ZERO, |acc, witness| acc + witness.rcv.as_scalar());
+        witnesses[0].rcv = Rcv::from_bytes(&(*rcv.as_scalar() - others).to_bytes())?;
+        Ok(QuantitySplit { witnesses })
+    }
+}
+
+#[test]
+fn test_quantity_split() {
+    use k256::ProjectivePoint;
+
+    let (nf_key, nk_commitment) = NullifierKey::random_pair();
+    let input = Resource::create(
+        Digest::from_bytes([1u8; 32]),
+        Digest::from_bytes([2u8; 32]),
+        10,
+        Digest::default(),
+        false,
+        Digest::default(),
+        nk_commitment,
+    );
+    let rcv = Rcv::random();
+
+    let split = QuantitySplitBuilder::new(input, nf_key.clone(), MerklePath::default())
+        .even(nk_commitment, 4)
+        .with_rcv(rcv)
+        .build()
+        .unwrap();
+    let quantities: Vec<u128> = split.outputs().iter().map(|r| r.quantity).collect();
+    assert_eq!(quantities, vec![3, 3, 2, 2]);
+    assert!(split.witnesses[1..]
+        .iter()
+        .all(|witness| is_split_filler(&witness.consumed_resource)));
+
+    // The kinds cancel and the unit deltas sum up to the total blinding.
+    let mut delta = ProjectivePoint::IDENTITY;
+    let mut nullifiers = std::collections::HashSet::new();
+    for witness in &split.witnesses {
+        let instance = witness.constrain().unwrap();
+        assert!(nullifiers.insert(instance.consumed_nullifier));
+        delta += instance.delta_projective().unwrap();
+    }
+    assert_eq!(delta, ProjectivePoint::GENERATOR * rcv.as_scalar());
+    assert_eq!(
+        split.delta_witness().unwrap().to_bytes(),
+        DeltaWitness::from_rcvs(&[rcv]).unwrap().to_bytes()
+    );
+
+    assert_eq!(
+        QuantitySplitBuilder::new(input, nf_key.clone(), MerklePath::default())
+            .output(nk_commitment, 4)
+            .output(nk_commitment, 5)
+            .build()
+            .err(),
+        Some(ArmError::QuantityMismatch)
+    );
+
+    let mut dust_policy = DustPolicy::new();
+    dust_policy.set_min_quantity(input.logic_ref, input.label_ref, 3);
+    assert_eq!(
+        QuantitySplitBuilder::new(input, nf_key, MerklePath::default())
+            .even(nk_commitment, 4)
+            .with_dust_policy(dust_policy)
+            .build()
+            .err(),
+        Some(ArmError::DustQuantity)
+    );
+}
@@ -0,0 +1,190 @@
+//! Epoch timelocks for resource logics.
+//!
+//! A circuit can't read the clock, so a logic that must only be satisfiable
+//! from some epoch on publishes a [`Timelock`] in its application payload and
+//! the verifier checks it against its current epoch with [`check_timelocks`],
+//! or against the chain time of the verification context with
+//! `TimelockPolicy`.
+
+use anoma_rm_risc0::{
+    chain::ChainContext,
+    error::ArmError,
+    logic_instance::{AppData, ExpirableBlob},
+    utils::{bytes_to_words, hash_bytes, words_to_bytes},
+};
+#[cfg(feature = "transaction")]
+use anoma_rm_risc0::{
+    verification::{RejectionReason, VerificationContext, VerificationPolicy},
+    Digest,
+};
+use serde::{Deserialize, Serialize};
+
+const TIMELOCK_DOMAIN: &[u8] = b"ARM_TIMELOCK_V1";
+
+/// A lock that opens at the given epoch.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Timelock {
+    /// The first epoch at which the lock is open.
+    pub not_before: u64,
+}
+
+impl Timelock {
+    /// Creates a lock opening at `not_before`.
+    pub fn new(not_before: u64) -> Self {
+        Timelock { not_before }
+    }
+
+    /// Checks whether the lock is open at `epoch`.
+    pub fn is_open(&self, epoch: u64) -> bool {
+        epoch >= self.not_before
+    }
+
+    /// Encodes the lock as an application payload blob, prefixed with the
+    /// timelock domain tag.
+    pub fn to_blob(&self) -> ExpirableBlob {
+        let mut blob = domain_tag();
+        blob.extend(bytes_to_words(&self.not_before.to_be_bytes()));
+        ExpirableBlob {
+            blob,
+            deletion_criterion: 0,
+        }
+    }
+
+    /// Decodes a lock from a blob produced by [`Timelock::to_blob`].
+    pub fn from_blob(blob: &ExpirableBlob) -> Option<Self> {
+        let tag = domain_tag();
+        if blob.blob.len() != tag.len() + 2 || blob.blob[..tag.len()] != tag[..] {
+            return None;
+        }
+        let bytes = words_to_bytes(&blob.blob[tag.len()..]).try_into().ok()?;
+        Some(Timelock::new(u64::from_be_bytes(bytes)))
+    }
+
+    /// Returns the locks published in the application payload.
+    pub fn from_app_data(app_data: &AppData) -> Vec<Self> {
+        app_data
+            .application_payload
+            .iter()
+            .filter_map(Timelock::from_blob)
+            .collect()
+    }
+}
+
+/// Converts a unix time in seconds to an epoch of `epoch_seconds` length.
+pub fn epoch_at(now: u64, epoch_seconds: u64) -> u64 {
+    now / epoch_seconds.max(1)
+}
+
+/// Checks that all locks published in the application payload are open at
+/// `epoch`.
+pub fn check_timelocks(app_data: &AppData, epoch: u64) -> Result<(), ArmError> {
+    if Timelock::from_app_data(app_data)
+        .iter()
+        .all(|lock| lock.is_open(epoch))
+    {
+        Ok(())
+    } else {
+        Err(ArmError::TimelockNotOpen)
+    }
+}
+
+/// Checks that all locks published in the application payload are open at
+/// the epoch of the chain, for epochs of `epoch_seconds` length.
+pub fn check_timelocks_at(
+    app_data: &AppData,
+    chain: &ChainContext,
+    epoch_seconds: u64,
+) -> Result<(), ArmError> {
+    check_timelocks(app_data, chain.epoch(epoch_seconds))
+}
+
+/// A verification policy rejecting transactions whose published locks aren't
+/// open at the epoch of the context's chain. Transactions publishing locks
+/// are rejected when the context has no chain.
+#[cfg(feature = "transaction")]
+#[derive(Debug, Clone, Copy)]
+pub struct TimelockPolicy {
+    /// The length of an epoch in seconds.
+    pub epoch_seconds: u64,
+}
+
+#[cfg(feature = "transaction")]
+impl VerificationPolicy for TimelockPolicy {
+    fn on_app_data(
+        &self,
+        ctx: &VerificationContext,
+        _action: usize,
+        _tag: &Digest,
+        app_data: &AppData,
+    ) -> Result<(), RejectionReason> {
+        let open = match &ctx.chain {
+            Some(chain) => check_timelocks_at(app_data, chain, self.epoch_seconds).is_ok(),
+            None => Timelock::from_app_data(app_data).is_empty(),
+        };
+        if open {
+            Ok(())
+        } else {
+            Err(RejectionReason::PolicyViolation)
+        }
+    }
+}
+
+fn domain_tag() -> Vec<u32> {
+    hash_bytes(TIMELOCK_DOMAIN).as_words().to_vec()
+}
+
+#[test]
+fn test_timelock() {
+    let lock = Timelock::new(u64::MAX - 1);
+    assert_eq!(Timelock::from_blob(&lock.to_blob()), Some(lock));
+    assert!(!lock.is_open(5));
+    assert!(lock.is_open(u64::MAX));
+
+    let mut app_data = AppData::new();
+    app_data.add_application_payload(ExpirableBlob {
+        blob: vec![1, 2],
+        deletion_criterion: 0,
+    });
+    assert!(check_timelocks(&app_data, 0).is_ok());
+
+    app_data.add_application_payload(Timelock::new(10).to_blob());
+    assert_eq!(Timelock::from_app_data(&app_data), vec![Timelock::new(10)]);
+    assert_eq!(
+        check_timelocks(&app_data, epoch_at(9 * 60, 60)),
+        Err(ArmError::TimelockNotOpen)
+    );
+    assert!(check_timelocks(&app_data, epoch_at(10 * 60, 60)).is_ok());
+    assert_eq!(
+        check_timelocks_at(&app_data, &ChainContext::new("testnet", 1, 9 * 60), 60),
+        Err(ArmError::TimelockNotOpen)
+    );
+    assert!(check_timelocks_at(&app_data, &ChainContext::new("testnet", 1, 10 * 60), 60).is_ok());
+}
+
+#[cfg(feature = "transaction")]
+#[test]
+fn test_timelock_policy() {
+    let policy = TimelockPolicy { epoch_seconds: 60 };
+    let mut app_data = AppData::new();
+    app_data.add_application_payload(Timelock::new(10).to_blob());
+    let on_app_data =
+        |ctx: &VerificationContext| policy.on_app_data(ctx, 0, &Digest::default(), &app_data);
+
+    assert_eq!(
+        on_app_data(&VerificationContext::new()),
+        Err(RejectionReason::PolicyViolation)
+    );
+    let ctx = VerificationContext::new().with_chain(ChainContext::new("testnet", 1, 9 * 60));
+    assert_eq!(on_app_data(&ctx), Err(RejectionReason::PolicyViolation));
+    let ctx = VerificationContext::new().with_chain(ChainContext::new("testnet", 2, 10 * 60));
+    assert_eq!(on_app_data(&ctx), Ok(()));
+    assert_eq!(
+        policy.on_app_data(
+            &VerificationContext::new(),
+            0,
+            &Digest::default(),
+            &AppData::new()
+        ),
+        Ok(())
+    );
+}
@@ -0,0 +1,293 @@
+//! Scoped viewing credentials for auditors.
+//!
+//! A [`ScopedViewingKey`] derives one encryption key per resource kind and
+//! epoch. Resources are encrypted to the [`ScopedViewingKey::scope_public_key`]
+//! of their [`ResourceScope`], and the owner exports a [`ViewingCredential`]
+//! for a [`ViewingGrant`]: the keys of some kinds, optionally restricted to a
+//! range of epochs. The credential decrypts the resources in its scope and
+//! nothing else, so an auditor can be given partial visibility.
+//!
+//! Keys are derived by hashing (kind keys from the master key, epoch keys
+//! from kind keys), so a credential reveals neither the master key nor the
+//! keys of other scopes. A kind granted for all epochs is shared as a single
+//! key; a range of epochs is shared as one key per epoch and kind.
+
+use crate::encryption::{generate_public_key, Ciphertext, SecretKey, SecurePlaintext};
+use anoma_rm_risc0::{error::ArmError, resource::Resource, utils::hash_bytes, Digest};
+use k256::{
+    elliptic_curve::{group::GroupEncoding, ops::Reduce},
+    AffinePoint, FieldBytes, Scalar, U256,
+};
+use serde::{Deserialize, Serialize};
+
+const KIND_KEY_DOMAIN: &[u8] = b"ARM_VIEWING_KIND_KEY_V1";
+const EPOCH_KEY_DOMAIN: &[u8] = b"ARM_VIEWING_EPOCH_KEY_V1";
+
+/// The maximum number of epochs of a [`ViewingGrant`].
+pub const MAX_GRANT_EPOCHS: u64 = 1 << 16;
+
+/// The scope a resource is encrypted under: its kind and the epoch it was
+/// created in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceScope {
+    /// The [`kind_id`] of the resource.
+    pub kind: Digest,
+    /// The epoch the resource was created in.
+    pub epoch: u64,
+}
+
+impl ResourceScope {
+    /// Returns the scope of a resource created in `epoch`.
+    pub fn of(resource: &Resource, epoch: u64) -> Result<Self, ArmError> {
+        Ok(ResourceScope {
+            kind: kind_id(resource)?,
+            epoch,
+        })
+    }
+}
+
+/// Identifies the kind of a resource by the hash of its kind point.
+pub fn kind_id(resource: &Resource) -> Result<Digest, ArmError> {
+    Ok(hash_bytes(&resource.kind()?.to_bytes()))
+}
+
+/// The scopes a [`ViewingCredential`] covers.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ViewingGrant {
+    /// The kinds of the visible resources.
+    pub kinds: Vec<Digest>,
+    /// The first and last epochs of the visible resources, or `None` for all
+    /// epochs.
+    pub epochs: Option<(u64, u64)>,
+}
+
+impl ViewingGrant {
+    /// Grants the resources of the given kinds.
+    pub fn kinds(kinds: Vec<Digest>) -> Self {
+        ViewingGrant {
+            kinds,
+            epochs: None,
+        }
+    }
+
+    /// Restricts the grant to the resources created from epoch `from` to
+    /// epoch `to`, inclusive.
+    pub fn within_epochs(mut self, from: u64, to: u64) -> Self {
+        self.epochs = Some((from, to));
+        self
+    }
+
+    /// Returns whether the grant covers the scope.
+    pub fn covers(&self, scope: &ResourceScope) -> bool {
+        self.kinds.contains(&scope.kind)
+            && self
+                .epochs
+                .is_none_or(|(from, to)| (from..=to).contains(&scope.epoch))
+    }
+}
+
+/// The viewing key of an owner, from which the keys of all scopes are
+/// derived.
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScopedViewingKey(SecretKey);
+
+impl ScopedViewingKey {
+    /// Creates a scoped viewing key from a master secret key.
+    pub fn new(master: SecretKey) -> Self {
+        ScopedViewingKey(master)
+    }
+
+    /// Generates a random scoped viewing key.
+    pub fn random() -> Self {
+        ScopedViewingKey(SecretKey::random())
+    }
+
+    /// Returns the secret key of a scope.
+    pub fn scope_key(&self, scope: &ResourceScope) -> SecretKey {
+        epoch_key(&self.kind_key(&scope.kind), scope.epoch)
+    }
+
+    /// Returns the public key resources of a scope are encrypted to.
+    pub fn scope_public_key(&self, scope: &ResourceScope) -> AffinePoint {
+        generate_public_key(self.scope_key(scope).inner())
+    }
+
+    /// Exports the credential of a grant.
+    pub fn export(&self, grant: &ViewingGrant) -> Result<ViewingCredential, ArmError> {
+        let keys = match grant.epochs {
+            None => grant
+                .kinds
+                .iter()
+                .map(|kind| ScopeKey::Kind {
+                    kind: *kind,
+                    key: self.kind_key(kind),
+                })
+                .collect(),
+            Some((from, to)) => {
+                if from > to || to - from >= MAX_GRANT_EPOCHS {
+                    return Err(ArmError::InvalidViewingGrant);
+                }
+                grant
+                    .kinds
+                    .iter()
+                    .flat_map(|kind| {
+                        let kind_key = self.kind_key(kind);
+                        (from..=to).map(move |epoch| ScopeKey::Epoch {
+                            scope: ResourceScope { kind: *kind, epoch },
+                            key: epoch_key(&kind_key, epoch),
+                        })
+                    })
+                    .collect()
+            }
+        };
+        Ok(ViewingCredential {
+            grant: grant.clone(),
+            keys,
+        })
+    }
+
+    fn kind_key(&self, kind: &Digest) -> SecretKey {
+        let bytes = [
+            KIND_KEY_DOMAIN,
+            &self.0.inner().to_bytes()[..],
+            kind.as_bytes(),
+        ]
+        .concat();
+        SecretKey::new(hash_to_scalar(&bytes))
+    }
+}
+
+/// A key shared by a [`ViewingCredential`].
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+enum ScopeKey {
+    // The key of a kind, from which the keys of all its epochs are derived.
+    Kind {
+        kind: Digest,
+        key: SecretKey,
+    },
+    // The key of a single scope.
+    Epoch {
+        scope: ResourceScope,
+        key: SecretKey,
+    },
+}
+
+/// The keys of the scopes of a [`ViewingGrant`], handed to an auditor.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ViewingCredential {
+    grant: ViewingGrant,
+    keys: Vec<ScopeKey>,
+}
+
+impl ViewingCredential {
+    /// Returns the grant of the credential.
+    pub fn grant(&self) -> &ViewingGrant {
+        &self.grant
+    }
+
+    /// Decrypts a resource ciphertext of the given scope, failing if the
+    /// scope isn't covered by the credential.
+    pub fn decrypt(
+        &self,
+        ciphertext: &Ciphertext,
+        scope: &ResourceScope,
+    ) -> Result<SecurePlaintext, ArmError> {
+        let key = self
+            .keys
+            .iter()
+            .find_map(|key| match key {
+                ScopeKey::Kind { kind, key } if *kind == scope.kind => {
+                    Some(epoch_key(key, scope.epoch))
+                }
+                ScopeKey::Epoch {
+                    scope: granted,
+                    key,
+                } if granted == scope => Some(key.clone()),
+                _ => None,
+            })
+            .ok_or(ArmError::ScopeNotGranted)?;
+        ciphertext.decrypt(&key)
+    }
+
+    /// Encrypts the credential to the auditor's public key.
+    pub fn seal(&self, auditor_pk: &AffinePoint) -> Result<Ciphertext, ArmError> {
+        let bytes = bincode::serialize(self).map_err(|_| ArmError::SerializationError)?;
+        Ciphertext::encrypt(&bytes, auditor_pk, &SecretKey::random())
+    }
+
+    /// Decrypts a credential sealed to the auditor.
+    pub fn open(sealed: &Ciphertext, auditor_sk: &SecretKey) -> Result<Self, ArmError> {
+        let plaintext = sealed.decrypt(auditor_sk)?;
+        bincode::deserialize(plaintext.as_bytes()).map_err(|_| ArmError::DeserializationError)
+    }
+}
+
+fn epoch_key(kind_key: &SecretKey, epoch: u64) -> SecretKey {
+    let bytes = [
+        EPOCH_KEY_DOMAIN,
+        &kind_key.inner().to_bytes()[..],
+        &epoch.to_be_bytes()[..],
+    ]
+    .concat();
+    SecretKey::new(hash_to_scalar(&bytes))
+}
+
+fn hash_to_scalar(bytes: &[u8]) -> Scalar {
+    let digest = FieldBytes::clone_from_slice(hash_bytes(bytes).as_bytes());
+    <Scalar as Reduce<U256>>::reduce_bytes(&digest)
+}
+
+#[test]
+fn test_viewing_credential() {
+    let owner = ScopedViewingKey::random();
+    let (auditor_sk, auditor_pk) = crate::encryption::random_keypair();
+    let kind = Digest::from_bytes([1u8; 32]);
+    let other_kind = Digest::from_bytes([2u8; 32]);
+    let encrypt = |scope: &ResourceScope| {
+        Ciphertext::encrypt(
+            &scope.epoch.to_be_bytes().to_vec(),
+            &owner.scope_public_key(scope),
+            &SecretKey::random(),
+        )
+        .unwrap()
+    };
+    let scope = |kind, epoch| ResourceScope { kind, epoch };
+
+    // A whole kind.
+    let credential = owner.export(&ViewingGrant::kinds(vec![kind])).unwrap();
+    let credential =
+        ViewingCredential::open(&credential.seal(&auditor_pk).unwrap(), &auditor_sk).unwrap();
+    for epoch in [0, 7, u64::MAX] {
+        let decrypted = credential
+            .decrypt(&encrypt(&scope(kind, epoch)), &scope(kind, epoch))
+            .unwrap();
+        assert_eq!(decrypted.as_bytes(), epoch.to_be_bytes());
+    }
+    let hidden = scope(other_kind, 7);
+    assert!(credential.decrypt(&encrypt(&hidden), &hidden).is_err());
+
+    // A range of epochs.
+    let grant = ViewingGrant::kinds(vec![kind, other_kind]).within_epochs(3, 5);
+    let credential = owner.export(&grant).unwrap();
+    assert!(grant.covers(&scope(other_kind, 3)) && !grant.covers(&scope(kind, 6)));
+    for s in [scope(kind, 3), scope(other_kind, 5)] {
+        assert!(credential.decrypt(&encrypt(&s), &s).is_ok());
+    }
+    for s in [scope(kind, 2), scope(other_kind, 6)] {
+        assert_eq!(
+            credential.decrypt(&encrypt(&s), &s).err(),
+            Some(ArmError::ScopeNotGranted)
+        );
+    }
+    // Claiming another scope doesn't help.
+    assert!(credential
+        .decrypt(&encrypt(&scope(kind, 6)), &scope(kind, 5))
+        .is_err());
+
+    assert!(owner
+        .export(&ViewingGrant::kinds(vec![kind]).within_epochs(5, 3))
+        .is_err());
+    assert!(owner
+        .export(&ViewingGrant::kinds(vec![kind]).within_epochs(0, MAX_GRANT_EPOCHS))
+        .is_err());
+}